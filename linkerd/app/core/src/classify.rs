@@ -26,9 +26,19 @@ pub enum Eos {
     Default(http::StatusCode),
     Grpc(GrpcEos),
     Profile(Class),
+    ProfileTrailers(ProfileEos),
     Error(&'static str),
 }
 
+/// A response that must be classified by profile response classes once its
+/// trailers have been received, since its gRPC status is not yet known.
+#[derive(Clone, Debug)]
+pub struct ProfileEos {
+    classes: profiles::http::ResponseClasses,
+    status: http::StatusCode,
+    headers: http::HeaderMap,
+}
+
 #[derive(Clone, Debug)]
 pub enum GrpcEos {
     NoBody(Class),
@@ -103,17 +113,30 @@ impl Default for Response {
 }
 
 impl Response {
-    fn match_class<B>(
-        rsp: &http::Response<B>,
+    fn match_class(
+        status: http::StatusCode,
+        headers: &http::HeaderMap,
+        trailers: Option<&http::HeaderMap>,
         classes: &[profiles::http::ResponseClass],
     ) -> Option<Class> {
         for class in classes {
-            if class.is_match(rsp) {
+            if class.is_match_eos(status, headers, trailers) {
                 let result = if class.is_failure() {
                     SuccessOrFailure::Failure
                 } else {
                     SuccessOrFailure::Success
                 };
+
+                // Classes that match on the gRPC status are labeled with it.
+                if class.is_grpc() {
+                    let grpc_status = trailers
+                        .and_then(profiles::http::grpc_status)
+                        .or_else(|| profiles::http::grpc_status(headers));
+                    if let Some(grpc_status) = grpc_status {
+                        return Some(Class::Grpc(result, grpc_status));
+                    }
+                }
+
                 return Some(Class::Default(result));
             }
         }
@@ -134,13 +157,27 @@ impl classify::ClassifyResponse for Response {
             Response::Grpc => grpc_class(rsp.headers())
                 .map(|c| Eos::Grpc(GrpcEos::NoBody(c)))
                 .unwrap_or(Eos::Grpc(GrpcEos::Open)),
-            Response::Profile(ref classes) => Self::match_class(rsp, classes.as_ref())
-                .map(Eos::Profile)
-                .unwrap_or_else(|| {
-                    grpc_class(rsp.headers())
-                        .map(|c| Eos::Grpc(GrpcEos::NoBody(c)))
-                        .unwrap_or_else(|| Eos::Default(rsp.status()))
-                }),
+            Response::Profile(ref classes)
+                if classes.iter().any(|c| c.is_grpc())
+                    && !rsp.headers().contains_key("grpc-status") =>
+            {
+                // The gRPC status is carried in the trailers, so the response
+                // can't be classified until the stream ends.
+                Eos::ProfileTrailers(ProfileEos {
+                    classes: classes.clone(),
+                    status: rsp.status(),
+                    headers: rsp.headers().clone(),
+                })
+            }
+            Response::Profile(ref classes) => {
+                Self::match_class(rsp.status(), rsp.headers(), None, classes.as_ref())
+                    .map(Eos::Profile)
+                    .unwrap_or_else(|| {
+                        grpc_class(rsp.headers())
+                            .map(|c| Eos::Grpc(GrpcEos::NoBody(c)))
+                            .unwrap_or_else(|| Eos::Default(rsp.status()))
+                    })
+            }
         }
     }
 
@@ -173,6 +210,10 @@ impl classify::ClassifyEos for Eos {
                 .and_then(grpc_class)
                 .unwrap_or(Class::Grpc(SuccessOrFailure::Success, 0)),
             Eos::Profile(class) => class,
+            Eos::ProfileTrailers(eos) => {
+                Response::match_class(eos.status, &eos.headers, trailers, eos.classes.as_ref())
+                    .unwrap_or_else(|| Eos::Default(eos.status).eos(trailers))
+            }
             Eos::Error(msg) => Class::Stream(SuccessOrFailure::Failure, msg.into()),
         }
     }
@@ -183,21 +224,17 @@ impl classify::ClassifyEos for Eos {
 }
 
 fn grpc_class(headers: &http::HeaderMap) -> Option<Class> {
-    headers
-        .get("grpc-status")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|s| s.parse::<u32>().ok())
-        .map(|grpc_status| {
-            let ok = match grpc::Code::from_i32(grpc_status as i32) {
-                grpc::Code::Unknown
-                | grpc::Code::DeadlineExceeded
-                | grpc::Code::Internal
-                | grpc::Code::Unavailable
-                | grpc::Code::DataLoss => SuccessOrFailure::Failure,
-                _ => SuccessOrFailure::Success,
-            };
-            Class::Grpc(ok, grpc_status)
-        })
+    profiles::http::grpc_status(headers).map(|grpc_status| {
+        let ok = match grpc::Code::from_i32(grpc_status as i32) {
+            grpc::Code::Unknown
+            | grpc::Code::DeadlineExceeded
+            | grpc::Code::Internal
+            | grpc::Code::Unavailable
+            | grpc::Code::DataLoss => SuccessOrFailure::Failure,
+            _ => SuccessOrFailure::Success,
+        };
+        Class::Grpc(ok, grpc_status)
+    })
 }
 
 fn h2_error(err: &Error) -> String {
@@ -316,4 +353,74 @@ mod tests {
             .eos(Some(&trailers));
         assert_eq!(class, Class::Grpc(SuccessOrFailure::Failure, 4));
     }
+
+    fn grpc_profile() -> super::Response {
+        use crate::profiles::http::{ResponseClass, ResponseMatch, Route};
+        let classes = vec![
+            ResponseClass::new(true, ResponseMatch::GrpcStatus { min: 14, max: 14 }),
+            ResponseClass::new(false, ResponseMatch::GrpcStatus { min: 0, max: 16 }),
+        ];
+        let route = Route::new(std::iter::empty(), classes);
+        super::Response::Profile(route.response_classes().clone())
+    }
+
+    #[test]
+    fn profile_grpc_status_trailer() {
+        let rsp = Response::builder().status(StatusCode::OK).body(()).unwrap();
+
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", 14.into());
+        let class = grpc_profile().start(&rsp).eos(Some(&trailers));
+        assert_eq!(class, Class::Grpc(SuccessOrFailure::Failure, 14));
+
+        trailers.insert("grpc-status", 5.into());
+        let class = grpc_profile().start(&rsp).eos(Some(&trailers));
+        assert_eq!(class, Class::Grpc(SuccessOrFailure::Success, 5));
+
+        // INTERNAL is ordinarily a failure, but the profile overrides it.
+        trailers.insert("grpc-status", 13.into());
+        let class = grpc_profile().start(&rsp).eos(Some(&trailers));
+        assert_eq!(class, Class::Grpc(SuccessOrFailure::Success, 13));
+
+        // Responses without a gRPC status fall back to HTTP classification.
+        let class = grpc_profile().start(&rsp).eos(None);
+        assert_eq!(class, Class::Default(SuccessOrFailure::Success));
+    }
+
+    #[test]
+    fn profile_grpc_status_trailers_only() {
+        let rsp = Response::builder()
+            .header("grpc-status", "14")
+            .status(StatusCode::OK)
+            .body(())
+            .unwrap();
+        let class = grpc_profile().start(&rsp).eos(None);
+        assert_eq!(class, Class::Grpc(SuccessOrFailure::Failure, 14));
+    }
+
+    #[test]
+    fn profile_response_header() {
+        use crate::profiles::http::{HeaderMatch, ResponseClass, ResponseMatch, Route, ValueMatch};
+        let classes = vec![ResponseClass::new(
+            true,
+            ResponseMatch::Header(HeaderMatch::new(
+                http::header::HeaderName::from_static("x-error"),
+                ValueMatch::Exact("true".into()),
+            )),
+        )];
+        let route = Route::new(std::iter::empty(), classes);
+        let profile = super::Response::Profile(route.response_classes().clone());
+
+        let rsp = Response::builder()
+            .header("x-error", "true")
+            .status(StatusCode::OK)
+            .body(())
+            .unwrap();
+        let class = profile.clone().start(&rsp).eos(None);
+        assert_eq!(class, Class::Default(SuccessOrFailure::Failure));
+
+        let rsp = Response::builder().status(StatusCode::OK).body(()).unwrap();
+        let class = profile.start(&rsp).eos(None);
+        assert_eq!(class, Class::Default(SuccessOrFailure::Success));
+    }
 }
@@ -89,7 +89,7 @@ impl Config {
         svc::stack(ConnectTcp::new(self.connect.keepalive))
             .push(tls::Client::layer(identity))
             .push_timeout(self.connect.timeout)
-            .push(self::client::layer())
+            .push(self::client::layer(self.connect.h2_settings))
             .push(reconnect::layer(connect_backoff))
             .push(self::resolve::layer(dns, resolve_backoff))
             .push_on_response(self::control::balance::layer())
@@ -278,12 +278,12 @@ mod client {
 
     // === impl Layer ===
 
-    pub fn layer<C, B>() -> impl svc::Layer<C, Service = Client<C, B>> + Copy
+    pub fn layer<C, B>(h2_settings: H2Settings) -> impl svc::Layer<C, Service = Client<C, B>> + Copy
     where
         http::h2::Connect<C, B>: tower::Service<Target>,
     {
-        svc::layer::mk(|mk_conn| {
-            let inner = http::h2::Connect::new(mk_conn, H2Settings::default());
+        svc::layer::mk(move |mk_conn| {
+            let inner = http::h2::Connect::new(mk_conn, h2_settings);
             Client { inner }
         })
     }
@@ -1,5 +1,10 @@
 use super::classify;
-use crate::profiles;
+use crate::{
+    http_tracing::{attributes, sample},
+    metrics::{RateLimitLabels, RouteLabels},
+    profiles, rate_limit,
+    svc::Param,
+};
 use linkerd_addr::Addr;
 use linkerd_http_classify::CanClassify;
 use linkerd_proxy_http::timeout;
@@ -29,6 +34,38 @@ impl timeout::HasTimeout for Route {
     }
 }
 
+impl rate_limit::HasRateLimit for Route {
+    type Key = RateLimitLabels;
+
+    fn rate_limit_key(&self) -> Option<RateLimitLabels> {
+        Some(RateLimitLabels::Route(Param::<RouteLabels>::param(self)))
+    }
+
+    fn rate_limit(&self) -> Option<rate_limit::Limit> {
+        self.route.rate_limit()
+    }
+}
+
+impl attributes::HasSpanAttributes for Route {
+    fn span_attributes(&self) -> Vec<(&'static str, String)> {
+        // The destination controller labels each route with its name.
+        self.route
+            .labels()
+            .get("route")
+            .map(|name| ("http.route", name.clone()))
+            .into_iter()
+            .collect()
+    }
+}
+
+impl sample::HasSampleRate for Route {
+    fn sample_rate(&self) -> Option<f64> {
+        self.route
+            .sample_rate()
+            .map(|sample_rate| sample_rate.probability())
+    }
+}
+
 impl fmt::Display for Route {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.target.fmt(f)
@@ -1,10 +1,13 @@
 use http::{header::HeaderValue, StatusCode};
+use linkerd_authz::Unauthorized;
+use linkerd_concurrency_limit::adaptive::LimitExceeded;
 use linkerd_errno::Errno;
 use linkerd_error::Error;
 use linkerd_error_metrics as metrics;
 use linkerd_error_respond as respond;
 pub use linkerd_error_respond::RespondLayer;
 use linkerd_proxy_http::{ClientHandle, HasH2Reason};
+use linkerd_rate_limit::RateLimited;
 use linkerd_timeout::{error::ResponseTimeout, FailFastError};
 use linkerd_tls as tls;
 use pin_project::pin_project;
@@ -45,6 +48,9 @@ pub enum Reason {
     FailFast,
     GatewayLoop,
     NotFound,
+    RateLimited,
+    ConcurrencyLimit,
+    Unauthorized,
     Unexpected,
 }
 
@@ -197,10 +203,15 @@ impl<RspB: Default + hyper::body::HttpBody> respond::Respond<http::Response<RspB
                     }
                 }
 
-                // Gracefully teardown the server-side connection.
-                if let Some(ClientHandle { ref close, .. }) = self.client.as_ref() {
-                    debug!("Closing server-side connection");
-                    close.close();
+                // Gracefully teardown the server-side connection. Requests
+                // that were shed by a rate or concurrency limit or that were
+                // not authorized don't indicate a problem with the
+                // connection, so it's left open for subsequent requests.
+                if !is_load_shed(&*error) && !is_unauthorized(&*error) {
+                    if let Some(ClientHandle { ref close, .. }) = self.client.as_ref() {
+                        debug!("Closing server-side connection");
+                        close.close();
+                    }
                 }
 
                 if self.is_grpc {
@@ -228,15 +239,30 @@ impl<RspB: Default + hyper::body::HttpBody> respond::Respond<http::Response<RspB
     }
 }
 
+fn is_load_shed(error: &(dyn std::error::Error + 'static)) -> bool {
+    error.is::<RateLimited>()
+        || error.is::<LimitExceeded>()
+        || error.source().map(is_load_shed).unwrap_or(false)
+}
+
+fn is_unauthorized(error: &(dyn std::error::Error + 'static)) -> bool {
+    error.is::<Unauthorized>() || error.source().map(is_unauthorized).unwrap_or(false)
+}
+
 fn http_status(error: &(dyn std::error::Error + 'static)) -> StatusCode {
     if let Some(HttpError { http, .. }) = error.downcast_ref::<HttpError>() {
         *http
     } else if error.is::<ResponseTimeout>() {
         http::StatusCode::GATEWAY_TIMEOUT
-    } else if error.is::<FailFastError>() || error.is::<tower::timeout::error::Elapsed>() {
+    } else if error.is::<FailFastError>()
+        || error.is::<tower::timeout::error::Elapsed>()
+        || error.is::<LimitExceeded>()
+    {
         http::StatusCode::SERVICE_UNAVAILABLE
-    } else if error.is::<IdentityRequired>() {
+    } else if error.is::<IdentityRequired>() || error.is::<Unauthorized>() {
         http::StatusCode::FORBIDDEN
+    } else if error.is::<RateLimited>() {
+        http::StatusCode::TOO_MANY_REQUESTS
     } else if let Some(source) = error.source() {
         http_status(source)
     } else {
@@ -286,6 +312,26 @@ fn set_grpc_status(
             headers.insert(GRPC_MESSAGE, msg);
         }
         code
+    } else if error.is::<Unauthorized>() {
+        // The rule that denied the request is not exposed to the client.
+        let code = Code::PermissionDenied;
+        headers.insert(GRPC_STATUS, code_header(code));
+        headers.insert(GRPC_MESSAGE, HeaderValue::from_static("unauthorized"));
+        code
+    } else if let Some(e) = error.downcast_ref::<RateLimited>() {
+        let code = Code::ResourceExhausted;
+        headers.insert(GRPC_STATUS, code_header(code));
+        if let Ok(msg) = HeaderValue::from_str(&e.to_string()) {
+            headers.insert(GRPC_MESSAGE, msg);
+        }
+        code
+    } else if let Some(e) = error.downcast_ref::<LimitExceeded>() {
+        let code = Code::Unavailable;
+        headers.insert(GRPC_STATUS, code_header(code));
+        if let Ok(msg) = HeaderValue::from_str(&e.to_string()) {
+            headers.insert(GRPC_MESSAGE, msg);
+        }
+        code
     } else if error.is::<std::io::Error>() {
         let code = Code::Unavailable;
         headers.insert(GRPC_STATUS, code_header(code));
@@ -364,6 +410,12 @@ impl LabelError {
             Reason::DispatchTimeout
         } else if err.is::<IdentityRequired>() {
             Reason::IdentityRequired
+        } else if err.is::<Unauthorized>() {
+            Reason::Unauthorized
+        } else if err.is::<RateLimited>() {
+            Reason::RateLimited
+        } else if err.is::<LimitExceeded>() {
+            Reason::ConcurrencyLimit
         } else if let Some(e) = err.downcast_ref::<std::io::Error>() {
             Reason::Io(e.raw_os_error().map(Errno::from))
         } else if let Some(e) = err.source() {
@@ -394,6 +446,9 @@ impl metrics::FmtLabels for Reason {
                 Reason::IdentityRequired => "identity required",
                 Reason::GatewayLoop => "gateway loop",
                 Reason::NotFound => "not found",
+                Reason::RateLimited => "rate limited",
+                Reason::ConcurrencyLimit => "concurrency limit",
+                Reason::Unauthorized => "unauthorized",
                 Reason::Io(_) => "i/o",
                 Reason::Unexpected => "unexpected",
             }
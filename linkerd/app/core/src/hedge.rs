@@ -0,0 +1,157 @@
+use super::dst::Route;
+use super::http_metrics::{retries::Handle, Latencies};
+use super::http_tracing::attributes::SpanAttributes;
+use super::metrics::{HttpRoute, HttpRouteRetry};
+use super::retry;
+use crate::{profiles, proxy::balance::PickedEndpoint};
+use linkerd_retry::NewHedgeLayer;
+use linkerd_stack::Param;
+use std::time::Duration;
+use tracing::trace;
+
+/// Hedges requests on routes that configure hedging.
+///
+/// When `default` is set, it applies to retryable routes that don't configure
+/// hedging, since those routes are known to be idempotent.
+pub fn layer(
+    metrics: HttpRouteRetry,
+    latencies: HttpRoute,
+    default: Option<profiles::http::Hedge>,
+) -> NewHedgeLayer<NewHedge> {
+    NewHedgeLayer::new(NewHedge {
+        metrics,
+        latencies,
+        default,
+    })
+}
+
+#[derive(Clone, Debug)]
+pub struct NewHedge {
+    metrics: HttpRouteRetry,
+    latencies: HttpRoute,
+    default: Option<profiles::http::Hedge>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Hedge {
+    metrics: Handle,
+    latencies: Latencies,
+    latency_percentile: f64,
+}
+
+impl linkerd_retry::NewPolicy<Route> for NewHedge {
+    type Policy = Hedge;
+
+    fn new_policy(&self, route: &Route) -> Option<Self::Policy> {
+        let hedge = route
+            .route
+            .hedge()
+            .or_else(|| route.route.retries().and(self.default))?;
+
+        Some(Hedge {
+            metrics: self.metrics.get_handle(route.param()),
+            latencies: self.latencies.latencies(route.param()),
+            latency_percentile: hedge.latency_percentile(),
+        })
+    }
+}
+
+impl<B> linkerd_retry::HedgePolicy<http::Request<B>> for Hedge
+where
+    B: http_body::Body + Default,
+{
+    fn delay(&self, req: &http::Request<B>) -> Option<Duration> {
+        // Request bodies can't be read by two requests at once, so only
+        // requests without bodies are hedged.
+        if !req.body().is_end_stream() {
+            return None;
+        }
+
+        let delay = self.latencies.percentile(self.latency_percentile);
+        trace!(?delay, percentile = self.latency_percentile, "Hedge delay");
+        delay
+    }
+
+    fn clone_request(&self, req: &http::Request<B>) -> Option<http::Request<B>> {
+        let clone = retry::clone_request_head(req, B::default());
+        SpanAttributes::record(&clone, "retry.hedged", "true");
+        Some(clone)
+    }
+
+    /// Round-robin and ring-hash balancers dispatch the hedged request to a
+    /// different endpoint than the original request, when one is ready, so
+    /// that it is not sent to the endpoint that is slow to respond.
+    /// Power-of-two-choices balancers account for the original request in the
+    /// load of its endpoint, so they already tend to choose another endpoint.
+    fn link(&self, original: &mut http::Request<B>, hedge: &mut http::Request<B>) {
+        let picked = PickedEndpoint::default();
+        original.extensions_mut().insert(picked.clone());
+        hedge.extensions_mut().insert(picked);
+    }
+
+    fn on_hedge(&self) {
+        self.metrics.incr_hedged();
+    }
+
+    fn on_hedge_won(&self) {
+        self.metrics.incr_hedge_won();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        metrics::Direction,
+        proxy::http::{BoxBody, ClientHandle, SetClientHandle},
+    };
+    use futures::{future, FutureExt};
+    use linkerd_retry::{HedgePolicy, NewPolicy};
+    use tower::{service_fn, ServiceExt};
+
+    #[test]
+    fn hedged_requests_keep_client_and_span_attributes() {
+        let mut route = profiles::http::Route::default();
+        route.set_hedge(profiles::http::Hedge::new(50.0).unwrap());
+        let hedge = NewHedge {
+            metrics: Default::default(),
+            latencies: Default::default(),
+            default: None,
+        }
+        .new_policy(&Route {
+            target: "example.com:80".parse().unwrap(),
+            route,
+            direction: Direction::Out,
+        })
+        .expect("route must be hedged");
+
+        let client_addr = ([10, 0, 0, 1], 40000).into();
+        let (set_client, _closed) = SetClientHandle::new(
+            client_addr,
+            service_fn(|req: http::Request<BoxBody>| future::ok::<_, ()>(req)),
+        );
+        let req = http::Request::get("http://example.com/")
+            .body(BoxBody::default())
+            .unwrap();
+        let mut req = set_client.oneshot(req).now_or_never().unwrap().unwrap();
+        let attributes = SpanAttributes::default();
+        attributes.insert("route", "GET /");
+        req.extensions_mut().insert(attributes.clone());
+
+        let hedged = hedge.clone_request(&req).expect("request must be cloned");
+        let client = hedged.extensions().get::<ClientHandle>();
+        assert_eq!(client.map(|c| c.addr), Some(client_addr));
+
+        let hedged_attributes = hedged.extensions().get::<SpanAttributes>().unwrap();
+        assert_eq!(hedged_attributes.get("route").as_deref(), Some("GET /"));
+        assert_eq!(
+            hedged_attributes.get("retry.hedged").as_deref(),
+            Some("true")
+        );
+        assert_eq!(
+            attributes.get("retry.hedged"),
+            None,
+            "the original request's attributes must not change"
+        );
+    }
+}
@@ -2,6 +2,7 @@ use linkerd_error::Error;
 use linkerd_opencensus::proto::trace::v1 as oc;
 use linkerd_stack::layer;
 use linkerd_trace_context::{self as trace_context, TraceContext};
+pub use linkerd_trace_context::{attributes, sample};
 use std::{collections::HashMap, error, fmt, sync::Arc};
 use tokio::sync::mpsc;
 
@@ -38,18 +39,23 @@ impl fmt::Display for IdLengthError {
     }
 }
 
+/// Records server spans. When a sampler is provided, requests that arrive
+/// without a trace context may start a new trace.
 pub fn server<S>(
     sink: OpenCensusSink,
+    sampler: Option<sample::Sampler>,
+    headers: attributes::CaptureHeaders,
     labels: impl Into<Labels>,
 ) -> impl layer::Layer<S, Service = TraceContext<Option<SpanConverter>, S>> + Clone {
-    SpanConverter::layer(Kind::Server, sink, labels)
+    SpanConverter::layer(Kind::Server, sink, sampler, headers, labels)
 }
 
 pub fn client<S>(
     sink: OpenCensusSink,
+    headers: attributes::CaptureHeaders,
     labels: impl Into<Labels>,
 ) -> impl layer::Layer<S, Service = TraceContext<Option<SpanConverter>, S>> + Clone {
-    SpanConverter::layer(Kind::Client, sink, labels)
+    SpanConverter::layer(Kind::Client, sink, None, headers, labels)
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -62,13 +68,16 @@ impl SpanConverter {
     fn layer<S>(
         kind: Kind,
         sink: OpenCensusSink,
+        sampler: Option<sample::Sampler>,
+        headers: attributes::CaptureHeaders,
         labels: impl Into<Labels>,
     ) -> impl layer::Layer<S, Service = TraceContext<Option<Self>, S>> + Clone {
-        TraceContext::layer(sink.map(move |sink| Self {
+        let sink = sink.map(move |sink| Self {
             kind,
             sink,
             labels: labels.into(),
-        }))
+        });
+        TraceContext::layer(sink, sampler, headers)
     }
 
     fn mk_span(&self, mut span: trace_context::Span) -> Result<oc::Span, IdLengthError> {
@@ -85,7 +94,7 @@ impl SpanConverter {
         }
         for (k, v) in span.labels.drain() {
             attributes.insert(
-                k.to_string(),
+                k,
                 oc::AttributeValue {
                     value: Some(oc::attribute_value::Value::StringValue(truncatable(v))),
                 },
@@ -95,7 +104,12 @@ impl SpanConverter {
             trace_id: into_bytes(span.trace_id, 16)?,
             span_id: into_bytes(span.span_id, 8)?,
             tracestate: None,
-            parent_span_id: into_bytes(span.parent_id, 8)?,
+            parent_span_id: if span.parent_id.is_empty() {
+                // The span is the root of a trace started by the proxy.
+                Vec::new()
+            } else {
+                into_bytes(span.parent_id, 8)?
+            },
             name: Some(truncatable(span.span_name)),
             kind: self.kind as i32,
             start_time: Some(span.start.into()),
@@ -105,7 +119,11 @@ impl SpanConverter {
                 dropped_attributes_count: 0,
             }),
             stack_trace: None,
-            time_events: None,
+            time_events: Some(oc::span::TimeEvents {
+                time_event: span.events.into_iter().map(time_event).collect(),
+                dropped_annotations_count: 0,
+                dropped_message_events_count: 0,
+            }),
             links: None,
             status: None, // TODO: this is gRPC status; we must read response trailers to populate this
             resource: None,
@@ -141,6 +159,18 @@ fn into_bytes(id: trace_context::Id, size: usize) -> Result<Vec<u8>, IdLengthErr
     }
 }
 
+fn time_event(event: trace_context::Event) -> oc::span::TimeEvent {
+    oc::span::TimeEvent {
+        time: Some(event.time.into()),
+        value: Some(oc::span::time_event::Value::Annotation(
+            oc::span::time_event::Annotation {
+                description: Some(truncatable(event.name.to_string())),
+                attributes: None,
+            },
+        )),
+    }
+}
+
 fn truncatable(value: String) -> oc::TruncatableString {
     oc::TruncatableString {
         value,
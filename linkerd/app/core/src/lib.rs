@@ -10,17 +10,23 @@
 #![deny(warnings, rust_2018_idioms)]
 
 pub use linkerd_addr::{self as addr, Addr, NameAddr};
+pub use linkerd_authz as authz;
 pub use linkerd_cache as cache;
+pub use linkerd_concurrency_limit as concurrency_limit;
 pub use linkerd_conditional::Conditional;
 pub use linkerd_detect as detect;
 pub use linkerd_dns;
 pub use linkerd_drain as drain;
 pub use linkerd_error::{Error, Never, Recover};
 pub use linkerd_exp_backoff as exp_backoff;
+pub use linkerd_http_access_log as access_log;
 pub use linkerd_http_metrics as http_metrics;
 pub use linkerd_identity as identity;
 pub use linkerd_io as io;
 pub use linkerd_opencensus as opencensus;
+pub use linkerd_opentelemetry as opentelemetry;
+pub use linkerd_port_policy as port_policy;
+pub use linkerd_rate_limit as rate_limit;
 pub use linkerd_reconnect as reconnect;
 pub use linkerd_service_profiles as profiles;
 pub use linkerd_stack_metrics as stack_metrics;
@@ -38,6 +44,7 @@ pub mod dns;
 pub mod dst;
 pub mod errors;
 pub mod handle_time;
+pub mod hedge;
 pub mod http_tracing;
 pub mod metrics;
 pub mod proxy;
@@ -61,6 +68,12 @@ pub struct ProxyRuntime {
     pub metrics: metrics::Proxy,
     pub tap: proxy::tap::Registry,
     pub span_sink: http_tracing::OpenCensusSink,
+    pub span_sampler: Option<http_tracing::sample::Sampler>,
+    pub span_headers: http_tracing::attributes::CaptureHeaders,
+    pub access_log: Option<access_log::AccessLog>,
+    /// Authorizes inbound connections and requests. Unused by the outbound
+    /// proxy.
+    pub authorize: Option<authz::Authorize>,
     pub drain: drain::Watch,
 }
 
@@ -1,8 +1,9 @@
 pub use crate::{
     classify::{Class, SuccessOrFailure},
-    control, dst, errors, http_metrics, http_metrics as metrics, opencensus, proxy,
+    concurrency_limit, control, dst, errors, http_metrics, http_metrics as metrics, opencensus,
+    opentelemetry, proxy,
     proxy::identity,
-    stack_metrics,
+    rate_limit, stack_metrics,
     svc::Param,
     telemetry, tls,
     transport::{
@@ -21,6 +22,16 @@ pub type ControlHttp = http_metrics::Requests<ControlLabels, Class>;
 
 pub type HttpEndpoint = http_metrics::Requests<EndpointLabels, Class>;
 
+pub type EndpointEjection = proxy::discover::eject::Registry<EndpointLabels>;
+
+pub type ZoneRequests = proxy::balance::zone::Registry;
+
+pub type RateLimits = rate_limit::Registry<RateLimitLabels>;
+
+pub type ConcurrencyLimits = concurrency_limit::adaptive::Registry<StackLabels>;
+
+pub type HttpPool = proxy::http::h1::pool::Registry<OutboundEndpointLabels>;
+
 pub type HttpRoute = http_metrics::Requests<RouteLabels, Class>;
 
 pub type HttpRouteRetry = http_metrics::Retries<RouteLabels>;
@@ -33,6 +44,11 @@ pub struct Proxy {
     pub http_route_actual: HttpRoute,
     pub http_route_retry: HttpRouteRetry,
     pub http_endpoint: HttpEndpoint,
+    pub http_pool: HttpPool,
+    pub endpoint_ejection: EndpointEjection,
+    pub zone_requests: ZoneRequests,
+    pub rate_limits: RateLimits,
+    pub concurrency_limits: ConcurrencyLimits,
     pub http_errors: errors::MetricsLayer,
     pub stack: Stack,
     pub transport: transport::Metrics,
@@ -44,6 +60,7 @@ pub struct Metrics {
     pub outbound: Proxy,
     pub control: ControlHttp,
     pub opencensus: opencensus::metrics::Registry,
+    pub opentelemetry: opentelemetry::metrics::Registry,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -87,6 +104,13 @@ pub struct RouteLabels {
     labels: Option<String>,
 }
 
+/// Identifies the bucket charged by a rate-limited request.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitLabels {
+    Route(RouteLabels),
+    Client(tls::server::ClientId),
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Direction {
     In,
@@ -150,6 +174,16 @@ impl Metrics {
             (m, r.without_latencies())
         };
 
+        let http_pool = HttpPool::default();
+
+        let endpoint_ejection = EndpointEjection::default();
+
+        let zone_requests = ZoneRequests::default();
+
+        let rate_limits = RateLimits::default();
+
+        let concurrency_limits = ConcurrencyLimits::default();
+
         let http_errors = errors::Metrics::default();
 
         let stack = stack_metrics::Registry::default();
@@ -158,9 +192,16 @@ impl Metrics {
 
         let (opencensus, opencensus_report) = opencensus::metrics::new();
 
+        let (opentelemetry, opentelemetry_report) = opentelemetry::metrics::new();
+
         let metrics = Metrics {
             inbound: Proxy {
                 http_endpoint: http_endpoint.clone(),
+                http_pool: http_pool.clone(),
+                endpoint_ejection: endpoint_ejection.clone(),
+                zone_requests: zone_requests.clone(),
+                rate_limits: rate_limits.clone(),
+                concurrency_limits: concurrency_limits.clone(),
                 http_route: http_route.clone(),
                 http_route_actual: http_route_actual.clone(),
                 http_route_retry: http_route_retry.clone(),
@@ -170,6 +211,11 @@ impl Metrics {
             },
             outbound: Proxy {
                 http_endpoint,
+                http_pool: http_pool.clone(),
+                endpoint_ejection: endpoint_ejection.clone(),
+                zone_requests: zone_requests.clone(),
+                rate_limits: rate_limits.clone(),
+                concurrency_limits: concurrency_limits.clone(),
                 http_route,
                 http_route_retry,
                 http_route_actual,
@@ -179,22 +225,37 @@ impl Metrics {
             },
             control,
             opencensus,
+            opentelemetry,
         };
 
         let report = (http_errors.report())
             .and_then(endpoint_report)
+            .and_then(http_pool)
+            .and_then(endpoint_ejection)
+            .and_then(zone_requests)
+            .and_then(rate_limits)
+            .and_then(concurrency_limits)
             .and_then(route_report)
             .and_then(retry_report)
             .and_then(actual_report)
             .and_then(control_report)
             .and_then(transport_report)
             .and_then(opencensus_report)
+            .and_then(opentelemetry_report)
             .and_then(stack)
             .and_then(process)
             .and_then(build_info);
 
         (metrics, report)
     }
+
+    /// Logs the transports accepted and established by both proxies as they
+    /// close.
+    pub fn log_connections(mut self, enabled: bool) -> Self {
+        self.inbound.transport = self.inbound.transport.log_connections(enabled);
+        self.outbound.transport = self.outbound.transport.log_connections(enabled);
+        self
+    }
 }
 
 // === impl CtlLabels ===
@@ -242,6 +303,20 @@ impl FmtLabels for RouteLabels {
     }
 }
 
+// === impl RateLimitLabels ===
+
+impl FmtLabels for RateLimitLabels {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Route(route) => {
+                route.fmt_labels(f)?;
+                write!(f, ",limit=\"route\"")
+            }
+            Self::Client(id) => write!(f, "client_id=\"{}\",limit=\"client\"", id),
+        }
+    }
+}
+
 // === impl EndpointLabels ===
 
 impl From<InboundEndpointLabels> for EndpointLabels {
@@ -1,6 +1,7 @@
 //! Tools for building a transparent TCP/HTTP proxy.
 
 pub use linkerd_proxy_api_resolve as api_resolve;
+pub use linkerd_proxy_balance as balance;
 pub use linkerd_proxy_core as core;
 pub use linkerd_proxy_discover as discover;
 pub use linkerd_proxy_dns_resolve as dns_resolve;
@@ -1,57 +1,88 @@
 use super::classify;
 use super::dst::Route;
+use super::http_tracing::attributes::SpanAttributes;
 // use super::handle_time;
 use super::http_metrics::retries::Handle;
 use super::metrics::HttpRouteRetry;
-use crate::profiles;
-use futures::future;
-use hyper::body::HttpBody;
+use crate::{exp_backoff::ExponentialBackoff, profiles, proxy::http::ClientHandle};
+use linkerd_error::Error;
 use linkerd_http_classify::{Classify, ClassifyEos, ClassifyResponse};
+use linkerd_http_retry::ReplayBody;
 use linkerd_retry::NewRetryLayer;
-use linkerd_stack::Param;
-use std::marker::PhantomData;
-use std::sync::Arc;
+use linkerd_stack::{Either, Param};
+use std::{future::Future, io, pin::Pin, sync::Arc, time::Duration};
+use tokio::time;
 use tower::retry::budget::Budget;
+use tracing::trace;
 
-pub fn layer(metrics: HttpRouteRetry) -> NewRetryLayer<NewRetry> {
-    NewRetryLayer::new(NewRetry::new(metrics))
+#[derive(Copy, Clone, Debug)]
+pub struct Config {
+    /// Request bodies are buffered (up to `max_body_bytes`) so that they may
+    /// be replayed on retries.
+    pub max_body_bytes: usize,
+
+    /// The maximum number of times a request may be attempted, including the
+    /// original request, unless the route overrides it.
+    pub max_attempts: usize,
+
+    /// The backoff applied between attempts, unless the route overrides it.
+    pub backoff: ExponentialBackoff,
+
+    /// Whether requests that fail with a connection-level error before a
+    /// response is received (see `RetryableError`) may be retried.
+    pub retry_connection_errors: bool,
 }
 
-pub trait CloneRequest<Req> {
-    fn clone_request(req: &Req) -> Option<Req>;
+pub fn layer(metrics: HttpRouteRetry, config: Config) -> NewRetryLayer<NewRetry> {
+    NewRetryLayer::new(NewRetry::new(metrics, config))
 }
 
 #[derive(Clone, Debug)]
-pub struct NewRetry<C = ()> {
+pub struct NewRetry {
     metrics: HttpRouteRetry,
-    _clone_request: PhantomData<C>,
+    config: Config,
 }
 
-pub struct Retry<C = ()> {
+#[derive(Clone, Debug)]
+pub struct Retry {
     metrics: Handle,
     budget: Arc<Budget>,
     response_classes: profiles::http::ResponseClasses,
-    _clone_request: PhantomData<C>,
+    max_body_bytes: usize,
+    max_attempts: usize,
+    backoff: ExponentialBackoff,
+    retry_connection_errors: bool,
+
+    /// The number of times the request has been attempted so far.
+    attempts: usize,
 }
 
-impl NewRetry {
-    pub fn new(metrics: HttpRouteRetry) -> Self {
-        Self {
-            metrics,
-            _clone_request: PhantomData,
-        }
-    }
+/// A connection-level error that occurred before a response was received and
+/// which indicates that the request may safely be retried.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum RetryableError {
+    /// The server refused the stream with an HTTP/2 `REFUSED_STREAM` reset,
+    /// which guarantees that the request was not processed.
+    RefusedStream,
 
-    pub fn clone_requests_via<C>(self) -> NewRetry<C> {
-        NewRetry {
-            metrics: self.metrics,
-            _clone_request: PhantomData,
-        }
+    /// The request was canceled before it could be dispatched, e.g. because
+    /// the connection closed.
+    Canceled,
+
+    /// The connection was reset or closed before response headers were
+    /// received. The server may have processed the request, so only requests
+    /// with idempotent methods are retried.
+    ConnectionReset,
+}
+
+impl NewRetry {
+    pub fn new(metrics: HttpRouteRetry, config: Config) -> Self {
+        Self { metrics, config }
     }
 }
 
-impl<C> linkerd_retry::NewPolicy<Route> for NewRetry<C> {
-    type Policy = Retry<C>;
+impl linkerd_retry::NewPolicy<Route> for NewRetry {
+    type Policy = Retry;
 
     fn new_policy(&self, route: &Route) -> Option<Self::Policy> {
         let retries = route.route.retries().cloned()?;
@@ -61,24 +92,39 @@ impl<C> linkerd_retry::NewPolicy<Route> for NewRetry<C> {
             metrics,
             budget: retries.budget().clone(),
             response_classes: route.route.response_classes().clone(),
-            _clone_request: self._clone_request,
+            max_body_bytes: self.config.max_body_bytes,
+            max_attempts: retries.max_attempts().unwrap_or(self.config.max_attempts),
+            backoff: retries.backoff().unwrap_or(self.config.backoff),
+            retry_connection_errors: self.config.retry_connection_errors,
+            attempts: 1,
         })
     }
 }
 
-impl<C, A, B, E> linkerd_retry::Policy<http::Request<A>, http::Response<B>, E> for Retry<C>
-where
-    C: CloneRequest<http::Request<A>>,
-{
-    type Future = future::Ready<Self>;
+// === impl Retry ===
 
-    fn retry(
+impl Retry {
+    /// Determines whether the request should be attempted again and, if so,
+    /// how long to wait before doing so.
+    fn retry_delay<A, B>(
         &self,
-        req: &http::Request<A>,
-        result: Result<&http::Response<B>, &E>,
-    ) -> Option<Self::Future> {
+        req: &http::Request<ReplayBody<A>>,
+        result: Result<&http::Response<B>, &Error>,
+    ) -> Option<Duration> {
         let retryable = match result {
-            Err(_) => false,
+            Err(_) if !self.retry_connection_errors => false,
+            Err(error) => match RetryableError::classify(&**error) {
+                Some(error) if error.may_replay(req.method()) => {
+                    trace!(?error, "Classified error as retryable");
+                    self.metrics.incr_retryable_error(error.as_str());
+                    true
+                }
+                Some(error) => {
+                    trace!(?error, method = %req.method(), "Request may have been processed");
+                    false
+                }
+                None => false,
+            },
             Ok(rsp) => classify::Request::from(self.response_classes.clone())
                 .classify(req)
                 .start(rsp)
@@ -91,48 +137,228 @@ where
             return None;
         }
 
+        // The request body was too large to be buffered, so it can't be
+        // replayed.
+        if req.body().is_capped() {
+            self.metrics.incr_body_too_large();
+            return None;
+        }
+
+        if self.attempts >= self.max_attempts {
+            self.metrics.incr_max_attempts();
+            return None;
+        }
+
         let withdrew = self.budget.withdraw().is_ok();
         self.metrics.incr_retryable(withdrew);
         if !withdrew {
             return None;
         }
 
-        Some(future::ready(self.clone()))
+        let iterations = (self.attempts - 1) as u32;
+        Some(self.backoff.delay(iterations, &mut rand::thread_rng()))
     }
+}
+
+impl<A, B> linkerd_retry::Policy<http::Request<ReplayBody<A>>, http::Response<B>, Error> for Retry {
+    type Future = Pin<Box<dyn Future<Output = Self> + Send + 'static>>;
+
+    fn retry(
+        &self,
+        req: &http::Request<ReplayBody<A>>,
+        result: Result<&http::Response<B>, &Error>,
+    ) -> Option<Self::Future> {
+        let delay = match self.retry_delay(req, result) {
+            Some(delay) => delay,
+            None => {
+                self.metrics.observe_attempts(self.attempts);
+                return None;
+            }
+        };
 
-    fn clone_request(&self, req: &http::Request<A>) -> Option<http::Request<A>> {
-        C::clone_request(req)
+        trace!(attempts = self.attempts, ?delay, "Retrying request");
+        let next = Self {
+            attempts: self.attempts + 1,
+            ..self.clone()
+        };
+        Some(Box::pin(async move {
+            time::sleep(delay).await;
+            next
+        }))
     }
+
+    fn clone_request(
+        &self,
+        req: &http::Request<ReplayBody<A>>,
+    ) -> Option<http::Request<ReplayBody<A>>> {
+        // Requests are cloned even when their bodies can no longer be replayed
+        // so that the policy is still consulted (and metrics are recorded)
+        // when the response is received.
+        let clone = clone_request_head(req, req.body().clone());
+        SpanAttributes::record(&clone, "retry.attempt", (self.attempts + 1).to_string());
+
+        // // Count retries toward the request's total handle time.
+        // if let Some(ext) = req.extensions().get::<handle_time::Tracker>() {
+        //     clone.extensions_mut().insert(ext.clone());
+        // }
+
+        Some(clone)
+    }
+}
+
+/// Clones a request for another attempt, with the given body.
+///
+/// The clone keeps the request's `ClientHandle`, so that it's balanced and
+/// logged like the original request, and a fork of its span attributes, so
+/// that each attempt records its own.
+pub(crate) fn clone_request_head<A, B>(req: &http::Request<A>, body: B) -> http::Request<B> {
+    let mut clone = http::Request::new(body);
+    *clone.method_mut() = req.method().clone();
+    *clone.uri_mut() = req.uri().clone();
+    *clone.headers_mut() = req.headers().clone();
+    *clone.version_mut() = req.version();
+
+    if let Some(client) = req.extensions().get::<ClientHandle>() {
+        clone.extensions_mut().insert(client.clone());
+    }
+    if let Some(attributes) = req.extensions().get::<SpanAttributes>() {
+        clone.extensions_mut().insert(attributes.fork());
+    }
+
+    clone
 }
 
-impl<C> Clone for Retry<C> {
-    fn clone(&self) -> Self {
-        Self {
-            metrics: self.metrics.clone(),
-            budget: self.budget.clone(),
-            response_classes: self.response_classes.clone(),
-            _clone_request: self._clone_request,
+impl<A, B, E> linkerd_retry::PrepareRequest<http::Request<A>, http::Response<B>, E> for Retry
+where
+    A: http_body::Body,
+    A::Error: Into<Error>,
+{
+    type RetryRequest = http::Request<ReplayBody<A>>;
+
+    fn prepare_request(
+        &self,
+        req: http::Request<A>,
+    ) -> Either<Self::RetryRequest, http::Request<A>> {
+        let (head, body) = req.into_parts();
+        match ReplayBody::try_new(body, self.max_body_bytes) {
+            Ok(body) => Either::A(http::Request::from_parts(head, body)),
+            Err(body) => {
+                trace!(
+                    size = body.size_hint().lower(),
+                    "Request body is too large to be retried"
+                );
+                Either::B(http::Request::from_parts(head, body))
+            }
         }
     }
 }
 
-impl<B: Default + HttpBody> CloneRequest<http::Request<B>> for () {
-    fn clone_request(req: &http::Request<B>) -> Option<http::Request<B>> {
-        if !req.body().is_end_stream() {
-            return None;
+// === impl RetryableError ===
+
+impl RetryableError {
+    fn classify(err: &(dyn std::error::Error + 'static)) -> Option<Self> {
+        if let Some(e) = err.downcast_ref::<h2::Error>() {
+            if e.reason() == Some(h2::Reason::REFUSED_STREAM) {
+                return Some(Self::RefusedStream);
+            }
+        } else if let Some(e) = err.downcast_ref::<hyper::Error>() {
+            if e.is_canceled() {
+                return Some(Self::Canceled);
+            }
+            if e.is_incomplete_message() {
+                return Some(Self::ConnectionReset);
+            }
+        } else if let Some(e) = err.downcast_ref::<io::Error>() {
+            if e.kind() == io::ErrorKind::ConnectionReset {
+                return Some(Self::ConnectionReset);
+            }
         }
 
-        let mut clone = http::Request::new(B::default());
-        *clone.method_mut() = req.method().clone();
-        *clone.uri_mut() = req.uri().clone();
-        *clone.headers_mut() = req.headers().clone();
-        *clone.version_mut() = req.version();
+        err.source().and_then(Self::classify)
+    }
 
-        // // Count retries toward the request's total handle time.
-        // if let Some(ext) = req.extensions().get::<handle_time::Tracker>() {
-        //     clone.extensions_mut().insert(ext.clone());
-        // }
+    /// Returns true if a request with the given method may be replayed after
+    /// failing with this error.
+    fn may_replay(&self, method: &http::Method) -> bool {
+        match self {
+            // The request was never processed.
+            Self::RefusedStream | Self::Canceled => true,
+            Self::ConnectionReset => method.is_idempotent(),
+        }
+    }
 
-        Some(clone)
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::RefusedStream => "refused_stream",
+            Self::Canceled => "canceled",
+            Self::ConnectionReset => "connection_reset",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(error: impl Into<Error>) -> Option<RetryableError> {
+        let error = error.into();
+        RetryableError::classify(&*error)
+    }
+
+    #[test]
+    fn refused_stream_is_retryable() {
+        assert_eq!(
+            classify(h2::Error::from(h2::Reason::REFUSED_STREAM)),
+            Some(RetryableError::RefusedStream)
+        );
+        assert_eq!(classify(h2::Error::from(h2::Reason::CANCEL)), None);
+    }
+
+    #[test]
+    fn connection_reset_is_retryable() {
+        assert_eq!(
+            classify(io::Error::from(io::ErrorKind::ConnectionReset)),
+            Some(RetryableError::ConnectionReset)
+        );
+        assert_eq!(
+            classify(io::Error::from(io::ErrorKind::ConnectionRefused)),
+            None
+        );
+    }
+
+    #[test]
+    fn connection_reset_only_replays_idempotent_methods() {
+        let reset = RetryableError::ConnectionReset;
+        assert!(reset.may_replay(&http::Method::GET));
+        assert!(reset.may_replay(&http::Method::PUT));
+        assert!(reset.may_replay(&http::Method::DELETE));
+        assert!(!reset.may_replay(&http::Method::POST));
+        assert!(!reset.may_replay(&http::Method::PATCH));
+
+        assert!(RetryableError::RefusedStream.may_replay(&http::Method::POST));
+        assert!(RetryableError::Canceled.may_replay(&http::Method::POST));
+    }
+
+    #[test]
+    fn classifies_error_sources() {
+        #[derive(Debug)]
+        struct Wrapped(io::Error);
+
+        impl std::fmt::Display for Wrapped {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "wrapped: {}", self.0)
+            }
+        }
+
+        impl std::error::Error for Wrapped {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        assert_eq!(
+            classify(Wrapped(io::ErrorKind::ConnectionReset.into())),
+            Some(RetryableError::ConnectionReset)
+        );
     }
 }
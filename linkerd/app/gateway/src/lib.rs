@@ -78,11 +78,13 @@ where
     O: svc::Service<outbound::http::Endpoint, Error = io::Error>
         + svc::Service<outbound::tcp::Endpoint, Error = io::Error>,
     O: Clone + Send + Sync + Unpin + 'static,
+    <O as svc::Service<outbound::http::Endpoint>>::Response: tls::HasNegotiatedProtocol,
     <O as svc::Service<outbound::http::Endpoint>>::Response:
-        io::AsyncRead + io::AsyncWrite + tls::HasNegotiatedProtocol + Send + Unpin + 'static,
+        io::AsyncRead + io::AsyncWrite + io::PeerAddr + Send + Unpin + 'static,
     <O as svc::Service<outbound::http::Endpoint>>::Future: Send + Unpin + 'static,
+    <O as svc::Service<outbound::tcp::Endpoint>>::Response: tls::HasNegotiatedProtocol,
     <O as svc::Service<outbound::tcp::Endpoint>>::Response:
-        io::AsyncRead + io::AsyncWrite + tls::HasNegotiatedProtocol + Send + Unpin + 'static,
+        io::AsyncRead + io::AsyncWrite + io::PeerAddr + Send + Unpin + 'static,
     <O as svc::Service<outbound::tcp::Endpoint>>::Future: Send + Unpin + 'static,
     P: profiles::GetProfile<profiles::LogicalAddr> + Clone + Send + Sync + Unpin + 'static,
     P::Future: Send + 'static,
@@ -0,0 +1,105 @@
+use crate::{direct::ClientInfo, target::TcpAccept};
+use linkerd_app_core::{
+    authz::{self, HasClient},
+    svc::stack::Predicate,
+    transport_header::TransportHeader,
+    Error,
+};
+
+/// A connection policy that fails connections that are not decoded as HTTP if
+/// the authorization policy doesn't allow their clients.
+///
+/// HTTP connections are not checked; instead, each of their requests is
+/// authorized.
+#[derive(Clone, Debug)]
+pub struct AuthorizeConnections(Option<authz::Authorize>);
+
+// === impl AuthorizeConnections ===
+
+impl From<Option<authz::Authorize>> for AuthorizeConnections {
+    fn from(authorize: Option<authz::Authorize>) -> Self {
+        Self(authorize)
+    }
+}
+
+impl Predicate<TcpAccept> for AuthorizeConnections {
+    type Request = TcpAccept;
+
+    fn check(&mut self, meta: TcpAccept) -> Result<TcpAccept, Error> {
+        if let Some(authorize) = self.0.as_ref() {
+            authorize.check_connection(meta.authz_port(), meta.authz_client())?;
+        }
+        Ok(meta)
+    }
+}
+
+/// Direct connections are authorized against the policy of the port named by
+/// their transport header, using the client's mTLS identity.
+///
+/// Connections that name a target are not forwarded to a local port; they are
+/// handled by the gateway.
+impl Predicate<(TransportHeader, ClientInfo)> for AuthorizeConnections {
+    type Request = (TransportHeader, ClientInfo);
+
+    fn check(
+        &mut self,
+        (header, client): (TransportHeader, ClientInfo),
+    ) -> Result<Self::Request, Error> {
+        if let (Some(authorize), None) = (self.0.as_ref(), header.name.as_ref()) {
+            let authz_client = authz::Client {
+                identity: Some(&client.client_id.0),
+                addr: client.client_addr.as_ref().ip(),
+            };
+            authorize.check_connection(header.port, authz_client)?;
+        }
+        Ok((header, client))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_app_core::{
+        tls,
+        transport::{ClientAddr, Remote},
+    };
+    use std::sync::Arc;
+
+    fn authorize(policy: &str) -> AuthorizeConnections {
+        let policy = authz::Policy::from_json(policy.as_bytes()).unwrap();
+        let (_tx, rx) = tokio::sync::watch::channel(Arc::new(policy));
+        AuthorizeConnections::from(Some(authz::Authorize::new(rx)))
+    }
+
+    fn direct(port: u16, id: &str) -> (TransportHeader, ClientInfo) {
+        let header = TransportHeader {
+            port,
+            name: None,
+            protocol: None,
+        };
+        let client = ClientInfo {
+            client_id: tls::ClientId(id.parse().unwrap()),
+            alpn: None,
+            client_addr: Remote(ClientAddr(([10, 0, 0, 1], 40000).into())),
+            local_addr: ([10, 0, 0, 2], 4143).into(),
+        };
+        (header, client)
+    }
+
+    #[test]
+    fn denies_direct_connections() {
+        let mut authorize = authorize(
+            r#"{"rules": [{
+                "name": "web",
+                "ports": [8080],
+                "identities": ["*.web.serviceaccount.identity.linkerd.cluster.local"]
+            }]}"#,
+        );
+        let web = "default.web.serviceaccount.identity.linkerd.cluster.local";
+        let ops = "default.ops.serviceaccount.identity.linkerd.cluster.local";
+
+        assert!(authorize.check(direct(8080, web)).is_ok());
+        assert!(authorize.check(direct(8080, ops)).is_err());
+        assert!(authorize.check(direct(9090, ops)).is_ok());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{target::TcpEndpoint, Inbound};
+use crate::{authorize::AuthorizeConnections, target::TcpEndpoint, Inbound};
 use linkerd_app_core::{
     io,
     proxy::identity::LocalCrtKey,
@@ -93,6 +93,7 @@ impl<T> Inbound<T> {
             stack: tcp,
         } = self;
         let detect_timeout = config.proxy.detect_protocol_timeout;
+        let authorize = AuthorizeConnections::from(rt.authorize.clone());
 
         let stack = tcp
             .instrument(|_: &TcpEndpoint| debug_span!("opaque"))
@@ -130,6 +131,9 @@ impl<T> Inbound<T> {
                     .instrument(|g: &GatewayTransportHeader| info_span!("gateway", dst = %g.target))
                     .into_inner(),
             )
+            // Connections forwarded to a local port must be allowed by that
+            // port's authorization policy.
+            .push_request_filter(authorize)
             // Use ALPN to determine whether a transport header should be read.
             //
             // When the transport header is not present, perform HTTP detection to
@@ -8,11 +8,11 @@ pub use linkerd_app_core::proxy::http::{
     Version,
 };
 use linkerd_app_core::{
-    classify,
+    access_log, authz, classify,
     config::{ProxyConfig, ServerConfig},
     dst, errors, http_tracing, io, profiles,
     proxy::{http, tap},
-    reconnect,
+    rate_limit, reconnect,
     svc::{self, Param},
     Error, DST_OVERRIDE_HEADER,
 };
@@ -64,6 +64,13 @@ impl<H> Inbound<H> {
                 svc::layers()
                     // Downgrades the protocol if upgraded by an outbound proxy.
                     .push(http::orig_proto::Downgrade::layer())
+                    // Shed requests in excess of a limit that adapts to the
+                    // application's response latency. Unlike the fixed limit
+                    // below, excess requests fail immediately.
+                    .push(rt.metrics.concurrency_limits.layer(
+                        crate::stack_labels("http", "server"),
+                        config.adaptive_concurrency_limit,
+                    ))
                     // Limit the number of in-flight requests. When the proxy is
                     // at capacity, go into failfast after a dispatch timeout.
                     // Note that the inner service _always_ returns ready (due
@@ -75,11 +82,19 @@ impl<H> Inbound<H> {
                     .push(rt.metrics.http_errors.clone())
                     // Synthesizes responses for proxy errors.
                     .push(errors::layer())
-                    .push(http_tracing::server(rt.span_sink.clone(), trace_labels()))
+                    .push(http_tracing::server(
+                        rt.span_sink.clone(),
+                        rt.span_sampler.clone(),
+                        rt.span_headers.clone(),
+                        trace_labels(),
+                    ))
                     // Record when an HTTP/1 URI was in absolute form
                     .push(http::normalize_uri::MarkAbsoluteForm::layer())
-                    .push(http::BoxRequest::layer())
-                    .push(http::BoxResponse::layer()),
+                    .push(http::BoxResponse::layer())
+                    // Logs requests with the attributes recorded on their
+                    // server spans.
+                    .push(access_log::layer(rt.access_log.clone(), "inbound"))
+                    .push(http::BoxRequest::layer()),
             )
             .check_new_service::<T, http::Request<_>>()
             .instrument(|t: &T| debug_span!("http", v=%Param::<Version>::param(t)))
@@ -96,7 +111,7 @@ impl<H> Inbound<H> {
 impl<C> Inbound<C>
 where
     C: svc::Service<TcpEndpoint> + Clone + Send + Sync + Unpin + 'static,
-    C::Response: io::AsyncRead + io::AsyncWrite + Send + Unpin + 'static,
+    C::Response: io::AsyncRead + io::AsyncWrite + io::PeerAddr + Send + Unpin + 'static,
     C::Error: Into<Error>,
     C::Future: Send + Unpin,
 {
@@ -125,13 +140,16 @@ where
             stack: connect,
         } = self;
 
-        // Creates HTTP clients for each inbound port & HTTP settings.
+        // Creates HTTP clients for each inbound port & HTTP settings. The
+        // clients for each port share a connection limit, though their pools
+        // are not reported.
         let endpoint = connect
             .push(rt.metrics.transport.layer_connect())
             .push_map_target(TcpEndpoint::from)
-            .push(http::client::layer(
+            .push(http::client::layer_with_pool_metrics(
                 config.proxy.connect.h1_settings,
                 config.proxy.connect.h2_settings,
+                http::h1::pool::Registry::<u16>::default(),
             ))
             .push(reconnect::layer({
                 let backoff = config.proxy.connect.backoff;
@@ -145,7 +163,11 @@ where
             .push(tap::NewTapHttp::layer(rt.tap.clone()))
             // Records metrics for each `Target`.
             .push(rt.metrics.http_endpoint.to_layer::<classify::Response, _>())
-            .push_on_response(http_tracing::client(rt.span_sink.clone(), trace_labels()))
+            .push_on_response(http_tracing::client(
+                rt.span_sink.clone(),
+                rt.span_headers.clone(),
+                trace_labels(),
+            ))
             .push_on_response(http::BoxResponse::layer())
             .check_new_service::<Target, http::Request<_>>();
 
@@ -160,6 +182,12 @@ where
             .push_map_target(Target::from)
             .push(profiles::http::route_request::layer(
                 svc::proxies()
+                    // Rejects requests once the route's rate limit is
+                    // exceeded.
+                    .push(rate_limit::NewRateLimit::layer(
+                        config.route_rate_limit,
+                        rt.metrics.rate_limits.clone(),
+                    ))
                     // Sets the route as a request extension so that it can be used
                     // by tap.
                     .push_http_insert_target::<dst::Route>()
@@ -168,6 +196,13 @@ where
                     // Sets the per-route response classifier as a request
                     // extension.
                     .push(classify::NewClassify::layer())
+                    // Revises the sampling decision of traces started by the
+                    // proxy with the route's sample rate.
+                    .push(http_tracing::sample::NewSampleRoute::layer(
+                        rt.span_sampler.clone(),
+                    ))
+                    // Records the route on the request's spans.
+                    .push(http_tracing::attributes::NewRecordAttributes::layer())
                     .check_new_clone::<dst::Route>()
                     .push_map_target(target::route)
                     .into_inner(),
@@ -222,6 +257,16 @@ where
             // target, and dispatches the request.
             .instrument_from_target()
             .push(svc::NewRouter::layer(RequestTarget::from))
+            // Rejects requests once the client's rate limit is exceeded.
+            .push(rate_limit::NewRateLimit::layer(
+                config.client_rate_limit,
+                rt.metrics.rate_limits.clone(),
+            ))
+            // Rejects requests that the authorization policy doesn't allow
+            // before they're counted against the client's rate limit.
+            .push(authz::NewAuthorizeRequests::layer(rt.authorize.clone()))
+            // Records the client on the request's spans and access log.
+            .push(http_tracing::attributes::NewRecordAttributes::layer())
             // Used by tap.
             .push_http_insert_target::<HttpAccept>();
 
@@ -6,6 +6,7 @@
 #![deny(warnings, rust_2018_idioms)]
 
 mod allow_discovery;
+mod authorize;
 pub mod direct;
 pub mod http;
 mod prevent_loop;
@@ -16,15 +17,17 @@ pub(crate) mod test_util;
 
 pub use self::target::{HttpEndpoint, Logical, RequestTarget, Target, TcpEndpoint};
 use self::{
+    authorize::AuthorizeConnections,
     prevent_loop::PreventLoop,
     require_identity::RequireIdentityForPorts,
     target::{HttpAccept, TcpAccept},
 };
 use linkerd_app_core::{
+    concurrency_limit,
     config::{ConnectConfig, ProxyConfig},
-    detect, drain, io, metrics, profiles,
+    detect, drain, io, metrics, port_policy, profiles,
     proxy::tcp,
-    serve,
+    rate_limit, serve,
     svc::{self, Param},
     tls,
     transport::{self, listen, Remote, ServerAddr},
@@ -40,10 +43,16 @@ pub struct Config {
     pub require_identity_for_inbound_ports: RequireIdentityForPorts,
     pub disable_protocol_detection_for_ports: SkipByPort,
     pub profile_idle_timeout: Duration,
+    /// Limits the rate of requests to each route.
+    pub route_rate_limit: Option<rate_limit::Limit>,
+    /// Limits the rate of requests from each authenticated client identity.
+    pub client_rate_limit: Option<rate_limit::Limit>,
+    /// Limits in-flight requests to a limit that adapts to response latency.
+    pub adaptive_concurrency_limit: Option<concurrency_limit::adaptive::Config>,
 }
 
 #[derive(Clone, Debug)]
-pub struct SkipByPort(std::sync::Arc<indexmap::IndexSet<u16>>);
+pub struct SkipByPort(port_policy::Ports);
 
 #[derive(Clone, Debug)]
 pub struct Inbound<S> {
@@ -54,6 +63,25 @@ pub struct Inbound<S> {
 
 // === impl Config ===
 
+impl Config {
+    /// Reads the ports that skip protocol detection and that require client
+    /// identities from `policy` as each connection is accepted, rather than
+    /// from the static configuration.
+    pub fn with_port_policy(self, policy: port_policy::Receiver) -> Self {
+        Self {
+            require_identity_for_inbound_ports: RequireIdentityForPorts::new(
+                port_policy::Ports::inbound_require_identity(policy.clone()),
+            ),
+            disable_protocol_detection_for_ports: SkipByPort::from(
+                port_policy::Ports::inbound_opaque(policy),
+            ),
+            ..self
+        }
+    }
+}
+
+// === impl Inbound ===
+
 impl<S> Inbound<S> {
     pub fn config(&self) -> &Config {
         &self.config
@@ -110,7 +138,7 @@ impl Inbound<()> {
         } = config.proxy.connect;
 
         let stack = svc::stack(transport::ConnectTcp::new(keepalive))
-            .push_map_target(|t: T| Remote(ServerAddr(([127, 0, 0, 1], t.param()).into())))
+            .push_map_target(|t: T| local_server_addr(t.param()))
             // Limits the time we wait for a connection to be established.
             .push_timeout(timeout)
             .push(svc::stack::BoxFuture::layer());
@@ -162,7 +190,7 @@ impl Inbound<()> {
 impl<C> Inbound<C>
 where
     C: svc::Service<TcpEndpoint> + Clone + Send + Sync + Unpin + 'static,
-    C::Response: io::AsyncRead + io::AsyncWrite + Send + Unpin + 'static,
+    C::Response: io::AsyncRead + io::AsyncWrite + io::PeerAddr + Send + Unpin + 'static,
     C::Error: Into<Error>,
     C::Future: Send + Unpin,
 {
@@ -231,6 +259,7 @@ where
     {
         let disable_detect = self.config.disable_protocol_detection_for_ports.clone();
         let require_id = self.config.require_identity_for_inbound_ports.clone();
+        let authorize = AuthorizeConnections::from(self.runtime.authorize.clone());
         let config = self.config.proxy.clone();
         self.clone()
             .push_http_router(profiles)
@@ -239,11 +268,12 @@ where
             .push_map_target(HttpAccept::from)
             .push(svc::UnwrapOr::layer(
                 // When HTTP detection fails, forward the connection to the
-                // application as an opaque TCP stream.
+                // application as an opaque TCP stream if it's authorized.
                 self.clone()
                     .push_tcp_forward(server_port)
                     .stack
                     .push_map_target(TcpEndpoint::from)
+                    .push_request_filter(authorize.clone())
                     .into_inner(),
             ))
             .push_map_target(detect::allow_timeout)
@@ -265,6 +295,7 @@ where
                     .push_tcp_forward(server_port)
                     .stack
                     .push_map_target(TcpEndpoint::from)
+                    .push_request_filter(authorize)
                     .push(self.runtime.metrics.transport.layer_accept())
                     .push_map_target(TcpAccept::port_skipped)
                     .instrument(|_: &_| debug_span!("forward"))
@@ -293,11 +324,17 @@ impl From<indexmap::IndexSet<u16>> for SkipByPort {
     }
 }
 
+impl From<port_policy::Ports> for SkipByPort {
+    fn from(ports: port_policy::Ports) -> Self {
+        SkipByPort(ports)
+    }
+}
+
 impl svc::Predicate<listen::Addrs> for SkipByPort {
     type Request = svc::Either<listen::Addrs, listen::Addrs>;
 
     fn check(&mut self, t: listen::Addrs) -> Result<Self::Request, Error> {
-        if !self.0.contains(&t.target_addr().port()) {
+        if !self.0.contains(t.target_addr().port()) {
             Ok(svc::Either::A(t))
         } else {
             Ok(svc::Either::B(t))
@@ -305,6 +342,47 @@ impl svc::Predicate<listen::Addrs> for SkipByPort {
     }
 }
 
+/// The address of the application server listening on `port`, to which
+/// inbound connections are forwarded.
+pub(crate) fn local_server_addr(port: u16) -> Remote<ServerAddr> {
+    Remote(ServerAddr(([127, 0, 0, 1], port).into()))
+}
+
 fn stack_labels(proto: &'static str, name: &'static str) -> metrics::StackLabels {
     metrics::StackLabels::inbound(proto, name)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::default_config;
+    use linkerd_app_core::{
+        svc::Predicate,
+        transport::{ClientAddr, Local, OrigDstAddr},
+    };
+    use std::sync::Arc;
+    use tokio::sync::watch;
+
+    #[test]
+    fn skips_detection_for_ports_added_to_policy() {
+        let (tx, rx) = watch::channel(Arc::new(port_policy::PortPolicy::default()));
+        let config = default_config(([10, 0, 0, 2], 3306).into()).with_port_policy(rx);
+        let mut skip = config.disable_protocol_detection_for_ports;
+        let addrs = listen::Addrs::new(
+            Local(ServerAddr(([10, 0, 0, 2], 4143).into())),
+            Remote(ClientAddr(([10, 0, 0, 1], 40000).into())),
+            Some(OrigDstAddr(([10, 0, 0, 2], 3306).into())),
+        );
+        assert!(matches!(skip.check(addrs.clone()), Ok(svc::Either::A(_))));
+
+        tx.send(Arc::new(port_policy::PortPolicy {
+            inbound_opaque_ports: vec![3306..=3306],
+            ..port_policy::PortPolicy::default()
+        }))
+        .unwrap();
+        assert!(
+            matches!(skip.check(addrs), Ok(svc::Either::B(_))),
+            "ports added to the policy must skip detection"
+        );
+    }
+}
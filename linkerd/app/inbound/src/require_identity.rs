@@ -1,13 +1,11 @@
 use crate::target::TcpAccept;
-use indexmap::IndexSet;
-use linkerd_app_core::{svc::stack::Predicate, tls, Conditional, Error};
-use std::sync::Arc;
+use linkerd_app_core::{port_policy::Ports, svc::stack::Predicate, tls, Conditional, Error};
 
 /// A connection policy that fails connections that don't have a client identity
 /// if they target one of the configured local ports.
 #[derive(Clone, Debug)]
 pub struct RequireIdentityForPorts {
-    ports: Arc<IndexSet<u16>>,
+    ports: Ports,
 }
 
 #[derive(Debug)]
@@ -15,11 +13,15 @@ pub struct IdentityRequired(());
 
 // === impl RequireIdentityForPorts ===
 
+impl RequireIdentityForPorts {
+    pub fn new(ports: Ports) -> Self {
+        Self { ports }
+    }
+}
+
 impl<T: IntoIterator<Item = u16>> From<T> for RequireIdentityForPorts {
     fn from(ports: T) -> Self {
-        Self {
-            ports: Arc::new(ports.into_iter().collect()),
-        }
+        Self::new(ports.into_iter().collect::<indexmap::IndexSet<_>>().into())
     }
 }
 
@@ -28,7 +30,7 @@ impl Predicate<TcpAccept> for RequireIdentityForPorts {
 
     fn check(&mut self, meta: TcpAccept) -> Result<TcpAccept, Error> {
         let port = meta.target_addr.port();
-        let id_required = self.ports.contains(&port);
+        let id_required = self.ports.contains(port);
 
         tracing::debug!(%port, tls = ?meta.tls, %id_required);
         if id_required {
@@ -1,9 +1,12 @@
 use indexmap::IndexMap;
 use linkerd_app_core::{
+    authz::{self, HasClient},
     classify, dst, http_request_authority_addr, http_request_host_addr,
-    http_request_l5d_override_dst_addr, metrics, profiles,
+    http_request_l5d_override_dst_addr,
+    http_tracing::attributes::HasSpanAttributes,
+    metrics, profiles,
     proxy::{http, tap},
-    stack_tracing,
+    rate_limit, stack_tracing,
     svc::{self, Param},
     tls,
     transport::{self, addrs::*, listen},
@@ -95,6 +98,26 @@ impl Param<transport::labels::Key> for TcpAccept {
     }
 }
 
+impl HasClient for TcpAccept {
+    fn authz_port(&self) -> u16 {
+        self.target_addr.port()
+    }
+
+    fn authz_client(&self) -> authz::Client<'_> {
+        let identity = match self.tls {
+            Conditional::Some(tls::ServerTls::Established {
+                client_id: Some(ref id),
+                ..
+            }) => Some(&id.0),
+            _ => None,
+        };
+        authz::Client {
+            identity,
+            addr: self.client_addr.as_ref().ip(),
+        }
+    }
+}
+
 // === impl HttpAccept ===
 
 impl From<(http::Version, TcpAccept)> for HttpAccept {
@@ -118,6 +141,46 @@ impl Param<http::normalize_uri::DefaultAuthority> for HttpAccept {
     }
 }
 
+/// Requests are limited by the client's identity. Requests from
+/// unauthenticated clients are not limited.
+impl rate_limit::HasRateLimit for HttpAccept {
+    type Key = metrics::RateLimitLabels;
+
+    fn rate_limit_key(&self) -> Option<metrics::RateLimitLabels> {
+        match self.tcp.tls {
+            Conditional::Some(tls::ServerTls::Established {
+                client_id: Some(ref id),
+                ..
+            }) => Some(metrics::RateLimitLabels::Client(id.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl HasClient for HttpAccept {
+    fn authz_port(&self) -> u16 {
+        self.tcp.authz_port()
+    }
+
+    fn authz_client(&self) -> authz::Client<'_> {
+        self.tcp.authz_client()
+    }
+}
+
+impl HasSpanAttributes for HttpAccept {
+    fn span_attributes(&self) -> Vec<(&'static str, String)> {
+        let mut attributes = vec![("peer.addr", self.tcp.client_addr.to_string())];
+        if let Conditional::Some(tls::ServerTls::Established {
+            client_id: Some(ref id),
+            ..
+        }) = self.tcp.tls
+        {
+            attributes.push(("peer.identity", id.to_string()));
+        }
+        attributes
+    }
+}
+
 // === impl HttpEndpoint ===
 
 impl Param<http::client::Settings> for HttpEndpoint {
@@ -126,6 +189,12 @@ impl Param<http::client::Settings> for HttpEndpoint {
     }
 }
 
+impl Param<u16> for HttpEndpoint {
+    fn param(&self) -> u16 {
+        self.port
+    }
+}
+
 impl From<Target> for HttpEndpoint {
     fn from(target: Target) -> Self {
         Self {
@@ -164,6 +233,12 @@ impl Param<u16> for TcpEndpoint {
     }
 }
 
+impl Param<Remote<ServerAddr>> for TcpEndpoint {
+    fn param(&self) -> Remote<ServerAddr> {
+        crate::local_server_addr(self.port)
+    }
+}
+
 impl Param<transport::labels::Key> for TcpEndpoint {
     fn param(&self) -> transport::labels::Key {
         transport::labels::Key::InboundConnect
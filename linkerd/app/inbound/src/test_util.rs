@@ -43,6 +43,8 @@ pub fn default_config(orig_dst: SocketAddr) -> Config {
                 h1_settings: h1::PoolSettings {
                     max_idle: 1,
                     idle_timeout: Duration::from_secs(1),
+                    max_connections: None,
+                    connect_timeout: Duration::from_secs(1),
                 },
                 h2_settings: h2::Settings::default(),
             },
@@ -55,6 +57,9 @@ pub fn default_config(orig_dst: SocketAddr) -> Config {
         require_identity_for_inbound_ports: RequireIdentityForPorts::from(None),
         disable_protocol_detection_for_ports: SkipByPort::from(indexmap::IndexSet::default()),
         profile_idle_timeout: Duration::from_millis(500),
+        route_rate_limit: None,
+        client_rate_limit: None,
+        adaptive_concurrency_limit: None,
     }
 }
 
@@ -67,6 +72,10 @@ pub fn runtime() -> (ProxyRuntime, drain::Signal) {
         metrics: metrics.outbound,
         tap,
         span_sink: None,
+        span_sampler: None,
+        span_headers: Default::default(),
+        access_log: None,
+        authorize: None,
         drain,
     };
     (runtime, drain_tx)
@@ -143,6 +143,14 @@ async fn retry_if_profile_allows() {
         budget: Some(controller::retry_budget(Duration::from_secs(10), 0.1, 1)),
         with_client: |client: client::Client| async move {
             assert_eq!(client.get("/0.5").await, "retried");
+        },
+        with_metrics: |metrics: client::Client, port| async move {
+            metrics::metric("route_response_attempts_sum")
+                .label("direction", "outbound")
+                .label("dst", format_args!("profiles.test.svc.cluster.local:{}", port))
+                .value(2u64)
+                .assert_in(&metrics)
+                .await;
         }
     }
 }
@@ -211,7 +219,7 @@ async fn does_not_retry_if_earlier_response_class_is_success() {
 }
 
 #[tokio::test]
-async fn does_not_retry_if_request_has_body() {
+async fn retry_with_small_request_body() {
     profile_test! {
         routes: [
             controller::route()
@@ -226,6 +234,28 @@ async fn does_not_retry_if_request_has_body() {
                 .body("req has a body".into())
                 .unwrap();
             let res = client.request_body(req).await;
+            assert_eq!(res.status(), 200);
+        }
+    }
+}
+
+#[tokio::test]
+async fn does_not_retry_if_request_body_is_too_large() {
+    profile_test! {
+        routes: [
+            controller::route()
+                .request_any()
+                .response_failure(500..600)
+                .retryable(true)
+        ],
+        budget: Some(controller::retry_budget(Duration::from_secs(10), 0.1, 1)),
+        with_client: |client: client::Client| async move {
+            // The default limit on buffered request bodies is 64KiB.
+            let req = client.request_builder("/0.5")
+                .method("POST")
+                .body(vec![b'x'; 64 * 1024 + 1].into())
+                .unwrap();
+            let res = client.request_body(req).await;
             assert_eq!(res.status(), 533);
         }
     }
@@ -1463,3 +1463,94 @@ async fn http1_orig_proto_does_not_propagate_rst_stream() {
 
     proxy.join_servers().await;
 }
+
+#[tokio::test]
+async fn inbound_http2_max_concurrent_streams() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let _trace = trace_init();
+
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let max_in_flight = Arc::new(AtomicUsize::new(0));
+    let srv = {
+        let in_flight = in_flight.clone();
+        let max_in_flight = max_in_flight.clone();
+        server::http2()
+            .route_async("/", move |_| {
+                let in_flight = in_flight.clone();
+                let max_in_flight = max_in_flight.clone();
+                async move {
+                    let n = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(n, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    Ok::<_, std::io::Error>(Response::new(Bytes::new()))
+                }
+            })
+            .run()
+            .await
+    };
+
+    let mut env = TestEnv::default();
+    env.put(
+        "LINKERD2_PROXY_INBOUND_HTTP2_MAX_CONCURRENT_STREAMS",
+        "1".to_string(),
+    );
+    let proxy = proxy::new()
+        .inbound_fuzz_addr(srv)
+        .run_with_test_env(env)
+        .await;
+    let client = client::http2(proxy.inbound, "transparency.example.com");
+
+    // The client may only open one stream at a time, so requests are
+    // dispatched to the server one after another.
+    let rsps = future::join_all((0..3).map(|_| client.request(client.request_builder("/")))).await;
+    for rsp in rsps {
+        assert_eq!(rsp.expect("response").status(), StatusCode::OK);
+    }
+    assert_eq!(max_in_flight.load(Ordering::SeqCst), 1);
+
+    proxy.join_servers().await;
+}
+
+#[tokio::test]
+async fn inbound_http2_adaptive_window_and_max_frame_size() {
+    let _trace = trace_init();
+
+    // Large enough to exceed the default window sizes and span many frames.
+    let body = "x".repeat(4 * 1024 * 1024);
+
+    let srv = server::http2()
+        .route_async("/", |req| async move {
+            let body = http_util::body_to_string(req.into_body()).await;
+            Ok::<_, std::io::Error>(Response::new(body.into()))
+        })
+        .run()
+        .await;
+
+    let mut env = TestEnv::default();
+    env.put(
+        "LINKERD2_PROXY_INBOUND_HTTP2_ADAPTIVE_WINDOW",
+        "true".to_string(),
+    );
+    env.put(
+        "LINKERD2_PROXY_INBOUND_HTTP2_MAX_FRAME_SIZE",
+        "65536".to_string(),
+    );
+    let proxy = proxy::new()
+        .inbound_fuzz_addr(srv)
+        .run_with_test_env(env)
+        .await;
+    let client = client::http2(proxy.inbound, "transparency.example.com");
+
+    let req = client
+        .request_builder("/")
+        .method("POST")
+        .body(body.clone().into())
+        .unwrap();
+    let rsp = client.request_body(req).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(http_util::body_to_string(rsp.into_body()).await, body);
+
+    proxy.join_servers().await;
+}
@@ -1,7 +1,7 @@
 use crate::{http, tcp, Outbound};
 use linkerd_app_core::{
     config::{ProxyConfig, ServerConfig},
-    detect, io, svc, Error,
+    detect, io, port_policy, svc, Error,
 };
 
 impl<T> Outbound<T> {
@@ -55,10 +55,13 @@ impl<T> Outbound<T> {
                 detect_protocol_timeout,
                 http::DetectHttp::default(),
             ))
-            // When the profile marks the target as opaque, we skip HTTP
-            // detection and just use the TCP logical stack directly.
+            // When the profile marks the target as opaque or the target port
+            // is configured as opaque, we skip HTTP detection and just use the
+            // TCP logical stack directly.
             .push_switch(
-                SkipByProfile,
+                SkipDetect {
+                    opaque_ports: config.opaque_ports.clone(),
+                },
                 tcp.push_on_response(svc::MapTargetLayer::new(io::EitherIo::Left))
                     .into_inner(),
             );
@@ -71,23 +74,60 @@ impl<T> Outbound<T> {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
-pub struct SkipByProfile;
+#[derive(Clone, Debug)]
+pub struct SkipDetect {
+    opaque_ports: port_policy::Ports,
+}
 
-// === impl SkipByProfile ===
+// === impl SkipDetect ===
 
-impl svc::Predicate<tcp::Logical> for SkipByProfile {
+impl svc::Predicate<tcp::Logical> for SkipDetect {
     type Request = svc::Either<tcp::Logical, tcp::Logical>;
 
     fn check(&mut self, l: tcp::Logical) -> Result<Self::Request, Error> {
-        if l.profile
+        let opaque_profile = l
+            .profile
             .as_ref()
-            .map(|p| !p.borrow().opaque_protocol)
-            .unwrap_or(true)
-        {
+            .map(|p| p.borrow().opaque_protocol)
+            .unwrap_or(false);
+        if !opaque_profile && !self.opaque_ports.contains(l.orig_dst.0.port()) {
             Ok(svc::Either::A(l))
         } else {
             Ok(svc::Either::B(l))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::default_config;
+    use linkerd_app_core::{svc::Predicate, transport::OrigDstAddr};
+    use std::sync::Arc;
+    use tokio::sync::watch;
+
+    #[test]
+    fn skips_detection_for_ports_added_to_policy() {
+        let (tx, rx) = watch::channel(Arc::new(port_policy::PortPolicy::default()));
+        let config = default_config(([10, 0, 0, 1], 3306).into()).with_port_policy(rx);
+        let mut skip = SkipDetect {
+            opaque_ports: config.opaque_ports,
+        };
+        let logical = tcp::Logical {
+            orig_dst: OrigDstAddr(([10, 0, 0, 1], 3306).into()),
+            profile: None,
+            protocol: (),
+        };
+        assert!(matches!(skip.check(logical.clone()), Ok(svc::Either::A(_))));
+
+        tx.send(Arc::new(port_policy::PortPolicy {
+            outbound_opaque_ports: vec![3306..=3306],
+            ..port_policy::PortPolicy::default()
+        }))
+        .unwrap();
+        assert!(
+            matches!(skip.check(logical), Ok(svc::Either::B(_))),
+            "ports added to the policy must skip detection"
+        );
+    }
+}
@@ -48,7 +48,11 @@ where
         // is typically used (i.e. when communicating with other proxies); though
         // HTTP/1.x fallback is supported as needed.
         let stack = connect
-            .push(http::client::layer(h1_settings, h2_settings))
+            .push(http::client::layer_with_pool_metrics(
+                h1_settings,
+                h2_settings,
+                rt.metrics.http_pool.clone(),
+            ))
             // Re-establishes a connection when the client fails.
             .push(reconnect::layer({
                 let backoff = backoff;
@@ -59,8 +63,11 @@ where
             .push(rt.metrics.http_endpoint.to_layer::<classify::Response, _>())
             .push_on_response(http_tracing::client(
                 rt.span_sink.clone(),
+                rt.span_headers.clone(),
                 crate::trace_labels(),
             ))
+            // Records the endpoint on the request's spans.
+            .push(http_tracing::attributes::NewRecordAttributes::layer())
             .push_on_response(http::strip_header::request::layer(L5D_REQUIRE_ID))
             .push(NewRequireIdentity::layer())
             .push(http::NewOverrideAuthority::layer(vec![
@@ -1,8 +1,8 @@
 use super::{CanonicalDstHeader, Concrete, Logical};
 use crate::{resolve, stack_labels, Outbound};
 use linkerd_app_core::{
-    classify, config, profiles,
-    proxy::{core::Resolve, http},
+    classify, config, hedge, http_tracing, metrics, profiles,
+    proxy::{balance::zone, core::Resolve, discover::eject, http},
     retry, svc, tls, Error, Never, DST_OVERRIDE_HEADER,
 };
 use tracing::debug_span;
@@ -32,7 +32,11 @@ impl<E> Outbound<E> {
         ESvc::Error: Into<Error>,
         ESvc::Future: Send,
         R: Resolve<Concrete, Error = Error> + Clone + Send + 'static,
-        R::Endpoint: From<(tls::NoClientTls, Logical)> + Clone + Send,
+        R::Endpoint: From<(tls::NoClientTls, Logical)>
+            + svc::Param<metrics::EndpointLabels>
+            + svc::Param<zone::Zone>
+            + Clone
+            + Send,
         R::Resolution: Send,
         R::Future: Send + Unpin,
     {
@@ -41,6 +45,11 @@ impl<E> Outbound<E> {
             runtime: rt,
             stack: endpoint,
         } = self;
+        let retry_config = config.retry;
+        let hedge_config = config.hedge;
+        let eject_config = config.eject;
+        let balancer = config.balancer.clone();
+        let local_zone = config.zone.clone();
         let config::ProxyConfig {
             buffer_capacity,
             cache_max_idle_age,
@@ -64,6 +73,18 @@ impl<E> Outbound<E> {
                     // the balancer need not drive them all directly.
                     .push(svc::layer::mk(svc::SpawnReady::new)),
             )
+            // Ejects endpoints that fail repeatedly so that the balancer
+            // routes requests to other endpoints until they recover.
+            .push(eject::NewEject::layer(
+                eject_config,
+                rt.metrics.endpoint_ejection.clone(),
+                |rsp: &http::Response<http::BoxBody>| rsp.status().is_server_error(),
+            ))
+            // Marks endpoints in the proxy's zone so that balancers prefer them.
+            .push(zone::NewZoned::layer(
+                local_zone,
+                rt.metrics.zone_requests.clone(),
+            ))
             .check_new_service::<R::Endpoint, http::Request<_>>()
             // Resolve the service to its endpoints and balance requests over them.
             //
@@ -71,16 +92,21 @@ impl<E> Outbound<E> {
             // When the balancer is in failfast, spawn the service in a background
             // task so it becomes ready without new requests.
             .push(resolve::layer(resolve, watchdog))
+            // Each balancer uses the strategy configured by the destination's
+            // profile or, otherwise, the proxy's default. Requests spill over
+            // to endpoints in other zones only when no local endpoint is ready.
+            .push(http::balance::MakeBalance::layer(
+                balancer,
+                zone::NewPreferLocal::new(http::balance::NewBalance::new(
+                    crate::EWMA_DEFAULT_RTT,
+                    crate::EWMA_DECAY,
+                )),
+            ))
             .push_on_response(
                 svc::layers()
-                    .push(http::balance::layer(
-                        crate::EWMA_DEFAULT_RTT,
-                        crate::EWMA_DECAY,
-                    ))
                     .push(rt.metrics.stack.layer(stack_labels("http", "balancer")))
                     .push(svc::layer::mk(svc::SpawnReady::new))
-                    .push(svc::FailFast::layer("HTTP Balancer", dispatch_timeout))
-                    .push(http::BoxResponse::layer()),
+                    .push(svc::FailFast::layer("HTTP Balancer", dispatch_timeout)),
             )
             .check_make_service::<Concrete, http::Request<_>>()
             .push(svc::MapErrLayer::new(Into::into))
@@ -114,7 +140,7 @@ impl<E> Outbound<E> {
             // If the traffic split is empty/unavailable, eagerly fail requests.
             // When the split is in failfast, spawn the service in a background
             // task so it becomes ready without new requests.
-            .push(profiles::split::layer())
+            .push(profiles::split::http_layer())
             .push_on_response(
                 svc::layers()
                     .push(svc::layer::mk(svc::SpawnReady::new))
@@ -126,13 +152,27 @@ impl<E> Outbound<E> {
             // Note: routes can't exert backpressure.
             .push(profiles::http::route_request::layer(
                 svc::proxies()
+                    // Erases the request body type, since requests are
+                    // wrapped in a replayable body only when they may be
+                    // retried.
+                    .push(http::EraseRequest::layer())
                     .push(
                         rt.metrics
                             .http_route_actual
                             .to_layer::<classify::Response, _>(),
                     )
                     // Sets an optional retry policy.
-                    .push(retry::layer(rt.metrics.http_route_retry.clone()))
+                    .push(retry::layer(
+                        rt.metrics.http_route_retry.clone(),
+                        retry_config,
+                    ))
+                    // Sets an optional hedging policy. Each hedged request may
+                    // be retried independently.
+                    .push(hedge::layer(
+                        rt.metrics.http_route_retry.clone(),
+                        rt.metrics.http_route_actual.clone(),
+                        hedge_config,
+                    ))
                     // Sets an optional request timeout.
                     .push(http::MakeTimeoutLayer::default())
                     // Records per-route metrics.
@@ -140,6 +180,14 @@ impl<E> Outbound<E> {
                     // Sets the per-route response classifier as a request
                     // extension.
                     .push(classify::NewClassify::layer())
+                    // Revises the sampling decision of traces started by the
+                    // proxy with the route's sample rate, before the request
+                    // may be retried or hedged.
+                    .push(http_tracing::sample::NewSampleRoute::layer(
+                        rt.span_sampler.clone(),
+                    ))
+                    // Records the route on the request's spans.
+                    .push(http_tracing::attributes::NewRecordAttributes::layer())
                     .push_map_target(Logical::mk_route)
                     .into_inner(),
             ))
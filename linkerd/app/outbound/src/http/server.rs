@@ -1,5 +1,5 @@
 use crate::{http, trace_labels, Outbound};
-use linkerd_app_core::{config, errors, http_tracing, svc, Error};
+use linkerd_app_core::{access_log, config, errors, http_tracing, svc, Error};
 use tracing::debug_span;
 
 impl<H, HSvc> Outbound<H>
@@ -54,8 +54,16 @@ where
                     // Synthesizes responses for proxy errors.
                     .push(errors::layer())
                     // Initiates OpenCensus tracing.
-                    .push(http_tracing::server(rt.span_sink.clone(), trace_labels()))
-                    .push(http::BoxResponse::layer()),
+                    .push(http_tracing::server(
+                        rt.span_sink.clone(),
+                        rt.span_sampler.clone(),
+                        rt.span_headers.clone(),
+                        trace_labels(),
+                    ))
+                    .push(http::BoxResponse::layer())
+                    // Logs requests with the attributes recorded on their
+                    // server spans.
+                    .push(access_log::layer(rt.access_log.clone(), "outbound")),
             )
             // Convert origin form HTTP/1 URIs to absolute form for Hyper's
             // `Client`.
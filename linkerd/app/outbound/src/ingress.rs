@@ -1,5 +1,6 @@
 use crate::{http, stack_labels, tcp, trace_labels, Config, Outbound};
 use linkerd_app_core::{
+    access_log,
     config::{ProxyConfig, ServerConfig},
     detect, discovery_rejected, drain, errors, http_request_l5d_override_dst_addr, http_tracing,
     io, profiles, svc, tls,
@@ -69,11 +70,7 @@ impl Outbound<()> {
             .into_inner();
 
         svc::stack(http)
-            .push_on_response(
-                svc::layers()
-                    .push(http::BoxRequest::layer())
-                    .push(svc::MapErrLayer::new(Into::into)),
-            )
+            .push_on_response(svc::MapErrLayer::new(Into::into))
             // Lookup the profile for the outbound HTTP target, if appropriate.
             //
             // This service is buffered because it needs to initialize the profile
@@ -114,9 +111,18 @@ impl Outbound<()> {
                     .push(errors::layer())
                     .push(http_tracing::server(
                         self.runtime.span_sink.clone(),
+                        self.runtime.span_sampler.clone(),
+                        self.runtime.span_headers.clone(),
                         trace_labels(),
                     ))
-                    .push(http::BoxResponse::layer()),
+                    .push(http::BoxResponse::layer())
+                    // Logs requests with the attributes recorded on their
+                    // server spans.
+                    .push(access_log::layer(
+                        self.runtime.access_log.clone(),
+                        "outbound",
+                    ))
+                    .push(http::BoxRequest::layer()),
             )
             .instrument(|a: &http::Accept| debug_span!("http", v = %a.protocol))
             .push(http::NewServeHttp::layer(
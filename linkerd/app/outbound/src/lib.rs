@@ -16,9 +16,11 @@ pub(crate) mod test_util;
 
 use linkerd_app_core::{
     config::ProxyConfig,
-    io, metrics, profiles,
-    proxy::{api_resolve::Metadata, core::Resolve, resolve::map_endpoint},
-    serve, svc, tls,
+    io, metrics, port_policy, profiles,
+    proxy::{
+        api_resolve::Metadata, balance, core::Resolve, discover::eject, resolve::map_endpoint,
+    },
+    retry, serve, svc, tls,
     transport::listen,
     AddrMatch, Error, ProxyRuntime,
 };
@@ -37,6 +39,27 @@ pub struct Config {
     // not perform per-target-address discovery. Non-HTTP connections are
     // forwarded without discovery/routing/mTLS.
     pub ingress_mode: bool,
+
+    // Configures how requests are retried on routes that permit retries.
+    pub retry: retry::Config,
+
+    // When set, requests on retryable routes are hedged.
+    pub hedge: Option<profiles::http::Hedge>,
+
+    // Configures when failing endpoints are ejected from load balancers.
+    pub eject: eject::Config,
+
+    // The load balancing strategy used when a destination's profile does not
+    // set one.
+    pub balancer: balance::Strategy,
+
+    // The zone in which the proxy runs. When set, load balancers prefer
+    // endpoints in the same zone.
+    pub zone: Option<String>,
+
+    // Connections to these ports are forwarded without protocol detection,
+    // as if their profiles marked them as opaque.
+    pub opaque_ports: port_policy::Ports,
 }
 
 #[derive(Clone, Debug)]
@@ -46,6 +69,21 @@ pub struct Outbound<S> {
     stack: svc::Stack<S>,
 }
 
+// === impl Config ===
+
+impl Config {
+    /// Reads the ports that skip protocol detection from `policy` as each
+    /// connection is accepted, rather than from the static configuration.
+    pub fn with_port_policy(self, policy: port_policy::Receiver) -> Self {
+        Self {
+            opaque_ports: port_policy::Ports::outbound_opaque(policy),
+            ..self
+        }
+    }
+}
+
+// === impl Outbound ===
+
 impl Outbound<()> {
     pub fn new(config: Config, runtime: ProxyRuntime) -> Self {
         Self {
@@ -103,14 +141,14 @@ impl<S> Outbound<S> {
         S: Clone + Send + Sync + Unpin + 'static,
         <S as svc::Service<http::Endpoint>>::Response: tls::HasNegotiatedProtocol,
         <S as svc::Service<http::Endpoint>>::Response:
-            tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin,
+            tokio::io::AsyncRead + tokio::io::AsyncWrite + io::PeerAddr + Send + Unpin,
         <S as svc::Service<http::Endpoint>>::Future: Send + Unpin,
         R: Resolve<http::Concrete, Endpoint = Metadata, Error = Error>,
         <R as Resolve<http::Concrete>>::Resolution: Send,
         <R as Resolve<http::Concrete>>::Future: Send + Unpin,
         <S as svc::Service<tcp::Endpoint>>::Response: tls::HasNegotiatedProtocol,
         <S as svc::Service<tcp::Endpoint>>::Response:
-            tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin,
+            tokio::io::AsyncRead + tokio::io::AsyncWrite + io::PeerAddr + Send + Unpin,
         <S as svc::Service<tcp::Endpoint>>::Future: Send,
         R: Resolve<tcp::Concrete, Endpoint = Metadata, Error = Error>,
         <R as Resolve<tcp::Concrete>>::Resolution: Send,
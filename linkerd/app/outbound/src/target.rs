@@ -1,7 +1,9 @@
 use linkerd_app_core::{
+    http_tracing::attributes::HasSpanAttributes,
     metrics, profiles,
     proxy::{
         api_resolve::{ConcreteAddr, Metadata},
+        balance,
         resolve::map_endpoint::MapEndpoint,
     },
     svc::{self, Param},
@@ -12,6 +14,9 @@ use linkerd_app_core::{
 use std::net::SocketAddr;
 use tracing::debug;
 
+/// The endpoint label that describes an endpoint's zone.
+const ZONE_LABEL: &str = "zone";
+
 #[derive(Copy, Clone)]
 pub struct EndpointFromMetadata {
     pub identity_disabled: bool,
@@ -173,6 +178,16 @@ impl<P> Param<ConcreteAddr> for Concrete<P> {
     }
 }
 
+/// Used to select a load balancing strategy.
+impl<P> Param<Option<balance::Strategy>> for Concrete<P> {
+    fn param(&self) -> Option<balance::Strategy> {
+        self.logical
+            .profile
+            .as_ref()
+            .and_then(|p| p.borrow().balancer.clone())
+    }
+}
+
 // === impl Endpoint ===
 
 impl<P> From<(tls::NoClientTls, Logical<P>)> for Endpoint<P> {
@@ -241,6 +256,23 @@ impl<P> Param<metrics::EndpointLabels> for Endpoint<P> {
     }
 }
 
+/// Used to prefer endpoints in the local zone.
+impl<P> Param<balance::zone::Zone> for Endpoint<P> {
+    fn param(&self) -> balance::zone::Zone {
+        balance::zone::Zone(self.metadata.labels().get(ZONE_LABEL).cloned())
+    }
+}
+
+impl<P> HasSpanAttributes for Endpoint<P> {
+    fn span_attributes(&self) -> Vec<(&'static str, String)> {
+        let mut attributes = vec![("peer.addr", self.addr.to_string())];
+        if let Conditional::Some(tls) = &self.tls {
+            attributes.push(("peer.identity", tls.server_id.to_string()));
+        }
+        attributes
+    }
+}
+
 impl<P: std::hash::Hash> std::hash::Hash for Endpoint<P> {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.addr.hash(state);
@@ -39,7 +39,7 @@ impl<C> Outbound<C> {
         Endpoint<P>: svc::Param<Option<SessionProtocol>>,
         C: svc::Service<Endpoint<P>, Error = io::Error> + Clone + Send + 'static,
         C::Response: tls::HasNegotiatedProtocol,
-        C::Response: io::AsyncRead + io::AsyncWrite + Send + Unpin + 'static,
+        C::Response: io::AsyncRead + io::AsyncWrite + io::PeerAddr + Send + Unpin + 'static,
         C::Future: Send + 'static,
     {
         let Self {
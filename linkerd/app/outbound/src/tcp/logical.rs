@@ -2,7 +2,7 @@ use super::{Concrete, Endpoint, Logical};
 use crate::{resolve, Outbound};
 use linkerd_app_core::{
     config, drain, io, profiles,
-    proxy::{api_resolve::ConcreteAddr, core::Resolve, tcp},
+    proxy::{api_resolve::ConcreteAddr, balance::zone, core::Resolve, tcp},
     svc, tls, Conditional, Error,
 };
 use tracing::{debug, debug_span};
@@ -64,13 +64,20 @@ where
                     debug_span!("endpoint", server.addr = %t.addr)
                 }
             })
+            .push(zone::NewZoned::layer(
+                config.zone.clone(),
+                rt.metrics.zone_requests.clone(),
+            ))
             .push(resolve::layer(resolve, config.proxy.cache_max_idle_age * 2))
+            .push(tcp::balance::MakeBalance::layer(
+                config.balancer.clone(),
+                zone::NewPreferLocal::new(tcp::balance::NewBalance::new(
+                    crate::EWMA_DEFAULT_RTT,
+                    crate::EWMA_DECAY,
+                )),
+            ))
             .push_on_response(
                 svc::layers()
-                    .push(tcp::balance::layer(
-                        crate::EWMA_DEFAULT_RTT,
-                        crate::EWMA_DECAY,
-                    ))
                     .push(
                         rt.metrics
                             .stack
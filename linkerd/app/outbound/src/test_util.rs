@@ -4,9 +4,11 @@ pub use ipnet::IpNet;
 use linkerd_app_core::{
     config, drain, exp_backoff, metrics,
     proxy::{
+        discover::eject,
         http::{h1, h2},
         tap,
     },
+    retry,
     transport::{BindTcp, Keepalive, ListenAddr},
     IpMatch, ProxyRuntime,
 };
@@ -18,6 +20,32 @@ const LOCALHOST: [u8; 4] = [127, 0, 0, 1];
 pub fn default_config(orig_dst: SocketAddr) -> Config {
     Config {
         ingress_mode: false,
+        retry: retry::Config {
+            max_body_bytes: 64 * 1024,
+            max_attempts: 3,
+            backoff: exp_backoff::ExponentialBackoff::new(
+                Duration::from_millis(25),
+                Duration::from_millis(250),
+                0.5,
+            )
+            .unwrap(),
+            retry_connection_errors: false,
+        },
+        hedge: None,
+        eject: eject::Config {
+            consecutive_failures: None,
+            failure_rate: None,
+            backoff: exp_backoff::ExponentialBackoff::new(
+                Duration::from_secs(1),
+                Duration::from_secs(10),
+                0.5,
+            )
+            .unwrap(),
+            max_ejected: 0.5,
+        },
+        balancer: Default::default(),
+        zone: None,
+        opaque_ports: Default::default(),
         allow_discovery: IpMatch::new(Some(IpNet::from_str("0.0.0.0/0").unwrap())).into(),
         proxy: config::ProxyConfig {
             server: config::ServerConfig {
@@ -40,6 +68,8 @@ pub fn default_config(orig_dst: SocketAddr) -> Config {
                 h1_settings: h1::PoolSettings {
                     max_idle: 1,
                     idle_timeout: Duration::from_secs(1),
+                    max_connections: None,
+                    connect_timeout: Duration::from_secs(1),
                 },
                 h2_settings: h2::Settings::default(),
             },
@@ -61,6 +91,10 @@ pub fn runtime() -> (ProxyRuntime, drain::Signal) {
         metrics: metrics.outbound,
         tap,
         span_sink: None,
+        span_sampler: None,
+        span_headers: Default::default(),
+        access_log: None,
+        authorize: None,
         drain,
     };
     (runtime, drain_tx)
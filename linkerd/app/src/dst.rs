@@ -11,6 +11,7 @@ use tonic::body::BoxBody;
 pub struct Config {
     pub control: control::Config,
     pub context: String,
+    pub profile_overrides: Option<profiles::overrides::Config>,
 }
 
 /// Handles to destination service clients.
@@ -24,6 +25,9 @@ pub struct Dst {
     /// Resolves endpoints.
     pub resolve:
         recover::Resolve<BackoffUnlessInvalidArgument, api::Resolve<control::Client<BoxBody>>>,
+
+    /// Reloads profile overrides, if they are configured.
+    pub profile_overrides_task: Option<profiles::overrides::Task>,
 }
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -42,10 +46,21 @@ impl Config {
         let backoff = BackoffUnlessInvalidArgument(self.control.connect.backoff);
         let svc = self.control.build(dns, metrics, identity);
 
+        let mut profiles = profiles::Client::new(svc.clone(), backoff, self.context.clone());
+        let profile_overrides_task = match self.profile_overrides {
+            None => None,
+            Some(config) => {
+                let (overrides, task) = config.build()?;
+                profiles = profiles.with_overrides(overrides);
+                Some(task)
+            }
+        };
+
         Ok(Dst {
             addr,
-            profiles: profiles::Client::new(svc.clone(), backoff, self.context.clone()),
+            profiles,
             resolve: recover::Resolve::new(backoff, api::Resolve::new(svc, self.context)),
+            profile_overrides_task,
         })
     }
 }
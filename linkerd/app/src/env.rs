@@ -1,16 +1,28 @@
 use crate::core::{
-    addr,
+    access_log, addr, authz, concurrency_limit,
     config::*,
     control::{Config as ControlConfig, ControlAddr},
-    proxy::http::{h1, h2},
-    tls,
+    http_tracing::{attributes::CaptureHeaders, sample},
+    port_policy, profiles,
+    proxy::{
+        balance,
+        discover::eject,
+        http::{self, h1, h2},
+    },
+    rate_limit, retry, tls,
     transport::{BindTcp, Keepalive, ListenAddr},
     Addr, AddrMatch, Conditional, NameMatch,
 };
 use crate::{dns, gateway, identity, inbound, oc_collector, outbound};
 use indexmap::IndexSet;
 use std::{
-    collections::HashMap, fmt, fs, net::SocketAddr, path::PathBuf, str::FromStr, time::Duration,
+    collections::HashMap,
+    fmt, fs,
+    net::SocketAddr,
+    num::{NonZeroU32, NonZeroUsize},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
 };
 use tracing::{debug, error, warn};
 
@@ -44,6 +56,25 @@ pub enum ParseError {
     NameError,
     InvalidTokenSource,
     InvalidTrustAnchors,
+    NotAPercentile,
+    NotARatio,
+    NotABalancer,
+    NotARateLimit,
+    NotAConcurrencyLimit,
+    NotAFrameSize,
+    NotATraceProtocol,
+    NotAHeaderName,
+    NotAnAccessLogFormat,
+}
+
+/// The source of the local identity.
+#[derive(Clone, Debug)]
+pub enum IdentityConfig {
+    /// Certificates are issued by the Identity service at the given address.
+    Certify(ControlAddr, identity::certify::Config),
+
+    /// Certificates are loaded from files.
+    Files(identity::files::Config),
 }
 
 // Environment variables to look at when loading the configuration
@@ -89,7 +120,83 @@ const ENV_INBOUND_MAX_IDLE_CONNS_PER_ENDPOINT: &str = "LINKERD2_PROXY_MAX_IDLE_C
 const ENV_OUTBOUND_MAX_IDLE_CONNS_PER_ENDPOINT: &str =
     "LINKERD2_PROXY_OUTBOUND_MAX_IDLE_CONNS_PER_ENDPOINT";
 
+/// Limits the number of HTTP/1 connections that may be open to each endpoint.
+/// Requests that cannot be served by an open connection wait for one to become
+/// available, failing if none does within the connect timeout. By default,
+/// connections are not limited.
+const ENV_INBOUND_MAX_CONNS_PER_ENDPOINT: &str = "LINKERD2_PROXY_INBOUND_MAX_CONNS_PER_ENDPOINT";
+const ENV_OUTBOUND_MAX_CONNS_PER_ENDPOINT: &str = "LINKERD2_PROXY_OUTBOUND_MAX_CONNS_PER_ENDPOINT";
+
+/// Limits the size of request bodies that are buffered so that requests may be
+/// retried. Requests with larger bodies are not retried.
+pub const ENV_OUTBOUND_MAX_RETRY_BODY_BYTES: &str = "LINKERD2_PROXY_OUTBOUND_MAX_RETRY_BODY_BYTES";
+
+/// Limits the number of times a request may be attempted (including the
+/// original request) on routes that permit retries.
+pub const ENV_OUTBOUND_RETRY_MAX_ATTEMPTS: &str = "LINKERD2_PROXY_OUTBOUND_RETRY_MAX_ATTEMPTS";
+
+/// Enables retries of requests that fail due to connection-level errors before
+/// a response is received (e.g. HTTP/2 `REFUSED_STREAM` resets or connections
+/// that are reset before response headers are received). Since a server may
+/// have processed a request before its connection was reset, such requests are
+/// only retried when their methods are idempotent.
+pub const ENV_OUTBOUND_RETRY_CONNECTION_ERRORS: &str =
+    "LINKERD2_PROXY_OUTBOUND_RETRY_CONNECTION_ERRORS";
+
+/// Enables hedging on retryable routes. When a request has not completed once
+/// this percentile (e.g. 95) of the route's response latency has elapsed, a
+/// second request is issued and whichever completes first is used.
+pub const ENV_OUTBOUND_HEDGE_LATENCY_PERCENTILE: &str =
+    "LINKERD2_PROXY_OUTBOUND_HEDGE_LATENCY_PERCENTILE";
+
+/// Ejects endpoints from outbound load balancers after this many consecutive
+/// failures (i.e. 5XX responses or connection errors).
+pub const ENV_OUTBOUND_EJECT_CONSECUTIVE_FAILURES: &str =
+    "LINKERD2_PROXY_OUTBOUND_EJECT_CONSECUTIVE_FAILURES";
+
+/// Ejects endpoints from outbound load balancers when this proportion (on
+/// `(0, 1]`) of their responses fail within a window.
+pub const ENV_OUTBOUND_EJECT_FAILURE_RATE: &str = "LINKERD2_PROXY_OUTBOUND_EJECT_FAILURE_RATE";
+pub const ENV_OUTBOUND_EJECT_FAILURE_RATE_MIN_REQUESTS: &str =
+    "LINKERD2_PROXY_OUTBOUND_EJECT_FAILURE_RATE_MIN_REQUESTS";
+pub const ENV_OUTBOUND_EJECT_FAILURE_RATE_WINDOW: &str =
+    "LINKERD2_PROXY_OUTBOUND_EJECT_FAILURE_RATE_WINDOW";
+
+/// The maximum proportion (on `(0, 1]`) of an outbound load balancer's
+/// endpoints that may be ejected at once.
+pub const ENV_OUTBOUND_EJECT_MAX_EJECTED: &str = "LINKERD2_PROXY_OUTBOUND_EJECT_MAX_EJECTED";
+
+/// Sets how outbound requests are balanced over a destination's endpoints
+/// when its profile does not specify a strategy. One of `peak-ewma` (the
+/// default), `least-request`, `round-robin`, `ring-hash:client-addr`, or
+/// `ring-hash:header:<name>`. Ring-hash strategies only apply to HTTP requests;
+/// opaque TCP connections carry no hash key, so they are balanced with
+/// `peak-ewma` instead.
+pub const ENV_OUTBOUND_BALANCER: &str = "LINKERD2_PROXY_OUTBOUND_BALANCER";
+
+/// The zone in which the proxy runs. When set, outbound load balancers prefer
+/// endpoints whose `zone` label matches, spilling over to endpoints in other
+/// zones only when no local endpoint is ready.
+pub const ENV_ZONE: &str = "LINKERD2_PROXY_ZONE";
+
 pub const ENV_INBOUND_MAX_IN_FLIGHT: &str = "LINKERD2_PROXY_INBOUND_MAX_IN_FLIGHT";
+
+/// Limits the rate of inbound requests to each route, as
+/// `<requests-per-second>[:<burst>]`. The burst defaults to the rate. Routes
+/// configured by `LINKERD2_PROXY_PROFILE_OVERRIDES` may set their own limit.
+pub const ENV_INBOUND_ROUTE_RATE_LIMIT: &str = "LINKERD2_PROXY_INBOUND_ROUTE_RATE_LIMIT";
+
+/// Limits the rate of inbound requests from each authenticated client
+/// identity, as `<requests-per-second>[:<burst>]`. Requests from clients
+/// without an identity are not limited.
+pub const ENV_INBOUND_CLIENT_RATE_LIMIT: &str = "LINKERD2_PROXY_INBOUND_CLIENT_RATE_LIMIT";
+
+/// Sheds inbound requests in excess of a concurrency limit that adapts to the
+/// application's response latency, as `<min>:<max>`. The limit starts at its
+/// minimum.
+pub const ENV_INBOUND_ADAPTIVE_CONCURRENCY_LIMIT: &str =
+    "LINKERD2_PROXY_INBOUND_ADAPTIVE_CONCURRENCY_LIMIT";
+
 pub const ENV_OUTBOUND_MAX_IN_FLIGHT: &str = "LINKERD2_PROXY_OUTBOUND_MAX_IN_FLIGHT";
 
 pub const ENV_TRACE_ATTRIBUTES_PATH: &str = "LINKERD2_PROXY_TRACE_ATTRIBUTES_PATH";
@@ -128,6 +235,17 @@ pub const ENV_INBOUND_PORTS_DISABLE_PROTOCOL_DETECTION: &str =
 pub const ENV_INBOUND_PORTS_REQUIRE_IDENTITY: &str =
     "LINKERD2_PROXY_INBOUND_PORTS_REQUIRE_IDENTITY";
 
+// Disables protocol detection for outbound connections whose SO_ORIGINAL_DST
+// has a port in the provided list.
+pub const ENV_OUTBOUND_PORTS_DISABLE_PROTOCOL_DETECTION: &str =
+    "LINKERD2_PROXY_OUTBOUND_PORTS_DISABLE_PROTOCOL_DETECTION";
+
+/// A JSON file describing the inbound ports that disable protocol detection or
+/// require identity and the outbound ports that disable protocol detection.
+/// The file is reloaded as it changes. When set, it replaces the corresponding
+/// port lists.
+pub const ENV_PORT_POLICY: &str = "LINKERD2_PROXY_PORT_POLICY";
+
 pub const ENV_IDENTITY_DISABLED: &str = "LINKERD2_PROXY_IDENTITY_DISABLED";
 pub const ENV_IDENTITY_DIR: &str = "LINKERD2_PROXY_IDENTITY_DIR";
 pub const ENV_IDENTITY_TRUST_ANCHORS: &str = "LINKERD2_PROXY_IDENTITY_TRUST_ANCHORS";
@@ -138,12 +256,67 @@ pub const ENV_IDENTITY_MAX_REFRESH: &str = "LINKERD2_PROXY_IDENTITY_MAX_REFRESH"
 
 pub const ENV_IDENTITY_SVC_BASE: &str = "LINKERD2_PROXY_IDENTITY_SVC";
 
+/// A comma-separated list of files holding PEM-encoded trust bundles, which
+/// are reloaded as they change. The roots in all of the bundles are trusted.
+/// May be set instead of `LINKERD2_PROXY_IDENTITY_TRUST_ANCHORS`.
+pub const ENV_IDENTITY_TRUST_ANCHORS_FILE: &str = "LINKERD2_PROXY_IDENTITY_TRUST_ANCHORS_FILE";
+
+/// Files holding a PEM-encoded certificate chain and a PKCS#8 or SEC1 (`EC
+/// PRIVATE KEY`) ECDSA P-256 private key. When
+/// set, the local identity is loaded from these files, which are reloaded as
+/// they change, instead of from the Identity service.
+pub const ENV_IDENTITY_CERTIFICATE_FILE: &str = "LINKERD2_PROXY_IDENTITY_CERTIFICATE_FILE";
+pub const ENV_IDENTITY_KEY_FILE: &str = "LINKERD2_PROXY_IDENTITY_KEY_FILE";
+
 pub const ENV_DESTINATION_SVC_BASE: &str = "LINKERD2_PROXY_DESTINATION_SVC";
 
 pub const ENV_HOSTNAME: &str = "HOSTNAME";
 
 pub const ENV_TRACE_COLLECTOR_SVC_BASE: &str = "LINKERD2_PROXY_TRACE_COLLECTOR_SVC";
 
+/// The protocol used to export spans to the trace collector: either
+/// `opencensus` (the default) or `opentelemetry` (OTLP over gRPC).
+pub const ENV_TRACE_COLLECTOR_PROTOCOL: &str = "LINKERD2_PROXY_TRACE_COLLECTOR_PROTOCOL";
+
+/// The probability, in (0, 1], that a request received without a trace
+/// context starts a new trace. When unset, the proxy only records spans for
+/// requests that are already traced, unless a trace limit is configured.
+pub const ENV_TRACE_SAMPLE_PROBABILITY: &str = "LINKERD2_PROXY_TRACE_SAMPLE_PROBABILITY";
+
+/// Limits the number of traces the proxy starts each second.
+pub const ENV_TRACE_SAMPLE_MAX_PER_SECOND: &str = "LINKERD2_PROXY_TRACE_SAMPLE_MAX_PER_SECOND";
+
+/// Comma-separated lists of request and response headers that are recorded
+/// as span attributes.
+pub const ENV_TRACE_REQUEST_HEADERS: &str = "LINKERD2_PROXY_TRACE_REQUEST_HEADERS";
+pub const ENV_TRACE_RESPONSE_HEADERS: &str = "LINKERD2_PROXY_TRACE_RESPONSE_HEADERS";
+
+/// Enables the HTTP access log. Either `json`, `apache` (the combined log
+/// format), or a template in which fields are named in braces, e.g.
+/// `{method} {path} {status}`.
+pub const ENV_ACCESS_LOG_FORMAT: &str = "LINKERD2_PROXY_ACCESS_LOG_FORMAT";
+
+/// A file to which access log lines are appended. When unset, lines are
+/// written to stderr.
+pub const ENV_ACCESS_LOG_PATH: &str = "LINKERD2_PROXY_ACCESS_LOG_PATH";
+
+/// The ratio, in (0, 1], of requests that are logged. Defaults to 1.
+pub const ENV_ACCESS_LOG_SAMPLE_RATIO: &str = "LINKERD2_PROXY_ACCESS_LOG_SAMPLE_RATIO";
+
+/// Logs each TCP connection accepted or established by the proxy when it
+/// closes.
+pub const ENV_CONNECTION_LOG: &str = "LINKERD2_PROXY_CONNECTION_LOG";
+
+/// A JSON file describing which clients may connect to each inbound port. The
+/// file is reloaded as it changes. When unset, inbound connections are not
+/// authorized.
+pub const ENV_INBOUND_AUTHORIZATION_POLICY: &str = "LINKERD2_PROXY_INBOUND_AUTHORIZATION_POLICY";
+
+/// A JSON file of per-profile overrides that configure the profile features
+/// the destination API can't describe (e.g. header-matched routes). The file
+/// is reloaded as it changes.
+pub const ENV_PROFILE_OVERRIDES: &str = "LINKERD2_PROXY_PROFILE_OVERRIDES";
+
 pub const ENV_DESTINATION_CONTEXT: &str = "LINKERD2_PROXY_DESTINATION_CONTEXT";
 pub const ENV_DESTINATION_PROFILE_INITIAL_TIMEOUT: &str =
     "LINKERD2_PROXY_DESTINATION_PROFILE_INITIAL_TIMEOUT";
@@ -167,6 +340,22 @@ const ENV_INITIAL_STREAM_WINDOW_SIZE: &str = "LINKERD2_PROXY_HTTP2_INITIAL_STREA
 const ENV_INITIAL_CONNECTION_WINDOW_SIZE: &str =
     "LINKERD2_PROXY_HTTP2_INITIAL_CONNECTION_WINDOW_SIZE";
 
+// HTTP/2 settings may be configured separately for the inbound proxy, the
+// outbound proxy, and control plane clients. Each of the following suffixes
+// is prefixed by `LINKERD2_PROXY_INBOUND`, `LINKERD2_PROXY_OUTBOUND`, or
+// `LINKERD2_PROXY_CONTROL`, e.g. `LINKERD2_PROXY_INBOUND_HTTP2_MAX_FRAME_SIZE`.
+// The inbound and outbound window sizes default to the values set above.
+const HTTP2_INITIAL_STREAM_WINDOW_SIZE: &str = "HTTP2_INITIAL_STREAM_WINDOW_SIZE";
+const HTTP2_INITIAL_CONNECTION_WINDOW_SIZE: &str = "HTTP2_INITIAL_CONNECTION_WINDOW_SIZE";
+/// Sizes flow control windows from an estimate of the bandwidth-delay product,
+/// overriding the initial window sizes.
+const HTTP2_ADAPTIVE_WINDOW: &str = "HTTP2_ADAPTIVE_WINDOW";
+/// Limits the number of concurrent streams a client may open to a server.
+const HTTP2_MAX_CONCURRENT_STREAMS: &str = "HTTP2_MAX_CONCURRENT_STREAMS";
+/// Limits the size of frames that a peer may send, between 16,384 and
+/// 16,777,215 bytes.
+const HTTP2_MAX_FRAME_SIZE: &str = "HTTP2_MAX_FRAME_SIZE";
+
 // Default values for various configuration fields
 const DEFAULT_OUTBOUND_LISTEN_ADDR: &str = "127.0.0.1:4140";
 pub const DEFAULT_INBOUND_LISTEN_ADDR: &str = "0.0.0.0:4143";
@@ -221,6 +410,33 @@ const DEFAULT_OUTBOUND_MAX_IDLE_CONNS_PER_ENDPOINT: usize = std::usize::MAX;
 const DEFAULT_INBOUND_MAX_IN_FLIGHT: usize = 100_000;
 const DEFAULT_OUTBOUND_MAX_IN_FLIGHT: usize = 100_000;
 
+// Request bodies are buffered so that they may be replayed when retried. This
+// bounds the memory each retryable request may hold onto.
+const DEFAULT_OUTBOUND_MAX_RETRY_BODY_BYTES: usize = 64 * 1024;
+
+// Retries are bounded by each route's retry budget, but a single request
+// should not be retried indefinitely while the budget allows. Retries are
+// delayed so that they don't pile onto an already-failing service.
+const DEFAULT_OUTBOUND_RETRY_MAX_ATTEMPTS: usize = 3;
+const DEFAULT_OUTBOUND_RETRY_BACKOFF: ExponentialBackoff = ExponentialBackoff {
+    min: Duration::from_millis(25),
+    max: Duration::from_millis(250),
+    jitter: 0.5,
+};
+
+// Ejected endpoints are probed after a backoff, which grows each time a probe
+// fails.
+const DEFAULT_OUTBOUND_EJECT_BACKOFF: ExponentialBackoff = ExponentialBackoff {
+    min: Duration::from_secs(5),
+    max: Duration::from_secs(60),
+    jitter: 0.5,
+};
+const DEFAULT_OUTBOUND_EJECT_FAILURE_RATE_MIN_REQUESTS: usize = 10;
+const DEFAULT_OUTBOUND_EJECT_FAILURE_RATE_WINDOW: Duration = Duration::from_secs(10);
+// At least half of a balancer's endpoints remain available, even when more of
+// them are failing.
+const DEFAULT_OUTBOUND_EJECT_MAX_EJECTED: f64 = 0.5;
+
 // This value should be large enough to admit requests without exerting
 // backpressure so that requests implicitly buffer in the executor; but it
 // should be small enough that callers can't force the proxy to consume an
@@ -239,6 +455,11 @@ const DEFAULT_IDENTITY_MAX_REFRESH: Duration = Duration::from_secs(60 * 60 * 24)
 
 const INBOUND_CONNECT_BASE: &str = "INBOUND_CONNECT";
 const OUTBOUND_CONNECT_BASE: &str = "OUTBOUND_CONNECT";
+const OUTBOUND_RETRY_BASE: &str = "OUTBOUND_RETRY";
+const OUTBOUND_EJECT_BASE: &str = "OUTBOUND_EJECT";
+const INBOUND_HTTP2_BASE: &str = "INBOUND";
+const OUTBOUND_HTTP2_BASE: &str = "OUTBOUND";
+const CONTROL_HTTP2_BASE: &str = "CONTROL";
 
 /// Load a `App` by reading ENV variables.
 pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError> {
@@ -273,6 +494,12 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         ENV_INBOUND_PORTS_DISABLE_PROTOCOL_DETECTION,
         parse_port_set,
     );
+    let outbound_disable_ports = parse(
+        strings,
+        ENV_OUTBOUND_PORTS_DISABLE_PROTOCOL_DETECTION,
+        parse_port_set,
+    );
+    let port_policy_path = parse(strings, ENV_PORT_POLICY, |s| Ok(PathBuf::from(s)));
 
     let buffer_capacity = parse(strings, ENV_BUFFER_CAPACITY, parse_number);
 
@@ -291,9 +518,51 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         ENV_OUTBOUND_MAX_IDLE_CONNS_PER_ENDPOINT,
         parse_number,
     );
+    let inbound_max_conns_per_endpoint = parse(
+        strings,
+        ENV_INBOUND_MAX_CONNS_PER_ENDPOINT,
+        parse_number::<NonZeroUsize>,
+    );
+    let outbound_max_conns_per_endpoint = parse(
+        strings,
+        ENV_OUTBOUND_MAX_CONNS_PER_ENDPOINT,
+        parse_number::<NonZeroUsize>,
+    );
 
     let inbound_max_in_flight = parse(strings, ENV_INBOUND_MAX_IN_FLIGHT, parse_number);
+    let inbound_route_rate_limit = parse(strings, ENV_INBOUND_ROUTE_RATE_LIMIT, parse_rate_limit);
+    let inbound_client_rate_limit = parse(strings, ENV_INBOUND_CLIENT_RATE_LIMIT, parse_rate_limit);
+    let inbound_adaptive_concurrency_limit = parse(
+        strings,
+        ENV_INBOUND_ADAPTIVE_CONCURRENCY_LIMIT,
+        parse_concurrency_limit,
+    );
     let outbound_max_in_flight = parse(strings, ENV_OUTBOUND_MAX_IN_FLIGHT, parse_number);
+    let outbound_max_retry_body_bytes =
+        parse(strings, ENV_OUTBOUND_MAX_RETRY_BODY_BYTES, parse_number);
+    let outbound_retry_max_attempts = parse(strings, ENV_OUTBOUND_RETRY_MAX_ATTEMPTS, parse_number);
+    let outbound_retry_connection_errors =
+        parse(strings, ENV_OUTBOUND_RETRY_CONNECTION_ERRORS, parse_bool);
+    let outbound_hedge = parse(strings, ENV_OUTBOUND_HEDGE_LATENCY_PERCENTILE, parse_hedge);
+    let outbound_eject_consecutive_failures = parse(
+        strings,
+        ENV_OUTBOUND_EJECT_CONSECUTIVE_FAILURES,
+        parse_number::<NonZeroUsize>,
+    );
+    let outbound_eject_failure_rate = parse(strings, ENV_OUTBOUND_EJECT_FAILURE_RATE, parse_ratio);
+    let outbound_balancer = parse(strings, ENV_OUTBOUND_BALANCER, parse_balancer);
+    let zone = strings.get(ENV_ZONE);
+    let outbound_eject_failure_rate_min_requests = parse(
+        strings,
+        ENV_OUTBOUND_EJECT_FAILURE_RATE_MIN_REQUESTS,
+        parse_number,
+    );
+    let outbound_eject_failure_rate_window = parse(
+        strings,
+        ENV_OUTBOUND_EJECT_FAILURE_RATE_WINDOW,
+        parse_duration,
+    );
+    let outbound_eject_max_ejected = parse(strings, ENV_OUTBOUND_EJECT_MAX_EJECTED, parse_ratio);
 
     let metrics_retain_idle = parse(strings, ENV_METRICS_RETAIN_IDLE, parse_duration);
 
@@ -326,8 +595,27 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
     } else {
         parse_control_addr(strings, ENV_TRACE_COLLECTOR_SVC_BASE)
     };
+    let trace_collector_protocol =
+        parse(strings, ENV_TRACE_COLLECTOR_PROTOCOL, parse_trace_protocol);
+    let trace_sample_probability = parse(strings, ENV_TRACE_SAMPLE_PROBABILITY, parse_ratio);
+    let trace_sample_max_per_second = parse(
+        strings,
+        ENV_TRACE_SAMPLE_MAX_PER_SECOND,
+        parse_number::<NonZeroU32>,
+    );
+    let trace_request_headers = parse(strings, ENV_TRACE_REQUEST_HEADERS, parse_header_names);
+    let trace_response_headers = parse(strings, ENV_TRACE_RESPONSE_HEADERS, parse_header_names);
+
+    let access_log_format = parse(strings, ENV_ACCESS_LOG_FORMAT, parse_access_log_format);
+    let access_log_path = parse(strings, ENV_ACCESS_LOG_PATH, |s| Ok(PathBuf::from(s)));
+    let access_log_sample_ratio = parse(strings, ENV_ACCESS_LOG_SAMPLE_RATIO, parse_ratio);
+    let log_connections = parse(strings, ENV_CONNECTION_LOG, parse_bool);
+    let inbound_authorization_policy = parse(strings, ENV_INBOUND_AUTHORIZATION_POLICY, |s| {
+        Ok(PathBuf::from(s))
+    });
 
     let dst_token = strings.get(ENV_DESTINATION_CONTEXT);
+    let profile_overrides_path = parse(strings, ENV_PROFILE_OVERRIDES, |s| Ok(PathBuf::from(s)));
 
     let gateway_suffixes = parse(strings, ENV_INBOUND_GATEWAY_SUFFIXES, parse_dns_suffixes);
     let dst_profile_idle_timeout = parse(
@@ -357,6 +645,11 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         ),
         ..Default::default()
     };
+    let inbound_h2_settings = parse_h2_settings(strings, INBOUND_HTTP2_BASE, h2_settings);
+    let outbound_h2_settings = parse_h2_settings(strings, OUTBOUND_HTTP2_BASE, h2_settings);
+    // Control plane clients don't use the proxy's window sizes by default.
+    let control_h2_settings =
+        parse_h2_settings(strings, CONTROL_HTTP2_BASE, h2::Settings::default());
 
     let buffer_capacity = buffer_capacity?.unwrap_or(DEFAULT_BUFFER_CAPACITY);
 
@@ -385,6 +678,7 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
     let dst_profile_networks = dst_profile_networks?.unwrap_or_default();
 
     let outbound = {
+        let h2_settings = outbound_h2_settings?;
         let ingress_mode = parse(strings, ENV_INGRESS_MODE, parse_bool)?.unwrap_or(false);
 
         let keepalive = Keepalive(outbound_accept_keepalive?);
@@ -407,9 +701,10 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         let max_idle =
             outbound_max_idle_per_endoint?.unwrap_or(DEFAULT_OUTBOUND_MAX_IDLE_CONNS_PER_ENDPOINT);
         let keepalive = Keepalive(outbound_connect_keepalive?);
+        let connect_timeout = outbound_connect_timeout?.unwrap_or(DEFAULT_OUTBOUND_CONNECT_TIMEOUT);
         let connect = ConnectConfig {
             keepalive,
-            timeout: outbound_connect_timeout?.unwrap_or(DEFAULT_OUTBOUND_CONNECT_TIMEOUT),
+            timeout: connect_timeout,
             backoff: parse_backoff(
                 strings,
                 OUTBOUND_CONNECT_BASE,
@@ -422,6 +717,8 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
             h1_settings: h1::PoolSettings {
                 max_idle,
                 idle_timeout: cache_max_idle_age,
+                max_connections: outbound_max_conns_per_endpoint?.map(NonZeroUsize::get),
+                connect_timeout,
             },
         };
 
@@ -430,8 +727,44 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         let dispatch_timeout =
             outbound_dispatch_timeout?.unwrap_or(DEFAULT_OUTBOUND_DISPATCH_TIMEOUT);
 
+        let eject_failure_rate_min_requests = outbound_eject_failure_rate_min_requests?
+            .unwrap_or(DEFAULT_OUTBOUND_EJECT_FAILURE_RATE_MIN_REQUESTS);
+        let eject_failure_rate_window = outbound_eject_failure_rate_window?
+            .unwrap_or(DEFAULT_OUTBOUND_EJECT_FAILURE_RATE_WINDOW);
+
         outbound::Config {
             ingress_mode,
+            retry: retry::Config {
+                max_body_bytes: outbound_max_retry_body_bytes?
+                    .unwrap_or(DEFAULT_OUTBOUND_MAX_RETRY_BODY_BYTES),
+                max_attempts: outbound_retry_max_attempts?
+                    .unwrap_or(DEFAULT_OUTBOUND_RETRY_MAX_ATTEMPTS),
+                backoff: parse_backoff(
+                    strings,
+                    OUTBOUND_RETRY_BASE,
+                    DEFAULT_OUTBOUND_RETRY_BACKOFF,
+                )?,
+                retry_connection_errors: outbound_retry_connection_errors?.unwrap_or(false),
+            },
+            hedge: outbound_hedge?,
+            eject: eject::Config {
+                consecutive_failures: outbound_eject_consecutive_failures?.map(NonZeroUsize::get),
+                failure_rate: outbound_eject_failure_rate?.map(|threshold| eject::FailureRate {
+                    threshold,
+                    min_requests: eject_failure_rate_min_requests,
+                    window: eject_failure_rate_window,
+                }),
+                backoff: parse_backoff(
+                    strings,
+                    OUTBOUND_EJECT_BASE,
+                    DEFAULT_OUTBOUND_EJECT_BACKOFF,
+                )?,
+                max_ejected: outbound_eject_max_ejected?
+                    .unwrap_or(DEFAULT_OUTBOUND_EJECT_MAX_EJECTED),
+            },
+            balancer: outbound_balancer?.unwrap_or_default(),
+            zone: zone?.filter(|z| !z.is_empty()),
+            opaque_ports: outbound_disable_ports?.unwrap_or_default().into(),
             allow_discovery: AddrMatch::new(dst_profile_suffixes.clone(), dst_profile_networks),
             proxy: ProxyConfig {
                 server,
@@ -451,6 +784,7 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
     };
 
     let inbound = {
+        let h2_settings = inbound_h2_settings?;
         let keepalive = Keepalive(inbound_accept_keepalive?);
         let bind = BindTcp::new(
             ListenAddr(
@@ -471,9 +805,10 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         let max_idle =
             inbound_max_idle_per_endpoint?.unwrap_or(DEFAULT_INBOUND_MAX_IDLE_CONNS_PER_ENDPOINT);
         let keepalive = Keepalive(inbound_connect_keepalive?);
+        let connect_timeout = inbound_connect_timeout?.unwrap_or(DEFAULT_INBOUND_CONNECT_TIMEOUT);
         let connect = ConnectConfig {
             keepalive,
-            timeout: inbound_connect_timeout?.unwrap_or(DEFAULT_INBOUND_CONNECT_TIMEOUT),
+            timeout: connect_timeout,
             backoff: parse_backoff(
                 strings,
                 INBOUND_CONNECT_BASE,
@@ -486,6 +821,8 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
             h1_settings: h1::PoolSettings {
                 max_idle,
                 idle_timeout: cache_max_idle_age,
+                max_connections: inbound_max_conns_per_endpoint?.map(NonZeroUsize::get),
+                connect_timeout,
             },
         };
 
@@ -543,9 +880,14 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
             profile_idle_timeout: dst_profile_idle_timeout?
                 .unwrap_or(DEFAULT_DESTINATION_PROFILE_IDLE_TIMEOUT),
             disable_protocol_detection_for_ports: inbound_opaque_ports.into(),
+            route_rate_limit: inbound_route_rate_limit?,
+            client_rate_limit: inbound_client_rate_limit?,
+            adaptive_concurrency_limit: inbound_adaptive_concurrency_limit?,
         }
     };
 
+    let control_h2_settings = control_h2_settings?;
+
     let dst = {
         let addr = dst_addr?.ok_or(EnvError::NoDestinationAddress)?;
         let connect = if addr.addr.is_loopback() {
@@ -553,8 +895,14 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         } else {
             outbound.proxy.connect.clone()
         };
+        let connect = ConnectConfig {
+            h2_settings: control_h2_settings,
+            ..connect
+        };
         super::dst::Config {
             context: dst_token?.unwrap_or_default(),
+            profile_overrides: profile_overrides_path?
+                .map(|path| profiles::overrides::Config { path }),
             control: ControlConfig {
                 addr,
                 connect,
@@ -593,6 +941,10 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
             } else {
                 outbound.proxy.connect.clone()
             };
+            let connect = ConnectConfig {
+                h2_settings: control_h2_settings,
+                ..connect
+            };
 
             let attributes = oc_attributes_file_path
                 .map(|path| match path {
@@ -601,9 +953,29 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
                 })
                 .unwrap_or_default();
 
+            let max_per_second = trace_sample_max_per_second?;
+            let sampler = match trace_sample_probability? {
+                Some(probability) => Some(sample::Config {
+                    probability,
+                    max_per_second,
+                }),
+                // When only a limit is configured, every request starts a
+                // trace until the limit is reached.
+                None => max_per_second.map(|max| sample::Config {
+                    probability: 1.0,
+                    max_per_second: Some(max),
+                }),
+            };
+
             oc_collector::Config::Enabled(Box::new(oc_collector::EnabledConfig {
                 attributes,
+                sampler,
+                headers: CaptureHeaders {
+                    request: trace_request_headers?.unwrap_or_default(),
+                    response: trace_response_headers?.unwrap_or_default(),
+                },
                 hostname: hostname?,
+                protocol: trace_collector_protocol?.unwrap_or(oc_collector::Protocol::OpenCensus),
                 control: ControlConfig {
                     addr,
                     connect,
@@ -613,6 +985,17 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         }
     };
 
+    let access_log = match access_log_format? {
+        None => None,
+        Some(format) => Some(access_log::Config {
+            format,
+            output: access_log_path?
+                .map(access_log::Output::File)
+                .unwrap_or(access_log::Output::Stderr),
+            sample_ratio: access_log_sample_ratio?.unwrap_or(1.0),
+        }),
+    };
+
     let tap = tap?
         .map(|(addr, ids)| super::tap::Config::Enabled {
             permitted_client_ids: ids,
@@ -624,21 +1007,28 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         .unwrap_or(super::tap::Config::Disabled);
 
     let identity = identity_config?
-        .map(|(addr, certify)| {
-            // If the address doesn't have a server identity, then we're on localhost.
-            let connect = if addr.identity.is_none() {
-                inbound.proxy.connect.clone()
-            } else {
-                outbound.proxy.connect.clone()
-            };
-            identity::Config::Enabled {
-                certify,
-                control: ControlConfig {
-                    addr,
-                    connect,
-                    buffer_capacity: 1,
-                },
+        .map(|config| match config {
+            IdentityConfig::Certify(addr, certify) => {
+                // If the address doesn't have a server identity, then we're on localhost.
+                let connect = if addr.identity.is_none() {
+                    inbound.proxy.connect.clone()
+                } else {
+                    outbound.proxy.connect.clone()
+                };
+                let connect = ConnectConfig {
+                    h2_settings: control_h2_settings,
+                    ..connect
+                };
+                identity::Config::Enabled {
+                    certify,
+                    control: ControlConfig {
+                        addr,
+                        connect,
+                        buffer_capacity: 1,
+                    },
+                }
             }
+            IdentityConfig::Files(files) => identity::Config::Files(files),
         })
         .unwrap_or(identity::Config::Disabled);
 
@@ -648,6 +1038,10 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         dst,
         tap,
         oc_collector,
+        access_log,
+        log_connections: log_connections?.unwrap_or(false),
+        inbound_authorization: inbound_authorization_policy?.map(|path| authz::Config { path }),
+        port_policy: port_policy_path?.map(|path| port_policy::Config { path }),
         identity,
         outbound,
         gateway,
@@ -719,7 +1113,7 @@ fn parse_tap_config(
     strings: &dyn Strings,
     id_disabled: bool,
 ) -> Result<Option<(SocketAddr, IndexSet<tls::server::ClientId>)>, EnvError> {
-    let tap_identity = parse(strings, ENV_TAP_SVC_NAME, parse_identity)?;
+    let tap_identity = parse(strings, ENV_TAP_SVC_NAME, parse_peer_identity)?;
     if id_disabled {
         if tap_identity.is_some() {
             warn!(
@@ -751,6 +1145,70 @@ where
     s.parse().map_err(|_| ParseError::NotANumber)
 }
 
+fn parse_hedge(s: &str) -> Result<profiles::http::Hedge, ParseError> {
+    let percentile = parse_number::<f64>(s)?;
+    profiles::http::Hedge::new(percentile).ok_or(ParseError::NotAPercentile)
+}
+
+fn parse_ratio(s: &str) -> Result<f64, ParseError> {
+    let ratio = parse_number::<f64>(s)?;
+    if ratio > 0.0 && ratio <= 1.0 {
+        Ok(ratio)
+    } else {
+        Err(ParseError::NotARatio)
+    }
+}
+
+fn parse_balancer(s: &str) -> Result<balance::Strategy, ParseError> {
+    s.parse().map_err(|_| ParseError::NotABalancer)
+}
+
+fn parse_rate_limit(s: &str) -> Result<rate_limit::Limit, ParseError> {
+    let (per_second, burst) = match s.find(':') {
+        Some(idx) => (&s[..idx], Some(&s[idx + 1..])),
+        None => (s, None),
+    };
+    let per_second = parse_number::<u32>(per_second)?;
+    let burst = match burst {
+        Some(burst) => parse_number::<u32>(burst)?,
+        None => per_second,
+    };
+    rate_limit::Limit::new(per_second, burst).ok_or(ParseError::NotARateLimit)
+}
+
+fn parse_concurrency_limit(s: &str) -> Result<concurrency_limit::adaptive::Config, ParseError> {
+    let idx = s.find(':').ok_or(ParseError::NotAConcurrencyLimit)?;
+    let min_limit = parse_number::<usize>(&s[..idx])?;
+    let max_limit = parse_number::<usize>(&s[idx + 1..])?;
+    if min_limit == 0 || min_limit > max_limit {
+        return Err(ParseError::NotAConcurrencyLimit);
+    }
+    Ok(concurrency_limit::adaptive::Config {
+        min_limit,
+        max_limit,
+        initial_limit: min_limit,
+    })
+}
+
+fn parse_h2_frame_size(s: &str) -> Result<u32, ParseError> {
+    // The bounds of SETTINGS_MAX_FRAME_SIZE, per RFC 7540 section 6.5.2.
+    const MIN: u32 = 16_384;
+    const MAX: u32 = 16_777_215;
+    let size = parse_number::<u32>(s)?;
+    if !(MIN..=MAX).contains(&size) {
+        return Err(ParseError::NotAFrameSize);
+    }
+    Ok(size)
+}
+
+fn parse_trace_protocol(s: &str) -> Result<oc_collector::Protocol, ParseError> {
+    match s {
+        "opencensus" => Ok(oc_collector::Protocol::OpenCensus),
+        "opentelemetry" => Ok(oc_collector::Protocol::OpenTelemetry),
+        _ => Err(ParseError::NotATraceProtocol),
+    }
+}
+
 fn parse_duration(s: &str) -> Result<Duration, ParseError> {
     use regex::Regex;
 
@@ -802,6 +1260,15 @@ pub(super) fn parse_identity(s: &str) -> Result<identity::Name, ParseError> {
     })
 }
 
+/// Parses a remote peer's identity, which may be a DNS-like name or a SPIFFE
+/// ID.
+pub(super) fn parse_peer_identity(s: &str) -> Result<identity::Id, ParseError> {
+    identity::Id::from_str(s).map_err(|identity::InvalidName| {
+        error!("Not a valid identity: {}", s);
+        ParseError::NameError
+    })
+}
+
 pub(super) fn parse<T, Parse>(
     strings: &dyn Strings,
     name: &str,
@@ -866,6 +1333,22 @@ fn parse_dns_suffix(s: &str) -> Result<dns::Suffix, ParseError> {
     dns::Suffix::from_str(s).map_err(|_| ParseError::NotADomainSuffix)
 }
 
+fn parse_header_names(list: &str) -> Result<Vec<http::HeaderName>, ParseError> {
+    let mut names = Vec::new();
+    for item in list.split(',') {
+        let item = item.trim();
+        if !item.is_empty() {
+            let name = http::HeaderName::from_str(item).map_err(|_| ParseError::NotAHeaderName)?;
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+fn parse_access_log_format(s: &str) -> Result<access_log::Format, ParseError> {
+    s.parse().map_err(|_| ParseError::NotAnAccessLogFormat)
+}
+
 fn parse_networks(list: &str) -> Result<IndexSet<ipnet::IpNet>, ParseError> {
     let mut nets = IndexSet::new();
     for input in list.split(',') {
@@ -908,6 +1391,39 @@ pub fn parse_backoff<S: Strings>(
     }
 }
 
+/// Overrides `default` with the HTTP/2 settings configured for `base`.
+pub fn parse_h2_settings<S: Strings>(
+    strings: &S,
+    base: &str,
+    default: h2::Settings,
+) -> Result<h2::Settings, EnvError> {
+    let env = |name| format!("LINKERD2_PROXY_{}_{}", base, name);
+    let initial_stream_window_size = parse(
+        strings,
+        &env(HTTP2_INITIAL_STREAM_WINDOW_SIZE),
+        parse_number,
+    );
+    let initial_connection_window_size = parse(
+        strings,
+        &env(HTTP2_INITIAL_CONNECTION_WINDOW_SIZE),
+        parse_number,
+    );
+    let adaptive_window = parse(strings, &env(HTTP2_ADAPTIVE_WINDOW), parse_bool);
+    let max_concurrent_streams = parse(strings, &env(HTTP2_MAX_CONCURRENT_STREAMS), parse_number);
+    let max_frame_size = parse(strings, &env(HTTP2_MAX_FRAME_SIZE), parse_h2_frame_size);
+
+    Ok(h2::Settings {
+        initial_stream_window_size: initial_stream_window_size?
+            .or(default.initial_stream_window_size),
+        initial_connection_window_size: initial_connection_window_size?
+            .or(default.initial_connection_window_size),
+        adaptive_window: adaptive_window?.unwrap_or(default.adaptive_window),
+        max_concurrent_streams: max_concurrent_streams?.or(default.max_concurrent_streams),
+        max_frame_size: max_frame_size?.or(default.max_frame_size),
+        ..default
+    })
+}
+
 pub fn parse_control_addr<S: Strings>(
     strings: &S,
     base: &str,
@@ -915,7 +1431,7 @@ pub fn parse_control_addr<S: Strings>(
     let a_env = format!("{}_ADDR", base);
     let a = parse(strings, &a_env, parse_addr);
     let n_env = format!("{}_NAME", base);
-    let n = parse(strings, &n_env, parse_identity);
+    let n = parse(strings, &n_env, parse_peer_identity);
     match (a?, n?) {
         (None, None) => Ok(None),
         (Some(ref addr), _) if addr.is_loopback() => Ok(Some(ControlAddr {
@@ -946,13 +1462,13 @@ pub fn parse_control_addr_disable_identity<S: Strings>(
     Ok(a.map(|addr| ControlAddr { addr, identity }))
 }
 
-pub fn parse_identity_config<S: Strings>(
-    strings: &S,
-) -> Result<Option<(ControlAddr, identity::certify::Config)>, EnvError> {
+pub fn parse_identity_config<S: Strings>(strings: &S) -> Result<Option<IdentityConfig>, EnvError> {
+    if let Some(files) = parse_identity_files_config(strings)? {
+        return Ok(Some(IdentityConfig::Files(files)));
+    }
+
     let control = parse_control_addr(strings, ENV_IDENTITY_SVC_BASE);
-    let ta = parse(strings, ENV_IDENTITY_TRUST_ANCHORS, |ref s| {
-        identity::TrustAnchors::from_pem(s).ok_or(ParseError::InvalidTrustAnchors)
-    });
+    let ta = parse_trust_anchors_config(strings);
     let dir = parse(strings, ENV_IDENTITY_DIR, |ref s| Ok(PathBuf::from(s)));
     let tok = parse(strings, ENV_IDENTITY_TOKEN_FILE, |ref s| {
         identity::TokenSource::if_nonempty_file(s.to_string()).map_err(|e| {
@@ -1036,7 +1552,7 @@ pub fn parse_identity_config<S: Strings>(
                     })
             };
 
-            Ok(Some((
+            Ok(Some(IdentityConfig::Certify(
                 control,
                 identity::certify::Config {
                     local_id: tls::LocalId(local_name),
@@ -1058,9 +1574,13 @@ pub fn parse_identity_config<S: Strings>(
             }
             let s = format!("{0}_ADDR and {0}_NAME", ENV_IDENTITY_SVC_BASE);
             let svc_env: &str = &s.as_str();
+            let ta_env = format!(
+                "{} or {}",
+                ENV_IDENTITY_TRUST_ANCHORS, ENV_IDENTITY_TRUST_ANCHORS_FILE
+            );
             for (unset, name) in &[
                 (addr.is_none(), svc_env),
-                (trust_anchors.is_none(), ENV_IDENTITY_TRUST_ANCHORS),
+                (trust_anchors.is_none(), ta_env.as_str()),
                 (end_entity_dir.is_none(), ENV_IDENTITY_DIR),
                 (local_id.is_none(), ENV_IDENTITY_IDENTITY_LOCAL_NAME),
                 (token.is_none(), ENV_IDENTITY_TOKEN_FILE),
@@ -1088,6 +1608,111 @@ impl fmt::Display for EnvError {
 
 impl ::std::error::Error for EnvError {}
 
+/// Reads the trust anchors, which are either set inline or read from files
+/// that are reloaded as they change.
+fn parse_trust_anchors_config<S: Strings>(
+    strings: &S,
+) -> Result<Option<identity::trust_anchors::Config>, EnvError> {
+    let pem = parse(strings, ENV_IDENTITY_TRUST_ANCHORS, |ref s| {
+        identity::TrustAnchors::from_pem(s).ok_or(ParseError::InvalidTrustAnchors)
+    });
+    let files = parse(strings, ENV_IDENTITY_TRUST_ANCHORS_FILE, |s| {
+        Ok(s.split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .collect::<Vec<_>>())
+    });
+
+    match (pem?, files?) {
+        (None, None) => Ok(None),
+        (Some(trust_anchors), None) => {
+            Ok(Some(identity::trust_anchors::Config::Static(trust_anchors)))
+        }
+        (None, Some(paths)) if !paths.is_empty() => {
+            Ok(Some(identity::trust_anchors::Config::Files(paths)))
+        }
+        (None, Some(_)) => {
+            error!("{} must not be empty.", ENV_IDENTITY_TRUST_ANCHORS_FILE);
+            Err(EnvError::InvalidEnvVar)
+        }
+        (Some(_), Some(_)) => {
+            error!(
+                "{} and {} must not both be set.",
+                ENV_IDENTITY_TRUST_ANCHORS, ENV_IDENTITY_TRUST_ANCHORS_FILE
+            );
+            Err(EnvError::InvalidEnvVar)
+        }
+    }
+}
+
+/// Reads the configuration for a local identity that is loaded from files,
+/// returning `None` if no identity files are configured.
+fn parse_identity_files_config<S: Strings>(
+    strings: &S,
+) -> Result<Option<identity::files::Config>, EnvError> {
+    let crt = parse(strings, ENV_IDENTITY_CERTIFICATE_FILE, |s| {
+        Ok(PathBuf::from(s))
+    });
+    let key = parse(strings, ENV_IDENTITY_KEY_FILE, |s| Ok(PathBuf::from(s)));
+
+    let (crt, key) = match (crt?, key?) {
+        (None, None) => return Ok(None),
+        (Some(crt), Some(key)) => (crt, key),
+        (crt, key) => {
+            for (unset, name) in &[
+                (crt.is_none(), ENV_IDENTITY_CERTIFICATE_FILE),
+                (key.is_none(), ENV_IDENTITY_KEY_FILE),
+            ] {
+                if *unset {
+                    error!("{} must be set when other identity files are set.", name);
+                }
+            }
+            return Err(EnvError::InvalidEnvVar);
+        }
+    };
+
+    let svc_addr = format!("{}_ADDR", ENV_IDENTITY_SVC_BASE);
+    let mut conflicts = false;
+    for name in &[
+        ENV_IDENTITY_DISABLED,
+        ENV_IDENTITY_DIR,
+        ENV_IDENTITY_TOKEN_FILE,
+        svc_addr.as_str(),
+    ] {
+        if strings.get(name)?.map_or(false, |v| !v.is_empty()) {
+            error!("{} must be unset when identity files are set.", name);
+            conflicts = true;
+        }
+    }
+    if conflicts {
+        return Err(EnvError::InvalidEnvVar);
+    }
+
+    let local_id = parse(strings, ENV_IDENTITY_IDENTITY_LOCAL_NAME, parse_identity)?;
+    let local_id = local_id.ok_or_else(|| {
+        error!(
+            "{} must be set when identity files are set.",
+            ENV_IDENTITY_IDENTITY_LOCAL_NAME
+        );
+        EnvError::InvalidEnvVar
+    })?;
+    let trust_anchors = parse_trust_anchors_config(strings)?.ok_or_else(|| {
+        error!(
+            "{} or {} must be set when identity files are set.",
+            ENV_IDENTITY_TRUST_ANCHORS, ENV_IDENTITY_TRUST_ANCHORS_FILE
+        );
+        EnvError::InvalidEnvVar
+    })?;
+
+    Ok(Some(identity::files::Config {
+        local_id: tls::LocalId(local_id),
+        trust_anchors,
+        crt,
+        key,
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1248,4 +1873,143 @@ mod tests {
             "names are coerced to lowercase"
         );
     }
+
+    #[test]
+    fn parse_ratio_bounds() {
+        assert_eq!(parse_ratio("0.5"), Ok(0.5));
+        assert_eq!(parse_ratio("1"), Ok(1.0));
+        assert_eq!(parse_ratio("0"), Err(ParseError::NotARatio));
+        assert_eq!(parse_ratio("1.5"), Err(ParseError::NotARatio));
+        assert_eq!(parse_ratio("NaN"), Err(ParseError::NotARatio));
+        assert_eq!(parse_ratio("half"), Err(ParseError::NotANumber));
+    }
+
+    #[test]
+    fn parse_balancer_strategies() {
+        use balance::{HashKey, Strategy};
+
+        assert_eq!(parse_balancer("peak-ewma"), Ok(Strategy::PeakEwma));
+        assert_eq!(parse_balancer("least-request"), Ok(Strategy::LeastRequest));
+        assert_eq!(parse_balancer("round-robin"), Ok(Strategy::RoundRobin));
+        assert_eq!(
+            parse_balancer("ring-hash:client-addr"),
+            Ok(Strategy::RingHash(HashKey::ClientAddr))
+        );
+        assert_eq!(
+            parse_balancer("ring-hash:header:x-session-id"),
+            Ok(Strategy::RingHash(HashKey::Header(
+                http::HeaderName::from_static("x-session-id")
+            )))
+        );
+        assert_eq!(
+            parse_balancer("ring-hash:header:"),
+            Err(ParseError::NotABalancer)
+        );
+        assert_eq!(parse_balancer("ring-hash"), Err(ParseError::NotABalancer));
+        assert_eq!(parse_balancer("random"), Err(ParseError::NotABalancer));
+    }
+
+    #[test]
+    fn parse_rate_limits() {
+        let limit = parse_rate_limit("100").unwrap();
+        assert_eq!((limit.per_second(), limit.burst()), (100, 100));
+        let limit = parse_rate_limit("100:20").unwrap();
+        assert_eq!((limit.per_second(), limit.burst()), (100, 20));
+        assert_eq!(parse_rate_limit("0"), Err(ParseError::NotARateLimit));
+        assert_eq!(parse_rate_limit("10:0"), Err(ParseError::NotARateLimit));
+        assert_eq!(parse_rate_limit("10:"), Err(ParseError::NotANumber));
+        assert_eq!(parse_rate_limit("fast"), Err(ParseError::NotANumber));
+    }
+
+    #[test]
+    fn parse_h2_frame_sizes() {
+        assert_eq!(parse_h2_frame_size("16384"), Ok(16_384));
+        assert_eq!(parse_h2_frame_size("16777215"), Ok(16_777_215));
+        assert_eq!(parse_h2_frame_size("16383"), Err(ParseError::NotAFrameSize));
+        assert_eq!(
+            parse_h2_frame_size("16777216"),
+            Err(ParseError::NotAFrameSize)
+        );
+        assert_eq!(parse_h2_frame_size("big"), Err(ParseError::NotANumber));
+    }
+
+    #[test]
+    fn parse_trace_protocols() {
+        use oc_collector::Protocol;
+
+        assert_eq!(parse_trace_protocol("opencensus"), Ok(Protocol::OpenCensus));
+        assert_eq!(
+            parse_trace_protocol("opentelemetry"),
+            Ok(Protocol::OpenTelemetry)
+        );
+        assert_eq!(
+            parse_trace_protocol("zipkin"),
+            Err(ParseError::NotATraceProtocol)
+        );
+    }
+
+    #[test]
+    fn parse_header_name_lists() {
+        assert_eq!(
+            parse_header_names("x-request-id, User-Agent,"),
+            Ok(vec![
+                http::HeaderName::from_static("x-request-id"),
+                http::HeaderName::from_static("user-agent"),
+            ])
+        );
+        assert_eq!(parse_header_names(""), Ok(vec![]));
+        assert_eq!(
+            parse_header_names("x-request-id,bad header"),
+            Err(ParseError::NotAHeaderName)
+        );
+    }
+
+    #[test]
+    fn parse_access_log_formats() {
+        assert_eq!(
+            parse_access_log_format("json"),
+            Ok(access_log::Format::Json)
+        );
+        assert_eq!(
+            parse_access_log_format("apache"),
+            Ok(access_log::Format::Apache)
+        );
+        assert!(matches!(
+            parse_access_log_format("{method} {path} {status}"),
+            Ok(access_log::Format::Template(_))
+        ));
+        assert_eq!(
+            parse_access_log_format("{method} {bogus}"),
+            Err(ParseError::NotAnAccessLogFormat)
+        );
+        assert_eq!(
+            parse_access_log_format("{method"),
+            Err(ParseError::NotAnAccessLogFormat)
+        );
+    }
+
+    #[test]
+    fn parse_concurrency_limits() {
+        let config = parse_concurrency_limit("10:1000").unwrap();
+        assert_eq!(
+            (config.min_limit, config.max_limit, config.initial_limit),
+            (10, 1000, 10)
+        );
+        assert_eq!(
+            parse_concurrency_limit("10").unwrap_err(),
+            ParseError::NotAConcurrencyLimit
+        );
+        assert_eq!(
+            parse_concurrency_limit("0:10").unwrap_err(),
+            ParseError::NotAConcurrencyLimit
+        );
+        assert_eq!(
+            parse_concurrency_limit("100:10").unwrap_err(),
+            ParseError::NotAConcurrencyLimit
+        );
+        assert_eq!(
+            parse_concurrency_limit("10:many").unwrap_err(),
+            ParseError::NotANumber
+        );
+    }
 }
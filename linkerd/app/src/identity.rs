@@ -1,7 +1,7 @@
 pub use linkerd_app_core::identity::{
-    Crt, CrtKey, Csr, InvalidName, Key, Name, TokenSource, TrustAnchors,
+    Crt, CrtKey, Csr, Id, InvalidName, Key, Name, SpiffeId, TokenSource, TrustAnchors,
 };
-pub use linkerd_app_core::proxy::identity::{certify, metrics, LocalCrtKey};
+pub use linkerd_app_core::proxy::identity::{certify, files, metrics, trust_anchors, LocalCrtKey};
 use linkerd_app_core::{
     control, dns,
     exp_backoff::{ExponentialBackoff, ExponentialBackoffStream},
@@ -21,6 +21,9 @@ pub enum Config {
         control: control::Config,
         certify: certify::Config,
     },
+    /// Loads the local identity from files rather than from the Identity
+    /// service.
+    Files(files::Config),
 }
 
 // The Disabled case is extraordinarily rare.
@@ -28,7 +31,9 @@ pub enum Config {
 pub enum Identity {
     Disabled,
     Enabled {
-        addr: control::ControlAddr,
+        /// The Identity service's address, unless the identity is loaded
+        /// from files.
+        addr: Option<control::ControlAddr>,
         local: LocalCrtKey,
         task: Task,
     },
@@ -44,7 +49,7 @@ impl Config {
         match self {
             Config::Disabled => Ok(Identity::Disabled),
             Config::Enabled { control, certify } => {
-                let (local, daemon) = LocalCrtKey::new(&certify);
+                let (local, daemon) = LocalCrtKey::new(&certify)?;
 
                 let addr = control.addr.clone();
                 let svc = control.build(dns, metrics, Some(local.clone()));
@@ -59,7 +64,24 @@ impl Config {
                     )
                 };
 
-                Ok(Identity::Enabled { addr, local, task })
+                Ok(Identity::Enabled {
+                    addr: Some(addr),
+                    local,
+                    task,
+                })
+            }
+            Config::Files(files) => {
+                let (local, daemon) = LocalCrtKey::from_files(&files)?;
+                let task = Box::pin(
+                    daemon
+                        .run()
+                        .instrument(tracing::debug_span!("identity_files")),
+                );
+                Ok(Identity::Enabled {
+                    addr: None,
+                    local,
+                    task,
+                })
             }
         }
     }
@@ -12,7 +12,10 @@ pub mod tap;
 pub use self::metrics::Metrics;
 use futures::{future, FutureExt, TryFutureExt};
 pub use linkerd_app_core::{self as core, metrics, trace};
-use linkerd_app_core::{control::ControlAddr, dns, drain, proxy::http, svc, Error, ProxyRuntime};
+use linkerd_app_core::{
+    access_log, authz, control::ControlAddr, dns, drain, port_policy, proxy::http, svc, Error,
+    ProxyRuntime,
+};
 use linkerd_app_gateway as gateway;
 use linkerd_app_inbound::{self as inbound, Inbound};
 use linkerd_app_outbound::{self as outbound, Outbound};
@@ -46,6 +49,10 @@ pub struct Config {
     pub admin: admin::Config,
     pub tap: tap::Config,
     pub oc_collector: oc_collector::Config,
+    pub access_log: Option<access_log::Config>,
+    pub log_connections: bool,
+    pub inbound_authorization: Option<authz::Config>,
+    pub port_policy: Option<port_policy::Config>,
 }
 
 pub struct App {
@@ -83,12 +90,17 @@ impl Config {
             identity,
             inbound,
             oc_collector,
+            access_log,
+            log_connections,
+            inbound_authorization,
+            port_policy,
             outbound,
             gateway,
             tap,
         } = self;
         debug!("building app");
         let (metrics, report) = Metrics::new(admin.metrics_retain_idle);
+        let metrics = metrics.log_connections(log_connections);
 
         let dns = dns.build();
 
@@ -96,6 +108,31 @@ impl Config {
             .in_scope(|| identity.build(dns.resolver.clone(), metrics.control.clone()))?;
         let report = identity.metrics().and_then(report);
 
+        let (authorize, authorize_task) = match inbound_authorization {
+            None => (None, None),
+            Some(config) => {
+                let (authorize, task) = info_span!("authz").in_scope(|| config.build())?;
+                (Some(authorize), Some(task))
+            }
+        };
+        let report = authorize
+            .as_ref()
+            .map(authz::Authorize::metrics)
+            .unwrap_or_default()
+            .and_then(report);
+
+        let (inbound, outbound, port_policy_task) = match port_policy {
+            None => (inbound, outbound, None),
+            Some(config) => {
+                let (policy, task) = info_span!("port_policy").in_scope(|| config.build())?;
+                (
+                    inbound.with_port_policy(policy.clone()),
+                    outbound.with_port_policy(policy),
+                    Some(task),
+                )
+            }
+        };
+
         let (drain_tx, drain_rx) = drain::channel();
 
         let tap = info_span!("tap").in_scope(|| tap.build(identity.local(), drain_rx.clone()))?;
@@ -109,11 +146,21 @@ impl Config {
             let identity = identity.local();
             let dns = dns.resolver;
             let client_metrics = metrics.control;
-            let metrics = metrics.opencensus;
-            info_span!("opencensus")
-                .in_scope(|| oc_collector.build(identity, dns, metrics, client_metrics))
+            let oc_metrics = metrics.opencensus;
+            let otel_metrics = metrics.opentelemetry;
+            info_span!("opencensus").in_scope(|| {
+                oc_collector.build(identity, dns, oc_metrics, otel_metrics, client_metrics)
+            })
         }?;
 
+        let (access_log, access_log_task) = match access_log {
+            None => (None, None),
+            Some(config) => {
+                let (log, task) = config.build()?;
+                (Some(log), Some(task))
+            }
+        };
+
         let admin = {
             let identity = identity.local();
             let drain = drain_rx.clone();
@@ -124,6 +171,7 @@ impl Config {
         };
 
         let dst_addr = dst.addr.clone();
+        let profile_overrides_task = dst.profile_overrides_task;
 
         let inbound = Inbound::new(
             inbound,
@@ -132,6 +180,10 @@ impl Config {
                 metrics: metrics.inbound,
                 tap: tap.registry(),
                 span_sink: oc_collector.span_sink(),
+                span_sampler: oc_collector.span_sampler(),
+                span_headers: oc_collector.span_headers(),
+                access_log: access_log.clone(),
+                authorize,
                 drain: drain_rx.clone(),
             },
         );
@@ -143,6 +195,10 @@ impl Config {
                 metrics: metrics.outbound,
                 tap: tap.registry(),
                 span_sink: oc_collector.span_sink(),
+                span_sampler: oc_collector.span_sampler(),
+                span_headers: oc_collector.span_headers(),
+                access_log,
+                authorize: None,
                 drain: drain_rx,
             },
         );
@@ -161,6 +217,18 @@ impl Config {
         let start_proxy = Box::pin(async move {
             tokio::spawn(outbound_serve.instrument(info_span!("outbound")));
             tokio::spawn(inbound_serve.instrument(info_span!("inbound")));
+            if let Some(task) = access_log_task {
+                tokio::spawn(task.instrument(info_span!("access_log")));
+            }
+            if let Some(task) = authorize_task {
+                tokio::spawn(task.instrument(info_span!("authz")));
+            }
+            if let Some(task) = port_policy_task {
+                tokio::spawn(task.instrument(info_span!("port_policy")));
+            }
+            if let Some(task) = profile_overrides_task {
+                tokio::spawn(task.instrument(info_span!("profile_overrides")));
+            }
         });
 
         Ok(App {
@@ -211,7 +279,7 @@ impl App {
     pub fn identity_addr(&self) -> Option<&ControlAddr> {
         match self.identity {
             identity::Identity::Disabled => None,
-            identity::Identity::Enabled { ref addr, .. } => Some(addr),
+            identity::Identity::Enabled { ref addr, .. } => addr.as_ref(),
         }
     }
 
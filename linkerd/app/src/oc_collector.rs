@@ -1,10 +1,20 @@
 use crate::{dns, identity::LocalCrtKey};
-use linkerd_app_core::{control, metrics::ControlHttp as HttpMetrics, Error};
+use futures::StreamExt;
+use linkerd_app_core::{
+    control,
+    http_tracing::{attributes::CaptureHeaders, sample},
+    metrics::ControlHttp as HttpMetrics,
+    Error,
+};
 use linkerd_channel::into_stream::IntoStream;
 use linkerd_opencensus::{self as opencensus, metrics, proto};
+use linkerd_opentelemetry as opentelemetry;
 use std::future::Future;
 use std::pin::Pin;
-use std::{collections::HashMap, time::SystemTime};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use tokio::sync::mpsc;
 use tracing::Instrument;
 
@@ -19,6 +29,20 @@ pub struct EnabledConfig {
     pub control: control::Config,
     pub attributes: HashMap<String, String>,
     pub hostname: Option<String>,
+    pub protocol: Protocol,
+
+    /// Unset when the proxy doesn't start traces.
+    pub sampler: Option<sample::Config>,
+
+    /// Request and response headers that are recorded on spans.
+    pub headers: CaptureHeaders,
+}
+
+/// The protocol used to export spans to the collector.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    OpenCensus,
+    OpenTelemetry,
 }
 
 pub type Task = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
@@ -33,6 +57,8 @@ pub enum OcCollector {
 pub struct EnabledCollector {
     pub addr: control::ControlAddr,
     pub span_sink: SpanSink,
+    pub sampler: Option<sample::Sampler>,
+    pub headers: CaptureHeaders,
     pub task: Task,
 }
 
@@ -45,44 +71,92 @@ impl Config {
         identity: Option<LocalCrtKey>,
         dns: dns::Resolver,
         metrics: metrics::Registry,
+        otel_metrics: opentelemetry::metrics::Registry,
         client_metrics: HttpMetrics,
     ) -> Result<OcCollector, Error> {
         match self {
             Config::Disabled => Ok(OcCollector::Disabled),
             Config::Enabled(inner) => {
                 let addr = inner.control.addr.clone();
+                let sampler = inner.sampler.map(sample::Sampler::new);
+                let headers = inner.headers.clone();
                 let svc = inner.control.build(dns, client_metrics, identity);
 
                 let (span_sink, spans_rx) = mpsc::channel(Self::SPAN_BUFFER_CAPACITY);
                 let spans_rx = spans_rx.into_stream();
 
-                let task = {
-                    use self::proto::agent::common::v1 as oc;
-
-                    let node = oc::Node {
-                        identifier: Some(oc::ProcessIdentifier {
-                            host_name: inner.hostname.unwrap_or_default(),
-                            pid: std::process::id(),
-                            start_timestamp: Some(SystemTime::now().into()),
-                        }),
-                        service_info: Some(oc::ServiceInfo {
-                            name: Self::SERVICE_NAME.to_string(),
-                        }),
-                        attributes: inner.attributes,
-                        ..oc::Node::default()
-                    };
+                let task: Task = match inner.protocol {
+                    Protocol::OpenCensus => {
+                        use self::proto::agent::common::v1 as oc;
+
+                        let node = oc::Node {
+                            identifier: Some(oc::ProcessIdentifier {
+                                host_name: inner.hostname.unwrap_or_default(),
+                                pid: std::process::id(),
+                                start_timestamp: Some(SystemTime::now().into()),
+                            }),
+                            service_info: Some(oc::ServiceInfo {
+                                name: Self::SERVICE_NAME.to_string(),
+                            }),
+                            attributes: inner.attributes,
+                            ..oc::Node::default()
+                        };
+
+                        let addr = addr.clone();
+                        Box::pin(
+                            opencensus::export_spans(svc, node, spans_rx, metrics)
+                                .instrument(tracing::debug_span!("opencensus", peer.addr = %addr)),
+                        )
+                    }
+                    Protocol::OpenTelemetry => {
+                        use self::opentelemetry::proto::{
+                            common::v1 as otel, resource::v1 as resource,
+                        };
+
+                        let mut attributes = vec![
+                            string_attribute("service.name", Self::SERVICE_NAME),
+                            otel::KeyValue {
+                                key: "process.pid".to_string(),
+                                value: Some(otel::AnyValue {
+                                    value: Some(otel::any_value::Value::IntValue(
+                                        std::process::id().into(),
+                                    )),
+                                }),
+                            },
+                        ];
+                        if let Some(hostname) = inner.hostname {
+                            attributes.push(string_attribute("host.name", hostname));
+                        }
+                        attributes.extend(
+                            inner
+                                .attributes
+                                .into_iter()
+                                .map(|(k, v)| string_attribute(k, v)),
+                        );
+                        let resource = resource::Resource {
+                            attributes,
+                            dropped_attributes_count: 0,
+                        };
 
-                    let addr = addr.clone();
-                    Box::pin(
-                        opencensus::export_spans(svc, node, spans_rx, metrics)
-                            .instrument(tracing::debug_span!("opencensus", peer.addr = %addr)),
-                    )
+                        let addr = addr.clone();
+                        Box::pin(
+                            opentelemetry::export_spans(
+                                svc,
+                                resource,
+                                spans_rx.map(otel_span),
+                                otel_metrics,
+                            )
+                            .instrument(tracing::debug_span!("opentelemetry", peer.addr = %addr)),
+                        )
+                    }
                 };
 
                 Ok(OcCollector::Enabled(Box::new(EnabledCollector {
                     addr,
                     task,
                     span_sink,
+                    sampler,
+                    headers,
                 })))
             }
         }
@@ -96,4 +170,124 @@ impl OcCollector {
             OcCollector::Enabled(inner) => Some(inner.span_sink.clone()),
         }
     }
+
+    pub fn span_sampler(&self) -> Option<sample::Sampler> {
+        match self {
+            OcCollector::Disabled => None,
+            OcCollector::Enabled(inner) => inner.sampler.clone(),
+        }
+    }
+
+    pub fn span_headers(&self) -> CaptureHeaders {
+        match self {
+            OcCollector::Disabled => CaptureHeaders::default(),
+            OcCollector::Enabled(inner) => inner.headers.clone(),
+        }
+    }
+}
+
+/// Converts a span recorded by the proxy to its OTLP representation.
+fn otel_span(span: proto::trace::v1::Span) -> opentelemetry::proto::trace::v1::Span {
+    use self::opentelemetry::proto::{common::v1 as otel_common, trace::v1 as otel};
+    use self::proto::trace::v1 as oc;
+
+    let kind = match oc::span::SpanKind::from_i32(span.kind) {
+        Some(oc::span::SpanKind::Server) => otel::span::SpanKind::Server,
+        Some(oc::span::SpanKind::Client) => otel::span::SpanKind::Client,
+        _ => otel::span::SpanKind::Unspecified,
+    };
+
+    let attributes = span
+        .attributes
+        .map(|attrs| {
+            attrs
+                .attribute_map
+                .into_iter()
+                .filter_map(|(key, attr)| {
+                    let value = match attr.value? {
+                        oc::attribute_value::Value::StringValue(s) => {
+                            otel_common::any_value::Value::StringValue(s.value)
+                        }
+                        oc::attribute_value::Value::IntValue(i) => {
+                            otel_common::any_value::Value::IntValue(i)
+                        }
+                        oc::attribute_value::Value::BoolValue(b) => {
+                            otel_common::any_value::Value::BoolValue(b)
+                        }
+                        oc::attribute_value::Value::DoubleValue(d) => {
+                            otel_common::any_value::Value::DoubleValue(d)
+                        }
+                    };
+                    Some(otel_common::KeyValue {
+                        key,
+                        value: Some(otel_common::AnyValue { value: Some(value) }),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let events = span
+        .time_events
+        .map(|events| {
+            events
+                .time_event
+                .into_iter()
+                .filter_map(|event| {
+                    // Message events aren't recorded by the proxy.
+                    let annotation = match event.value? {
+                        oc::span::time_event::Value::Annotation(a) => a,
+                        oc::span::time_event::Value::MessageEvent(_) => return None,
+                    };
+                    Some(otel::span::Event {
+                        time_unix_nano: event
+                            .time
+                            .map(|t| unix_nanos(t.into()))
+                            .unwrap_or_default(),
+                        name: annotation.description.map(|d| d.value).unwrap_or_default(),
+                        ..otel::span::Event::default()
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    otel::Span {
+        trace_id: span.trace_id,
+        span_id: span.span_id,
+        parent_span_id: span.parent_span_id,
+        name: span.name.map(|n| n.value).unwrap_or_default(),
+        kind: kind as i32,
+        start_time_unix_nano: span
+            .start_time
+            .map(|t| unix_nanos(t.into()))
+            .unwrap_or_default(),
+        end_time_unix_nano: span
+            .end_time
+            .map(|t| unix_nanos(t.into()))
+            .unwrap_or_default(),
+        attributes,
+        events,
+        ..otel::Span::default()
+    }
+}
+
+fn unix_nanos(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH)
+        .unwrap_or_else(|_| Duration::from_secs(0))
+        .as_nanos() as u64
+}
+
+fn string_attribute(
+    key: impl Into<String>,
+    value: impl Into<String>,
+) -> opentelemetry::proto::common::v1::KeyValue {
+    use self::opentelemetry::proto::common::v1 as otel;
+
+    otel::KeyValue {
+        key: key.into(),
+        value: Some(otel::AnyValue {
+            value: Some(otel::any_value::Value::StringValue(value.into())),
+        }),
+    }
 }
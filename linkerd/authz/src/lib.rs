@@ -0,0 +1,311 @@
+#![deny(warnings, rust_2018_idioms)]
+
+//! Authorizes inbound connections and requests by the client's identity and
+//! address.
+//!
+//! The policy is read from a file that is reloaded as it changes. See
+//! [`Policy::from_json`] for its format. HTTP requests are checked
+//! individually, so that rules may be scoped to request paths and so that
+//! policy changes apply to existing connections; other connections are checked
+//! once, when they are accepted.
+
+mod metrics;
+mod policy;
+
+use self::policy::Decision;
+pub use self::{
+    metrics::Registry,
+    policy::{Client, InvalidPolicy, Policy},
+};
+use futures::{future, prelude::*};
+use linkerd_error::Error;
+pub use linkerd_file_watch::Task;
+use linkerd_stack::{layer, NewService};
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::sync::watch;
+use tracing::debug;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub path: PathBuf,
+}
+
+/// Checks connections and requests against the current policy.
+#[derive(Clone, Debug)]
+pub struct Authorize {
+    policy: watch::Receiver<Arc<Policy>>,
+    metrics: Registry,
+}
+
+/// Describes the local port and the client of a connection.
+pub trait HasClient {
+    fn authz_port(&self) -> u16;
+
+    fn authz_client(&self) -> Client<'_>;
+}
+
+#[derive(Clone, Debug)]
+pub struct NewAuthorizeRequests<N> {
+    authorize: Option<Authorize>,
+    inner: N,
+}
+
+#[derive(Clone, Debug)]
+pub struct AuthorizeRequests<T, S> {
+    authorize: Option<Authorize>,
+    target: T,
+    inner: S,
+}
+
+/// Indicates that a connection or request was denied.
+#[derive(Clone, Debug)]
+pub struct Unauthorized {
+    rule: Option<Arc<str>>,
+}
+
+// === impl Config ===
+
+impl Config {
+    const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Loads the policy, failing if it is invalid, and returns a task that
+    /// reloads it as the file changes.
+    pub fn build(self) -> Result<(Authorize, Task), Error> {
+        let (policy, task) = linkerd_file_watch::watch(self.path, Self::POLL_INTERVAL, |bytes| {
+            Ok(Arc::new(Policy::from_json(bytes)?))
+        })?;
+        Ok((Authorize::new(policy), task))
+    }
+}
+
+// === impl Authorize ===
+
+impl Authorize {
+    pub fn new(policy: watch::Receiver<Arc<Policy>>) -> Self {
+        Self {
+            policy,
+            metrics: Registry::default(),
+        }
+    }
+
+    pub fn metrics(&self) -> Registry {
+        self.metrics.clone()
+    }
+
+    /// Checks a connection that is not decoded as HTTP.
+    pub fn check_connection(&self, port: u16, client: Client<'_>) -> Result<(), Unauthorized> {
+        let policy = self.policy.borrow().clone();
+        let decision = policy.check_connection(port, client);
+        self.record(decision, port, client)
+    }
+
+    /// Checks an HTTP request with the given path.
+    pub fn check_request(
+        &self,
+        port: u16,
+        client: Client<'_>,
+        path: &str,
+    ) -> Result<(), Unauthorized> {
+        let policy = self.policy.borrow().clone();
+        let decision = policy.check_request(port, client, path);
+        self.record(decision, port, client)
+    }
+
+    fn record(
+        &self,
+        decision: Decision,
+        port: u16,
+        client: Client<'_>,
+    ) -> Result<(), Unauthorized> {
+        match decision {
+            Decision::Unrestricted => Ok(()),
+            Decision::Allow(rule) => {
+                self.metrics.allow(rule);
+                Ok(())
+            }
+            Decision::Deny(rule) => {
+                debug!(
+                    port,
+                    client.addr = %client.addr,
+                    client.id = ?client.identity,
+                    ?rule,
+                    "Unauthorized"
+                );
+                self.metrics.deny(rule.clone());
+                Err(Unauthorized { rule })
+            }
+        }
+    }
+}
+
+// === impl NewAuthorizeRequests ===
+
+impl<N> NewAuthorizeRequests<N> {
+    pub fn layer(authorize: Option<Authorize>) -> impl layer::Layer<N, Service = Self> + Clone {
+        layer::mk(move |inner| Self {
+            authorize: authorize.clone(),
+            inner,
+        })
+    }
+}
+
+impl<T, N> NewService<T> for NewAuthorizeRequests<N>
+where
+    T: HasClient + Clone,
+    N: NewService<T>,
+{
+    type Service = AuthorizeRequests<T, N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        AuthorizeRequests {
+            authorize: self.authorize.clone(),
+            inner: self.inner.new_service(target.clone()),
+            target,
+        }
+    }
+}
+
+// === impl AuthorizeRequests ===
+
+impl<B, T, S> tower::Service<http::Request<B>> for AuthorizeRequests<T, S>
+where
+    T: HasClient,
+    S: tower::Service<http::Request<B>>,
+    S::Error: Into<Error>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = future::Either<
+        future::ErrInto<S::Future, Error>,
+        future::Ready<Result<S::Response, Error>>,
+    >;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        if let Some(authorize) = self.authorize.as_ref() {
+            let port = self.target.authz_port();
+            let client = self.target.authz_client();
+            if let Err(e) = authorize.check_request(port, client, req.uri().path()) {
+                return future::Either::Right(future::err(e.into()));
+            }
+        }
+        future::Either::Left(self.inner.call(req).err_into::<Error>())
+    }
+}
+
+// === impl Unauthorized ===
+
+impl Unauthorized {
+    /// The name of the rule that denied the client, if a rule did so
+    /// explicitly.
+    pub fn rule(&self) -> Option<&str> {
+        self.rule.as_deref()
+    }
+}
+
+impl fmt::Display for Unauthorized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.rule.as_ref() {
+            Some(rule) => write!(f, "unauthorized by rule '{}'", rule),
+            None => write!(f, "unauthorized: no rule allows the client"),
+        }
+    }
+}
+
+impl std::error::Error for Unauthorized {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_metrics::FmtMetrics;
+    use linkerd_stack::layer::Layer;
+    use tower::ServiceExt;
+
+    #[derive(Clone)]
+    struct Target(Option<linkerd_identity::Id>);
+
+    impl HasClient for Target {
+        fn authz_port(&self) -> u16 {
+            8080
+        }
+
+        fn authz_client(&self) -> Client<'_> {
+            Client {
+                identity: self.0.as_ref(),
+                addr: [10, 0, 0, 1].into(),
+            }
+        }
+    }
+
+    fn authorize(policy: &str) -> Authorize {
+        let policy = Policy::from_json(policy.as_bytes()).unwrap();
+        let (_tx, rx) = watch::channel(Arc::new(policy));
+        Authorize::new(rx)
+    }
+
+    #[test]
+    fn counts_decisions() {
+        let authz = authorize(
+            r#"{"rules": [
+                {"name": "local", "ports": [8080], "networks": ["127.0.0.1/32"]},
+                {"name": "blocked", "action": "deny", "ports": [8080], "networks": ["10.0.0.1/32"]}
+            ]}"#,
+        );
+        let client = |addr: &str| Client {
+            identity: None,
+            addr: addr.parse().unwrap(),
+        };
+
+        assert!(authz.check_connection(8080, client("127.0.0.1")).is_ok());
+        assert!(authz.check_connection(80, client("10.0.0.1")).is_ok());
+        let err = authz
+            .check_connection(8080, client("10.0.0.1"))
+            .unwrap_err();
+        assert_eq!(err.rule(), Some("blocked"));
+        let err = authz
+            .check_request(8080, client("10.0.0.2"), "/")
+            .unwrap_err();
+        assert_eq!(err.rule(), None);
+
+        let metrics = authz.metrics().as_display().to_string();
+        assert!(metrics.contains("inbound_authz_allowed_total{rule=\"local\"} 1\n"));
+        assert!(metrics.contains("inbound_authz_denied_total{rule=\"blocked\"} 1\n"));
+        assert!(metrics.contains("inbound_authz_denied_total{rule=\"default\"} 1\n"));
+    }
+
+    #[tokio::test]
+    async fn authorizes_requests() {
+        let authz = authorize(
+            r#"{"rules": [
+                {"name": "web", "ports": [8080], "identities": ["*.web.example.com"]}
+            ]}"#,
+        );
+        let inner =
+            |_: Target| tower::service_fn(|_: http::Request<()>| async { Ok::<_, Error>(()) });
+        let mut new_svc = NewAuthorizeRequests::layer(Some(authz)).layer(inner);
+
+        let req = || http::Request::get("/").body(()).unwrap();
+        let id = "default.web.example.com".parse().unwrap();
+        let svc = new_svc.new_service(Target(Some(id)));
+        assert!(svc.oneshot(req()).await.is_ok());
+
+        let svc = new_svc.new_service(Target(None));
+        let err = svc.oneshot(req()).await.unwrap_err();
+        assert!(err.is::<Unauthorized>());
+
+        // Requests are not checked without a policy.
+        let mut new_svc = NewAuthorizeRequests::layer(None).layer(inner);
+        let svc = new_svc.new_service(Target(None));
+        assert!(svc.oneshot(req()).await.is_ok());
+    }
+}
@@ -0,0 +1,82 @@
+use indexmap::IndexMap;
+use linkerd_metrics::{metrics, Counter, FmtLabels, FmtMetrics};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+metrics! {
+    inbound_authz_allowed_total: Counter {
+        "Total number of inbound connections and requests allowed by each authorization rule"
+    },
+    inbound_authz_denied_total: Counter {
+        "Total number of inbound connections and requests denied by each authorization rule"
+    }
+}
+
+/// Counts the connections and requests allowed and denied by each rule.
+///
+/// Counters are retained across policy reloads so that they are not reset when
+/// a rule is unchanged.
+#[derive(Clone, Debug, Default)]
+pub struct Registry(Arc<Mutex<IndexMap<RuleLabel, Arc<Counters>>>>);
+
+/// Labels counters by rule name. Denials that are not caused by a rule (i.e.
+/// because no rule allows a client) are labeled `rule="default"`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct RuleLabel(Option<Arc<str>>);
+
+#[derive(Debug, Default)]
+struct Counters {
+    allowed: Counter,
+    denied: Counter,
+}
+
+// === impl Registry ===
+
+impl Registry {
+    pub(crate) fn allow(&self, rule: Arc<str>) {
+        self.counters(Some(rule)).allowed.incr();
+    }
+
+    pub(crate) fn deny(&self, rule: Option<Arc<str>>) {
+        self.counters(rule).denied.incr();
+    }
+
+    fn counters(&self, rule: Option<Arc<str>>) -> Arc<Counters> {
+        self.0
+            .lock()
+            .expect("authz metrics lock poisoned")
+            .entry(RuleLabel(rule))
+            .or_default()
+            .clone()
+    }
+}
+
+impl FmtMetrics for Registry {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counters = self.0.lock().expect("authz metrics lock poisoned");
+        if counters.is_empty() {
+            return Ok(());
+        }
+
+        inbound_authz_allowed_total.fmt_help(f)?;
+        inbound_authz_allowed_total.fmt_scopes(f, counters.iter(), |c| &c.allowed)?;
+
+        inbound_authz_denied_total.fmt_help(f)?;
+        inbound_authz_denied_total.fmt_scopes(f, counters.iter(), |c| &c.denied)?;
+
+        Ok(())
+    }
+}
+
+// === impl RuleLabel ===
+
+impl FmtLabels for RuleLabel {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.as_ref() {
+            Some(rule) => write!(f, "rule=\"{}\"", rule),
+            None => write!(f, "rule=\"default\""),
+        }
+    }
+}
@@ -0,0 +1,505 @@
+use ipnet::IpNet;
+use linkerd_file_watch::json::{self, Value};
+use linkerd_identity as id;
+use std::{fmt, net::IpAddr, ops::RangeInclusive, str::FromStr, sync::Arc};
+
+/// An ordered list of authorization rules.
+///
+/// The first rule that applies to a connection or request decides whether it's
+/// allowed. Connections and requests to ports that no rule names are not
+/// restricted; those to ports that are named by a rule are denied unless a
+/// rule allows them.
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    name: Arc<str>,
+    action: Action,
+    ports: Vec<RangeInclusive<u16>>,
+
+    /// When empty, the rule applies to all clients, including those without
+    /// an identity.
+    identities: Vec<IdentityMatch>,
+
+    /// When empty, the rule applies to clients from any address.
+    networks: Vec<IpNet>,
+
+    /// Path prefixes. When set, the rule only applies to HTTP requests with a
+    /// matching path.
+    paths: Vec<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Action {
+    Allow,
+    Deny,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum IdentityMatch {
+    /// Matches any authenticated client.
+    Any,
+    Exact(String),
+
+    /// Matches identities that end with the suffix, which includes its
+    /// leading `.`.
+    Suffix(String),
+
+    /// Matches a SPIFFE ID. Unlike names, SPIFFE IDs are case-sensitive.
+    Spiffe(String),
+
+    /// Matches SPIFFE IDs that start with the prefix, which includes its
+    /// trailing `/`.
+    SpiffePrefix(String),
+}
+
+/// Describes the client of a connection or request.
+#[derive(Copy, Clone, Debug)]
+pub struct Client<'c> {
+    pub identity: Option<&'c id::Id>,
+    pub addr: IpAddr,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidPolicy(String);
+
+/// The result of checking a connection or request against a policy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Decision {
+    /// No rule applies to the port.
+    Unrestricted,
+
+    Allow(Arc<str>),
+
+    /// Denied by the named rule or, when unnamed, because no rule allows it.
+    Deny(Option<Arc<str>>),
+}
+
+// === impl Policy ===
+
+impl Policy {
+    /// Parses a JSON policy, e.g.:
+    ///
+    /// ```json
+    /// {
+    ///   "rules": [
+    ///     {
+    ///       "name": "metrics",
+    ///       "ports": [9090],
+    ///       "networks": ["10.0.0.0/8"]
+    ///     },
+    ///     {
+    ///       "name": "admin",
+    ///       "action": "deny",
+    ///       "ports": ["8080-8081"],
+    ///       "paths": ["/admin/"]
+    ///     },
+    ///     {
+    ///       "name": "web",
+    ///       "ports": ["8080-8081"],
+    ///       "identities": ["*.web.serviceaccount.identity.linkerd.cluster.local"]
+    ///     }
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// Each rule must have a name and at least one port. Its action is
+    /// `allow` unless it's `deny`. Identities may be exact names, suffixes
+    /// preceded by `*.`, SPIFFE IDs (e.g. `spiffe://cluster.local/ns/web/sa/web`),
+    /// SPIFFE ID prefixes followed by `/*`, or `*` to match any authenticated
+    /// client. Names and suffixes only match clients with DNS-like identities,
+    /// and SPIFFE IDs and prefixes only match clients with SPIFFE IDs.
+    pub fn from_json(bytes: &[u8]) -> Result<Self, InvalidPolicy> {
+        let value = serde_json::from_slice::<Value>(bytes)
+            .map_err(|e| InvalidPolicy(format!("invalid JSON: {}", e)))?;
+        let mut obj = json::into_object(value)
+            .ok_or_else(|| InvalidPolicy("policy must be an object".into()))?;
+        let rules = match obj.remove("rules") {
+            Some(Value::Array(rules)) => rules
+                .into_iter()
+                .map(Rule::from_json)
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(_) => return Err(InvalidPolicy("rules must be an array".into())),
+            None => Vec::new(),
+        };
+        if let Some(field) = obj.keys().next() {
+            return Err(InvalidPolicy(format!("unknown field '{}'", field)));
+        }
+        Ok(Self { rules })
+    }
+
+    /// Checks a connection that is not decoded as HTTP. Rules that are scoped
+    /// to request paths don't apply to it.
+    pub(crate) fn check_connection(&self, port: u16, client: Client<'_>) -> Decision {
+        let mut restricted = false;
+        for rule in self.rules.iter().filter(|r| r.applies_to_port(port)) {
+            restricted = true;
+            if rule.paths.is_empty() && rule.applies_to_client(client) {
+                return rule.decide();
+            }
+        }
+
+        if restricted {
+            Decision::Deny(None)
+        } else {
+            Decision::Unrestricted
+        }
+    }
+
+    pub(crate) fn check_request(&self, port: u16, client: Client<'_>, path: &str) -> Decision {
+        let mut restricted = false;
+        for rule in self.rules.iter().filter(|r| r.applies_to_port(port)) {
+            restricted = true;
+            if rule.applies_to_client(client) && rule.applies_to_path(path) {
+                return rule.decide();
+            }
+        }
+
+        if restricted {
+            Decision::Deny(None)
+        } else {
+            Decision::Unrestricted
+        }
+    }
+}
+
+// === impl Rule ===
+
+impl Rule {
+    fn from_json(value: Value) -> Result<Self, InvalidPolicy> {
+        let mut obj = json::into_object(value)
+            .ok_or_else(|| InvalidPolicy("rule must be an object".into()))?;
+
+        let name: Arc<str> = match obj.remove("name") {
+            Some(Value::String(name)) if !name.is_empty() => name.into(),
+            _ => return Err(InvalidPolicy("each rule must have a name".into())),
+        };
+        let invalid = |msg: &str| InvalidPolicy(format!("rule '{}': {}", name, msg));
+
+        let action = match obj.remove("action") {
+            None => Action::Allow,
+            Some(Value::String(a)) if a == "allow" => Action::Allow,
+            Some(Value::String(a)) if a == "deny" => Action::Deny,
+            Some(_) => return Err(invalid("action must be 'allow' or 'deny'")),
+        };
+
+        let ports =
+            json::port_ranges(obj.remove("ports")).ok_or_else(|| invalid("invalid ports"))?;
+        if ports.is_empty() {
+            return Err(invalid("at least one port must be specified"));
+        }
+
+        let identities = json::strings_or_numbers(obj.remove("identities"))
+            .and_then(|ids| ids.iter().map(|id| IdentityMatch::parse(id)).collect())
+            .ok_or_else(|| invalid("invalid identities"))?;
+
+        let networks = json::strings_or_numbers(obj.remove("networks"))
+            .and_then(|nets| nets.iter().map(|n| parse_network(n)).collect())
+            .ok_or_else(|| invalid("invalid networks"))?;
+
+        let paths = json::strings_or_numbers(obj.remove("paths"))
+            .filter(|paths| paths.iter().all(|p| p.starts_with('/')))
+            .ok_or_else(|| invalid("paths must start with '/'"))?;
+
+        if let Some(field) = obj.keys().next() {
+            return Err(invalid(&format!("unknown field '{}'", field)));
+        }
+
+        Ok(Self {
+            name,
+            action,
+            ports,
+            identities,
+            networks,
+            paths,
+        })
+    }
+
+    fn applies_to_port(&self, port: u16) -> bool {
+        self.ports.iter().any(|r| r.contains(&port))
+    }
+
+    fn applies_to_client(&self, client: Client<'_>) -> bool {
+        let identity = self.identities.is_empty()
+            || client
+                .identity
+                .map(|id| self.identities.iter().any(|m| m.matches(id)))
+                .unwrap_or(false);
+        let network =
+            self.networks.is_empty() || self.networks.iter().any(|n| n.contains(&client.addr));
+        identity && network
+    }
+
+    fn applies_to_path(&self, path: &str) -> bool {
+        self.paths.is_empty() || self.paths.iter().any(|p| path.starts_with(p.as_str()))
+    }
+
+    fn decide(&self) -> Decision {
+        match self.action {
+            Action::Allow => Decision::Allow(self.name.clone()),
+            Action::Deny => Decision::Deny(Some(self.name.clone())),
+        }
+    }
+}
+
+// === impl IdentityMatch ===
+
+impl IdentityMatch {
+    fn parse(s: &str) -> Option<Self> {
+        if s == "*" {
+            return Some(Self::Any);
+        }
+        if s.is_empty() || s.contains(char::is_whitespace) {
+            return None;
+        }
+        if s.starts_with("spiffe://") {
+            if s.ends_with("/*") {
+                return Some(Self::SpiffePrefix(s[..s.len() - 1].to_string()));
+            }
+            return Some(Self::Spiffe(s.to_string()));
+        }
+        let s = s.to_ascii_lowercase();
+        if s.starts_with("*.") {
+            return Some(Self::Suffix(s[1..].to_string()));
+        }
+        Some(Self::Exact(s))
+    }
+
+    fn matches(&self, id: &id::Id) -> bool {
+        match (self, id) {
+            (Self::Any, _) => true,
+            (Self::Exact(name), id::Id::Dns(id)) => id.as_ref().eq_ignore_ascii_case(name),
+            (Self::Suffix(suffix), id::Id::Dns(id)) => {
+                let id = id.as_ref();
+                id.len() > suffix.len()
+                    && id
+                        .get(id.len() - suffix.len()..)
+                        .map(|s| s.eq_ignore_ascii_case(suffix))
+                        .unwrap_or(false)
+            }
+            (Self::Spiffe(spiffe_id), id::Id::Spiffe(id)) => id.as_ref() == spiffe_id,
+            (Self::SpiffePrefix(prefix), id::Id::Spiffe(id)) => {
+                let id = id.as_ref();
+                id.len() > prefix.len() && id.starts_with(prefix.as_str())
+            }
+            _ => false,
+        }
+    }
+}
+
+// === impl InvalidPolicy ===
+
+impl fmt::Display for InvalidPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid authorization policy: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidPolicy {}
+
+fn parse_network(s: &str) -> Option<IpNet> {
+    IpNet::from_str(s)
+        .or_else(|_| IpAddr::from_str(s).map(IpNet::from))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(s: &str) -> id::Id {
+        s.parse().unwrap()
+    }
+
+    fn client<'c>(identity: Option<&'c id::Id>, addr: &str) -> Client<'c> {
+        Client {
+            identity,
+            addr: addr.parse().unwrap(),
+        }
+    }
+
+    fn allow(name: &str) -> Decision {
+        Decision::Allow(name.into())
+    }
+
+    fn deny(name: &str) -> Decision {
+        Decision::Deny(Some(name.into()))
+    }
+
+    const POLICY: &str = r#"{
+        "rules": [
+            {
+                "name": "metrics",
+                "ports": [9090],
+                "networks": ["10.0.0.0/8", "192.168.1.1"]
+            },
+            {
+                "name": "admin",
+                "action": "deny",
+                "ports": ["8080-8081"],
+                "paths": ["/admin/"]
+            },
+            {
+                "name": "web",
+                "ports": ["8080-8081"],
+                "identities": ["*.web.serviceaccount.identity.linkerd.cluster.local"]
+            },
+            {
+                "name": "ops",
+                "ports": [8080],
+                "identities": ["ops.ops.serviceaccount.identity.linkerd.cluster.local"],
+                "paths": ["/debug"]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn checks_connections() {
+        let policy = Policy::from_json(POLICY.as_bytes()).unwrap();
+        let web = id("default.web.serviceaccount.identity.linkerd.cluster.local");
+        let ops = id("ops.ops.serviceaccount.identity.linkerd.cluster.local");
+
+        assert_eq!(
+            policy.check_connection(9090, client(None, "10.1.2.3")),
+            allow("metrics")
+        );
+        assert_eq!(
+            policy.check_connection(9090, client(None, "192.168.1.1")),
+            allow("metrics")
+        );
+        assert_eq!(
+            policy.check_connection(9090, client(Some(&web), "172.16.0.1")),
+            Decision::Deny(None)
+        );
+        assert_eq!(
+            policy.check_connection(80, client(None, "172.16.0.1")),
+            Decision::Unrestricted
+        );
+
+        // Rules that are scoped to paths don't apply to connections.
+        assert_eq!(
+            policy.check_connection(8080, client(Some(&web), "10.1.2.3")),
+            allow("web")
+        );
+        assert_eq!(
+            policy.check_connection(8080, client(Some(&ops), "10.1.2.3")),
+            Decision::Deny(None)
+        );
+    }
+
+    #[test]
+    fn checks_requests() {
+        let policy = Policy::from_json(POLICY.as_bytes()).unwrap();
+        let web = id("Default.Web.serviceaccount.identity.linkerd.cluster.local");
+        let ops = id("ops.ops.serviceaccount.identity.linkerd.cluster.local");
+        let addr = "10.1.2.3";
+
+        assert_eq!(
+            policy.check_request(8080, client(Some(&web), addr), "/"),
+            allow("web")
+        );
+        assert_eq!(
+            policy.check_request(8080, client(Some(&web), addr), "/admin/users"),
+            deny("admin")
+        );
+        assert_eq!(
+            policy.check_request(8080, client(Some(&ops), addr), "/debug/pprof"),
+            allow("ops")
+        );
+        assert_eq!(
+            policy.check_request(8081, client(Some(&ops), addr), "/debug/pprof"),
+            Decision::Deny(None)
+        );
+        assert_eq!(
+            policy.check_request(8080, client(None, addr), "/"),
+            Decision::Deny(None)
+        );
+        assert_eq!(
+            policy.check_request(80, client(None, addr), "/admin/"),
+            Decision::Unrestricted
+        );
+    }
+
+    #[test]
+    fn matches_spiffe_ids() {
+        let policy = Policy::from_json(
+            br#"{"rules": [
+                {"name": "web", "ports": [8080], "identities": ["spiffe://cluster.local/ns/web/*"]},
+                {"name": "ops", "ports": [8080], "identities": ["spiffe://cluster.local/ns/ops/sa/Ops"]}
+            ]}"#,
+        )
+        .unwrap();
+        let check = |s| policy.check_connection(8080, client(Some(&id(s)), "10.1.2.3"));
+
+        assert_eq!(
+            check("spiffe://cluster.local/ns/web/sa/default"),
+            allow("web")
+        );
+        assert_eq!(check("spiffe://cluster.local/ns/ops/sa/Ops"), allow("ops"));
+        assert_eq!(
+            check("spiffe://cluster.local/ns/ops/sa/ops"),
+            Decision::Deny(None)
+        );
+        assert_eq!(check("spiffe://cluster.local/ns/web"), Decision::Deny(None));
+        assert_eq!(
+            check("web.ns.serviceaccount.identity.linkerd.cluster.local"),
+            Decision::Deny(None)
+        );
+    }
+
+    #[test]
+    fn matches_names_only_to_dns_identities() {
+        let policy = Policy::from_json(
+            br#"{"rules": [{
+                "name": "web",
+                "ports": [8080],
+                "identities": ["*.web.serviceaccount.identity.linkerd.cluster.local"]
+            }]}"#,
+        )
+        .unwrap();
+        let check = |s| policy.check_connection(8080, client(Some(&id(s)), "10.1.2.3"));
+
+        assert_eq!(
+            check("default.web.serviceaccount.identity.linkerd.cluster.local"),
+            allow("web")
+        );
+        assert_eq!(
+            check("spiffe://other.org/x.web.serviceaccount.identity.linkerd.cluster.local"),
+            Decision::Deny(None)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_policies() {
+        for invalid in &[
+            "",
+            "[]",
+            r#"{"rules": {}}"#,
+            r#"{"rules": [], "default": "deny"}"#,
+            r#"{"rules": [{"ports": [80]}]}"#,
+            r#"{"rules": [{"name": "a"}]}"#,
+            r#"{"rules": [{"name": "a", "ports": [0]}]}"#,
+            r#"{"rules": [{"name": "a", "ports": ["90-80"]}]}"#,
+            r#"{"rules": [{"name": "a", "ports": [80], "action": "reject"}]}"#,
+            r#"{"rules": [{"name": "a", "ports": [80], "networks": ["10.0.0.0/33"]}]}"#,
+            r#"{"rules": [{"name": "a", "ports": [80], "identities": [""]}]}"#,
+            r#"{"rules": [{"name": "a", "ports": [80], "paths": ["admin"]}]}"#,
+            r#"{"rules": [{"name": "a", "ports": [80], "identity": ["*"]}]}"#,
+        ] {
+            assert!(
+                Policy::from_json(invalid.as_bytes()).is_err(),
+                "{} must be invalid",
+                invalid
+            );
+        }
+
+        let empty = Policy::from_json(b"{}").unwrap();
+        assert_eq!(
+            empty.check_connection(80, client(None, "10.1.2.3")),
+            Decision::Unrestricted
+        );
+    }
+}
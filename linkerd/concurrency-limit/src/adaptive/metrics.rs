@@ -0,0 +1,67 @@
+use super::Limiter;
+use indexmap::IndexMap;
+use linkerd_metrics::{metrics, Counter, FmtLabels, FmtMetrics, Gauge};
+use std::{
+    fmt,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+metrics! {
+    concurrency_limit: Gauge {
+        "The current limit on in-flight requests, as adapted to response latency"
+    },
+    concurrency_limit_rejected_total: Counter {
+        "Total number of requests rejected because the concurrency limit was reached"
+    }
+}
+
+/// Reports the state of each `L`-labeled adaptive limit.
+#[derive(Debug)]
+pub struct Registry<L: Hash + Eq>(Arc<Mutex<IndexMap<L, Arc<Limiter>>>>);
+
+// === impl Registry ===
+
+impl<L: Hash + Eq> Registry<L> {
+    pub(super) fn register(&self, labels: L, limiter: Arc<Limiter>) {
+        self.0
+            .lock()
+            .expect("concurrency limit metrics lock poisoned")
+            .insert(labels, limiter);
+    }
+}
+
+impl<L: Hash + Eq> Default for Registry<L> {
+    fn default() -> Self {
+        Registry(Default::default())
+    }
+}
+
+impl<L: Hash + Eq> Clone for Registry<L> {
+    fn clone(&self) -> Self {
+        Registry(self.0.clone())
+    }
+}
+
+impl<L: FmtLabels + Hash + Eq> FmtMetrics for Registry<L> {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limiters = self
+            .0
+            .lock()
+            .expect("concurrency limit metrics lock poisoned");
+        if limiters.is_empty() {
+            return Ok(());
+        }
+
+        concurrency_limit.fmt_help(f)?;
+        for (labels, limiter) in limiters.iter() {
+            let limit = Gauge::from(limiter.limit() as u64);
+            concurrency_limit.fmt_metric_labeled(f, &limit, labels)?;
+        }
+
+        concurrency_limit_rejected_total.fmt_help(f)?;
+        concurrency_limit_rejected_total.fmt_scopes(f, limiters.iter(), |l| &l.rejected)?;
+
+        Ok(())
+    }
+}
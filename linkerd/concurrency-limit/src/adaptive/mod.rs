@@ -0,0 +1,396 @@
+//! A concurrency limit that adapts to the inner service's response latency.
+//!
+//! The limit is adjusted with a gradient: each response's latency is compared
+//! with a long-term average. While latency stays near the average, the limit
+//! grows by a small allowance for queueing; as latency rises above the
+//! average, the limit shrinks in proportion, down to a minimum. Requests in
+//! excess of the limit are not queued--they fail immediately with
+//! `LimitExceeded` so that the proxy sheds load before the application is
+//! overwhelmed.
+
+mod metrics;
+
+pub use self::metrics::Registry;
+use futures::{future, ready, TryFuture};
+use linkerd_error::Error;
+use linkerd_metrics::Counter;
+use linkerd_stack::layer;
+use pin_project::pin_project;
+use std::{
+    fmt,
+    future::Future,
+    hash::Hash,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+use tokio::time::Instant;
+use tower::Service;
+use tracing::{debug, trace};
+
+/// The number of responses over which the long-term latency is averaged.
+const LONG_WINDOW: f64 = 600.0;
+
+/// How much latency may increase over its long-term average before the limit
+/// shrinks.
+const TOLERANCE: f64 = 1.5;
+
+/// The proportion of each new limit that is applied, so that a single
+/// response cannot move the limit too far.
+const SMOOTHING: f64 = 0.2;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Config {
+    /// The limit never shrinks below this value.
+    pub min_limit: usize,
+
+    /// The limit never grows beyond this value.
+    pub max_limit: usize,
+
+    /// The limit before any responses have been observed.
+    pub initial_limit: usize,
+}
+
+/// Enforces an adaptive limit on the number of concurrent requests to the
+/// inner service.
+///
+/// The limit is shared by all services built by the same layer.
+#[derive(Clone, Debug)]
+pub struct AdaptiveConcurrencyLimit<S> {
+    inner: S,
+
+    /// Unset when the limit is disabled.
+    limiter: Option<Arc<Limiter>>,
+}
+
+/// Indicates that a request was rejected because the concurrency limit was
+/// reached.
+#[derive(Debug)]
+pub struct LimitExceeded {
+    limit: usize,
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct ResponseFuture<F> {
+    #[pin]
+    inner: F,
+    permit: Option<Permit>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Limiter {
+    config: Config,
+    state: Mutex<State>,
+    rejected: Counter,
+}
+
+#[derive(Debug)]
+struct State {
+    limit: f64,
+    in_flight: usize,
+
+    /// The long-term average latency, in seconds.
+    long_rtt: Option<f64>,
+}
+
+/// Counts a request as in-flight until it is dropped.
+#[derive(Debug)]
+struct Permit {
+    limiter: Arc<Limiter>,
+    start: Instant,
+}
+
+// === impl Registry ===
+
+impl<L: Hash + Eq> Registry<L> {
+    /// Limits requests to the services built by the returned layer, reporting
+    /// the limit under `labels`. When `config` is `None`, requests are not
+    /// limited.
+    pub fn layer<S>(
+        &self,
+        labels: L,
+        config: Option<Config>,
+    ) -> impl layer::Layer<S, Service = AdaptiveConcurrencyLimit<S>> + Clone {
+        let limiter = config.map(|config| {
+            let limiter = Arc::new(Limiter::new(config));
+            self.register(labels, limiter.clone());
+            limiter
+        });
+        layer::mk(move |inner| AdaptiveConcurrencyLimit {
+            inner,
+            limiter: limiter.clone(),
+        })
+    }
+}
+
+// === impl AdaptiveConcurrencyLimit ===
+
+impl<S, Req> Service<Req> for AdaptiveConcurrencyLimit<S>
+where
+    S: Service<Req>,
+    S::Error: Into<Error>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future =
+        future::Either<ResponseFuture<S::Future>, future::Ready<Result<S::Response, Error>>>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let permit = match self.limiter.as_ref() {
+            None => None,
+            Some(limiter) => match Limiter::acquire(limiter) {
+                Ok(permit) => Some(permit),
+                Err(e) => return future::Either::Right(future::err(e.into())),
+            },
+        };
+
+        future::Either::Left(ResponseFuture {
+            inner: self.inner.call(req),
+            permit,
+        })
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<F> Future for ResponseFuture<F>
+where
+    F: TryFuture,
+    F::Error: Into<Error>,
+{
+    type Output = Result<F::Ok, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let res = ready!(this.inner.try_poll(cx));
+        if let Some(permit) = this.permit.take() {
+            // Only successful responses indicate the service's latency;
+            // failures may be fast or slow for reasons unrelated to load.
+            if res.is_ok() {
+                permit.complete();
+            }
+        }
+        Poll::Ready(res.map_err(Into::into))
+    }
+}
+
+// === impl Limiter ===
+
+impl Limiter {
+    fn new(config: Config) -> Self {
+        let initial = config
+            .initial_limit
+            .max(config.min_limit)
+            .min(config.max_limit);
+        Self {
+            config,
+            state: Mutex::new(State {
+                limit: initial as f64,
+                in_flight: 0,
+                long_rtt: None,
+            }),
+            rejected: Counter::default(),
+        }
+    }
+
+    pub(crate) fn limit(&self) -> usize {
+        self.state.lock().expect("limiter lock poisoned").limit as usize
+    }
+
+    fn acquire(this: &Arc<Self>) -> Result<Permit, LimitExceeded> {
+        let mut state = this.state.lock().expect("limiter lock poisoned");
+        let limit = state.limit as usize;
+        if state.in_flight >= limit {
+            this.rejected.incr();
+            debug!(%limit, "Concurrency limit reached");
+            return Err(LimitExceeded { limit });
+        }
+
+        state.in_flight += 1;
+        Ok(Permit {
+            limiter: this.clone(),
+            start: Instant::now(),
+        })
+    }
+}
+
+// === impl State ===
+
+impl State {
+    /// Adjusts the limit from the latency of a response that completed while
+    /// `in_flight` requests (including itself) were in flight.
+    fn update(&mut self, config: &Config, rtt: f64, in_flight: usize) {
+        // Guard against division by zero for exceptionally fast responses.
+        let rtt = rtt.max(f64::EPSILON);
+
+        let mut long_rtt = match self.long_rtt {
+            None => rtt,
+            Some(long) => long + (rtt - long) / LONG_WINDOW,
+        };
+        // When latency has dropped well below the long-term average (e.g.
+        // after a period of overload), decay the average more quickly so
+        // the limit can recover.
+        if long_rtt / rtt > 2.0 {
+            long_rtt *= 0.95;
+        }
+        self.long_rtt = Some(long_rtt);
+
+        // When the service isn't using most of its limit, its latency says
+        // nothing about whether the limit is too low.
+        if (in_flight as f64) < self.limit / 2.0 {
+            return;
+        }
+
+        let gradient = (TOLERANCE * long_rtt / rtt).max(0.5).min(1.0);
+        let queue = self.limit.sqrt();
+        let limit = self.limit * gradient + queue;
+        let limit = self.limit * (1.0 - SMOOTHING) + limit * SMOOTHING;
+        self.limit = limit
+            .max(config.min_limit as f64)
+            .min(config.max_limit as f64);
+        trace!(limit = %self.limit, %gradient, "Updated limit");
+    }
+}
+
+// === impl Permit ===
+
+impl Permit {
+    fn complete(self) {
+        let rtt = self.start.elapsed().as_secs_f64();
+        let mut state = self.limiter.state.lock().expect("limiter lock poisoned");
+        let in_flight = state.in_flight;
+        state.update(&self.limiter.config, rtt, in_flight);
+        // The permit is released when it is dropped.
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().expect("limiter lock poisoned");
+        state.in_flight -= 1;
+    }
+}
+
+// === impl LimitExceeded ===
+
+impl LimitExceeded {
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "concurrency limit of {} in-flight requests reached",
+            self.limit
+        )
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_stack::layer::Layer;
+    use tower::{service_fn, ServiceExt};
+
+    const CONFIG: Config = Config {
+        min_limit: 2,
+        max_limit: 100,
+        initial_limit: 10,
+    };
+
+    fn state() -> State {
+        State {
+            limit: CONFIG.initial_limit as f64,
+            in_flight: 0,
+            long_rtt: None,
+        }
+    }
+
+    #[test]
+    fn grows_while_latency_is_stable() {
+        let mut state = state();
+        for _ in 0..1000 {
+            let in_flight = state.limit as usize;
+            state.update(&CONFIG, 0.010, in_flight);
+        }
+        assert_eq!(state.limit as usize, CONFIG.max_limit);
+    }
+
+    #[test]
+    fn holds_when_underutilized() {
+        let mut state = state();
+        for _ in 0..1000 {
+            state.update(&CONFIG, 0.010, 1);
+        }
+        assert_eq!(state.limit as usize, CONFIG.initial_limit);
+    }
+
+    #[test]
+    fn shrinks_when_latency_rises() {
+        let config = Config {
+            min_limit: 5,
+            ..CONFIG
+        };
+        let mut state = state();
+        for _ in 0..100 {
+            let in_flight = state.limit as usize;
+            state.update(&config, 0.010, in_flight);
+        }
+        let limit = state.limit;
+
+        for _ in 0..10 {
+            let in_flight = state.limit as usize;
+            state.update(&config, 0.100, in_flight);
+        }
+        assert!(state.limit < limit, "{} < {}", state.limit, limit);
+
+        // A sustained spike drives the limit down to its minimum.
+        for _ in 0..100 {
+            let in_flight = state.limit as usize;
+            state.update(&config, 1.0, in_flight);
+        }
+        assert_eq!(state.limit as usize, config.min_limit);
+    }
+
+    #[tokio::test]
+    async fn rejects_requests_over_limit() {
+        let registry = Registry::default();
+        let config = Config {
+            initial_limit: 2,
+            ..CONFIG
+        };
+        let mut svc = registry
+            .layer((), Some(config))
+            .layer(service_fn(|()| future::pending::<Result<(), Error>>()));
+
+        let first = svc.ready().await.unwrap().call(());
+        let _second = svc.ready().await.unwrap().call(());
+        let err = svc.ready().await.unwrap().call(()).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<LimitExceeded>().unwrap().limit(), 2);
+
+        // Dropping an in-flight request releases its permit.
+        drop(first);
+        let _third = svc.ready().await.unwrap().call(());
+    }
+
+    #[tokio::test]
+    async fn disabled_without_config() {
+        let registry = Registry::<()>::default();
+        let mut svc = registry
+            .layer((), None)
+            .layer(service_fn(|()| future::pending::<Result<(), Error>>()));
+
+        let _pending = (0..1000).map(|_| svc.call(())).collect::<Vec<_>>();
+    }
+}
@@ -12,7 +12,7 @@ use tokio::time;
 
 /// A jittered exponential backoff strategy.
 // The raw fields are exposed so this type can be constructed statically.
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct ExponentialBackoff {
     /// The minimum amount of time to wait before resuming an operation.
     pub min: Duration,
@@ -72,6 +72,12 @@ impl ExponentialBackoff {
         Ok(ExponentialBackoff { min, max, jitter })
     }
 
+    /// Returns a jittered delay for the given number of prior iterations.
+    pub fn delay<R: rand::Rng>(&self, iterations: u32, rng: &mut R) -> Duration {
+        let base = self.base(iterations);
+        base + self.jitter(base, rng)
+    }
+
     fn base(&self, iterations: u32) -> Duration {
         debug_assert!(
             self.min <= self.max,
@@ -126,10 +132,7 @@ impl Stream for ExponentialBackoffStream {
                 return Poll::Ready(None);
             }
 
-            let backoff = {
-                let base = this.backoff.base(*this.iterations);
-                base + this.backoff.jitter(base, &mut this.rng)
-            };
+            let backoff = this.backoff.delay(*this.iterations, &mut this.rng);
             this.sleep.as_mut().reset(time::Instant::now() + backoff);
             *this.sleeping = true;
         }
@@ -172,6 +175,17 @@ mod tests {
             TestResult::from_bool(min <= delay && delay <= max)
         }
 
+        fn backoff_delay(min_ms: u64, max_ms: u64, jitter: f64, iterations: u32) -> TestResult {
+            let min = Duration::from_millis(min_ms);
+            let max = Duration::from_millis(max_ms);
+            let backoff = match ExponentialBackoff::new(min, max, jitter) {
+                Err(_) => return TestResult::discard(),
+                Ok(backoff) => backoff,
+            };
+            let delay = backoff.delay(iterations, &mut rand::thread_rng());
+            TestResult::from_bool(min <= delay && delay <= max)
+        }
+
         fn backoff_jitter(base_ms: u64, max_ms: u64, jitter: f64) -> TestResult {
             let base = Duration::from_millis(base_ms);
             let max = Duration::from_millis(max_ms);
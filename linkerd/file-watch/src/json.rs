@@ -0,0 +1,86 @@
+//! Helpers for decoding JSON configuration files.
+//!
+//! Configurations are decoded from untyped [`Value`]s so that each one may
+//! describe its own errors. These helpers return `None` when a value is
+//! malformed and leave it to the caller to say why.
+
+pub use serde_json::{Map, Value};
+use std::ops::RangeInclusive;
+
+pub type Object = Map<String, Value>;
+
+pub fn into_object(value: Value) -> Option<Object> {
+    match value {
+        Value::Object(obj) => Some(obj),
+        _ => None,
+    }
+}
+
+/// Reads an optional array of strings (or numbers, which are read as
+/// strings).
+pub fn strings_or_numbers(value: Option<Value>) -> Option<Vec<String>> {
+    match value {
+        None => Some(Vec::new()),
+        Some(Value::Array(values)) => values
+            .into_iter()
+            .map(|v| match v {
+                Value::String(s) => Some(s),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            })
+            .collect(),
+        Some(_) => None,
+    }
+}
+
+/// Reads an optional array of ports, each of which is either a number or a
+/// string holding a number or an inclusive range of numbers (e.g. `8000-8100`).
+pub fn port_ranges(value: Option<Value>) -> Option<Vec<RangeInclusive<u16>>> {
+    strings_or_numbers(value)?
+        .iter()
+        .map(|p| parse_port_range(p))
+        .collect()
+}
+
+pub fn parse_port_range(s: &str) -> Option<RangeInclusive<u16>> {
+    let mut parts = s.splitn(2, '-');
+    let low = parts.next()?.trim().parse::<u16>().ok()?;
+    let high = match parts.next() {
+        Some(high) => high.trim().parse::<u16>().ok()?,
+        None => low,
+    };
+    if low == 0 || high < low {
+        return None;
+    }
+    Some(low..=high)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_port_ranges() {
+        let value = serde_json::json!([3306, "5432", "8080-8082", " 9000 - 9001 "]);
+        assert_eq!(
+            port_ranges(Some(value)),
+            Some(vec![3306..=3306, 5432..=5432, 8080..=8082, 9000..=9001])
+        );
+        assert_eq!(port_ranges(None), Some(vec![]));
+
+        for invalid in &[
+            serde_json::json!(3306),
+            serde_json::json!([0]),
+            serde_json::json!([70000]),
+            serde_json::json!(["90-80"]),
+            serde_json::json!([[80]]),
+        ] {
+            assert_eq!(
+                port_ranges(Some(invalid.clone())),
+                None,
+                "{} must be invalid",
+                invalid
+            );
+        }
+    }
+}
@@ -0,0 +1,106 @@
+#![deny(warnings, rust_2018_idioms)]
+
+//! Reloads configuration from files as they change.
+//!
+//! Files are polled rather than watched for filesystem events, so that files
+//! that are replaced (e.g. a Kubernetes ConfigMap or Secret volume, which
+//! swaps a symlink when it's updated) are reloaded like files that are written
+//! in place.
+
+pub mod json;
+
+use linkerd_error::Error;
+use std::{future::Future, path::PathBuf, pin::Pin, time::Duration};
+use tokio::{sync::watch, time};
+use tracing::{debug, warn};
+
+pub type Task = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Reads and parses the file at `path`.
+///
+/// Returns a receiver holding the parsed file and a task that polls the file
+/// every `interval`, parsing and publishing its contents each time they
+/// change. The task completes once all receivers have been dropped.
+///
+/// Fails if the file can't be read or parsed initially. Once the file has been
+/// loaded, a version that can't be read or parsed is logged and ignored, so
+/// the receiver continues to hold the last valid version.
+pub fn watch<T, F>(
+    path: PathBuf,
+    interval: Duration,
+    parse: F,
+) -> Result<(watch::Receiver<T>, Task), Error>
+where
+    T: Send + Sync + 'static,
+    F: Fn(&[u8]) -> Result<T, Error> + Send + 'static,
+{
+    let mut contents = std::fs::read(&path)?;
+    let (tx, rx) = watch::channel(parse(&contents)?);
+    debug!(path = %path.display(), "Loaded");
+
+    let task = Box::pin(async move {
+        loop {
+            time::sleep(interval).await;
+
+            let next = match tokio::fs::read(&path).await {
+                Ok(next) => next,
+                Err(error) => {
+                    warn!(path = %path.display(), %error, "Failed to read file");
+                    continue;
+                }
+            };
+            if next == contents {
+                continue;
+            }
+
+            match parse(&next) {
+                Ok(value) => {
+                    debug!(path = %path.display(), "Reloaded");
+                    if tx.send(value).is_err() {
+                        debug!(path = %path.display(), "Receivers dropped");
+                        return;
+                    }
+                }
+                Err(error) => {
+                    warn!(path = %path.display(), %error, "Ignoring invalid file");
+                }
+            }
+            contents = next;
+        }
+    });
+
+    Ok((rx, task))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Result<u32, Error> {
+        Ok(std::str::from_utf8(bytes)?.trim().parse()?)
+    }
+
+    #[tokio::test]
+    async fn reloads_changes() {
+        let path = std::env::temp_dir().join(format!("linkerd-file-watch-{}", std::process::id()));
+        std::fs::write(&path, "1").unwrap();
+
+        let (mut rx, task) = watch(path.clone(), Duration::from_millis(10), parse).unwrap();
+        assert_eq!(*rx.borrow(), 1);
+        tokio::spawn(task);
+
+        std::fs::write(&path, "2").unwrap();
+        rx.changed().await.unwrap();
+        assert_eq!(*rx.borrow(), 2);
+
+        // Invalid versions are ignored.
+        std::fs::write(&path, "two").unwrap();
+        time::sleep(Duration::from_millis(50)).await;
+        std::fs::write(&path, "3").unwrap();
+        rx.changed().await.unwrap();
+        assert_eq!(*rx.borrow(), 3);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(watch(path, Duration::from_millis(10), parse).is_err());
+    }
+}
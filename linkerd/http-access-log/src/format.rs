@@ -0,0 +1,385 @@
+use chrono::{DateTime, Utc};
+use std::{
+    fmt::{self, Write},
+    net::SocketAddr,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+/// Describes how each request is written to the access log.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Format {
+    /// One JSON object per line.
+    Json,
+
+    /// The Apache "combined" log format.
+    Apache,
+
+    /// A line with `{field}` placeholders.
+    Template(Template),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template(Vec<Segment>);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Field {
+    Timestamp,
+    Direction,
+    ClientAddr,
+    Method,
+    Authority,
+    Path,
+    Version,
+    Status,
+    GrpcStatus,
+    LatencyUs,
+    BytesIn,
+    BytesOut,
+    Identity,
+    Route,
+    UserAgent,
+    Referer,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidTemplate {
+    UnknownField(String),
+    Unclosed,
+}
+
+/// A request as it's written to the access log.
+#[derive(Clone, Debug)]
+pub(crate) struct Entry {
+    pub timestamp: SystemTime,
+    pub direction: &'static str,
+    pub client_addr: Option<SocketAddr>,
+    pub method: http::Method,
+    pub authority: Option<String>,
+    pub path: String,
+    pub version: http::Version,
+    pub status: Option<http::StatusCode>,
+    pub grpc_status: Option<String>,
+    pub latency: Duration,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub identity: Option<String>,
+    pub route: Option<String>,
+    pub user_agent: Option<String>,
+    pub referer: Option<String>,
+}
+
+// === impl Format ===
+
+impl Format {
+    pub(crate) fn format(&self, entry: &Entry) -> String {
+        match self {
+            Self::Json => Self::json(entry),
+            Self::Apache => Self::apache(entry),
+            Self::Template(Template(segments)) => {
+                let mut line = String::new();
+                for segment in segments {
+                    match segment {
+                        Segment::Literal(s) => line.push_str(s),
+                        Segment::Field(field) => match field.value(entry) {
+                            Some(value) => escape(&value, &mut line),
+                            None => line.push('-'),
+                        },
+                    }
+                }
+                line
+            }
+        }
+    }
+
+    fn json(entry: &Entry) -> String {
+        let mut object = serde_json::Map::new();
+        for field in Field::ALL.iter() {
+            let value = match field.value(entry) {
+                None => serde_json::Value::Null,
+                Some(value) => match field {
+                    // Numeric fields are written as numbers.
+                    Field::Status | Field::LatencyUs | Field::BytesIn | Field::BytesOut => value
+                        .parse::<u64>()
+                        .map(Into::into)
+                        .unwrap_or(serde_json::Value::String(value)),
+                    _ => serde_json::Value::String(value),
+                },
+            };
+            object.insert(field.name().to_string(), value);
+        }
+        serde_json::Value::Object(object).to_string()
+    }
+
+    fn apache(entry: &Entry) -> String {
+        let mut line = String::new();
+        match entry.client_addr {
+            Some(addr) => line.push_str(&addr.ip().to_string()),
+            None => line.push('-'),
+        }
+        line.push_str(" - ");
+        escape_or_dash(entry.identity.as_deref(), &mut line);
+        let timestamp = DateTime::<Utc>::from(entry.timestamp);
+        let _ = write!(
+            line,
+            " [{}] \"{} ",
+            timestamp.format("%d/%b/%Y:%H:%M:%S +0000"),
+            entry.method
+        );
+        escape(&entry.path, &mut line);
+        let _ = write!(line, " {:?}\" ", entry.version);
+        match entry.status {
+            Some(status) => line.push_str(status.as_str()),
+            None => line.push('-'),
+        }
+        let _ = write!(line, " {} \"", entry.bytes_out);
+        escape_or_dash(entry.referer.as_deref(), &mut line);
+        line.push_str("\" \"");
+        escape_or_dash(entry.user_agent.as_deref(), &mut line);
+        line.push('"');
+        line
+    }
+}
+
+/// Writes a value as Apache does, so that client-controlled values can't
+/// end a quoted field or the line: quotes and backslashes are escaped with a
+/// backslash and all other bytes outside of printable ASCII are written as
+/// `\xNN`.
+fn escape(value: &str, out: &mut String) {
+    for b in value.bytes() {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b' '..=b'~' => out.push(b as char),
+            b => {
+                let _ = write!(out, "\\x{:02x}", b);
+            }
+        }
+    }
+}
+
+fn escape_or_dash(value: Option<&str>, out: &mut String) {
+    match value {
+        Some(value) => escape(value, out),
+        None => out.push('-'),
+    }
+}
+
+impl FromStr for Format {
+    type Err = InvalidTemplate;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "apache" => Ok(Self::Apache),
+            template => template.parse().map(Self::Template),
+        }
+    }
+}
+
+// === impl Template ===
+
+impl FromStr for Template {
+    type Err = InvalidTemplate;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..].find('}').ok_or(InvalidTemplate::Unclosed)? + start;
+            let name = &rest[start + 1..end];
+            let field = Field::from_name(name)
+                .ok_or_else(|| InvalidTemplate::UnknownField(name.to_string()))?;
+            segments.push(Segment::Field(field));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+        Ok(Self(segments))
+    }
+}
+
+// === impl Field ===
+
+impl Field {
+    const ALL: [Field; 16] = [
+        Self::Timestamp,
+        Self::Direction,
+        Self::ClientAddr,
+        Self::Method,
+        Self::Authority,
+        Self::Path,
+        Self::Version,
+        Self::Status,
+        Self::GrpcStatus,
+        Self::LatencyUs,
+        Self::BytesIn,
+        Self::BytesOut,
+        Self::Identity,
+        Self::Route,
+        Self::UserAgent,
+        Self::Referer,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Timestamp => "timestamp",
+            Self::Direction => "direction",
+            Self::ClientAddr => "client_addr",
+            Self::Method => "method",
+            Self::Authority => "authority",
+            Self::Path => "path",
+            Self::Version => "version",
+            Self::Status => "status",
+            Self::GrpcStatus => "grpc_status",
+            Self::LatencyUs => "latency_us",
+            Self::BytesIn => "bytes_in",
+            Self::BytesOut => "bytes_out",
+            Self::Identity => "identity",
+            Self::Route => "route",
+            Self::UserAgent => "user_agent",
+            Self::Referer => "referer",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|f| f.name() == name)
+    }
+
+    fn value(&self, entry: &Entry) -> Option<String> {
+        match self {
+            Self::Timestamp => Some(
+                DateTime::<Utc>::from(entry.timestamp)
+                    .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+                    .to_string(),
+            ),
+            Self::Direction => Some(entry.direction.to_string()),
+            Self::ClientAddr => entry.client_addr.map(|a| a.to_string()),
+            Self::Method => Some(entry.method.to_string()),
+            Self::Authority => entry.authority.clone(),
+            Self::Path => Some(entry.path.clone()),
+            Self::Version => Some(format!("{:?}", entry.version)),
+            Self::Status => entry.status.map(|s| s.as_str().to_string()),
+            Self::GrpcStatus => entry.grpc_status.clone(),
+            Self::LatencyUs => Some(entry.latency.as_micros().to_string()),
+            Self::BytesIn => Some(entry.bytes_in.to_string()),
+            Self::BytesOut => Some(entry.bytes_out.to_string()),
+            Self::Identity => entry.identity.clone(),
+            Self::Route => entry.route.clone(),
+            Self::UserAgent => entry.user_agent.clone(),
+            Self::Referer => entry.referer.clone(),
+        }
+    }
+}
+
+// === impl InvalidTemplate ===
+
+impl fmt::Display for InvalidTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownField(name) => write!(f, "unknown access log field: {}", name),
+            Self::Unclosed => write!(f, "unclosed access log field"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidTemplate {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> Entry {
+        Entry {
+            // 2021-02-03T04:05:06.789Z
+            timestamp: std::time::UNIX_EPOCH + Duration::from_millis(1_612_325_106_789),
+            direction: "inbound",
+            client_addr: Some(([10, 1, 2, 3], 40000).into()),
+            method: http::Method::GET,
+            authority: Some("books.example.com".to_string()),
+            path: "/books/1".to_string(),
+            version: http::Version::HTTP_11,
+            status: Some(http::StatusCode::OK),
+            grpc_status: None,
+            latency: Duration::from_micros(1500),
+            bytes_in: 0,
+            bytes_out: 42,
+            identity: Some("web.default.serviceaccount.identity.linkerd.cluster.local".to_string()),
+            route: Some("GET /books/{id}".to_string()),
+            user_agent: Some("curl/7.74.0".to_string()),
+            referer: None,
+        }
+    }
+
+    #[test]
+    fn formats_json() {
+        let line = Format::Json.format(&entry());
+        let json = serde_json::from_str::<serde_json::Value>(&line).unwrap();
+        assert_eq!(json["timestamp"], "2021-02-03T04:05:06.789Z");
+        assert_eq!(json["direction"], "inbound");
+        assert_eq!(json["client_addr"], "10.1.2.3:40000");
+        assert_eq!(json["method"], "GET");
+        assert_eq!(json["status"], 200);
+        assert_eq!(json["grpc_status"], serde_json::Value::Null);
+        assert_eq!(json["latency_us"], 1500);
+        assert_eq!(json["bytes_out"], 42);
+        assert_eq!(json["route"], "GET /books/{id}");
+    }
+
+    #[test]
+    fn formats_apache() {
+        assert_eq!(
+            Format::Apache.format(&entry()),
+            "10.1.2.3 - web.default.serviceaccount.identity.linkerd.cluster.local \
+             [03/Feb/2021:04:05:06 +0000] \"GET /books/1 HTTP/1.1\" 200 42 \"-\" \"curl/7.74.0\""
+        );
+    }
+
+    #[test]
+    fn formats_templates() {
+        let format = "{method} {authority}{path} -> {status} grpc={grpc_status} ({latency_us}us)"
+            .parse::<Format>()
+            .unwrap();
+        assert_eq!(
+            format.format(&entry()),
+            "GET books.example.com/books/1 -> 200 grpc=- (1500us)"
+        );
+
+        assert_eq!(
+            "{method} {nope}".parse::<Format>(),
+            Err(InvalidTemplate::UnknownField("nope".to_string()))
+        );
+        assert_eq!("{method".parse::<Format>(), Err(InvalidTemplate::Unclosed));
+    }
+
+    #[test]
+    fn escapes_client_values() {
+        let mut entry = entry();
+        entry.path = "/books?q=\\\"".to_string();
+        entry.user_agent = Some("curl\" 200 0 \"-\" \"-\"\n10.9.9.9 - - [x] \"GET /".to_string());
+        entry.referer = Some("http://é.example.com".to_string());
+        assert_eq!(
+            Format::Apache.format(&entry),
+            "10.1.2.3 - web.default.serviceaccount.identity.linkerd.cluster.local \
+             [03/Feb/2021:04:05:06 +0000] \"GET /books?q=\\\\\\\" HTTP/1.1\" 200 42 \
+             \"http://\\xc3\\xa9.example.com\" \
+             \"curl\\\" 200 0 \\\"-\\\" \\\"-\\\"\\x0a10.9.9.9 - - [x] \\\"GET /\""
+        );
+
+        let format = "ua={user_agent}".parse::<Format>().unwrap();
+        assert_eq!(
+            format.format(&entry),
+            "ua=curl\\\" 200 0 \\\"-\\\" \\\"-\\\"\\x0a10.9.9.9 - - [x] \\\"GET /"
+        );
+    }
+}
@@ -0,0 +1,599 @@
+#![deny(warnings, rust_2018_idioms)]
+
+//! Writes an access log line for each HTTP request.
+
+mod format;
+
+use self::format::Entry;
+pub use self::format::{Format, InvalidTemplate, Template};
+use bytes::Buf;
+use futures::{prelude::*, ready};
+use http_body::Body as HttpBody;
+use linkerd_proxy_http::{client_handle::ClientHandle, BoxBody};
+use linkerd_stack::{layer, Either};
+use linkerd_trace_context::attributes::SpanAttributes;
+use pin_project::{pin_project, pinned_drop};
+use rand::Rng;
+use std::{
+    fs, io,
+    path::PathBuf,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Instant, SystemTime},
+};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::mpsc,
+};
+use tracing::{debug, trace, warn};
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub format: Format,
+    pub output: Output,
+
+    /// The ratio of requests that are logged.
+    pub sample_ratio: f64,
+}
+
+/// Where log lines are written.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Output {
+    Stderr,
+
+    /// Lines are appended to the file, which is created if necessary.
+    File(PathBuf),
+}
+
+/// A handle used to write log lines.
+#[derive(Clone, Debug)]
+pub struct AccessLog {
+    lines: mpsc::Sender<String>,
+    format: Arc<Format>,
+    sample_ratio: f64,
+}
+
+pub type Task = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Logs each request once its response completes.
+#[derive(Clone, Debug)]
+pub struct LogRequests<S> {
+    inner: S,
+    log: AccessLog,
+    direction: &'static str,
+}
+
+#[pin_project]
+pub struct RequestBody<B> {
+    #[pin]
+    inner: B,
+    bytes: Arc<AtomicU64>,
+}
+
+/// A response body that writes the request's log line when it completes.
+#[pin_project(PinnedDrop)]
+pub struct ResponseBody<B> {
+    #[pin]
+    inner: B,
+    pending: Option<Pending>,
+}
+
+/// A request that is logged once its response completes.
+struct Pending {
+    log: AccessLog,
+    entry: Entry,
+    start: Instant,
+    bytes_in: Arc<AtomicU64>,
+    attributes: SpanAttributes,
+
+    /// Set when the response's status is expected in its trailers, so the
+    /// line isn't written until they are read.
+    grpc: bool,
+}
+
+/// Logs requests when an access log is configured. Otherwise, the inner
+/// service is used directly.
+pub fn layer<S>(
+    log: Option<AccessLog>,
+    direction: &'static str,
+) -> impl layer::Layer<S, Service = Either<LogRequests<S>, S>> + Clone {
+    layer::mk(move |inner| match log.clone() {
+        Some(log) => Either::A(LogRequests {
+            inner,
+            log,
+            direction,
+        }),
+        None => Either::B(inner),
+    })
+}
+
+// === impl Config ===
+
+impl Config {
+    const LINE_BUFFER_CAPACITY: usize = 1000;
+
+    /// Returns a log handle and a task that writes its lines.
+    pub fn build(self) -> io::Result<(AccessLog, Task)> {
+        let (lines, rx) = mpsc::channel(Self::LINE_BUFFER_CAPACITY);
+        let task: Task = match self.output {
+            Output::Stderr => Box::pin(write_lines(rx, tokio::io::stderr())),
+            Output::File(path) => {
+                let file = fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)?;
+                debug!(path = %path.display(), "Writing access log");
+                Box::pin(write_lines(rx, tokio::fs::File::from_std(file)))
+            }
+        };
+        let log = AccessLog {
+            lines,
+            format: Arc::new(self.format),
+            sample_ratio: self.sample_ratio,
+        };
+        Ok((log, task))
+    }
+}
+
+async fn write_lines<W: AsyncWrite + Unpin>(mut rx: mpsc::Receiver<String>, mut out: W) {
+    while let Some(line) = rx.recv().await {
+        let write = async {
+            out.write_all(line.as_bytes()).await?;
+            out.flush().await
+        };
+        if let Err(error) = write.await {
+            warn!(%error, "Failed to write access log");
+        }
+    }
+}
+
+// === impl AccessLog ===
+
+impl AccessLog {
+    fn is_sampled(&self) -> bool {
+        self.sample_ratio >= 1.0 || rand::thread_rng().gen::<f64>() < self.sample_ratio
+    }
+
+    fn write(&self, entry: &Entry) {
+        let mut line = self.format.format(entry);
+        line.push('\n');
+        if self.lines.try_send(line).is_err() {
+            // Lines are dropped rather than applying backpressure to
+            // requests.
+            debug!("Access log line dropped");
+        }
+    }
+}
+
+// === impl LogRequests ===
+
+impl<S> tower::Service<http::Request<BoxBody>> for LogRequests<S>
+where
+    S: tower::Service<http::Request<BoxBody>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = future::Either<
+        S::Future,
+        Pin<Box<dyn Future<Output = Result<Self::Response, S::Error>> + Send + 'static>>,
+    >;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<BoxBody>) -> Self::Future {
+        if !self.log.is_sampled() {
+            return future::Either::Left(self.inner.call(req));
+        }
+
+        // Inner layers record the request's route and peer identity.
+        let attributes = match req.extensions().get::<SpanAttributes>() {
+            Some(attributes) => attributes.clone(),
+            None => {
+                let attributes = SpanAttributes::default();
+                req.extensions_mut().insert(attributes.clone());
+                attributes
+            }
+        };
+
+        let header = |name: http::header::HeaderName| {
+            req.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+        let entry = Entry {
+            timestamp: SystemTime::now(),
+            direction: self.direction,
+            client_addr: req.extensions().get::<ClientHandle>().map(|c| c.addr),
+            method: req.method().clone(),
+            authority: req
+                .uri()
+                .authority()
+                .map(|a| a.to_string())
+                .or_else(|| header(http::header::HOST)),
+            path: req
+                .uri()
+                .path_and_query()
+                .map(|pq| pq.to_string())
+                .unwrap_or_else(|| "/".to_string()),
+            version: req.version(),
+            status: None,
+            grpc_status: None,
+            latency: Default::default(),
+            bytes_in: 0,
+            bytes_out: 0,
+            identity: None,
+            route: None,
+            user_agent: header(http::header::USER_AGENT),
+            referer: header(http::header::REFERER),
+        };
+
+        let bytes_in = Arc::new(AtomicU64::new(0));
+        let req = req.map(|inner| {
+            BoxBody::new(RequestBody {
+                inner,
+                bytes: bytes_in.clone(),
+            })
+        });
+        let mut pending = Pending {
+            log: self.log.clone(),
+            entry,
+            start: Instant::now(),
+            bytes_in,
+            attributes,
+            grpc: false,
+        };
+
+        future::Either::Right(Box::pin(self.inner.call(req).map(move |res| match res {
+            Ok(rsp) => {
+                pending.entry.status = Some(rsp.status());
+                pending.entry.grpc_status = grpc_status(rsp.headers());
+                pending.grpc = pending.entry.grpc_status.is_none() && is_grpc(rsp.headers());
+                if rsp.body().is_end_stream() {
+                    pending.complete();
+                    return Ok(rsp);
+                }
+                Ok(rsp.map(|inner| {
+                    BoxBody::new(ResponseBody {
+                        inner,
+                        pending: Some(pending),
+                    })
+                }))
+            }
+            Err(error) => {
+                // Requests that fail without a response are logged without a
+                // status.
+                pending.complete();
+                Err(error)
+            }
+        })))
+    }
+}
+
+fn is_grpc(headers: &http::HeaderMap) -> bool {
+    headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.starts_with("application/grpc"))
+        .unwrap_or(false)
+}
+
+fn grpc_status(headers: &http::HeaderMap) -> Option<String> {
+    headers
+        .get("grpc-status")
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+}
+
+// === impl RequestBody ===
+
+impl<B: HttpBody> HttpBody for RequestBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, B::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.inner.poll_data(cx));
+        if let Some(Ok(ref data)) = frame {
+            this.bytes
+                .fetch_add(data.remaining() as u64, Ordering::Relaxed);
+        }
+        Poll::Ready(frame)
+    }
+
+    #[inline]
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, B::Error>> {
+        self.project().inner.poll_trailers(cx)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+// === impl ResponseBody ===
+
+impl<B: HttpBody> HttpBody for ResponseBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, B::Error>>> {
+        let mut this = self.project();
+        let frame = ready!(this.inner.as_mut().poll_data(cx));
+        match frame {
+            Some(Ok(ref data)) => {
+                if let Some(pending) = this.pending.as_mut() {
+                    pending.entry.bytes_out += data.remaining() as u64;
+                }
+            }
+            // The body may end without having reported the end of the stream,
+            // and its trailers may never be polled (e.g. by an HTTP/1 server).
+            None if !this.pending.as_ref().map(|p| p.grpc).unwrap_or(false) => {
+                complete(this.pending);
+            }
+            _ => {}
+        }
+        if this.inner.is_end_stream() {
+            complete(this.pending);
+        }
+        Poll::Ready(frame)
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, B::Error>> {
+        let this = self.project();
+        let trailers = ready!(this.inner.poll_trailers(cx));
+        if let Some(pending) = this.pending.as_mut() {
+            if let Ok(Some(ref trailers)) = trailers {
+                if let Some(status) = grpc_status(trailers) {
+                    pending.entry.grpc_status = Some(status);
+                }
+            }
+        }
+        complete(this.pending);
+        Poll::Ready(trailers)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[pinned_drop]
+impl<B> PinnedDrop for ResponseBody<B> {
+    fn drop(self: Pin<&mut Self>) {
+        // If the body is dropped before it completes, the request is logged
+        // when it's dropped.
+        complete(self.project().pending);
+    }
+}
+
+fn complete(pending: &mut Option<Pending>) {
+    if let Some(pending) = pending.take() {
+        pending.complete();
+    }
+}
+
+// === impl Pending ===
+
+impl Pending {
+    fn complete(self) {
+        let Self {
+            log,
+            mut entry,
+            start,
+            bytes_in,
+            attributes,
+            ..
+        } = self;
+        entry.latency = start.elapsed();
+        entry.bytes_in = bytes_in.load(Ordering::Relaxed);
+        entry.identity = attributes.get("peer.identity");
+        entry.route = attributes.get("http.route");
+        trace!(?entry);
+        log.write(&entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_error::Error;
+    use linkerd_proxy_http::BoxResponse;
+    use linkerd_trace_context::{attributes::CaptureHeaders, Span, TraceContext};
+    use tower::{Layer, ServiceExt};
+
+    fn access_log(sample_ratio: f64) -> (AccessLog, mpsc::Receiver<String>) {
+        let (lines, rx) = mpsc::channel(10);
+        let log = AccessLog {
+            lines,
+            format: Arc::new(
+                "{method} {path} {status} {grpc_status} {bytes_in} {bytes_out} {route}"
+                    .parse()
+                    .unwrap(),
+            ),
+            sample_ratio,
+        };
+        (log, rx)
+    }
+
+    #[tokio::test]
+    async fn logs_completed_requests() {
+        let (log, mut lines) = access_log(1.0);
+        let svc = layer(Some(log), "inbound").layer(tower::service_fn(
+            |req: http::Request<BoxBody>| async move {
+                // Inner layers record the request's route.
+                SpanAttributes::record(&req, "http.route", "POST /books");
+                let data = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                let mut trailers = http::HeaderMap::new();
+                trailers.insert("grpc-status", "0".parse().unwrap());
+                let (mut tx, body) = hyper::Body::channel();
+                tokio::spawn(async move {
+                    tx.send_data(data).await.unwrap();
+                    tx.send_trailers(trailers).await.unwrap();
+                });
+                let rsp = http::Response::builder()
+                    .header(http::header::CONTENT_TYPE, "application/grpc")
+                    .body(BoxBody::new(body))
+                    .unwrap();
+                Ok::<_, Error>(rsp)
+            },
+        ));
+
+        let req = http::Request::post("/books")
+            .body(BoxBody::new(hyper::Body::from("hello")))
+            .unwrap();
+        let rsp = svc.clone().oneshot(req).await.unwrap();
+        assert!(
+            lines.recv().now_or_never().is_none(),
+            "logged before the body completed"
+        );
+
+        let mut body = rsp.into_body();
+        let data = hyper::body::to_bytes(&mut body).await.unwrap();
+        assert_eq!(data, "hello");
+        body.trailers().await.unwrap();
+        assert_eq!(
+            lines.recv().await.unwrap(),
+            "POST /books 200 0 5 5 POST /books\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn logs_when_body_ends() {
+        let (log, mut lines) = access_log(1.0);
+        let svc = layer(Some(log), "inbound").layer(tower::service_fn(
+            |_: http::Request<BoxBody>| async move {
+                // The body doesn't know that it has ended until it's polled
+                // again.
+                let (mut tx, body) = hyper::Body::channel();
+                tokio::spawn(async move {
+                    tx.send_data("hello".into()).await.unwrap();
+                });
+                Ok::<_, Error>(http::Response::new(BoxBody::new(body)))
+            },
+        ));
+
+        let req = http::Request::get("/").body(BoxBody::default()).unwrap();
+        let rsp = svc.clone().oneshot(req).await.unwrap();
+
+        // The line is written once the data has been read, though the body's
+        // trailers aren't polled and it isn't dropped.
+        let mut body = rsp.into_body();
+        let data = hyper::body::to_bytes(&mut body).await.unwrap();
+        assert_eq!(data, "hello");
+        assert_eq!(
+            lines.recv().now_or_never().flatten().unwrap(),
+            "GET / 200 - 0 5 -\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn shares_span_attributes() {
+        fn route(
+            req: http::Request<BoxBody>,
+        ) -> future::Ready<Result<http::Response<BoxBody>, Error>> {
+            SpanAttributes::record(&req, "http.route", "GET /books");
+            future::ok(http::Response::new(BoxBody::default()))
+        }
+
+        fn trace<S>(
+            spans: linkerd_channel::Sender<Span>,
+        ) -> impl Layer<S, Service = TraceContext<Option<linkerd_channel::Sender<Span>>, S>>
+        {
+            TraceContext::layer(Some(spans), None, CaptureHeaders::default())
+        }
+
+        fn req() -> http::Request<BoxBody> {
+            let traceparent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+            http::Request::get("/books")
+                .header("traceparent", traceparent)
+                .body(BoxBody::default())
+                .unwrap()
+        }
+
+        // The route is recorded on both the span and the log line, whether
+        // the access log is below...
+        let (log, mut lines) = access_log(1.0);
+        let (spans_tx, mut spans) = linkerd_channel::channel(1);
+        let svc =
+            trace(spans_tx).layer(layer(Some(log), "inbound").layer(tower::service_fn(route)));
+        drop(svc.oneshot(req()).await.unwrap());
+        assert_eq!(
+            lines.recv().await.unwrap(),
+            "GET /books 200 - 0 0 GET /books\n"
+        );
+        assert_eq!(
+            spans.recv().await.unwrap().labels["http.route"],
+            "GET /books"
+        );
+
+        // ...or above the server span.
+        let (log, mut lines) = access_log(1.0);
+        let (spans_tx, mut spans) = linkerd_channel::channel(1);
+        let svc = layer(Some(log), "inbound")
+            .layer(BoxResponse::layer().layer(trace(spans_tx).layer(tower::service_fn(route))));
+        drop(svc.oneshot(req()).await.unwrap());
+        assert_eq!(
+            lines.recv().await.unwrap(),
+            "GET /books 200 - 0 0 GET /books\n"
+        );
+        assert_eq!(
+            spans.recv().await.unwrap().labels["http.route"],
+            "GET /books"
+        );
+    }
+
+    #[tokio::test]
+    async fn samples_requests() {
+        let (log, mut lines) = access_log(0.0);
+        let svc = layer(Some(log), "outbound").layer(tower::service_fn(
+            |_: http::Request<BoxBody>| async move {
+                Ok::<_, Error>(http::Response::new(BoxBody::default()))
+            },
+        ));
+        let req = http::Request::get("/").body(BoxBody::default()).unwrap();
+        svc.clone().oneshot(req).await.unwrap();
+        assert!(lines.recv().now_or_never().is_none());
+    }
+
+    #[test]
+    fn disabled_without_a_log() {
+        let svc = layer(None, "inbound").layer(tower::service_fn(|_: http::Request<BoxBody>| {
+            future::ok::<_, Error>(http::Response::new(BoxBody::default()))
+        }));
+        assert!(
+            matches!(svc, Either::B(_)),
+            "the inner service must be used directly"
+        );
+    }
+}
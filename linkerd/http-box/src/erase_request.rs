@@ -0,0 +1,70 @@
+//! A middleware that boxes HTTP request bodies of any type.
+
+use crate::BoxBody;
+use linkerd_error::Error;
+use linkerd_stack::{layer, NewService, Proxy};
+use std::task::{Context, Poll};
+
+/// Boxes request bodies, erasing the original type.
+///
+/// Unlike `BoxRequest`, which erases a single body type, `EraseRequest` erases
+/// the type of *any* request body, so it may be used where the same inner
+/// service receives requests with differing body types (e.g. because a
+/// request may or may not be wrapped for retries).
+///
+/// `BoxRequest` should be preferred where it is sufficient, since this
+/// middleware leaves request body types uninferrable.
+#[derive(Clone, Debug)]
+pub struct EraseRequest<S>(S);
+
+impl<S> EraseRequest<S> {
+    pub fn layer() -> impl layer::Layer<S, Service = Self> + Clone + Copy {
+        layer::mk(EraseRequest)
+    }
+}
+
+impl<T, N: NewService<T>> NewService<T> for EraseRequest<N> {
+    type Service = EraseRequest<N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        EraseRequest(self.0.new_service(target))
+    }
+}
+
+impl<S, B> tower::Service<http::Request<B>> for EraseRequest<S>
+where
+    B: http_body::Body + Send + 'static,
+    B::Data: Send + 'static,
+    B::Error: Into<Error>,
+    S: tower::Service<http::Request<BoxBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        self.0.call(req.map(BoxBody::new))
+    }
+}
+
+impl<P, S, B> Proxy<http::Request<B>, S> for EraseRequest<P>
+where
+    B: http_body::Body + Send + 'static,
+    B::Data: Send + 'static,
+    B::Error: Into<Error>,
+    P: Proxy<http::Request<BoxBody>, S>,
+    S: tower::Service<P::Request>,
+{
+    type Request = P::Request;
+    type Response = P::Response;
+    type Error = P::Error;
+    type Future = P::Future;
+
+    fn proxy(&self, svc: &mut S, req: http::Request<B>) -> Self::Future {
+        self.0.proxy(svc, req.map(BoxBody::new))
+    }
+}
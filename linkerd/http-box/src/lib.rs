@@ -1,11 +1,13 @@
 #![deny(warnings, rust_2018_idioms)]
 
 mod body;
+mod erase_request;
 mod request;
 mod response;
 
 pub use self::{
     body::{BoxBody, Data},
+    erase_request::EraseRequest,
     request::BoxRequest,
     response::BoxResponse,
 };
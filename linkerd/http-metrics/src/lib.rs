@@ -1,6 +1,9 @@
 #![deny(warnings, rust_2018_idioms)]
 
-pub use self::{requests::Requests, retries::Retries};
+pub use self::{
+    requests::{Latencies, Requests},
+    retries::Retries,
+};
 use linkerd_metrics::{LastUpdate, Store};
 use std::fmt;
 use std::hash::Hash;
@@ -4,7 +4,7 @@ mod service;
 use super::{LastUpdate, Registry, Report};
 use indexmap::IndexMap;
 use linkerd_http_classify::ClassifyResponse;
-use linkerd_metrics::{latency, Counter, FmtMetrics, Histogram};
+use linkerd_metrics::{latency, Bucket, Counter, FmtMetrics, Histogram};
 use linkerd_stack::layer;
 use std::{
     fmt::Debug,
@@ -23,6 +23,13 @@ where
     T: Hash + Eq,
     C: Hash + Eq;
 
+/// A handle to a target's response latency distribution.
+///
+/// The distribution is read without locking the target's metrics, so it may be
+/// consulted on every request.
+#[derive(Clone, Debug)]
+pub struct Latencies(Arc<Histogram<latency::Ms>>);
+
 #[derive(Debug)]
 pub struct Metrics<C>
 where
@@ -30,6 +37,8 @@ where
 {
     last_update: Instant,
     total: Counter,
+    /// Response latencies across all statuses.
+    latency: Arc<Histogram<latency::Ms>>,
     by_status: IndexMap<Option<http::StatusCode>, StatusMetrics<C>>,
 }
 
@@ -70,6 +79,17 @@ impl<T: Hash + Eq, C: Hash + Eq> Requests<T, C> {
         let reg = self.0.clone();
         layer::mk(move |inner| NewHttpMetrics::new(reg.clone(), inner))
     }
+
+    pub fn latencies(&self, target: T) -> Latencies {
+        let mut reg = self.0.lock().expect("request metrics registry poisoned");
+        let metrics = reg.entry(target).or_default();
+        let latency = metrics
+            .lock()
+            .expect("request metrics poisoned")
+            .latency
+            .clone();
+        Latencies(latency)
+    }
 }
 
 impl<T: Hash + Eq, C: Hash + Eq> Clone for Requests<T, C> {
@@ -78,6 +98,45 @@ impl<T: Hash + Eq, C: Hash + Eq> Clone for Requests<T, C> {
     }
 }
 
+// === impl Latencies ===
+
+impl Latencies {
+    /// Estimates a percentile (on `[0, 100]`) of response latencies across
+    /// all response statuses.
+    ///
+    /// The estimate is the upper bound of the histogram bucket that contains
+    /// the percentile, so it is only as precise as the histogram's buckets.
+    /// `None` is returned when no responses have been recorded or when the
+    /// percentile falls in the unbounded bucket.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        let buckets = &*self.0;
+        let total = buckets
+            .into_iter()
+            .map(|(_, count)| Into::<u64>::into(count))
+            .sum::<u64>();
+        if total == 0 {
+            return None;
+        }
+
+        // Responses may be recorded while the buckets are read, in which case
+        // the estimate may be slightly off; if the rank is never reached, no
+        // estimate is made.
+        let rank = ((percentile / 100.0) * total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in buckets {
+            seen += Into::<u64>::into(count);
+            if seen >= rank {
+                return match *bucket {
+                    Bucket::Le(ms) => Some(Duration::from_millis(ms as u64)),
+                    Bucket::Inf => None,
+                };
+            }
+        }
+
+        None
+    }
+}
+
 // === impl Metrics ===
 
 impl<C: Hash + Eq> Default for Metrics<C> {
@@ -85,6 +144,7 @@ impl<C: Hash + Eq> Default for Metrics<C> {
         Self {
             last_update: Instant::now(),
             total: Counter::default(),
+            latency: Arc::new(Histogram::default()),
             by_status: IndexMap::default(),
         }
     }
@@ -177,4 +237,25 @@ mod tests {
 
         drop((registry, report));
     }
+
+    #[test]
+    fn latency_percentile() {
+        use std::time::Duration;
+
+        let r = super::Requests::<(), ()>::default();
+        let latencies = r.latencies(());
+        assert_eq!(latencies.percentile(50.0), None, "no responses recorded");
+
+        // Latencies are recorded across all response statuses.
+        for _ in 0..90 {
+            latencies.0.add(Duration::from_millis(3));
+        }
+        for _ in 0..10 {
+            latencies.0.add(Duration::from_millis(150));
+        }
+
+        assert_eq!(latencies.percentile(50.0), Some(Duration::from_millis(3)));
+        assert_eq!(latencies.percentile(90.0), Some(Duration::from_millis(3)));
+        assert_eq!(latencies.percentile(95.0), Some(Duration::from_millis(200)));
+    }
 }
@@ -396,6 +396,7 @@ where
         };
 
         (*metrics).last_update = now;
+        metrics.latency.add(now - *this.stream_open_at);
 
         let status_metrics = metrics
             .by_status
@@ -1,5 +1,8 @@
 use super::{LastUpdate, Prefixed, Registry, Report};
-use linkerd_metrics::{Counter, FmtLabels, FmtMetric, FmtMetrics, Metric};
+use linkerd_metrics::{
+    Bounds, Bucket, Counter, FmtLabels, FmtMetric, FmtMetrics, Histogram, Metric,
+};
+use std::collections::HashMap;
 use std::fmt;
 use std::hash::Hash;
 use std::sync::{Arc, Mutex};
@@ -19,10 +22,33 @@ pub struct Metrics {
     last_update: Instant,
     retryable: Counter,
     no_budget: Counter,
+    body_too_large: Counter,
+    max_attempts: Counter,
+    attempts: Histogram<u64>,
+    errors: HashMap<&'static str, Counter>,
+    hedged: Counter,
+    hedge_wins: Counter,
 }
 
 struct NoBudgetLabel;
 
+struct BodyTooLargeLabel;
+
+struct MaxAttemptsLabel;
+
+struct ErrorLabel(&'static str);
+
+/// The number of attempts made to obtain a response.
+const ATTEMPTS_BOUNDS: &Bounds = &Bounds(&[
+    Bucket::Le(1.0),
+    Bucket::Le(2.0),
+    Bucket::Le(3.0),
+    Bucket::Le(4.0),
+    Bucket::Le(5.0),
+    Bucket::Le(10.0),
+    Bucket::Inf,
+]);
+
 // === impl Retries ===
 
 impl<T: Hash + Eq> Default for Retries<T> {
@@ -60,6 +86,62 @@ impl Handle {
             }
         }
     }
+
+    /// Records a retryable response whose request body could not be replayed.
+    pub fn incr_body_too_large(&self) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.retryable.incr();
+            m.body_too_large.incr();
+        }
+    }
+
+    /// Records a retryable response whose request was not retried because it
+    /// had already been attempted the maximum number of times.
+    pub fn incr_max_attempts(&self) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.retryable.incr();
+            m.max_attempts.incr();
+        }
+    }
+
+    /// Records an error that was classified as safe to retry.
+    ///
+    /// Because a retryable error is treated like a retryable response, the
+    /// caller is also expected to record the outcome (e.g. via
+    /// `incr_retryable`).
+    pub fn incr_retryable_error(&self, error: &'static str) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.errors.entry(error).or_default().incr();
+        }
+    }
+
+    /// Records that a hedged request was issued.
+    pub fn incr_hedged(&self) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.hedged.incr();
+        }
+    }
+
+    /// Records that a hedged request completed before the original request.
+    pub fn incr_hedge_won(&self) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.hedge_wins.incr();
+        }
+    }
+
+    /// Records the number of times a request was attempted before its final
+    /// response was returned.
+    pub fn observe_attempts(&self, attempts: usize) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.attempts.add(attempts as u64);
+        }
+    }
 }
 
 // === impl Metrics ===
@@ -70,6 +152,12 @@ impl Default for Metrics {
             last_update: Instant::now(),
             retryable: Counter::default(),
             no_budget: Counter::default(),
+            body_too_large: Counter::default(),
+            max_attempts: Counter::default(),
+            attempts: Histogram::new(ATTEMPTS_BOUNDS),
+            errors: HashMap::default(),
+            hedged: Counter::default(),
+            hedge_wins: Counter::default(),
         }
     }
 }
@@ -92,6 +180,34 @@ where
             "Total count of retryable HTTP responses.",
         )
     }
+
+    fn retryable_errors_total(&self) -> Metric<'_, Prefixed<'_, &'static str>, Counter> {
+        Metric::new(
+            self.prefix_key("retryable_errors_total"),
+            "Total count of errors that were classified as safe to retry.",
+        )
+    }
+
+    fn hedged_total(&self) -> Metric<'_, Prefixed<'_, &'static str>, Counter> {
+        Metric::new(
+            self.prefix_key("hedged_total"),
+            "Total count of hedged HTTP requests.",
+        )
+    }
+
+    fn hedge_wins_total(&self) -> Metric<'_, Prefixed<'_, &'static str>, Counter> {
+        Metric::new(
+            self.prefix_key("hedge_wins_total"),
+            "Total count of hedged HTTP requests that completed before the original request.",
+        )
+    }
+
+    fn response_attempts(&self) -> Metric<'_, Prefixed<'_, &'static str>, Histogram<u64>> {
+        Metric::new(
+            self.prefix_key("response_attempts"),
+            "A histogram of the number of times requests were attempted before a response was returned.",
+        )
+    }
 }
 
 impl<T> FmtMetrics for Report<T, Metrics>
@@ -120,6 +236,44 @@ where
                 m.retryable.fmt_metric_labeled(f, &metric.name, tgt)?;
                 m.no_budget
                     .fmt_metric_labeled(f, &metric.name, (tgt, NoBudgetLabel))?;
+                m.body_too_large
+                    .fmt_metric_labeled(f, &metric.name, (tgt, BodyTooLargeLabel))?;
+                m.max_attempts
+                    .fmt_metric_labeled(f, &metric.name, (tgt, MaxAttemptsLabel))?;
+            }
+        }
+
+        let metric = self.retryable_errors_total();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                for (error, counter) in m.errors.iter() {
+                    counter.fmt_metric_labeled(f, &metric.name, (tgt, ErrorLabel(error)))?;
+                }
+            }
+        }
+
+        let metric = self.hedged_total();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                m.hedged.fmt_metric_labeled(f, &metric.name, tgt)?;
+            }
+        }
+
+        let metric = self.hedge_wins_total();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                m.hedge_wins.fmt_metric_labeled(f, &metric.name, tgt)?;
+            }
+        }
+
+        let metric = self.response_attempts();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                m.attempts.fmt_metric_labeled(f, &metric.name, tgt)?;
             }
         }
 
@@ -134,3 +288,21 @@ impl FmtLabels for NoBudgetLabel {
         write!(f, "skipped=\"no_budget\"")
     }
 }
+
+impl FmtLabels for BodyTooLargeLabel {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "skipped=\"body_too_large\"")
+    }
+}
+
+impl FmtLabels for MaxAttemptsLabel {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "skipped=\"max_attempts\"")
+    }
+}
+
+impl FmtLabels for ErrorLabel {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error=\"{}\"", self.0)
+    }
+}
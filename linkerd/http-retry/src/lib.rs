@@ -0,0 +1,461 @@
+#![deny(warnings, rust_2018_idioms)]
+
+use bytes::{Buf, Bytes};
+use http::HeaderMap;
+use http_body::{Body, SizeHint};
+use linkerd_error::Error;
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+};
+use tracing::trace;
+
+/// Wraps an HTTP body type and lazily buffers data as it is read from the inner
+/// body.
+///
+/// When this body is dropped, if a clone exists, any buffered data is shared
+/// with its clones. The first clone to be polled will take ownership over the
+/// data until it is dropped. When *that* clone is dropped, the buffered data
+/// --- including any new data read from the body by the clone, if the body has
+/// not yet completed --- will be shared with any remaining clones.
+///
+/// Once more than `max_bytes` of data have been read, the buffer is discarded
+/// and clones of the body can no longer be replayed (see
+/// [`ReplayBody::is_capped`]).
+pub struct ReplayBody<B> {
+    /// The state owned by this body while it is being read. It is returned to
+    /// `shared` when this body is dropped.
+    state: Option<BodyState<B>>,
+
+    shared: Arc<SharedState<B>>,
+
+    /// The number of data chunks this body has yielded.
+    chunks_read: usize,
+
+    /// Set once this body has yielded its trailers.
+    trailers_read: bool,
+}
+
+/// An error indicating that a `ReplayBody` could not be replayed.
+#[derive(Clone, Debug)]
+pub struct ReplayError(&'static str);
+
+struct SharedState<B> {
+    body: Mutex<Option<BodyState<B>>>,
+
+    /// Set when more than `max_bytes` have been read from the inner body.
+    is_capped: AtomicBool,
+
+    /// Whether the inner body was already at end-of-stream when it was
+    /// wrapped.
+    was_empty: bool,
+
+    size_hint: SizeHint,
+}
+
+struct BodyState<B> {
+    buf: Vec<Bytes>,
+    buffered_bytes: usize,
+    max_bytes: usize,
+
+    /// The total number of data chunks read from the inner body.
+    chunks: usize,
+
+    trailers: Option<HeaderMap>,
+
+    /// The remainder of the inner body, or `None` once its trailers have been
+    /// read.
+    rest: Option<Pin<Box<B>>>,
+
+    /// Set when the inner body has no more data.
+    is_data_done: bool,
+}
+
+// === impl ReplayBody ===
+
+impl<B> ReplayBody<B> {
+    /// Returns `true` if the body has exceeded its buffer limit, in which case
+    /// it can no longer be replayed.
+    pub fn is_capped(&self) -> bool {
+        self.shared.is_capped.load(Ordering::Acquire)
+    }
+}
+
+impl<B: Body> ReplayBody<B> {
+    /// Wraps `body` so that it may be replayed, buffering up to `max_bytes` of
+    /// data.
+    ///
+    /// If the body's size hint indicates that it is larger than `max_bytes`,
+    /// the original body is returned, since it could never be replayed.
+    pub fn try_new(body: B, max_bytes: usize) -> Result<Self, B> {
+        let size_hint = body.size_hint();
+        if size_hint.lower() > max_bytes as u64 {
+            return Err(body);
+        }
+
+        let was_empty = body.is_end_stream();
+        let state = BodyState {
+            buf: Vec::new(),
+            buffered_bytes: 0,
+            max_bytes,
+            chunks: 0,
+            trailers: None,
+            rest: Some(Box::pin(body)),
+            is_data_done: was_empty,
+        };
+        Ok(Self {
+            state: None,
+            shared: Arc::new(SharedState {
+                body: Mutex::new(Some(state)),
+                is_capped: AtomicBool::new(false),
+                was_empty,
+                size_hint,
+            }),
+            chunks_read: 0,
+            trailers_read: false,
+        })
+    }
+
+    /// Takes the shared body state, if this body does not already own it.
+    ///
+    /// Returns `None` if the state is currently owned by another clone.
+    fn acquire_state<'a>(
+        state: &'a mut Option<BodyState<B>>,
+        shared: &SharedState<B>,
+    ) -> Option<&'a mut BodyState<B>> {
+        if state.is_none() {
+            *state = shared.body.lock().ok()?.take();
+        }
+        state.as_mut()
+    }
+}
+
+impl<B> Body for ReplayBody<B>
+where
+    B: Body,
+    B::Error: Into<Error>,
+{
+    type Data = Bytes;
+    type Error = Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        let state = match Self::acquire_state(&mut this.state, &this.shared) {
+            Some(state) => state,
+            None => return Poll::Ready(Some(Err(ReplayError::IN_USE.into()))),
+        };
+
+        // Replay any data that was buffered by a prior clone before reading
+        // more from the inner body.
+        if this.chunks_read < state.chunks {
+            if this.shared.is_capped.load(Ordering::Acquire) {
+                return Poll::Ready(Some(Err(ReplayError::CAPPED.into())));
+            }
+            let chunk = state.buf[this.chunks_read].clone();
+            this.chunks_read += 1;
+            return Poll::Ready(Some(Ok(chunk)));
+        }
+
+        if state.is_data_done {
+            return Poll::Ready(None);
+        }
+        let rest = match state.rest.as_mut() {
+            Some(rest) => rest,
+            None => return Poll::Ready(None),
+        };
+
+        let mut data = match rest.as_mut().poll_data(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(None) => {
+                state.is_data_done = true;
+                return Poll::Ready(None);
+            }
+            Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e.into()))),
+            Poll::Ready(Some(Ok(data))) => data,
+        };
+        let chunk = data.copy_to_bytes(data.remaining());
+        state.chunks += 1;
+        this.chunks_read += 1;
+
+        if !this.shared.is_capped.load(Ordering::Acquire) {
+            state.buffered_bytes += chunk.len();
+            if state.buffered_bytes > state.max_bytes {
+                trace!(
+                    buffered = state.buffered_bytes,
+                    max = state.max_bytes,
+                    "Buffer limit exceeded; body can no longer be replayed"
+                );
+                state.buf = Vec::new();
+                this.shared.is_capped.store(true, Ordering::Release);
+            } else {
+                state.buf.push(chunk.clone());
+            }
+        }
+
+        Poll::Ready(Some(Ok(chunk)))
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let this = self.get_mut();
+        let state = match Self::acquire_state(&mut this.state, &this.shared) {
+            Some(state) => state,
+            None => return Poll::Ready(Err(ReplayError::IN_USE.into())),
+        };
+
+        if let Some(rest) = state.rest.as_mut() {
+            let trailers = match rest.as_mut().poll_trailers(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(res) => res.map_err(Into::into)?,
+            };
+            state.trailers = trailers;
+            state.is_data_done = true;
+            state.rest = None;
+        }
+
+        this.trailers_read = true;
+        Poll::Ready(Ok(state.trailers.clone()))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        let state = match self.state.as_ref() {
+            Some(state) => state,
+            // If this body has not been polled, it only knows whether the
+            // original body was empty.
+            None => return self.shared.was_empty,
+        };
+
+        let is_rest_done = match state.rest.as_ref() {
+            Some(rest) => rest.is_end_stream(),
+            // The inner body's trailers have been read, so this body ends once
+            // it has replayed them.
+            None => self.trailers_read || state.trailers.is_none(),
+        };
+        self.chunks_read == state.chunks && is_rest_done
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.shared.size_hint.clone()
+    }
+}
+
+impl<B> Clone for ReplayBody<B> {
+    fn clone(&self) -> Self {
+        Self {
+            state: None,
+            shared: self.shared.clone(),
+            chunks_read: 0,
+            trailers_read: false,
+        }
+    }
+}
+
+impl<B> Drop for ReplayBody<B> {
+    fn drop(&mut self) {
+        // Return the body state so that a clone may replay it.
+        if let Some(state) = self.state.take() {
+            if let Ok(mut body) = self.shared.body.lock() {
+                *body = Some(state);
+            }
+        }
+    }
+}
+
+impl<B> fmt::Debug for ReplayBody<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReplayBody")
+            .field("is_capped", &self.is_capped())
+            .field("chunks_read", &self.chunks_read)
+            .finish()
+    }
+}
+
+// === impl ReplayError ===
+
+impl ReplayError {
+    const IN_USE: Self = Self("body is being read by another request");
+    const CAPPED: Self = Self("body exceeded the replay buffer limit");
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot replay request body: {}", self.0)
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    #[derive(Debug, Default)]
+    struct TestBody {
+        data: VecDeque<&'static str>,
+        trailers: Option<HeaderMap>,
+        exact_size: bool,
+    }
+
+    impl TestBody {
+        fn new(data: &[&'static str]) -> Self {
+            Self {
+                data: data.iter().copied().collect(),
+                ..Self::default()
+            }
+        }
+    }
+
+    impl Body for TestBody {
+        type Data = Bytes;
+        type Error = Error;
+
+        fn poll_data(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+            Poll::Ready(self.get_mut().data.pop_front().map(|d| Ok(Bytes::from(d))))
+        }
+
+        fn poll_trailers(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+            Poll::Ready(Ok(self.get_mut().trailers.take()))
+        }
+
+        fn is_end_stream(&self) -> bool {
+            self.data.is_empty() && self.trailers.is_none()
+        }
+
+        fn size_hint(&self) -> SizeHint {
+            if !self.exact_size {
+                return SizeHint::default();
+            }
+            let len = self.data.iter().map(|d| d.len() as u64).sum();
+            SizeHint::with_exact(len)
+        }
+    }
+
+    async fn read_to_string<B>(body: &mut ReplayBody<B>) -> String
+    where
+        B: Body,
+        B::Error: Into<Error>,
+    {
+        let mut s = String::new();
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk.expect("body must not fail");
+            s.push_str(std::str::from_utf8(chunk.chunk()).unwrap());
+        }
+        s
+    }
+
+    #[tokio::test]
+    async fn replays_data_and_trailers() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("x-trailer", "yes".parse().unwrap());
+        let body = TestBody {
+            trailers: Some(trailers.clone()),
+            ..TestBody::new(&["hello ", "world"])
+        };
+
+        let mut initial = ReplayBody::try_new(body, 64).expect("body must fit");
+        let mut replay = initial.clone();
+
+        assert_eq!(read_to_string(&mut initial).await, "hello world");
+        assert_eq!(initial.trailers().await.unwrap(), Some(trailers.clone()));
+        drop(initial);
+
+        assert_eq!(read_to_string(&mut replay).await, "hello world");
+        assert_eq!(replay.trailers().await.unwrap(), Some(trailers));
+        assert!(!replay.is_capped());
+    }
+
+    #[tokio::test]
+    async fn ends_once_trailers_are_read() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("x-trailer", "yes".parse().unwrap());
+        let body = TestBody {
+            trailers: Some(trailers),
+            ..TestBody::new(&["hello"])
+        };
+
+        let mut initial = ReplayBody::try_new(body, 64).unwrap();
+        let mut replay = initial.clone();
+
+        assert_eq!(read_to_string(&mut initial).await, "hello");
+        assert!(!initial.is_end_stream(), "trailers have not been read");
+        initial.trailers().await.unwrap();
+        assert!(initial.is_end_stream());
+        drop(initial);
+
+        // The replayed body ends once it has replayed the buffered trailers.
+        assert_eq!(read_to_string(&mut replay).await, "hello");
+        assert!(!replay.is_end_stream(), "trailers have not been replayed");
+        replay.trailers().await.unwrap();
+        assert!(replay.is_end_stream());
+    }
+
+    #[tokio::test]
+    async fn replays_partially_read_body() {
+        let mut initial = ReplayBody::try_new(TestBody::new(&["a", "b", "c"]), 64).unwrap();
+        let mut replay = initial.clone();
+
+        let chunk = initial.data().await.unwrap().unwrap();
+        assert_eq!(chunk.chunk(), b"a");
+        drop(initial);
+
+        assert_eq!(read_to_string(&mut replay).await, "abc");
+        assert!(replay.is_end_stream());
+    }
+
+    #[tokio::test]
+    async fn caps_buffered_data() {
+        let mut initial = ReplayBody::try_new(TestBody::new(&["aaaa", "bbbb"]), 4).unwrap();
+        let mut replay = initial.clone();
+
+        assert_eq!(read_to_string(&mut initial).await, "aaaabbbb");
+        assert!(initial.is_capped());
+        drop(initial);
+
+        assert!(replay.is_capped());
+        let err = replay.data().await.unwrap().unwrap_err();
+        assert!(err.is::<ReplayError>());
+    }
+
+    #[tokio::test]
+    async fn fails_while_another_clone_is_reading() {
+        let mut initial = ReplayBody::try_new(TestBody::new(&["a", "b"]), 64).unwrap();
+        let mut replay = initial.clone();
+
+        let _ = initial.data().await.unwrap().unwrap();
+        let err = replay.data().await.unwrap().unwrap_err();
+        assert!(err.is::<ReplayError>());
+    }
+
+    #[test]
+    fn rejects_bodies_larger_than_limit() {
+        let body = TestBody {
+            exact_size: true,
+            ..TestBody::new(&["hello world"])
+        };
+        assert!(ReplayBody::try_new(body, 5).is_err());
+    }
+
+    #[test]
+    fn empty_body_is_end_stream() {
+        let body = ReplayBody::try_new(TestBody::default(), 64).unwrap();
+        assert!(body.is_end_stream());
+        assert!(body.clone().is_end_stream());
+    }
+}
@@ -0,0 +1,255 @@
+//! Just enough DER decoding to read a certificate's expiration and URI
+//! subject alternative names, and enough encoding to convert a SEC1 private
+//! key to PKCS#8.
+
+use chrono::NaiveDate;
+use std::{
+    convert::{TryFrom, TryInto},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const BOOLEAN: u8 = 0x01;
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+const SEQUENCE: u8 = 0x30;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const EXPLICIT_VERSION: u8 = 0xa0;
+const EXPLICIT_EXTENSIONS: u8 = 0xa3;
+const GENERAL_NAME_URI: u8 = 0x86;
+
+/// id-ce-subjectAltName (2.5.29.17).
+const SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+
+/// The `AlgorithmIdentifier` of a P-256 key: id-ecPublicKey (1.2.840.10045.2.1)
+/// with the prime256v1 curve (1.2.840.10045.3.1.7).
+const P256_ALGORITHM: &[u8] = &[
+    SEQUENCE, 0x13, OID, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, OID, 0x08, 0x2a, 0x86,
+    0x48, 0xce, 0x3d, 0x03, 0x01, 0x07,
+];
+
+/// Reads the `notAfter` time of a DER-encoded X.509 certificate.
+pub(crate) fn not_after(crt: &[u8]) -> Option<SystemTime> {
+    let mut tbs = tbs_certificate(crt)?;
+    for tag in &[INTEGER, SEQUENCE, SEQUENCE] {
+        tbs = expect(tbs, *tag)?.1;
+    }
+
+    // Validity ::= SEQUENCE { notBefore Time, notAfter Time }
+    let (validity, _) = expect(tbs, SEQUENCE)?;
+    let (_, _, validity) = next(validity)?;
+    let (tag, time, _) = next(validity)?;
+    let time = std::str::from_utf8(time).ok()?;
+    match tag {
+        UTC_TIME if time.len() == 13 => {
+            // Two-digit years are in the range 1950-2049 (RFC 5280 §4.1.2.5.1).
+            let yy = digits(&time[0..2])?;
+            let year = if yy < 50 { 2000 + yy } else { 1900 + yy };
+            to_system_time(year, &time[2..])
+        }
+        GENERALIZED_TIME if time.len() == 15 => to_system_time(digits(&time[0..4])?, &time[4..]),
+        _ => None,
+    }
+}
+
+/// Reads the URIs in a DER-encoded X.509 certificate's subject alternative
+/// names. A certificate that can't be decoded has no URIs.
+pub(crate) fn uri_sans(crt: &[u8]) -> Vec<&[u8]> {
+    let mut uris = Vec::new();
+    if let Some(mut names) = subject_alt_names(crt) {
+        while let Some((tag, name, rest)) = next(names) {
+            if tag == GENERAL_NAME_URI {
+                uris.push(name);
+            }
+            names = rest;
+        }
+    }
+    uris
+}
+
+/// Wraps a DER-encoded SEC1 `ECPrivateKey` for the P-256 curve in a PKCS#8
+/// `PrivateKeyInfo`. The key itself is validated when it's loaded.
+pub(crate) fn p256_sec1_to_pkcs8(sec1: &[u8]) -> Vec<u8> {
+    // PrivateKeyInfo ::= SEQUENCE {
+    //   version INTEGER (0), privateKeyAlgorithm, privateKey OCTET STRING }
+    let mut info = vec![INTEGER, 0x01, 0x00];
+    info.extend_from_slice(P256_ALGORITHM);
+    info.extend(encode(OCTET_STRING, sec1));
+    encode(SEQUENCE, &info)
+}
+
+/// Returns the contents of a certificate's `GeneralNames` sequence of subject
+/// alternative names, if it has one.
+fn subject_alt_names(crt: &[u8]) -> Option<&[u8]> {
+    // Skips serialNumber, signature, issuer, validity, subject and
+    // subjectPublicKeyInfo; then the optional issuerUniqueID and
+    // subjectUniqueID, which precede the extensions.
+    let mut tbs = tbs_certificate(crt)?;
+    for tag in &[INTEGER, SEQUENCE, SEQUENCE, SEQUENCE, SEQUENCE, SEQUENCE] {
+        tbs = expect(tbs, *tag)?.1;
+    }
+    let mut extensions = loop {
+        let (tag, value, rest) = next(tbs)?;
+        if tag == EXPLICIT_EXTENSIONS {
+            break expect(value, SEQUENCE)?.0;
+        }
+        tbs = rest;
+    };
+
+    // Extension ::= SEQUENCE { extnID, critical BOOLEAN DEFAULT FALSE, extnValue }
+    while let Some((extension, rest)) = expect(extensions, SEQUENCE) {
+        extensions = rest;
+        let (id, mut extension) = expect(extension, OID)?;
+        if id != SUBJECT_ALT_NAME {
+            continue;
+        }
+        if extension.first() == Some(&BOOLEAN) {
+            extension = expect(extension, BOOLEAN)?.1;
+        }
+        let (value, _) = expect(extension, OCTET_STRING)?;
+        return expect(value, SEQUENCE).map(|(names, _)| names);
+    }
+    None
+}
+
+/// Returns the contents of a certificate's `TBSCertificate`, following its
+/// version.
+fn tbs_certificate(crt: &[u8]) -> Option<&[u8]> {
+    // Certificate ::= SEQUENCE { tbsCertificate, signatureAlgorithm, signature }
+    let (crt, _) = expect(crt, SEQUENCE)?;
+    let (mut tbs, _) = expect(crt, SEQUENCE)?;
+
+    // TBSCertificate ::= SEQUENCE {
+    //   version [0] EXPLICIT Version DEFAULT v1,
+    //   serialNumber, signature, issuer, validity, subject,
+    //   subjectPublicKeyInfo, ..., extensions [3] EXPLICIT Extensions OPTIONAL }
+    if tbs.first() == Some(&EXPLICIT_VERSION) {
+        tbs = expect(tbs, EXPLICIT_VERSION)?.1;
+    }
+    Some(tbs)
+}
+
+/// Splits the next element, which must have the given tag, from the input,
+/// returning its contents and the remaining input.
+fn expect(input: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    let (t, value, rest) = next(input)?;
+    if t != tag {
+        return None;
+    }
+    Some((value, rest))
+}
+
+/// Splits the next element from the input, returning its tag, its contents,
+/// and the remaining input.
+fn next(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, input) = input.split_first()?;
+    let (&len, mut input) = input.split_first()?;
+    let len = if len < 0x80 {
+        len as usize
+    } else {
+        let n = (len & 0x7f) as usize;
+        if n == 0 || n > 4 || input.len() < n {
+            return None;
+        }
+        let (bytes, rest) = input.split_at(n);
+        input = rest;
+        bytes.iter().fold(0, |len, b| (len << 8) | *b as usize)
+    };
+    if input.len() < len {
+        return None;
+    }
+    let (value, rest) = input.split_at(len);
+    Some((tag, value, rest))
+}
+
+/// Encodes an element with the given tag and contents.
+fn encode(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    if value.len() < 0x80 {
+        out.push(value.len() as u8);
+    } else {
+        let len = (value.len() as u32).to_be_bytes();
+        let zeros = len.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (len.len() - zeros) as u8);
+        out.extend_from_slice(&len[zeros..]);
+    }
+    out.extend_from_slice(value);
+    out
+}
+
+/// Converts a year and a `MMDDHHMMSSZ` suffix to a time.
+fn to_system_time(year: u64, time: &str) -> Option<SystemTime> {
+    if time.len() != 11 || !time.ends_with('Z') {
+        return None;
+    }
+    let date = NaiveDate::from_ymd_opt(
+        year.try_into().ok()?,
+        digits(&time[0..2])?.try_into().ok()?,
+        digits(&time[2..4])?.try_into().ok()?,
+    )?;
+    let time_of_day = date.and_hms_opt(
+        digits(&time[4..6])?.try_into().ok()?,
+        digits(&time[6..8])?.try_into().ok()?,
+        0,
+    )?;
+    // Leap seconds are permitted.
+    let second = digits(&time[8..10])?;
+    if second > 60 {
+        return None;
+    }
+    let secs = u64::try_from(time_of_day.timestamp()).ok()? + second;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+fn digits(s: &str) -> Option<u64> {
+    if !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_not_after() {
+        // notAfter=Mar 17 08:09:00 2030 GMT
+        let crt = include_bytes!("testdata/foo-ns1-ca1/crt.der");
+        assert_eq!(
+            not_after(crt),
+            Some(UNIX_EPOCH + Duration::from_secs(1_899_965_340))
+        );
+
+        assert_eq!(
+            to_system_time(2000, "0301000000Z"),
+            Some(UNIX_EPOCH + Duration::from_secs(951_868_800))
+        );
+        assert_eq!(to_system_time(1970, "0101000000Z"), Some(UNIX_EPOCH));
+        assert_eq!(to_system_time(2030, "1301000000Z"), None);
+        assert_eq!(to_system_time(1969, "1231235959Z"), None);
+        assert_eq!(not_after(&crt[..crt.len() / 2]), None);
+    }
+
+    #[test]
+    fn reads_uri_sans() {
+        let crt = include_bytes!("testdata/spiffe-foo-ns1-ca1/crt.der");
+        assert_eq!(
+            uri_sans(crt),
+            vec![&b"spiffe://cluster.local/ns/ns1/sa/foo"[..]]
+        );
+
+        let crt = include_bytes!("testdata/foo-ns1-ca1/crt.der");
+        assert!(uri_sans(crt).is_empty());
+    }
+
+    #[test]
+    fn encodes_lengths() {
+        for len in &[0, 0x7f, 0x80, 0x100, 0x1_0000] {
+            let value = vec![0; *len];
+            let encoded = encode(OCTET_STRING, &value);
+            assert_eq!(next(&encoded), Some((OCTET_STRING, &value[..], &[][..])));
+        }
+    }
+}
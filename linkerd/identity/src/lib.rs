@@ -6,9 +6,12 @@ use ring::signature::EcdsaKeyPair;
 use std::{convert::TryFrom, error::Error, fmt, fs, io, str::FromStr, sync::Arc, time::SystemTime};
 use tracing::{debug, warn};
 
+mod der;
+mod spiffe;
 #[cfg(any(test, feature = "test-util"))]
 pub mod test_util;
 
+pub use self::spiffe::SpiffeId;
 pub use linkerd_dns_name::InvalidName;
 
 /// A DER-encoded X.509 certificate signing request.
@@ -19,14 +22,29 @@ pub struct Csr(Arc<Vec<u8>>);
 #[derive(Clone, Eq, PartialEq, Hash)]
 pub struct Name(Arc<linkerd_dns_name::Name>);
 
+/// A peer's identity, which is either a DNS-like name or a SPIFFE ID.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Id {
+    Dns(Name),
+    Spiffe(SpiffeId),
+}
+
 #[derive(Clone, Debug)]
 pub struct Key(Arc<EcdsaKeyPair>);
 
 struct SigningKey(Arc<EcdsaKeyPair>);
 struct Signer(Arc<EcdsaKeyPair>);
 
+/// Root certificates that are trusted to issue peers' certificates.
 #[derive(Clone)]
-pub struct TrustAnchors(Arc<rustls::ClientConfig>);
+pub struct TrustAnchors {
+    config: Arc<rustls::ClientConfig>,
+    fingerprints: Arc<[Fingerprint]>,
+}
+
+/// The SHA-256 digest of a DER-encoded certificate.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Fingerprint([u8; 32]);
 
 #[derive(Clone, Debug)]
 pub struct TokenSource(Arc<String>);
@@ -90,6 +108,24 @@ impl Key {
         let k = EcdsaKeyPair::from_pkcs8(SIGNATURE_ALG_RING_SIGNING, b)?;
         Ok(Key(Arc::new(k)))
     }
+
+    /// Reads the first private key from a PEM document. Keys may be
+    /// PKCS#8-encoded (`PRIVATE KEY`) or, as written by `openssl ecparam
+    /// -genkey`, SEC1-encoded (`EC PRIVATE KEY`).
+    pub fn from_pem(s: &str) -> Option<Self> {
+        let keys = rustls::internal::pemfile::pkcs8_private_keys(&mut io::Cursor::new(s)).ok()?;
+        let pkcs8 = match keys.into_iter().next() {
+            Some(key) => key.0,
+            None => der::p256_sec1_to_pkcs8(&pem_section(s, "EC PRIVATE KEY")?),
+        };
+        match Self::from_pkcs8(&pkcs8) {
+            Ok(key) => Some(key),
+            Err(e) => {
+                warn!("invalid key: {}", e);
+                None
+            }
+        }
+    }
 }
 
 impl rustls::sign::SigningKey for SigningKey {
@@ -187,6 +223,62 @@ impl fmt::Display for Name {
     }
 }
 
+// === impl Id ===
+
+impl Id {
+    /// Returns the identity's DNS-like name, unless it's a SPIFFE ID.
+    pub fn as_dns(&self) -> Option<&Name> {
+        match self {
+            Self::Dns(name) => Some(name),
+            Self::Spiffe(_) => None,
+        }
+    }
+}
+
+impl From<Name> for Id {
+    fn from(name: Name) -> Self {
+        Self::Dns(name)
+    }
+}
+
+impl From<SpiffeId> for Id {
+    fn from(id: SpiffeId) -> Self {
+        Self::Spiffe(id)
+    }
+}
+
+impl FromStr for Id {
+    type Err = InvalidName;
+
+    /// Parses a SPIFFE ID if the string has a `spiffe://` prefix and a DNS-like
+    /// name otherwise.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("spiffe://") {
+            SpiffeId::from_str(s).map(Self::Spiffe)
+        } else {
+            Name::from_str(s).map(Self::Dns)
+        }
+    }
+}
+
+impl AsRef<str> for Id {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Dns(name) => name.as_ref(),
+            Self::Spiffe(id) => id.as_ref(),
+        }
+    }
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dns(name) => fmt::Display::fmt(name, f),
+            Self::Spiffe(id) => fmt::Display::fmt(id, f),
+        }
+    }
+}
+
 // === impl TokenSource ===
 
 impl TokenSource {
@@ -211,21 +303,60 @@ impl TokenSource {
 impl TrustAnchors {
     #[cfg(any(test, feature = "test-util"))]
     fn empty() -> Self {
-        TrustAnchors(Arc::new(rustls::ClientConfig::new()))
+        TrustAnchors {
+            config: Arc::new(rustls::ClientConfig::new()),
+            fingerprints: Arc::new([]),
+        }
     }
 
     pub fn from_pem(s: &str) -> Option<Self> {
-        use std::io::Cursor;
+        let crts = rustls::internal::pemfile::certs(&mut io::Cursor::new(s)).ok()?;
 
         let mut roots = rustls::RootCertStore::empty();
-        let (added, skipped) = roots.add_pem_file(&mut Cursor::new(s)).ok()?;
+        let mut fingerprints = Vec::with_capacity(crts.len());
+        for crt in &crts {
+            if roots.add(crt).is_ok() {
+                fingerprints.push(Fingerprint::of(&crt.0));
+            }
+        }
+        let skipped = crts.len() - fingerprints.len();
         if skipped != 0 {
             warn!("skipped {} trust anchors in trust anchors file", skipped);
         }
-        if added == 0 {
+        if fingerprints.is_empty() {
             return None;
         }
 
+        Some(Self::new(roots, fingerprints))
+    }
+
+    /// Combines trust bundles so that the anchors in each of them are trusted,
+    /// e.g. while certificates are reissued by a new root CA.
+    ///
+    /// Returns `None` if no bundles are provided.
+    pub fn merge<I: IntoIterator<Item = Self>>(bundles: I) -> Option<Self> {
+        let mut roots = rustls::RootCertStore::empty();
+        let mut fingerprints = Vec::new();
+        for bundle in bundles {
+            let anchors = bundle.config.root_store.roots.iter();
+            for (anchor, fingerprint) in anchors.zip(bundle.fingerprints.iter()) {
+                if !fingerprints.contains(fingerprint) {
+                    roots.roots.push(anchor.clone());
+                    fingerprints.push(*fingerprint);
+                }
+            }
+        }
+        if fingerprints.is_empty() {
+            return None;
+        }
+
+        Some(Self::new(roots, fingerprints))
+    }
+
+    /// Builds a client configuration that trusts the given roots, each of
+    /// which must have the fingerprint at the same index.
+    fn new(roots: rustls::RootCertStore, fingerprints: Vec<Fingerprint>) -> Self {
+        debug_assert_eq!(roots.roots.len(), fingerprints.len());
         let mut c = rustls::ClientConfig::new();
 
         // XXX: Rustls's built-in verifiers don't let us tweak things as fully
@@ -239,11 +370,19 @@ impl TrustAnchors {
         // more tested.
         c.enable_tickets = false;
 
-        Some(TrustAnchors(Arc::new(c)))
+        TrustAnchors {
+            config: Arc::new(c),
+            fingerprints: fingerprints.into(),
+        }
+    }
+
+    /// Returns the fingerprints of the trusted root certificates.
+    pub fn fingerprints(&self) -> &[Fingerprint] {
+        &self.fingerprints
     }
 
     pub fn certify(&self, key: Key, crt: Crt) -> Result<CrtKey, InvalidCrt> {
-        let mut client = self.0.as_ref().clone();
+        let mut client = self.config.as_ref().clone();
 
         // Ensure the certificate is valid for the services we terminate for
         // TLS. This assumes that server cert validation does the same or
@@ -281,7 +420,7 @@ impl TrustAnchors {
         //
         // TODO: Change Rustls's API to Avoid needing to clone `root_cert_store`.
         let mut server = rustls::ServerConfig::new(
-            rustls::AllowAnyAnonymousOrAuthenticatedClient::new(self.0.root_store.clone()),
+            rustls::AllowAnyAnonymousOrAuthenticatedClient::new(self.config.root_store.clone()),
         );
         server.versions = TLS_VERSIONS.to_vec();
         server.cert_resolver = resolver;
@@ -295,13 +434,51 @@ impl TrustAnchors {
     }
 
     pub fn client_config(&self) -> Arc<rustls::ClientConfig> {
-        self.0.clone()
+        self.config.clone()
     }
 }
 
+/// Trust anchors are equal if they trust the same roots, in the same order.
+impl PartialEq for TrustAnchors {
+    fn eq(&self, other: &Self) -> bool {
+        self.fingerprints == other.fingerprints
+    }
+}
+
+impl Eq for TrustAnchors {}
+
 impl fmt::Debug for TrustAnchors {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("TrustAnchors").finish()
+        f.debug_struct("TrustAnchors")
+            .field("fingerprints", &self.fingerprints)
+            .finish()
+    }
+}
+
+// === impl Fingerprint ===
+
+impl Fingerprint {
+    fn of(der: &[u8]) -> Self {
+        let digest = ring::digest::digest(&ring::digest::SHA256, der);
+        let mut fingerprint = [0; 32];
+        fingerprint.copy_from_slice(digest.as_ref());
+        Fingerprint(fingerprint)
+    }
+}
+
+impl fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Formats the digest as lowercase hexadecimal.
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in &self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
     }
 }
 
@@ -321,6 +498,20 @@ impl Crt {
         Self { id, chain, expiry }
     }
 
+    /// Reads a certificate chain from a PEM document, which must list the
+    /// leaf certificate first, followed by any intermediate certificates.
+    ///
+    /// The chain expires when its leaf certificate does.
+    pub fn from_pem(id: LocalId, s: &str) -> Option<Self> {
+        let chain = rustls::internal::pemfile::certs(&mut io::Cursor::new(s)).ok()?;
+        let expiry = der::not_after(&chain.first()?.0)?;
+        Some(Self { id, chain, expiry })
+    }
+
+    pub fn expiry(&self) -> SystemTime {
+        self.expiry
+    }
+
     pub fn name(&self) -> &Name {
         self.id.as_ref()
     }
@@ -464,15 +655,51 @@ impl Error for InvalidCrt {
     }
 }
 
+/// Decodes the first section of a PEM document with the given label.
+fn pem_section(s: &str, label: &str) -> Option<Vec<u8>> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+    let start = s.find(&begin)? + begin.len();
+    let len = s[start..].find(&end)?;
+    let b64 = s[start..start + len].split_whitespace().collect::<String>();
+    base64::decode(b64).ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::test_util::*;
+    use super::{Crt, Key, LocalId, Name, TrustAnchors};
+    use std::str::FromStr;
 
     #[test]
     fn can_construct_client_and_server_config_from_valid_settings() {
         FOO_NS1.validate().expect("foo.ns1 must be valid");
     }
 
+    #[test]
+    fn can_construct_crt_key_from_pem() {
+        let id = LocalId(Name::from_str(FOO_NS1.name).unwrap());
+        let crt = Crt::from_pem(id.clone(), include_str!("testdata/foo-ns1-ca1/crt.pem"))
+            .expect("crt must be valid");
+        let key =
+            Key::from_pem(include_str!("testdata/foo-ns1-ca1/key.pem")).expect("key must be valid");
+        FOO_NS1
+            .trust_anchors()
+            .certify(key, crt)
+            .expect("foo.ns1 must be valid");
+
+        let key = Key::from_pem(include_str!("testdata/foo-ns1-ca1/key-ec.pem"))
+            .expect("SEC1 key must be valid");
+        let crt = Crt::from_pem(id.clone(), include_str!("testdata/foo-ns1-ca1/crt.pem")).unwrap();
+        FOO_NS1
+            .trust_anchors()
+            .certify(key, crt)
+            .expect("foo.ns1 must be valid with a SEC1 key");
+
+        assert!(Key::from_pem(include_str!("testdata/foo-ns1-ca1/crt.pem")).is_none());
+        assert!(Crt::from_pem(id, include_str!("testdata/foo-ns1-ca1/key.pem")).is_none());
+    }
+
     #[test]
     fn recognize_ca_did_not_issue_cert() {
         let s = Identity {
@@ -492,6 +719,35 @@ mod tests {
         assert!(s.validate().is_err(), "identity should not be valid");
     }
 
+    #[test]
+    fn merges_trust_anchors() {
+        let ca1 = TrustAnchors::from_pem(include_str!("testdata/ca1.pem")).unwrap();
+        let ca2 = TrustAnchors::from_pem(include_str!("testdata/ca2.pem")).unwrap();
+        assert_eq!(
+            ca1.fingerprints()[0].to_string(),
+            "b52fe3ea21621a115bae80c1a08bf1d6aeec741ecf08b31a11836cb8c2930949"
+        );
+
+        let both = TrustAnchors::merge(vec![ca1.clone(), ca2.clone(), ca1.clone()]).unwrap();
+        assert_eq!(
+            both.fingerprints(),
+            &[ca1.fingerprints()[0], ca2.fingerprints()[0]]
+        );
+        let foo_ns1_ca2 = Identity {
+            trust_anchors: include_bytes!("testdata/ca2.pem"),
+            crt: include_bytes!("testdata/foo-ns1-ca2/crt.der"),
+            key: include_bytes!("testdata/foo-ns1-ca2/key.p8"),
+            ..FOO_NS1
+        };
+        for id in &[&FOO_NS1, &foo_ns1_ca2] {
+            both.certify(id.key(), id.crt())
+                .expect("both CAs must be trusted");
+        }
+
+        assert_eq!(TrustAnchors::merge(vec![ca1.clone()]), Some(ca1));
+        assert_eq!(TrustAnchors::merge(vec![]), None);
+    }
+
     #[test]
     #[ignore] // XXX this doesn't fail because we don't actually check the key against the cert...
     fn recognize_private_key_is_not_valid_for_cert() {
@@ -0,0 +1,219 @@
+use crate::{der, InvalidName};
+use std::{fmt, str::FromStr, sync::Arc, time::SystemTime};
+
+/// A SPIFFE ID, e.g. `spiffe://cluster.local/ns/default/sa/web`, which
+/// identifies a peer by a URI subject alternative name rather than by a DNS
+/// name.
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct SpiffeId(Arc<str>);
+
+/// Authenticates a server by its SPIFFE ID rather than by a DNS name.
+struct SpiffeVerifier(SpiffeId);
+
+/// The signature algorithms accepted in server certificate chains. These match
+/// the algorithms that rustls's built-in verifier accepts.
+static SIGNATURE_ALGS: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::ED25519,
+    &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+// === impl SpiffeId ===
+
+impl SpiffeId {
+    const SCHEME: &'static str = "spiffe://";
+    const MAX_LEN: usize = 2048;
+
+    /// Reads the first SPIFFE ID in a DER-encoded certificate's URI subject
+    /// alternative names.
+    pub fn from_crt(der: &[u8]) -> Option<Self> {
+        der::uri_sans(der)
+            .into_iter()
+            .find_map(|uri| std::str::from_utf8(uri).ok()?.parse().ok())
+    }
+
+    pub fn trust_domain(&self) -> &str {
+        let id = &self.0[Self::SCHEME.len()..];
+        match id.find('/') {
+            Some(path) => &id[..path],
+            None => id,
+        }
+    }
+
+    /// Returns a copy of `config` that authenticates servers by this ID.
+    ///
+    /// The connector must still be given a DNS name, but SNI is disabled so the
+    /// name isn't sent, and sessions aren't stored so that they can't be
+    /// resumed with another server by the same name.
+    pub fn client_config(&self, config: &rustls::ClientConfig) -> rustls::ClientConfig {
+        let mut config = config.clone();
+        config.enable_sni = false;
+        config.session_persistence = Arc::new(rustls::NoClientSessionStorage {});
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(SpiffeVerifier(self.clone())));
+        config
+    }
+}
+
+impl FromStr for SpiffeId {
+    type Err = InvalidName;
+
+    /// Parses a SPIFFE ID, which must have a lowercase trust domain and may
+    /// have a path of non-empty segments, without a query or fragment.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() > Self::MAX_LEN || !s.starts_with(Self::SCHEME) {
+            return Err(InvalidName);
+        }
+
+        let mut segments = s[Self::SCHEME.len()..].split('/');
+        let trust_domain = segments.next().unwrap_or_default();
+        let is_trust_domain_char =
+            |b: u8| b.is_ascii_lowercase() || b.is_ascii_digit() || b"-._".contains(&b);
+        if trust_domain.is_empty() || !trust_domain.bytes().all(is_trust_domain_char) {
+            return Err(InvalidName);
+        }
+
+        let is_path_char = |b: u8| b.is_ascii_alphanumeric() || b"-._".contains(&b);
+        for segment in segments {
+            if segment.is_empty()
+                || segment == "."
+                || segment == ".."
+                || !segment.bytes().all(is_path_char)
+            {
+                return Err(InvalidName);
+            }
+        }
+
+        Ok(Self(s.into()))
+    }
+}
+
+impl AsRef<str> for SpiffeId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SpiffeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for SpiffeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+// === impl SpiffeVerifier ===
+
+impl rustls::ServerCertVerifier for SpiffeVerifier {
+    fn verify_server_cert(
+        &self,
+        roots: &rustls::RootCertStore,
+        presented_certs: &[rustls::Certificate],
+        _: webpki::DNSNameRef<'_>,
+        _: &[u8],
+    ) -> Result<rustls::ServerCertVerified, rustls::TLSError> {
+        let (end_entity, intermediates) = presented_certs
+            .split_first()
+            .ok_or(rustls::TLSError::NoCertificatesPresented)?;
+        let cert = webpki::EndEntityCert::from(end_entity.as_ref())
+            .map_err(rustls::TLSError::WebPKIError)?;
+        let intermediates = intermediates.iter().map(|c| c.as_ref()).collect::<Vec<_>>();
+        let anchors = roots
+            .roots
+            .iter()
+            .map(|r| r.to_trust_anchor())
+            .collect::<Vec<_>>();
+        let now = webpki::Time::try_from(SystemTime::now())
+            .map_err(|_| rustls::TLSError::FailedToGetCurrentTime)?;
+        cert.verify_is_valid_tls_server_cert(
+            SIGNATURE_ALGS,
+            &webpki::TLSServerTrustAnchors(&anchors),
+            &intermediates,
+            now,
+        )
+        .map_err(rustls::TLSError::WebPKIError)?;
+
+        if SpiffeId::from_crt(end_entity.as_ref()).as_ref() != Some(&self.0) {
+            return Err(rustls::TLSError::WebPKIError(
+                webpki::Error::CertNotValidForName,
+            ));
+        }
+        Ok(rustls::ServerCertVerified::assertion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::ServerCertVerifier;
+
+    #[test]
+    fn parses_spiffe_ids() {
+        let id = SpiffeId::from_str("spiffe://cluster.local/ns/ns1/sa/foo").unwrap();
+        assert_eq!(id.trust_domain(), "cluster.local");
+        assert_eq!(
+            SpiffeId::from_str("spiffe://cluster.local")
+                .unwrap()
+                .trust_domain(),
+            "cluster.local"
+        );
+
+        for invalid in &[
+            "spiffe://",
+            "spiffe:///ns/ns1",
+            "spiffe://Cluster.local/ns/ns1",
+            "spiffe://cluster.local:8080/ns/ns1",
+            "spiffe://cluster.local/",
+            "spiffe://cluster.local/ns//ns1",
+            "spiffe://cluster.local/ns/../ns1",
+            "spiffe://cluster.local/ns/ns1?sa=foo",
+            "https://cluster.local/ns/ns1",
+            "foo.ns1.serviceaccount.identity.linkerd.cluster.local",
+        ] {
+            assert!(
+                SpiffeId::from_str(invalid).is_err(),
+                "{} must be invalid",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn verifies_servers_by_spiffe_id() {
+        let trust_anchors = crate::TrustAnchors::from_pem(include_str!("testdata/ca1.pem"))
+            .expect("trust anchors must be valid");
+        let client_config = trust_anchors.client_config();
+        let roots = &client_config.root_store;
+        let chain = [rustls::Certificate(
+            include_bytes!("testdata/spiffe-foo-ns1-ca1/crt.der").to_vec(),
+        )];
+        let verify = |id: &str| {
+            let dns_name = webpki::DNSNameRef::try_from_ascii_str("example.com").unwrap();
+            SpiffeVerifier(id.parse().unwrap()).verify_server_cert(roots, &chain, dns_name, &[])
+        };
+
+        assert_eq!(
+            SpiffeId::from_crt(&chain[0].0),
+            Some("spiffe://cluster.local/ns/ns1/sa/foo".parse().unwrap())
+        );
+        assert!(verify("spiffe://cluster.local/ns/ns1/sa/foo").is_ok());
+        assert!(verify("spiffe://cluster.local/ns/ns1/sa/bar").is_err());
+
+        let crt = include_bytes!("testdata/foo-ns1-ca1/crt.der");
+        assert_eq!(SpiffeId::from_crt(crt), None);
+    }
+}
@@ -34,12 +34,18 @@ ee() {
   openssl pkcs8 -topk8 -nocrypt -inform pem -outform der \
     -in "${ee}-key.pem" \
     -out "${ee}/key.p8"
+  openssl pkcs8 -topk8 -nocrypt -inform pem -outform pem \
+    -in "${ee}-key.pem" \
+    -out "${ee}/key.pem"
+  openssl ec -inform pem -outform pem \
+    -in "${ee}-key.pem" \
+    -out "${ee}/key-ec.pem"
   rm "${ee}-key.pem"
 
   openssl x509 -inform pem -outform der \
     -in "${ee}.pem" \
     -out "${ee}/crt.der"
-  rm "${ee}.pem"
+  mv "${ee}.pem" "${ee}/crt.pem"
 
   ## TODO DER-encode?
   #openssl x509 -inform pem -outform der \
@@ -48,6 +54,38 @@ ee() {
   mv "${ee}.csr" "${ee}/csr.pem"
 }
 
+# An end entity identified only by a SPIFFE ID (a URI SAN).
+spiffe() {
+  ca_name=$1
+  ee_name=$2
+  ee_ns=$3
+  trust_domain=$4
+
+  ee="spiffe-${ee_name}-${ee_ns}-${ca_name}"
+  mkdir -p "${ee}"
+
+  openssl ecparam -name prime256v1 -genkey -noout \
+    | openssl pkcs8 -topk8 -nocrypt -out "${ee}/key.pem"
+  openssl pkcs8 -topk8 -nocrypt -outform der \
+    -in "${ee}/key.pem" \
+    -out "${ee}/key.p8"
+
+  openssl req -new -key "${ee}/key.pem" -subj "/O=SPIFFE" \
+    | openssl x509 -req -CA "${ca_name}.pem" -CAkey "${ca_name}-key.pem" -CAcreateserial \
+        -days 3650 -out "${ee}/crt.pem" \
+        -extfile <(cat <<EXT
+keyUsage = critical, digitalSignature, keyEncipherment
+extendedKeyUsage = serverAuth, clientAuth
+basicConstraints = critical, CA:FALSE
+subjectAltName = critical, URI:spiffe://${trust_domain}/ns/${ee_ns}/sa/${ee_name}
+EXT
+)
+  rm -f "${ca_name}.srl"
+  openssl x509 -inform pem -outform der \
+    -in "${ee}/crt.pem" \
+    -out "${ee}/crt.der"
+}
+
 ca "Cluster-local CA 1" ca1
 ca "Cluster-local CA 1" ca2 # Same name, different key pair.
 
@@ -57,3 +95,5 @@ ee ca1 controller linkerd linkerd
 ee ca1 foo ns1 linkerd
 ee ca2 foo ns1 linkerd # Same, but different CA
 ee ca1 bar ns1 linkerd # Different service.
+
+spiffe ca1 foo ns1 cluster.local
@@ -27,6 +27,10 @@ impl PeerAddr for BoxedIo {
     fn peer_addr(&self) -> Result<std::net::SocketAddr> {
         self.0.peer_addr()
     }
+
+    fn local_addr(&self) -> Result<std::net::SocketAddr> {
+        self.0.local_addr()
+    }
 }
 
 impl AsyncRead for BoxedIo {
@@ -78,6 +82,10 @@ mod tests {
         fn peer_addr(&self) -> Result<std::net::SocketAddr> {
             Ok(([0, 0, 0, 0], 0).into())
         }
+
+        fn local_addr(&self) -> Result<std::net::SocketAddr> {
+            Ok(([0, 0, 0, 0], 0).into())
+        }
     }
 
     impl AsyncRead for WriteBufDetector {
@@ -31,6 +31,14 @@ impl<L: io::PeerAddr, R: io::PeerAddr> io::PeerAddr for EitherIo<L, R> {
             Self::Right(r) => r.peer_addr(),
         }
     }
+
+    #[inline]
+    fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        match self {
+            Self::Left(l) => l.local_addr(),
+            Self::Right(r) => r.local_addr(),
+        }
+    }
 }
 
 impl<L: io::AsyncRead, R: io::AsyncRead> io::AsyncRead for EitherIo<L, R> {
@@ -44,24 +44,38 @@ impl Peek for tokio::net::TcpStream {
 
 pub trait PeerAddr {
     fn peer_addr(&self) -> Result<SocketAddr>;
+
+    fn local_addr(&self) -> Result<SocketAddr>;
 }
 
 impl PeerAddr for tokio::net::TcpStream {
     fn peer_addr(&self) -> Result<SocketAddr> {
         tokio::net::TcpStream::peer_addr(self)
     }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        tokio::net::TcpStream::local_addr(self)
+    }
 }
 
 impl<T: PeerAddr> PeerAddr for tokio_rustls::client::TlsStream<T> {
     fn peer_addr(&self) -> Result<SocketAddr> {
         self.get_ref().0.peer_addr()
     }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.get_ref().0.local_addr()
+    }
 }
 
 impl<T: PeerAddr> PeerAddr for tokio_rustls::server::TlsStream<T> {
     fn peer_addr(&self) -> Result<SocketAddr> {
         self.get_ref().0.peer_addr()
     }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.get_ref().0.local_addr()
+    }
 }
 
 #[cfg(feature = "tokio-test")]
@@ -69,6 +83,10 @@ impl PeerAddr for tokio_test::io::Mock {
     fn peer_addr(&self) -> Result<SocketAddr> {
         Ok(([0, 0, 0, 0], 0).into())
     }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(([0, 0, 0, 0], 0).into())
+    }
 }
 
 #[cfg(feature = "tokio-test")]
@@ -76,4 +94,8 @@ impl PeerAddr for tokio::io::DuplexStream {
     fn peer_addr(&self) -> Result<SocketAddr> {
         Ok(([0, 0, 0, 0], 0).into())
     }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(([0, 0, 0, 0], 0).into())
+    }
 }
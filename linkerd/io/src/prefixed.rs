@@ -48,6 +48,11 @@ impl<I: io::PeerAddr> io::PeerAddr for PrefixedIo<I> {
     fn peer_addr(&self) -> io::Result<std::net::SocketAddr> {
         self.io.peer_addr()
     }
+
+    #[inline]
+    fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.io.local_addr()
+    }
 }
 
 impl<I: io::AsyncRead> io::AsyncRead for PrefixedIo<I> {
@@ -75,6 +75,11 @@ impl<I: io::PeerAddr> io::PeerAddr for ScopedIo<I> {
     fn peer_addr(&self) -> io::Result<std::net::SocketAddr> {
         self.io.peer_addr().map_err(self.scope.err())
     }
+
+    #[inline]
+    fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.io.local_addr().map_err(self.scope.err())
+    }
 }
 
 impl<I: io::AsyncRead> io::AsyncRead for ScopedIo<I> {
@@ -81,4 +81,8 @@ impl<T: PeerAddr, S> PeerAddr for SensorIo<T, S> {
     fn peer_addr(&self) -> Result<std::net::SocketAddr> {
         self.io.peer_addr()
     }
+
+    fn local_addr(&self) -> Result<std::net::SocketAddr> {
+        self.io.local_addr()
+    }
 }
@@ -0,0 +1,319 @@
+#![deny(warnings, rust_2018_idioms)]
+
+pub mod metrics;
+
+use futures::{
+    stream::{Stream, StreamExt},
+    FutureExt,
+};
+use http_body::Body as HttpBody;
+use linkerd_error::Error;
+use metrics::Registry;
+pub use opentelemetry_proto as proto;
+use opentelemetry_proto::collector::trace::v1::{
+    trace_service_client::TraceServiceClient, ExportTraceServiceRequest,
+};
+use opentelemetry_proto::resource::v1::Resource;
+use opentelemetry_proto::trace::v1::{InstrumentationLibrarySpans, ResourceSpans, Span};
+use tokio::time;
+use tonic::{self as grpc, body::BoxBody, client::GrpcService};
+use tracing::{debug, trace};
+
+pub async fn export_spans<T, S>(client: T, resource: Resource, spans: S, metrics: Registry)
+where
+    T: GrpcService<BoxBody> + Clone,
+    T::Error: Into<Error>,
+    <T::ResponseBody as HttpBody>::Error: Into<Error> + Send + Sync,
+    T::ResponseBody: 'static,
+    S: Stream<Item = Span> + Unpin,
+{
+    debug!("Span exporter running");
+    SpanExporter::new(client, resource, spans, metrics)
+        .run()
+        .await
+}
+
+/// SpanExporter sends batches of spans to the given OTLP TraceService gRPC
+/// service.
+struct SpanExporter<T, S> {
+    client: T,
+    resource: Resource,
+    spans: S,
+    metrics: Registry,
+}
+
+#[derive(Debug)]
+struct SpanRxClosed;
+
+// === impl SpanExporter ===
+
+impl<T, S> SpanExporter<T, S>
+where
+    T: GrpcService<BoxBody>,
+    T::Error: Into<Error>,
+    <T::ResponseBody as HttpBody>::Error: Into<Error> + Send + Sync,
+    T::ResponseBody: 'static,
+    S: Stream<Item = Span> + Unpin,
+{
+    const MAX_BATCH_SIZE: usize = 1000;
+    const MAX_BATCH_IDLE: time::Duration = time::Duration::from_secs(10);
+    const RETRY_BACKOFF: time::Duration = time::Duration::from_secs(1);
+    /// Bounds how many times a batch is sent, so that an unavailable collector
+    /// doesn't stall the exporter while new spans are dropped.
+    const MAX_EXPORT_ATTEMPTS: usize = 3;
+
+    fn new(client: T, resource: Resource, spans: S, metrics: Registry) -> Self {
+        Self {
+            client,
+            resource,
+            spans,
+            metrics,
+        }
+    }
+
+    async fn run(self) {
+        let Self {
+            client,
+            resource,
+            mut spans,
+            mut metrics,
+        } = self;
+
+        // Holds the batch of pending spans. Cleared as the spans are flushed.
+        // Contains no more than MAX_BATCH_SIZE spans.
+        let mut accum = Vec::new();
+
+        let mut svc = TraceServiceClient::new(client);
+        loop {
+            // Collect spans into a batch.
+            let collect = Self::collect_batch(&mut spans, &mut accum).await;
+
+            // If we collected spans, flush them.
+            if !accum.is_empty() {
+                Self::export(&mut svc, &resource, &mut accum, &mut metrics).await;
+            }
+
+            // If the span source was closed, end the task.
+            if let Err(SpanRxClosed) = collect {
+                debug!("Span channel lost");
+                return;
+            }
+        }
+    }
+
+    /// Sends a batch of spans to the collector, retrying while the collector
+    /// is unavailable.
+    ///
+    /// The batch is cleared once it has been exported, rejected by the
+    /// collector, or has failed `MAX_EXPORT_ATTEMPTS` times.
+    async fn export(
+        svc: &mut TraceServiceClient<T>,
+        resource: &Resource,
+        accum: &mut Vec<Span>,
+        metrics: &mut Registry,
+    ) {
+        for attempt in 1..=Self::MAX_EXPORT_ATTEMPTS {
+            let msg = ExportTraceServiceRequest {
+                resource_spans: vec![ResourceSpans {
+                    resource: Some(resource.clone()),
+                    instrumentation_library_spans: vec![InstrumentationLibrarySpans {
+                        instrumentation_library: None,
+                        spans: accum.clone(),
+                    }],
+                }],
+            };
+            trace!(spans = accum.len(), "Sending batch");
+            match svc.export(grpc::Request::new(msg)).await {
+                Ok(_rsp) => {
+                    metrics.send(accum.len() as u64);
+                    accum.clear();
+                    return;
+                }
+                Err(status)
+                    if is_retryable(status.code()) && attempt < Self::MAX_EXPORT_ATTEMPTS =>
+                {
+                    metrics.fail();
+                    debug!(%status, "Export failed; retrying");
+                    time::sleep(Self::RETRY_BACKOFF).await;
+                }
+                Err(status) => {
+                    metrics.fail();
+                    debug!(%status, spans = accum.len(), "Export failed; dropping spans");
+                    accum.clear();
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Collects spans from the proxy into `accum`.
+    ///
+    /// Returns an error when the span sream has completed. An error may be
+    /// returned after accumulating spans.
+    async fn collect_batch(spans: &mut S, accum: &mut Vec<Span>) -> Result<(), SpanRxClosed> {
+        loop {
+            if accum.len() == Self::MAX_BATCH_SIZE {
+                trace!(capacity = Self::MAX_BATCH_SIZE, "Batch capacity reached");
+                return Ok(());
+            }
+
+            futures::select_biased! {
+                res = spans.next().fuse() => match res {
+                    Some(span) => {
+                        trace!(?span, "Adding to batch");
+                        accum.push(span);
+                    }
+                    None => return Err(SpanRxClosed),
+                },
+                // Don't hold spans indefinitely. Return if we hit an idle
+                // timeout and spans have been collected.
+                _ = time::sleep(Self::MAX_BATCH_IDLE).fuse() => {
+                    if !accum.is_empty() {
+                        trace!(spans = accum.len(), "Flushing spans due to inactivitiy");
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Indicates whether a failed export may succeed if it is retried.
+///
+/// Connection failures are reported as `Unknown`, so they are retried along
+/// with the codes that OTLP considers transient.
+fn is_retryable(code: grpc::Code) -> bool {
+    matches!(
+        code,
+        grpc::Code::Unknown
+            | grpc::Code::Cancelled
+            | grpc::Code::DeadlineExceeded
+            | grpc::Code::ResourceExhausted
+            | grpc::Code::Aborted
+            | grpc::Code::OutOfRange
+            | grpc::Code::Unavailable
+            | grpc::Code::DataLoss
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_channel::into_stream::IntoStream;
+    use linkerd_metrics::FmtMetrics;
+    use opentelemetry_proto::collector::trace::v1::{
+        trace_service_server::{TraceService, TraceServiceServer},
+        ExportTraceServiceResponse,
+    };
+    use opentelemetry_proto::common::v1::{any_value, AnyValue, KeyValue};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::mpsc;
+
+    /// An in-process collector that fails its first `failures` requests.
+    struct MockCollector {
+        failures: AtomicUsize,
+        exports: mpsc::UnboundedSender<ExportTraceServiceRequest>,
+    }
+
+    #[grpc::async_trait]
+    impl TraceService for MockCollector {
+        async fn export(
+            &self,
+            req: grpc::Request<ExportTraceServiceRequest>,
+        ) -> Result<grpc::Response<ExportTraceServiceResponse>, grpc::Status> {
+            if self.failures.load(Ordering::SeqCst) > 0 {
+                self.failures.fetch_sub(1, Ordering::SeqCst);
+                return Err(grpc::Status::unavailable("collector unavailable"));
+            }
+            self.exports
+                .send(req.into_inner())
+                .expect("test must be running");
+            Ok(grpc::Response::new(ExportTraceServiceResponse {}))
+        }
+    }
+
+    fn resource() -> Resource {
+        Resource {
+            attributes: vec![KeyValue {
+                key: "service.name".to_string(),
+                value: Some(AnyValue {
+                    value: Some(any_value::Value::StringValue("linkerd-proxy".to_string())),
+                }),
+            }],
+            dropped_attributes_count: 0,
+        }
+    }
+
+    fn span(name: &str) -> Span {
+        Span {
+            trace_id: vec![1; 16],
+            span_id: vec![2; 8],
+            name: name.to_string(),
+            ..Span::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn exports_to_collector() {
+        time::pause();
+        let (exports_tx, mut exports_rx) = mpsc::unbounded_channel();
+        let collector = TraceServiceServer::new(MockCollector {
+            failures: AtomicUsize::new(1),
+            exports: exports_tx,
+        });
+
+        let (spans_tx, spans_rx) = mpsc::channel(10);
+        spans_tx.send(span("a")).await.unwrap();
+        spans_tx.send(span("b")).await.unwrap();
+        drop(spans_tx);
+
+        // The exporter retries the batch after the collector fails and
+        // completes once the span stream closes.
+        let (registry, report) = metrics::new();
+        export_spans(collector, resource(), spans_rx.into_stream(), registry).await;
+
+        let req = exports_rx.recv().await.expect("batch must be exported");
+        assert_eq!(req.resource_spans.len(), 1);
+        let resource_spans = &req.resource_spans[0];
+        assert_eq!(resource_spans.resource, Some(resource()));
+        let names = resource_spans.instrumentation_library_spans[0]
+            .spans
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "b"]);
+        assert!(
+            exports_rx.recv().now_or_never().flatten().is_none(),
+            "only one batch expected"
+        );
+
+        let metrics = report.as_display().to_string();
+        assert!(metrics.contains("opentelemetry_span_export_requests 2\n"));
+        assert!(metrics.contains("opentelemetry_span_export_failures 1\n"));
+        assert!(metrics.contains("opentelemetry_span_exports 2\n"));
+    }
+
+    #[tokio::test]
+    async fn drops_batch_after_max_attempts() {
+        time::pause();
+        let (exports_tx, mut exports_rx) = mpsc::unbounded_channel();
+        let collector = TraceServiceServer::new(MockCollector {
+            failures: AtomicUsize::new(usize::MAX),
+            exports: exports_tx,
+        });
+
+        let (spans_tx, spans_rx) = mpsc::channel(10);
+        spans_tx.send(span("a")).await.unwrap();
+        drop(spans_tx);
+
+        // The exporter gives up on the batch rather than retrying forever.
+        let (registry, report) = metrics::new();
+        export_spans(collector, resource(), spans_rx.into_stream(), registry).await;
+        assert!(exports_rx.recv().now_or_never().flatten().is_none());
+
+        let metrics = report.as_display().to_string();
+        assert!(metrics.contains("opentelemetry_span_export_requests 3\n"));
+        assert!(metrics.contains("opentelemetry_span_export_failures 3\n"));
+        assert!(metrics.contains("opentelemetry_span_exports 0\n"));
+    }
+}
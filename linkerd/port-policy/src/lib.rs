@@ -0,0 +1,268 @@
+#![deny(warnings, rust_2018_idioms)]
+
+//! Port-based protocol policies that may be updated at runtime.
+//!
+//! A [`PortPolicy`] names the ports on which the proxy skips protocol
+//! detection or requires client identities. Stacks hold [`Ports`], which read
+//! the current policy as each connection is accepted, so that updates apply to
+//! new connections without disturbing existing ones.
+
+use indexmap::IndexSet;
+use linkerd_error::Error;
+use linkerd_file_watch::json::{self, Object, Value};
+pub use linkerd_file_watch::Task;
+use std::{fmt, ops::RangeInclusive, path::PathBuf, sync::Arc, time::Duration};
+use tokio::sync::watch;
+
+/// Reads a policy from a JSON file that is reloaded as it changes, e.g.:
+///
+/// ```json
+/// {
+///   "inbound": {
+///     "opaque_ports": [3306, "5432"],
+///     "require_identity_ports": ["8080-8081"]
+///   },
+///   "outbound": {
+///     "opaque_ports": [3306]
+///   }
+/// }
+/// ```
+///
+/// Omitted port sets are empty.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PortPolicy {
+    /// Inbound connections to these ports are forwarded without protocol
+    /// detection.
+    pub inbound_opaque_ports: Vec<RangeInclusive<u16>>,
+
+    /// Inbound connections to these ports must be from clients with a TLS
+    /// identity.
+    pub inbound_require_identity_ports: Vec<RangeInclusive<u16>>,
+
+    /// Outbound connections to these ports are forwarded without protocol
+    /// detection.
+    pub outbound_opaque_ports: Vec<RangeInclusive<u16>>,
+}
+
+pub type Receiver = watch::Receiver<Arc<PortPolicy>>;
+
+/// One of a policy's port sets, or a fixed set of ports.
+#[derive(Clone, Debug)]
+pub struct Ports(Inner);
+
+#[derive(Clone, Debug)]
+enum Inner {
+    Fixed(Arc<IndexSet<u16>>),
+    Policy { policy: Receiver, set: Set },
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Set {
+    InboundOpaque,
+    InboundRequireIdentity,
+    OutboundOpaque,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidPolicy(String);
+
+// === impl Config ===
+
+impl Config {
+    const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Loads the policy, failing if it is invalid, and returns a task that
+    /// reloads it as the file changes.
+    pub fn build(self) -> Result<(Receiver, Task), Error> {
+        linkerd_file_watch::watch(self.path, Self::POLL_INTERVAL, |bytes| {
+            Ok(Arc::new(PortPolicy::from_json(bytes)?))
+        })
+    }
+}
+
+// === impl PortPolicy ===
+
+impl PortPolicy {
+    pub fn from_json(bytes: &[u8]) -> Result<Self, InvalidPolicy> {
+        let value = serde_json::from_slice::<Value>(bytes)
+            .map_err(|e| InvalidPolicy(format!("invalid JSON: {}", e)))?;
+        let mut policy = into_object(value, "policy")?;
+
+        let mut inbound = match policy.remove("inbound") {
+            Some(inbound) => into_object(inbound, "inbound")?,
+            None => Object::new(),
+        };
+        let inbound_opaque_ports = port_set(inbound.remove("opaque_ports"), "inbound")?;
+        let inbound_require_identity_ports =
+            port_set(inbound.remove("require_identity_ports"), "inbound")?;
+        deny_unknown(&inbound, "inbound")?;
+
+        let mut outbound = match policy.remove("outbound") {
+            Some(outbound) => into_object(outbound, "outbound")?,
+            None => Object::new(),
+        };
+        let outbound_opaque_ports = port_set(outbound.remove("opaque_ports"), "outbound")?;
+        deny_unknown(&outbound, "outbound")?;
+
+        deny_unknown(&policy, "policy")?;
+
+        Ok(Self {
+            inbound_opaque_ports,
+            inbound_require_identity_ports,
+            outbound_opaque_ports,
+        })
+    }
+}
+
+// === impl Ports ===
+
+impl Ports {
+    pub fn inbound_opaque(policy: Receiver) -> Self {
+        Self(Inner::Policy {
+            policy,
+            set: Set::InboundOpaque,
+        })
+    }
+
+    pub fn inbound_require_identity(policy: Receiver) -> Self {
+        Self(Inner::Policy {
+            policy,
+            set: Set::InboundRequireIdentity,
+        })
+    }
+
+    pub fn outbound_opaque(policy: Receiver) -> Self {
+        Self(Inner::Policy {
+            policy,
+            set: Set::OutboundOpaque,
+        })
+    }
+
+    pub fn contains(&self, port: u16) -> bool {
+        match self.0 {
+            Inner::Fixed(ref ports) => ports.contains(&port),
+            Inner::Policy { ref policy, set } => {
+                let policy = policy.borrow();
+                let ranges = match set {
+                    Set::InboundOpaque => &policy.inbound_opaque_ports,
+                    Set::InboundRequireIdentity => &policy.inbound_require_identity_ports,
+                    Set::OutboundOpaque => &policy.outbound_opaque_ports,
+                };
+                ranges.iter().any(|r| r.contains(&port))
+            }
+        }
+    }
+}
+
+impl From<IndexSet<u16>> for Ports {
+    fn from(ports: IndexSet<u16>) -> Self {
+        Self(Inner::Fixed(Arc::new(ports)))
+    }
+}
+
+impl Default for Ports {
+    fn default() -> Self {
+        IndexSet::default().into()
+    }
+}
+
+// === impl InvalidPolicy ===
+
+impl fmt::Display for InvalidPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid port policy: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidPolicy {}
+
+fn into_object(value: Value, what: &str) -> Result<Object, InvalidPolicy> {
+    json::into_object(value).ok_or_else(|| InvalidPolicy(format!("{} must be an object", what)))
+}
+
+fn deny_unknown(obj: &Object, what: &str) -> Result<(), InvalidPolicy> {
+    match obj.keys().next() {
+        Some(field) => Err(InvalidPolicy(format!(
+            "{}: unknown field '{}'",
+            what, field
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn port_set(value: Option<Value>, what: &str) -> Result<Vec<RangeInclusive<u16>>, InvalidPolicy> {
+    json::port_ranges(value).ok_or_else(|| InvalidPolicy(format!("{}: invalid ports", what)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ports(ports: &[u16]) -> Vec<RangeInclusive<u16>> {
+        ports.iter().map(|&p| p..=p).collect()
+    }
+
+    #[test]
+    fn parses_policies() {
+        let policy = PortPolicy::from_json(
+            br#"{
+                "inbound": {
+                    "opaque_ports": [3306, "5432"],
+                    "require_identity_ports": ["8080-8082"]
+                },
+                "outbound": {"opaque_ports": [3306]}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(policy.inbound_opaque_ports, ports(&[3306, 5432]));
+        assert_eq!(policy.inbound_require_identity_ports, vec![8080..=8082]);
+        assert_eq!(policy.outbound_opaque_ports, ports(&[3306]));
+
+        assert_eq!(PortPolicy::from_json(b"{}"), Ok(PortPolicy::default()));
+
+        for invalid in &[
+            "[]",
+            r#"{"inbound": []}"#,
+            r#"{"inbound": {"opaque_ports": 3306}}"#,
+            r#"{"inbound": {"opaque_ports": [0]}}"#,
+            r#"{"inbound": {"opaque_ports": [70000]}}"#,
+            r#"{"inbound": {"opaque_ports": ["90-80"]}}"#,
+            r#"{"inbound": {"skip_ports": [80]}}"#,
+            r#"{"outbound": {"require_identity_ports": [80]}}"#,
+            r#"{"ports": [80]}"#,
+        ] {
+            assert!(
+                PortPolicy::from_json(invalid.as_bytes()).is_err(),
+                "{} must be invalid",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn ports_follow_updates() {
+        let (tx, rx) = watch::channel(Arc::new(PortPolicy {
+            inbound_opaque_ports: ports(&[3306]),
+            ..PortPolicy::default()
+        }));
+        let opaque = Ports::inbound_opaque(rx.clone());
+        let require_id = Ports::inbound_require_identity(rx);
+        assert!(opaque.contains(3306));
+        assert!(!require_id.contains(3306));
+
+        tx.send(Arc::new(PortPolicy {
+            inbound_require_identity_ports: vec![3306..=3307],
+            ..PortPolicy::default()
+        }))
+        .unwrap();
+        assert!(!opaque.contains(3306));
+        assert!(require_id.contains(3306));
+        assert!(require_id.contains(3307));
+        assert!(!require_id.contains(3308));
+    }
+}
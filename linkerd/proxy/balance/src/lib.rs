@@ -0,0 +1,94 @@
+//! Load balancing strategies over a discovered set of endpoints.
+//!
+//! Power-of-two-choices balancers are provided by `tower::balance`; this crate
+//! provides the balancers that choose endpoints deterministically (i.e.
+//! round-robin and consistent hashing) and the means to select a strategy for
+//! each target.
+
+#![deny(warnings, rust_2018_idioms)]
+
+mod make;
+mod ring_hash;
+mod round_robin;
+mod service;
+pub mod zone;
+
+pub use self::{
+    make::{MakeBalance, NewBalance},
+    ring_hash::{HashRequest, RingHash},
+    round_robin::RoundRobin,
+    service::{Balance, GetPickedEndpoint, Pick, PickedEndpoint, ReadySet},
+};
+use http::header::HeaderName;
+use std::{fmt, str::FromStr};
+
+/// Describes how requests are distributed over a destination's endpoints.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Strategy {
+    /// Chooses the less loaded of two random endpoints, where load is
+    /// measured as the peak-EWMA of each endpoint's latency.
+    PeakEwma,
+
+    /// Chooses the less loaded of two random endpoints, where load is
+    /// measured as the number of each endpoint's in-flight requests.
+    LeastRequest,
+
+    /// Chooses each endpoint in turn.
+    RoundRobin,
+
+    /// Chooses an endpoint by consistently hashing a key extracted from each
+    /// request, so that requests with the same key are sent to the same
+    /// endpoint for as long as it is available.
+    RingHash(HashKey),
+}
+
+/// Describes the key on which a ring-hash balancer hashes requests.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    /// The value of a request header.
+    Header(HeaderName),
+
+    /// The client's IP address.
+    ClientAddr,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidStrategy(String);
+
+// === impl Strategy ===
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Self::PeakEwma
+    }
+}
+
+/// Parses one of `peak-ewma`, `least-request`, `round-robin`,
+/// `ring-hash:client-addr`, or `ring-hash:header:<name>`.
+impl FromStr for Strategy {
+    type Err = InvalidStrategy;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const HEADER: &str = "ring-hash:header:";
+        match s {
+            "peak-ewma" => Ok(Self::PeakEwma),
+            "least-request" => Ok(Self::LeastRequest),
+            "round-robin" => Ok(Self::RoundRobin),
+            "ring-hash:client-addr" => Ok(Self::RingHash(HashKey::ClientAddr)),
+            s if s.starts_with(HEADER) => HeaderName::from_str(&s[HEADER.len()..])
+                .map(|name| Self::RingHash(HashKey::Header(name)))
+                .map_err(|_| InvalidStrategy(s.to_string())),
+            s => Err(InvalidStrategy(s.to_string())),
+        }
+    }
+}
+
+// === impl InvalidStrategy ===
+
+impl fmt::Display for InvalidStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid balancer: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidStrategy {}
@@ -0,0 +1,92 @@
+use crate::Strategy;
+use futures::{ready, TryFuture};
+use linkerd_stack::{layer, Param};
+use pin_project::pin_project;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Builds a balancer over a `D`-typed discovery of endpoints.
+pub trait NewBalance<D> {
+    type Service;
+
+    fn new_balance(&self, strategy: &Strategy, discover: D) -> Self::Service;
+}
+
+/// Builds a balancer for each target using the target's balancing strategy,
+/// if it has one, or a default strategy.
+///
+/// The strategy is fixed when the balancer is built.
+#[derive(Clone, Debug)]
+pub struct MakeBalance<N, M> {
+    default: Strategy,
+    new_balance: N,
+    inner: M,
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct MakeFuture<N, F> {
+    #[pin]
+    inner: F,
+    strategy: Strategy,
+    new_balance: N,
+}
+
+// === impl MakeBalance ===
+
+impl<N: Clone, M> MakeBalance<N, M> {
+    pub fn layer(
+        default: Strategy,
+        new_balance: N,
+    ) -> impl layer::Layer<M, Service = Self> + Clone {
+        layer::mk(move |inner| Self {
+            default: default.clone(),
+            new_balance: new_balance.clone(),
+            inner,
+        })
+    }
+}
+
+impl<T, N, M> tower::Service<T> for MakeBalance<N, M>
+where
+    T: Param<Option<Strategy>>,
+    M: tower::Service<T>,
+    N: NewBalance<M::Response> + Clone,
+{
+    type Response = N::Service;
+    type Error = M::Error;
+    type Future = MakeFuture<N, M::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, target: T) -> Self::Future {
+        let strategy = target.param().unwrap_or_else(|| self.default.clone());
+        tracing::debug!(?strategy, "Building balancer");
+        MakeFuture {
+            inner: self.inner.call(target),
+            strategy,
+            new_balance: self.new_balance.clone(),
+        }
+    }
+}
+
+// === impl MakeFuture ===
+
+impl<N, F> Future for MakeFuture<N, F>
+where
+    F: TryFuture,
+    N: NewBalance<F::Ok>,
+{
+    type Output = Result<N::Service, F::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let discover = ready!(this.inner.try_poll(cx))?;
+        Poll::Ready(Ok(this.new_balance.new_balance(this.strategy, discover)))
+    }
+}
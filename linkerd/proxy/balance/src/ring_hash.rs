@@ -0,0 +1,190 @@
+use crate::{Pick, ReadySet, RoundRobin};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+/// The number of points each endpoint occupies on the ring.
+///
+/// More points spread requests more evenly over endpoints at the cost of a
+/// larger ring.
+const POINTS_PER_ENDPOINT: u32 = 100;
+
+/// Chooses endpoints by consistently hashing a key extracted from each
+/// request.
+///
+/// Each endpoint is placed at several points on a hash ring and each request
+/// is dispatched to the first ready endpoint at or after the request's hash.
+/// Adding or removing an endpoint only moves the requests that hash near that
+/// endpoint's points. Requests without a key are distributed round-robin.
+#[derive(Clone, Debug)]
+pub struct RingHash<K, H> {
+    hash: H,
+    ring: Vec<(u64, K)>,
+    unhashed: RoundRobin<K>,
+}
+
+/// Extracts a hash key from a request.
+pub trait HashRequest<Req> {
+    /// Returns the hash of the request's key, if it has one.
+    fn hash_request(&self, req: &Req) -> Option<u64>;
+}
+
+// === impl RingHash ===
+
+impl<K, H> RingHash<K, H> {
+    pub fn new(hash: H) -> Self {
+        Self {
+            hash,
+            ring: Vec::new(),
+            unhashed: RoundRobin::default(),
+        }
+    }
+}
+
+impl<K, H, Req> Pick<K, Req> for RingHash<K, H>
+where
+    K: Clone + Hash + PartialEq,
+    H: HashRequest<Req>,
+{
+    fn insert(&mut self, key: &K) {
+        Pick::<K, Req>::insert(&mut self.unhashed, key);
+        if self.ring.iter().any(|(_, k)| k == key) {
+            return;
+        }
+
+        for point in 0..POINTS_PER_ENDPOINT {
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            point.hash(&mut hasher);
+            self.ring.push((hasher.finish(), key.clone()));
+        }
+        self.ring.sort_by_key(|(h, _)| *h);
+    }
+
+    fn remove(&mut self, key: &K) {
+        Pick::<K, Req>::remove(&mut self.unhashed, key);
+        self.ring.retain(|(_, k)| k != key);
+    }
+
+    fn pick<R: ReadySet<K>>(&mut self, req: &Req, ready: &R) -> usize {
+        let hash = match self.hash.hash_request(req) {
+            Some(hash) => hash,
+            None => return self.unhashed.pick(req, ready),
+        };
+
+        // Walk the ring from the request's hash until a ready endpoint is
+        // found.
+        let start = match self.ring.binary_search_by_key(&hash, |(h, _)| *h) {
+            Ok(i) | Err(i) => i,
+        };
+        let len = self.ring.len();
+        for i in 0..len {
+            let (_, ref key) = self.ring[(start + i) % len];
+            if let Some(index) = ready.ready_index(key) {
+                return index;
+            }
+        }
+
+        // Every ready endpoint should be on the ring, but fall back to the
+        // first ready endpoint rather than fail the request.
+        0
+    }
+}
+
+// === impl HashRequest ===
+
+impl<Req, F> HashRequest<Req> for F
+where
+    F: Fn(&Req) -> Option<u64>,
+{
+    fn hash_request(&self, req: &Req) -> Option<u64> {
+        (self)(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Ready(Vec<u16>);
+
+    impl ReadySet<u16> for Ready {
+        fn ready_len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn ready_index(&self, key: &u16) -> Option<usize> {
+            self.0.iter().position(|k| k == key)
+        }
+    }
+
+    fn hash(key: &u64) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    fn ring(endpoints: &[u16]) -> RingHash<u16, fn(&u64) -> Option<u64>> {
+        let mut ring = RingHash::new(hash as fn(&u64) -> Option<u64>);
+        for ep in endpoints {
+            Pick::<_, u64>::insert(&mut ring, ep);
+        }
+        ring
+    }
+
+    fn pick(ring: &mut RingHash<u16, fn(&u64) -> Option<u64>>, ready: &Ready, req: u64) -> u16 {
+        ready.0[ring.pick(&req, ready)]
+    }
+
+    #[test]
+    fn consistent() {
+        let endpoints = (0..10).collect::<Vec<u16>>();
+        let mut ring = ring(&endpoints);
+        let ready = Ready(endpoints.clone());
+
+        let picked = (0..1000)
+            .map(|req| pick(&mut ring, &ready, req))
+            .collect::<Vec<_>>();
+        for (req, ep) in picked.iter().enumerate() {
+            assert_eq!(pick(&mut ring, &ready, req as u64), *ep);
+        }
+
+        // Every endpoint receives some requests.
+        for ep in &endpoints {
+            assert!(picked.contains(ep), "endpoint {} was never picked", ep);
+        }
+
+        // Removing an endpoint only moves the requests that it handled.
+        Pick::<_, u64>::remove(&mut ring, &0);
+        let ready = Ready((1..10).collect());
+        for (req, ep) in picked.iter().enumerate() {
+            let moved = pick(&mut ring, &ready, req as u64);
+            if *ep != 0 {
+                assert_eq!(moved, *ep);
+            }
+        }
+    }
+
+    #[test]
+    fn skips_unready() {
+        let endpoints = (0..10).collect::<Vec<u16>>();
+        let mut ring = ring(&endpoints);
+
+        let picked = (0..100)
+            .map(|req| pick(&mut ring, &Ready(endpoints.clone()), req))
+            .collect::<Vec<_>>();
+
+        // Requests for an endpoint that is not ready move to another
+        // endpoint while other requests are unaffected.
+        let ready = Ready((1..10).collect());
+        for (req, ep) in picked.iter().enumerate() {
+            let moved = pick(&mut ring, &ready, req as u64);
+            if *ep == 0 {
+                assert_ne!(moved, 0);
+            } else {
+                assert_eq!(moved, *ep);
+            }
+        }
+    }
+}
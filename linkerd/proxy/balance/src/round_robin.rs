@@ -0,0 +1,100 @@
+use crate::{Pick, ReadySet};
+
+/// Chooses each ready endpoint in turn.
+///
+/// Endpoints are ordered by when they were discovered, and endpoints that are
+/// not ready are skipped.
+#[derive(Clone, Debug)]
+pub struct RoundRobin<K> {
+    keys: Vec<K>,
+    next: usize,
+}
+
+// === impl RoundRobin ===
+
+impl<K> Default for RoundRobin<K> {
+    fn default() -> Self {
+        Self {
+            keys: Vec::new(),
+            next: 0,
+        }
+    }
+}
+
+impl<K: Clone + PartialEq, Req> Pick<K, Req> for RoundRobin<K> {
+    fn insert(&mut self, key: &K) {
+        if !self.keys.contains(key) {
+            self.keys.push(key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &K) {
+        self.keys.retain(|k| k != key);
+    }
+
+    fn pick<R: ReadySet<K>>(&mut self, _: &Req, ready: &R) -> usize {
+        let len = self.keys.len();
+        for i in 0..len {
+            let idx = (self.next + i) % len;
+            if let Some(index) = ready.ready_index(&self.keys[idx]) {
+                self.next = (idx + 1) % len;
+                return index;
+            }
+        }
+
+        // Every ready endpoint should be known, but fall back to the first
+        // ready endpoint rather than fail the request.
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Ready(Vec<&'static str>);
+
+    impl ReadySet<&'static str> for Ready {
+        fn ready_len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn ready_index(&self, key: &&'static str) -> Option<usize> {
+            self.0.iter().position(|k| k == key)
+        }
+    }
+
+    fn pick(rr: &mut RoundRobin<&'static str>, ready: &Ready) -> &'static str {
+        ready.0[Pick::<_, ()>::pick(rr, &(), ready)]
+    }
+
+    #[test]
+    fn picks_in_turn() {
+        let mut rr = RoundRobin::default();
+        for k in &["a", "b", "c"] {
+            Pick::<_, ()>::insert(&mut rr, k);
+        }
+
+        // The order of the ready set does not affect the order in which
+        // endpoints are chosen.
+        let ready = Ready(vec!["c", "a", "b"]);
+        let picked = (0..6).map(|_| pick(&mut rr, &ready)).collect::<Vec<_>>();
+        assert_eq!(picked, vec!["a", "b", "c", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn skips_unready() {
+        let mut rr = RoundRobin::default();
+        for k in &["a", "b", "c"] {
+            Pick::<_, ()>::insert(&mut rr, k);
+        }
+
+        let ready = Ready(vec!["a", "c"]);
+        let picked = (0..4).map(|_| pick(&mut rr, &ready)).collect::<Vec<_>>();
+        assert_eq!(picked, vec!["a", "c", "a", "c"]);
+
+        Pick::<_, ()>::remove(&mut rr, &"a");
+        assert_eq!(pick(&mut rr, &ready), "c");
+        assert_eq!(pick(&mut rr, &ready), "c");
+    }
+}
@@ -0,0 +1,279 @@
+use futures::{future, prelude::*};
+use linkerd_error::Error;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+use tower::{
+    discover::{Change, Discover},
+    ready_cache::{error::Failed, ReadyCache},
+};
+use tracing::{debug, trace};
+
+/// Distributes requests over a set of discovered endpoints, dispatching each
+/// request to the ready endpoint chosen by a `P`-typed policy.
+pub struct Balance<D, P, Req>
+where
+    D: Discover,
+    D::Key: Hash,
+{
+    discover: D,
+    services: ReadyCache<D::Key, D::Service, Req>,
+    pick: P,
+}
+
+/// Chooses the endpoint that handles each request.
+pub trait Pick<K, Req> {
+    /// Notifies the policy that an endpoint was discovered.
+    fn insert(&mut self, key: &K);
+
+    /// Notifies the policy that an endpoint was removed.
+    fn remove(&mut self, key: &K);
+
+    /// Returns the index of the ready endpoint that should handle `req`.
+    ///
+    /// The ready set is never empty.
+    fn pick<R: ReadySet<K>>(&mut self, req: &Req, ready: &R) -> usize;
+}
+
+/// Records the endpoint to which a request was dispatched, so that a copy of
+/// the request (i.e. a hedged request) may be dispatched to another endpoint.
+///
+/// When requests share a `PickedEndpoint` (as a request extension), the first
+/// request records its endpoint and later requests avoid that endpoint if
+/// another endpoint is ready.
+#[derive(Clone, Debug, Default)]
+pub struct PickedEndpoint(Arc<AtomicU64>);
+
+/// Gets a request's `PickedEndpoint`, if it has one.
+pub trait GetPickedEndpoint {
+    fn picked_endpoint(&self) -> Option<&PickedEndpoint>;
+}
+
+/// The set of endpoints that are ready to handle a request.
+pub trait ReadySet<K> {
+    fn ready_len(&self) -> usize;
+
+    fn ready_index(&self, key: &K) -> Option<usize>;
+}
+
+// === impl Balance ===
+
+impl<D, P, Req> Balance<D, P, Req>
+where
+    D: Discover,
+    D::Key: Hash,
+    D::Service: tower::Service<Req>,
+{
+    pub fn new(discover: D, pick: P) -> Self {
+        Self {
+            discover,
+            services: ReadyCache::default(),
+            pick,
+        }
+    }
+}
+
+impl<D, P, Req> Balance<D, P, Req>
+where
+    D: Discover + Unpin,
+    D::Key: Hash + Clone,
+    D::Error: Into<Error>,
+    D::Service: tower::Service<Req>,
+    <D::Service as tower::Service<Req>>::Error: Into<Error>,
+    P: Pick<D::Key, Req>,
+{
+    fn update_from_discover(&mut self, cx: &mut Context<'_>) -> Result<(), Error> {
+        while let Poll::Ready(change) = Pin::new(&mut self.discover).poll_discover(cx) {
+            match change.transpose().map_err(Into::into)? {
+                None => return Ok(()),
+                Some(Change::Insert(key, svc)) => {
+                    trace!("Inserting endpoint");
+                    self.pick.insert(&key);
+                    // If this endpoint already existed, it is replaced once the
+                    // new service becomes ready.
+                    self.services.push(key, svc);
+                }
+                Some(Change::Remove(key)) => {
+                    trace!("Removing endpoint");
+                    self.pick.remove(&key);
+                    self.services.evict(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Avoids the endpoint that handled a linked request, if another endpoint
+    /// is ready, or otherwise records the picked endpoint.
+    fn avoid_picked(&self, index: usize, picked: &PickedEndpoint) -> usize {
+        let key_hash = |index| {
+            let (key, _) = self.services.get_ready_index(index)?;
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            // Zero indicates that no endpoint has been picked.
+            Some(hasher.finish().max(1))
+        };
+
+        let hash = match key_hash(index) {
+            Some(hash) => hash,
+            None => return index,
+        };
+        match picked
+            .0
+            .compare_exchange(0, hash, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => index,
+            Err(prior) if prior == hash && self.services.ready_len() > 1 => {
+                let other = (index + 1) % self.services.ready_len();
+                trace!(index = other, "Avoiding previously picked endpoint");
+                other
+            }
+            Err(_) => index,
+        }
+    }
+
+    fn promote_pending_to_ready(&mut self, cx: &mut Context<'_>) {
+        // Failed endpoints are dropped from the cache but remain known to the
+        // policy until they are removed by discovery, since they may be
+        // replaced by a new service for the same endpoint.
+        while let Poll::Ready(Err(Failed(_, error))) = self.services.poll_pending(cx) {
+            debug!(%error, "Dropping failed endpoint");
+        }
+    }
+}
+
+impl<D, P, Req> tower::Service<Req> for Balance<D, P, Req>
+where
+    D: Discover + Unpin,
+    D::Key: Hash + Clone,
+    D::Error: Into<Error>,
+    D::Service: tower::Service<Req>,
+    <D::Service as tower::Service<Req>>::Error: Into<Error>,
+    P: Pick<D::Key, Req>,
+    Req: GetPickedEndpoint,
+{
+    type Response = <D::Service as tower::Service<Req>>::Response;
+    type Error = Error;
+    type Future = future::ErrInto<<D::Service as tower::Service<Req>>::Future, Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.update_from_discover(cx)?;
+        self.promote_pending_to_ready(cx);
+
+        if self.services.ready_len() == 0 {
+            trace!(pending = self.services.pending_len(), "No ready endpoints");
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let mut index = self.pick.pick(&req, &self.services);
+        if let Some(picked) = req.picked_endpoint() {
+            index = self.avoid_picked(index, picked);
+        }
+        self.services
+            .call_ready_index(index, req)
+            .err_into::<Error>()
+    }
+}
+
+// === impl GetPickedEndpoint ===
+
+impl<B> GetPickedEndpoint for http::Request<B> {
+    fn picked_endpoint(&self) -> Option<&PickedEndpoint> {
+        self.extensions().get()
+    }
+}
+
+/// Connections are never linked.
+impl GetPickedEndpoint for () {
+    fn picked_endpoint(&self) -> Option<&PickedEndpoint> {
+        None
+    }
+}
+
+// === impl ReadySet ===
+
+impl<K, S, Req> ReadySet<K> for ReadyCache<K, S, Req>
+where
+    K: Clone + Eq + Hash,
+    S: tower::Service<Req>,
+    S::Error: Into<Error>,
+{
+    fn ready_len(&self) -> usize {
+        ReadyCache::ready_len(self)
+    }
+
+    fn ready_index(&self, key: &K) -> Option<usize> {
+        self.get_ready(key).map(|(index, _, _)| index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RoundRobin;
+    use tower::{discover::ServiceList, service_fn, util::BoxService, Service, ServiceExt};
+
+    type Endpoint = BoxService<http::Request<()>, usize, Error>;
+
+    fn endpoints(n: usize) -> ServiceList<Vec<Endpoint>> {
+        let endpoints = (0..n)
+            .map(|ep| BoxService::new(service_fn(move |_| future::ok(ep))))
+            .collect::<Vec<_>>();
+        ServiceList::new(endpoints)
+    }
+
+    #[tokio::test]
+    async fn dispatches_to_picked_endpoint() {
+        let mut balance = Balance::new(endpoints(3), RoundRobin::default());
+
+        let mut rsps = Vec::new();
+        for _ in 0..6 {
+            let req = http::Request::new(());
+            rsps.push(balance.ready().await.unwrap().call(req).await.unwrap());
+        }
+        assert_eq!(rsps, vec![0, 1, 2, 0, 1, 2]);
+    }
+
+    /// Always picks the same endpoint.
+    struct Fixed(usize);
+
+    impl<Req> Pick<usize, Req> for Fixed {
+        fn insert(&mut self, _: &usize) {}
+
+        fn remove(&mut self, _: &usize) {}
+
+        fn pick<R: ReadySet<usize>>(&mut self, _: &Req, ready: &R) -> usize {
+            ready.ready_index(&self.0).expect("endpoint must be ready")
+        }
+    }
+
+    #[tokio::test]
+    async fn linked_requests_avoid_picked_endpoint() {
+        let mut balance = Balance::new(endpoints(2), Fixed(0));
+
+        let picked = PickedEndpoint::default();
+        let mut rsps = Vec::new();
+        for _ in 0..2 {
+            let mut req = http::Request::new(());
+            req.extensions_mut().insert(picked.clone());
+            rsps.push(balance.ready().await.unwrap().call(req).await.unwrap());
+        }
+        let req = http::Request::new(());
+        rsps.push(balance.ready().await.unwrap().call(req).await.unwrap());
+        assert_eq!(
+            rsps,
+            vec![0, 1, 0],
+            "linked requests must use another endpoint"
+        );
+    }
+}
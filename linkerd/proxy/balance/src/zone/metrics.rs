@@ -0,0 +1,60 @@
+use indexmap::IndexMap;
+use linkerd_metrics::{metrics, Counter, FmtLabels, FmtMetrics};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+metrics! {
+    zone_requests_total: Counter {
+        "Total number of requests dispatched to endpoints in each zone"
+    }
+}
+
+/// Counts requests dispatched to each zone.
+#[derive(Clone, Debug, Default)]
+pub struct Registry(Arc<Mutex<IndexMap<ZoneLabels, Arc<Counter>>>>);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(super) struct ZoneLabels {
+    pub(super) zone: Option<String>,
+    pub(super) local: bool,
+}
+
+// === impl Registry ===
+
+impl Registry {
+    pub(super) fn requests(&self, labels: ZoneLabels) -> Arc<Counter> {
+        self.0
+            .lock()
+            .expect("zone metrics lock poisoned")
+            .entry(labels)
+            .or_default()
+            .clone()
+    }
+}
+
+impl FmtMetrics for Registry {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metrics = self.0.lock().expect("zone metrics lock poisoned");
+        if metrics.is_empty() {
+            return Ok(());
+        }
+
+        zone_requests_total.fmt_help(f)?;
+        zone_requests_total.fmt_scopes(f, metrics.iter(), |c| &**c)?;
+
+        Ok(())
+    }
+}
+
+// === impl ZoneLabels ===
+
+impl FmtLabels for ZoneLabels {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(zone) = self.zone.as_ref() {
+            write!(f, "dst_zone=\"{}\",", zone)?;
+        }
+        write!(f, "dst_zone_local=\"{}\"", self.local)
+    }
+}
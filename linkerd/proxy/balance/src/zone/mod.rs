@@ -0,0 +1,413 @@
+//! Prefers endpoints in the proxy's own zone.
+//!
+//! Discovered endpoints are partitioned into those in the local zone and all
+//! others, and each partition is balanced independently. Requests are
+//! dispatched to local endpoints whenever one is ready and only spill over to
+//! other zones when no local endpoint is ready--i.e. when local endpoints are
+//! unavailable, ejected for failing, or at capacity.
+
+mod metrics;
+
+pub use self::metrics::Registry;
+use self::metrics::ZoneLabels;
+use crate::{NewBalance, Strategy};
+use futures::{future, prelude::*, ready};
+use linkerd_error::{Error, Never};
+use linkerd_metrics::Counter;
+use linkerd_stack::{layer, NewService, Param};
+use std::{
+    collections::{HashSet, VecDeque},
+    hash::Hash,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+use tower::discover::{Change, Discover};
+use tracing::trace;
+
+/// An endpoint's zone, if known.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Zone(pub Option<String>);
+
+/// Indicates whether an endpoint is in the local zone.
+pub trait Locality {
+    fn is_local(&self) -> bool;
+}
+
+/// Marks each endpoint service with its locality and counts the requests
+/// dispatched to its zone.
+#[derive(Clone, Debug)]
+pub struct NewZoned<N> {
+    local: Option<String>,
+    metrics: Registry,
+    inner: N,
+}
+
+#[derive(Clone, Debug)]
+pub struct Zoned<S> {
+    inner: S,
+    is_local: bool,
+    requests: Arc<Counter>,
+}
+
+/// Builds balancers that prefer local endpoints, using an `N`-typed balancer
+/// for each partition.
+#[derive(Clone, Debug)]
+pub struct NewPreferLocal<N> {
+    inner: N,
+}
+
+pub struct PreferLocal<D, B>
+where
+    D: Discover,
+{
+    discover: D,
+    local: Side<D::Key, D::Service, B>,
+    remote: Side<D::Key, D::Service, B>,
+    ready: Option<Ready>,
+}
+
+/// Discovers the endpoints in one partition.
+pub struct Partition<K, S>(Arc<Mutex<VecDeque<Change<K, S>>>>);
+
+struct Side<K, S, B> {
+    keys: HashSet<K>,
+    changes: Partition<K, S>,
+    balance: B,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Ready {
+    Local,
+    Remote,
+}
+
+// === impl NewZoned ===
+
+impl<N> NewZoned<N> {
+    /// Marks endpoints in the `local` zone as local. When the local zone is
+    /// not known, no endpoints are local.
+    pub fn layer(
+        local: Option<String>,
+        metrics: Registry,
+    ) -> impl layer::Layer<N, Service = Self> + Clone {
+        layer::mk(move |inner| Self {
+            local: local.clone(),
+            metrics: metrics.clone(),
+            inner,
+        })
+    }
+}
+
+impl<T, N> NewService<T> for NewZoned<N>
+where
+    T: Param<Zone>,
+    N: NewService<T>,
+{
+    type Service = Zoned<N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        let Zone(zone) = target.param();
+        let is_local = match (self.local.as_ref(), zone.as_ref()) {
+            (Some(local), Some(zone)) => local == zone,
+            _ => false,
+        };
+        let requests = self.metrics.requests(ZoneLabels {
+            zone,
+            local: is_local,
+        });
+        Zoned {
+            inner: self.inner.new_service(target),
+            is_local,
+            requests,
+        }
+    }
+}
+
+// === impl Zoned ===
+
+impl<S> Locality for Zoned<S> {
+    fn is_local(&self) -> bool {
+        self.is_local
+    }
+}
+
+impl<Req, S: tower::Service<Req>> tower::Service<Req> for Zoned<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    #[inline]
+    fn call(&mut self, req: Req) -> Self::Future {
+        self.requests.incr();
+        self.inner.call(req)
+    }
+}
+
+// === impl NewPreferLocal ===
+
+impl<N> NewPreferLocal<N> {
+    pub fn new(inner: N) -> Self {
+        Self { inner }
+    }
+}
+
+impl<D, N> NewBalance<D> for NewPreferLocal<N>
+where
+    D: Discover,
+    D::Key: Hash,
+    N: NewBalance<Partition<D::Key, D::Service>>,
+{
+    type Service = PreferLocal<D, N::Service>;
+
+    fn new_balance(&self, strategy: &Strategy, discover: D) -> Self::Service {
+        let local = Partition::default();
+        let remote = Partition::default();
+        PreferLocal {
+            discover,
+            local: Side {
+                keys: HashSet::default(),
+                balance: self.inner.new_balance(strategy, local.clone()),
+                changes: local,
+            },
+            remote: Side {
+                keys: HashSet::default(),
+                balance: self.inner.new_balance(strategy, remote.clone()),
+                changes: remote,
+            },
+            ready: None,
+        }
+    }
+}
+
+// === impl PreferLocal ===
+
+impl<D, B> PreferLocal<D, B>
+where
+    D: Discover + Unpin,
+    D::Key: Hash + Clone,
+    D::Error: Into<Error>,
+    D::Service: Locality,
+{
+    fn update_from_discover(&mut self, cx: &mut Context<'_>) -> Result<(), Error> {
+        while let Poll::Ready(change) = Pin::new(&mut self.discover).poll_discover(cx) {
+            match change.transpose().map_err(Into::into)? {
+                None => return Ok(()),
+                Some(Change::Insert(key, svc)) => {
+                    let (side, other) = if svc.is_local() {
+                        (&mut self.local, &mut self.remote)
+                    } else {
+                        (&mut self.remote, &mut self.local)
+                    };
+                    // An endpoint may move between zones.
+                    other.remove(&key);
+                    side.keys.insert(key.clone());
+                    side.changes.push(Change::Insert(key, svc));
+                }
+                Some(Change::Remove(key)) => {
+                    self.local.remove(&key);
+                    self.remote.remove(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<D, B, Req> tower::Service<Req> for PreferLocal<D, B>
+where
+    D: Discover + Unpin,
+    D::Key: Hash + Clone,
+    D::Error: Into<Error>,
+    D::Service: Locality,
+    B: tower::Service<Req>,
+    B::Error: Into<Error>,
+{
+    type Response = B::Response;
+    type Error = Error;
+    type Future = future::ErrInto<B::Future, Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.update_from_discover(cx)?;
+
+        if let Poll::Ready(res) = self.local.balance.poll_ready(cx) {
+            res.map_err(Into::into)?;
+            self.ready = Some(Ready::Local);
+            return Poll::Ready(Ok(()));
+        }
+
+        // Spill over to other zones only when no local endpoints are ready.
+        ready!(self.remote.balance.poll_ready(cx)).map_err(Into::into)?;
+        trace!(local = self.local.keys.len(), "No local endpoints ready");
+        self.ready = Some(Ready::Remote);
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        match self.ready.take().expect("called before ready") {
+            Ready::Local => self.local.balance.call(req).err_into::<Error>(),
+            Ready::Remote => self.remote.balance.call(req).err_into::<Error>(),
+        }
+    }
+}
+
+// === impl Side ===
+
+impl<K: Hash + Eq + Clone, S, B> Side<K, S, B> {
+    fn remove(&mut self, key: &K) {
+        if self.keys.remove(key) {
+            self.changes.push(Change::Remove(key.clone()));
+        }
+    }
+}
+
+// === impl Partition ===
+
+impl<K, S> Partition<K, S> {
+    fn push(&self, change: Change<K, S>) {
+        self.0
+            .lock()
+            .expect("partition lock poisoned")
+            .push_back(change);
+    }
+}
+
+impl<K, S> Default for Partition<K, S> {
+    fn default() -> Self {
+        Partition(Arc::new(Mutex::new(VecDeque::new())))
+    }
+}
+
+impl<K, S> Clone for Partition<K, S> {
+    fn clone(&self) -> Self {
+        Partition(self.0.clone())
+    }
+}
+
+/// Changes are pushed as the `PreferLocal` balancer polls its discovery, just
+/// before it polls each partition's balancer, so no waker is registered.
+impl<K, S> Stream for Partition<K, S> {
+    type Item = Result<Change<K, S>, Never>;
+
+    fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.0.lock().expect("partition lock poisoned").pop_front() {
+            Some(change) => Poll::Ready(Some(Ok(change))),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Balance, RoundRobin};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio_test::{assert_ready_ok, task};
+    use tower::{discover::ServiceList, Service};
+
+    #[derive(Clone)]
+    struct Endpoint {
+        id: usize,
+        is_local: bool,
+        ready: Arc<AtomicBool>,
+    }
+
+    #[derive(Clone)]
+    struct NewRoundRobin;
+
+    impl Locality for Endpoint {
+        fn is_local(&self) -> bool {
+            self.is_local
+        }
+    }
+
+    impl Service<()> for Endpoint {
+        type Response = usize;
+        type Error = Error;
+        type Future = future::Ready<Result<usize, Error>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+            if self.ready.load(Ordering::SeqCst) {
+                Poll::Ready(Ok(()))
+            } else {
+                // Ensure the endpoint is polled again.
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+
+        fn call(&mut self, (): ()) -> Self::Future {
+            future::ok(self.id)
+        }
+    }
+
+    impl<D> NewBalance<D> for NewRoundRobin
+    where
+        D: Discover,
+        D::Key: Hash,
+        D::Service: Service<()>,
+    {
+        type Service = Balance<D, RoundRobin<D::Key>, ()>;
+
+        fn new_balance(&self, _: &Strategy, discover: D) -> Self::Service {
+            Balance::new(discover, RoundRobin::default())
+        }
+    }
+
+    #[tokio::test]
+    async fn spills_over_when_local_unready() {
+        let endpoints = (0..4)
+            .map(|id| Endpoint {
+                id,
+                is_local: id < 2,
+                ready: Arc::new(AtomicBool::new(true)),
+            })
+            .collect::<Vec<_>>();
+        let local_ready = endpoints[..2]
+            .iter()
+            .map(|ep| ep.ready.clone())
+            .collect::<Vec<_>>();
+
+        let mut balance = NewPreferLocal::new(NewRoundRobin)
+            .new_balance(&Strategy::RoundRobin, ServiceList::new(endpoints));
+        let mut task = task::spawn(());
+        let mut dispatch = || {
+            assert_ready_ok!(task.enter(|cx, _| balance.poll_ready(cx)));
+            balance.call(())
+        };
+
+        // Requests are only dispatched to local endpoints while they are
+        // ready.
+        for _ in 0..4 {
+            assert!(dispatch().await.unwrap() < 2);
+        }
+
+        // When one local endpoint is not ready, the other is used. An endpoint
+        // that was ready before it became unready may still receive a
+        // request.
+        local_ready[0].store(false, Ordering::SeqCst);
+        dispatch().await.unwrap();
+        for _ in 0..4 {
+            assert_eq!(dispatch().await.unwrap(), 1);
+        }
+
+        // When no local endpoints are ready, requests spill over to other
+        // zones.
+        local_ready[1].store(false, Ordering::SeqCst);
+        dispatch().await.unwrap();
+        for _ in 0..4 {
+            assert!(dispatch().await.unwrap() >= 2);
+        }
+
+        // Once a local endpoint is ready again, it is preferred.
+        local_ready[0].store(true, Ordering::SeqCst);
+        for _ in 0..4 {
+            assert_eq!(dispatch().await.unwrap(), 0);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use indexmap::IndexMap;
+use linkerd_metrics::{metrics, Counter, FmtLabels, FmtMetrics, Gauge};
+use std::{
+    fmt,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+metrics! {
+    endpoint_ejected: Gauge {
+        "The number of load balancers from which an endpoint is currently ejected"
+    },
+    endpoint_ejections_total: Counter {
+        "Total number of times an endpoint has been ejected from a load balancer"
+    }
+}
+
+type Shared<L> = Arc<Mutex<IndexMap<L, Arc<Metrics>>>>;
+
+/// Tracks ejections for each `L`-labeled endpoint.
+///
+/// Endpoints are reported for as long as they are in a load balancer.
+#[derive(Debug)]
+pub struct Registry<L: Hash + Eq>(Shared<L>);
+
+#[derive(Debug, Default)]
+pub(super) struct Metrics {
+    pub(super) ejected: Gauge,
+    pub(super) ejections: Counter,
+}
+
+// === impl Registry ===
+
+impl<L: Hash + Eq> Registry<L> {
+    pub(super) fn metrics(&self, labels: L) -> Arc<Metrics> {
+        self.0
+            .lock()
+            .expect("ejection metrics lock poisoned")
+            .entry(labels)
+            .or_default()
+            .clone()
+    }
+}
+
+impl<L: Hash + Eq> Default for Registry<L> {
+    fn default() -> Self {
+        Registry(Shared::default())
+    }
+}
+
+impl<L: Hash + Eq> Clone for Registry<L> {
+    fn clone(&self) -> Self {
+        Registry(self.0.clone())
+    }
+}
+
+impl<L: FmtLabels + Hash + Eq> FmtMetrics for Registry<L> {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut metrics = self.0.lock().expect("ejection metrics lock poisoned");
+
+        // Drop endpoints that are no longer held by a balancer.
+        metrics.retain(|_, m| Arc::strong_count(m) > 1);
+        if metrics.is_empty() {
+            return Ok(());
+        }
+
+        endpoint_ejected.fmt_help(f)?;
+        endpoint_ejected.fmt_scopes(f, metrics.iter(), |m| &m.ejected)?;
+
+        endpoint_ejections_total.fmt_help(f)?;
+        endpoint_ejections_total.fmt_scopes(f, metrics.iter(), |m| &m.ejections)?;
+
+        Ok(())
+    }
+}
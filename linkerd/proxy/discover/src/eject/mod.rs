@@ -0,0 +1,553 @@
+//! Ejects failing endpoints from load balancers.
+//!
+//! Each endpoint service accrues failures as responses complete. Once an
+//! endpoint fails too often, it is ejected: it does not become ready until an
+//! (exponentially increasing) backoff elapses, so load balancers route
+//! requests to other endpoints. When the backoff elapses, the endpoint is
+//! probed--if the next response succeeds, the endpoint is restored; otherwise
+//! it is ejected again.
+//!
+//! Only a limited proportion of a load balancer's endpoints may be ejected at
+//! once, so that a balancer isn't left without endpoints when failures are
+//! caused by something other than the endpoints (e.g. a bad request pattern).
+
+mod metrics;
+
+pub use self::metrics::Registry;
+use futures::ready;
+use linkerd_exp_backoff::ExponentialBackoff;
+use linkerd_stack::{layer, NewService, Param};
+use pin_project::pin_project;
+use std::{
+    future::Future,
+    hash::Hash,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time;
+use tracing::debug;
+
+/// Configures when endpoints are ejected.
+#[derive(Copy, Clone, Debug)]
+pub struct Config {
+    /// Ejects an endpoint after this many consecutive failures.
+    pub consecutive_failures: Option<usize>,
+
+    /// Ejects an endpoint when its rate of failures exceeds a threshold.
+    pub failure_rate: Option<FailureRate>,
+
+    /// Determines how long an endpoint remains ejected before it is probed.
+    pub backoff: ExponentialBackoff,
+
+    /// The maximum proportion of a load balancer's endpoints, on `(0, 1]`,
+    /// that may be ejected at once. An endpoint that should be ejected while
+    /// this many endpoints are ejected remains in the balancer.
+    pub max_ejected: f64,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FailureRate {
+    /// The proportion of failed responses, on `(0, 1]`, at which an endpoint
+    /// is ejected.
+    pub threshold: f64,
+
+    /// The minimum number of responses in a window before the endpoint may be
+    /// ejected.
+    pub min_requests: usize,
+
+    /// The interval over which failures are counted.
+    pub window: Duration,
+}
+
+/// Builds endpoint services that may be ejected from a load balancer.
+///
+/// A clone is made for each load balancer, so each clone limits the ejections
+/// of the endpoints it builds independently.
+#[derive(Debug)]
+pub struct NewEject<L: Hash + Eq, C, N> {
+    config: Config,
+    metrics: Registry<L>,
+    balancer: Arc<Balancer>,
+    classify: C,
+    inner: N,
+}
+
+#[derive(Debug)]
+pub struct Eject<C, S> {
+    inner: S,
+    classify: C,
+
+    /// Unset when no failure accrual policy is configured.
+    state: Option<Arc<Mutex<State>>>,
+    sleep: Option<Pin<Box<time::Sleep>>>,
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct ResponseFuture<C, F> {
+    #[pin]
+    inner: F,
+    classify: C,
+    state: Option<Arc<Mutex<State>>>,
+}
+
+/// Counts the endpoints in a load balancer and how many of them are ejected.
+#[derive(Debug, Default)]
+struct Balancer {
+    endpoints: AtomicUsize,
+    ejected: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct State {
+    config: Config,
+    metrics: Arc<metrics::Metrics>,
+    balancer: Arc<Balancer>,
+    status: Status,
+    consecutive_failures: usize,
+    window: Window,
+
+    /// The number of times the endpoint has been ejected since it last
+    /// succeeded a probe.
+    ejections: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Status {
+    Active,
+    Ejected(time::Instant),
+    Probing,
+}
+
+#[derive(Debug)]
+struct Window {
+    start: time::Instant,
+    requests: usize,
+    failures: usize,
+}
+
+// === impl Config ===
+
+impl Config {
+    fn is_enabled(&self) -> bool {
+        self.consecutive_failures.is_some() || self.failure_rate.is_some()
+    }
+}
+
+// === impl NewEject ===
+
+impl<L: Hash + Eq, C: Clone, N> NewEject<L, C, N> {
+    /// Ejects endpoints according to `config`.
+    ///
+    /// Endpoints fail when a request fails with an error or when `classify`
+    /// indicates that its response is a failure.
+    pub fn layer(
+        config: Config,
+        metrics: Registry<L>,
+        classify: C,
+    ) -> impl layer::Layer<N, Service = Self> + Clone {
+        layer::mk(move |inner| Self {
+            config,
+            metrics: metrics.clone(),
+            balancer: Arc::new(Balancer::default()),
+            classify: classify.clone(),
+            inner,
+        })
+    }
+}
+
+impl<L: Hash + Eq, C: Clone, N: Clone> Clone for NewEject<L, C, N> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config,
+            metrics: self.metrics.clone(),
+            // Clones build endpoints for a different load balancer.
+            balancer: Arc::new(Balancer::default()),
+            classify: self.classify.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T, L, C, N> NewService<T> for NewEject<L, C, N>
+where
+    T: Param<L>,
+    L: Hash + Eq,
+    C: Clone,
+    N: NewService<T>,
+{
+    type Service = Eject<C, N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        let state = if self.config.is_enabled() {
+            let metrics = self.metrics.metrics(target.param());
+            let state = State::new(self.config, metrics, self.balancer.clone());
+            Some(Arc::new(Mutex::new(state)))
+        } else {
+            None
+        };
+        let inner = self.inner.new_service(target);
+        Eject {
+            inner,
+            classify: self.classify.clone(),
+            state,
+            sleep: None,
+        }
+    }
+}
+
+// === impl Eject ===
+
+impl<Req, Rsp, C, S> tower::Service<Req> for Eject<C, S>
+where
+    C: Fn(&Rsp) -> bool + Clone,
+    S: tower::Service<Req, Response = Rsp>,
+{
+    type Response = Rsp;
+    type Error = S::Error;
+    type Future = ResponseFuture<C, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        if let Some(state) = self.state.as_ref() {
+            let status = state.lock().expect("ejection state poisoned").status;
+            if let Status::Ejected(until) = status {
+                // Don't become ready until the ejection elapses so that
+                // balancers route requests to other endpoints.
+                let sleep = self
+                    .sleep
+                    .get_or_insert_with(|| Box::pin(time::sleep_until(until)));
+                if sleep.deadline() != until {
+                    sleep.as_mut().reset(until);
+                }
+                ready!(sleep.as_mut().poll(cx));
+                state.lock().expect("ejection state poisoned").probe();
+            }
+        }
+
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        ResponseFuture {
+            inner: self.inner.call(req),
+            classify: self.classify.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<C, F, Rsp, E> Future for ResponseFuture<C, F>
+where
+    C: Fn(&Rsp) -> bool,
+    F: Future<Output = Result<Rsp, E>>,
+{
+    type Output = Result<Rsp, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let res = ready!(this.inner.poll(cx));
+        if let Some(state) = this.state.as_ref() {
+            let failed = match res {
+                Ok(ref rsp) => (this.classify)(rsp),
+                Err(_) => true,
+            };
+            state
+                .lock()
+                .expect("ejection state poisoned")
+                .record(failed);
+        }
+        Poll::Ready(res)
+    }
+}
+
+// === impl State ===
+
+impl State {
+    fn new(config: Config, metrics: Arc<metrics::Metrics>, balancer: Arc<Balancer>) -> Self {
+        balancer.endpoints.fetch_add(1, Ordering::AcqRel);
+        Self {
+            config,
+            metrics,
+            balancer,
+            status: Status::Active,
+            consecutive_failures: 0,
+            window: Window::new(time::Instant::now()),
+            ejections: 0,
+        }
+    }
+
+    fn record(&mut self, failed: bool) {
+        match self.status {
+            // Responses to requests that were dispatched before the endpoint
+            // was ejected are ignored.
+            Status::Ejected(_) => return,
+            Status::Probing if failed => return self.eject(),
+            Status::Probing => {
+                debug!(ejections = self.ejections, "Endpoint restored");
+                self.status = Status::Active;
+                self.ejections = 0;
+            }
+            Status::Active => {}
+        }
+
+        if failed {
+            self.consecutive_failures += 1;
+        } else {
+            self.consecutive_failures = 0;
+        }
+        if let Some(max) = self.config.consecutive_failures {
+            if self.consecutive_failures >= max {
+                return self.eject();
+            }
+        }
+
+        if let Some(rate) = self.config.failure_rate {
+            let now = time::Instant::now();
+            if now.saturating_duration_since(self.window.start) >= rate.window {
+                self.window = Window::new(now);
+            }
+            self.window.requests += 1;
+            if failed {
+                self.window.failures += 1;
+            }
+            if self.window.requests >= rate.min_requests
+                && self.window.failures as f64 >= rate.threshold * self.window.requests as f64
+            {
+                self.eject();
+            }
+        }
+    }
+
+    fn eject(&mut self) {
+        let now = time::Instant::now();
+        let ejected = self.balancer.ejected.load(Ordering::Acquire);
+        let endpoints = self.balancer.endpoints.load(Ordering::Acquire);
+        if (ejected + 1) as f64 > self.config.max_ejected * endpoints as f64 {
+            debug!(
+                ejected,
+                endpoints, "Too many endpoints ejected; not ejecting"
+            );
+            // Failures are counted afresh so that the endpoint may be ejected
+            // once other endpoints are restored.
+            self.status = Status::Active;
+            self.consecutive_failures = 0;
+            self.window = Window::new(now);
+            return;
+        }
+
+        let delay = self
+            .config
+            .backoff
+            .delay(self.ejections, &mut rand::thread_rng());
+        debug!(
+            consecutive_failures = self.consecutive_failures,
+            window.requests = self.window.requests,
+            window.failures = self.window.failures,
+            ?delay,
+            "Ejecting endpoint"
+        );
+
+        self.status = Status::Ejected(now + delay);
+        self.ejections = self.ejections.saturating_add(1);
+        self.consecutive_failures = 0;
+        self.window = Window::new(now);
+        self.balancer.ejected.fetch_add(1, Ordering::AcqRel);
+        self.metrics.ejected.incr();
+        self.metrics.ejections.incr();
+    }
+
+    fn probe(&mut self) {
+        if let Status::Ejected(_) = self.status {
+            debug!("Probing ejected endpoint");
+            self.status = Status::Probing;
+            self.balancer.ejected.fetch_sub(1, Ordering::AcqRel);
+            self.metrics.ejected.decr();
+        }
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        if let Status::Ejected(_) = self.status {
+            self.balancer.ejected.fetch_sub(1, Ordering::AcqRel);
+            self.metrics.ejected.decr();
+        }
+        self.balancer.endpoints.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+// === impl Window ===
+
+impl Window {
+    fn new(start: time::Instant) -> Self {
+        Self {
+            start,
+            requests: 0,
+            failures: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower::{Service, ServiceExt};
+
+    const BACKOFF: ExponentialBackoff = ExponentialBackoff {
+        min: Duration::from_secs(1),
+        max: Duration::from_secs(10),
+        jitter: 0.0,
+    };
+
+    /// Returns an endpoint that fails odd-numbered requests.
+    fn endpoint(
+        config: Config,
+        metrics: Arc<metrics::Metrics>,
+    ) -> Eject<fn(&bool) -> bool, impl tower::Service<u32, Response = bool, Error = ()>> {
+        balanced(config, metrics, Default::default())
+    }
+
+    /// Like `endpoint`, except that the endpoint is in the given balancer.
+    fn balanced(
+        config: Config,
+        metrics: Arc<metrics::Metrics>,
+        balancer: Arc<Balancer>,
+    ) -> Eject<fn(&bool) -> bool, impl tower::Service<u32, Response = bool, Error = ()>> {
+        let inner = tower::service_fn(|n: u32| futures::future::ready(Ok::<_, ()>(n % 2 == 1)));
+        let state = State::new(config, metrics, balancer);
+        Eject {
+            inner,
+            classify: |failed: &bool| *failed,
+            state: Some(Arc::new(Mutex::new(state))),
+            sleep: None,
+        }
+    }
+
+    async fn is_ready<S: tower::Service<u32>>(svc: &mut S) -> bool {
+        futures::poll!(futures::future::poll_fn(|cx| svc.poll_ready(cx))).is_ready()
+    }
+
+    #[tokio::test]
+    async fn consecutive_failures() {
+        time::pause();
+        let metrics = Arc::new(metrics::Metrics::default());
+        let mut svc = endpoint(
+            Config {
+                consecutive_failures: Some(2),
+                failure_rate: None,
+                backoff: BACKOFF,
+                max_ejected: 1.0,
+            },
+            metrics.clone(),
+        );
+
+        assert!(svc.ready().await.unwrap().call(1).await.unwrap());
+        assert!(!svc.ready().await.unwrap().call(0).await.unwrap());
+        assert!(svc.ready().await.unwrap().call(1).await.unwrap());
+        assert!(is_ready(&mut svc).await, "must not be ejected");
+
+        assert!(svc.ready().await.unwrap().call(1).await.unwrap());
+        assert!(!is_ready(&mut svc).await, "must be ejected");
+        assert_eq!(metrics.ejected.value(), 1);
+
+        time::advance(Duration::from_millis(1_001)).await;
+        assert!(is_ready(&mut svc).await, "must be probed");
+        assert_eq!(metrics.ejected.value(), 0);
+
+        // A failed probe ejects the endpoint again, with a longer backoff.
+        svc.call(1).await.unwrap();
+        assert!(!is_ready(&mut svc).await, "must be ejected");
+        time::advance(Duration::from_millis(1_001)).await;
+        assert!(!is_ready(&mut svc).await, "must be ejected");
+        time::advance(Duration::from_millis(1_000)).await;
+        assert!(is_ready(&mut svc).await, "must be probed");
+
+        // A successful probe restores the endpoint.
+        svc.call(0).await.unwrap();
+        svc.ready().await.unwrap().call(1).await.unwrap();
+        assert!(is_ready(&mut svc).await, "must not be ejected");
+        assert_eq!(metrics.ejected.value(), 0);
+        assert_eq!(metrics.ejections.value(), 2.0);
+    }
+
+    #[tokio::test]
+    async fn failure_rate() {
+        time::pause();
+        let metrics = Arc::new(metrics::Metrics::default());
+        let mut svc = endpoint(
+            Config {
+                consecutive_failures: None,
+                failure_rate: Some(FailureRate {
+                    threshold: 0.5,
+                    min_requests: 4,
+                    window: Duration::from_secs(10),
+                }),
+                backoff: BACKOFF,
+                max_ejected: 1.0,
+            },
+            metrics.clone(),
+        );
+
+        for n in 0..3 {
+            svc.ready().await.unwrap().call(n).await.unwrap();
+        }
+        assert!(is_ready(&mut svc).await, "must not be ejected");
+
+        // Failures from a prior window are not counted.
+        time::advance(Duration::from_secs(10)).await;
+        svc.ready().await.unwrap().call(3).await.unwrap();
+        assert!(is_ready(&mut svc).await, "must not be ejected");
+
+        for n in &[1, 0, 1] {
+            svc.ready().await.unwrap().call(*n).await.unwrap();
+        }
+        assert!(!is_ready(&mut svc).await, "must be ejected");
+        assert_eq!(metrics.ejected.value(), 1);
+    }
+
+    #[tokio::test]
+    async fn max_ejected() {
+        time::pause();
+        let config = Config {
+            consecutive_failures: Some(1),
+            failure_rate: None,
+            backoff: BACKOFF,
+            max_ejected: 0.5,
+        };
+        let balancer = Arc::new(Balancer::default());
+        let metrics = Arc::new(metrics::Metrics::default());
+        let mut svcs = (0..4)
+            .map(|_| balanced(config, metrics.clone(), balancer.clone()))
+            .collect::<Vec<_>>();
+
+        for svc in svcs.iter_mut() {
+            svc.ready().await.unwrap().call(1).await.unwrap();
+        }
+        let ejected = futures::future::join_all(svcs.iter_mut().map(is_ready)).await;
+        assert_eq!(
+            ejected,
+            vec![false, false, true, true],
+            "only half of the endpoints may be ejected"
+        );
+        assert_eq!(metrics.ejected.value(), 2);
+
+        // Once an ejected endpoint is probed, another may be ejected.
+        time::advance(Duration::from_millis(1_001)).await;
+        assert!(is_ready(&mut svcs[0]).await, "must be probed");
+        svcs[2].ready().await.unwrap().call(1).await.unwrap();
+        assert!(!is_ready(&mut svcs[2]).await, "must be ejected");
+        assert_eq!(metrics.ejected.value(), 2);
+
+        // Endpoints that are removed from the balancer are no longer counted.
+        drop(svcs.drain(..2));
+        assert_eq!(balancer.endpoints.load(Ordering::Acquire), 2);
+        assert_eq!(balancer.ejected.load(Ordering::Acquire), 1);
+    }
+}
@@ -3,6 +3,7 @@
 use linkerd_proxy_core::Resolve;
 
 pub mod buffer;
+pub mod eject;
 pub mod from_resolve;
 pub mod make_endpoint;
 
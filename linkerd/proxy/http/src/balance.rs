@@ -1,13 +1,21 @@
-use crate::Error;
+use crate::{BoxResponse, ClientHandle, Error};
 use hyper::body::HttpBody;
 pub use hyper_balance::{PendingUntilFirstData, PendingUntilFirstDataBody};
+use linkerd_proxy_balance::{self as balance, HashRequest, RingHash, RoundRobin};
+pub use linkerd_proxy_balance::{HashKey, MakeBalance, Strategy};
 use rand::thread_rng;
-use std::{hash::Hash, marker::PhantomData, time::Duration};
-use tower::discover::Discover;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    time::Duration,
+};
+use tower::layer::Layer as _;
 pub use tower::{
     balance::p2c::Balance,
     load::{Load, PeakEwmaDiscover},
 };
+use tower::{discover::Discover, load::PendingRequestsDiscover, util::Either};
 
 /// Configures a stack to resolve `T` typed targets to balance requests over
 /// `M`-typed endpoint stacks.
@@ -57,3 +65,107 @@ where
         Balance::from_rng(loaded, &mut thread_rng()).expect("RNG must be valid")
     }
 }
+
+/// Builds a balancer for each of the supported balancing strategies.
+///
+/// Power-of-two-choices balancers treat a request as pending until the first
+/// frame of its response body is received.
+#[derive(Debug)]
+pub struct NewBalance<A, B> {
+    decay: Duration,
+    default_rtt: Duration,
+    _marker: PhantomData<fn(A) -> B>,
+}
+
+/// A balancer that distributes requests according to a `Strategy`.
+pub type StrategyBalance<D, A> = Either<
+    Either<
+        BoxResponse<Balance<PeakEwmaDiscover<D, PendingUntilFirstData>, http::Request<A>>>,
+        BoxResponse<Balance<PendingRequestsDiscover<D, PendingUntilFirstData>, http::Request<A>>>,
+    >,
+    Either<
+        BoxResponse<balance::Balance<D, RoundRobin<<D as Discover>::Key>, http::Request<A>>>,
+        BoxResponse<
+            balance::Balance<D, RingHash<<D as Discover>::Key, RequestHash>, http::Request<A>>,
+        >,
+    >,
+>;
+
+/// Hashes requests for ring-hash balancers.
+#[derive(Clone, Debug)]
+pub struct RequestHash(HashKey);
+
+// === impl NewBalance ===
+
+impl<A, B> NewBalance<A, B> {
+    pub fn new(default_rtt: Duration, decay: Duration) -> Self {
+        Self {
+            decay,
+            default_rtt,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A, B> Clone for NewBalance<A, B> {
+    fn clone(&self) -> Self {
+        Self::new(self.default_rtt, self.decay)
+    }
+}
+
+impl<D, S, A, B> balance::NewBalance<D> for NewBalance<A, B>
+where
+    A: HttpBody,
+    B: HttpBody,
+    D: Discover<Service = S>,
+    D::Key: Hash,
+    S: tower::Service<http::Request<A>, Response = http::Response<B>>,
+    S::Error: Into<Error>,
+{
+    type Service = StrategyBalance<D, A>;
+
+    fn new_balance(&self, strategy: &Strategy, discover: D) -> Self::Service {
+        let instrument = PendingUntilFirstData::default();
+        match strategy {
+            Strategy::PeakEwma => {
+                let loaded =
+                    PeakEwmaDiscover::new(discover, self.default_rtt, self.decay, instrument);
+                let balance =
+                    Balance::from_rng(loaded, &mut thread_rng()).expect("RNG must be valid");
+                Either::A(Either::A(BoxResponse::layer().layer(balance)))
+            }
+            Strategy::LeastRequest => {
+                let loaded = PendingRequestsDiscover::new(discover, instrument);
+                let balance =
+                    Balance::from_rng(loaded, &mut thread_rng()).expect("RNG must be valid");
+                Either::A(Either::B(BoxResponse::layer().layer(balance)))
+            }
+            Strategy::RoundRobin => {
+                let balance = balance::Balance::new(discover, RoundRobin::default());
+                Either::B(Either::A(BoxResponse::layer().layer(balance)))
+            }
+            Strategy::RingHash(key) => {
+                let hash = RingHash::new(RequestHash(key.clone()));
+                let balance = balance::Balance::new(discover, hash);
+                Either::B(Either::B(BoxResponse::layer().layer(balance)))
+            }
+        }
+    }
+}
+
+// === impl RequestHash ===
+
+impl<B> HashRequest<http::Request<B>> for RequestHash {
+    fn hash_request(&self, req: &http::Request<B>) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        match self.0 {
+            HashKey::Header(ref name) => req.headers().get(name)?.as_bytes().hash(&mut hasher),
+            HashKey::ClientAddr => {
+                // Client ports vary by connection, so only the IP is hashed.
+                let client = req.extensions().get::<ClientHandle>()?;
+                client.addr.ip().hash(&mut hasher)
+            }
+        }
+        Some(hasher.finish())
+    }
+}
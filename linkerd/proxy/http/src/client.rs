@@ -10,6 +10,7 @@ use futures::prelude::*;
 use linkerd_error::Error;
 use linkerd_stack::{layer, Param};
 use std::{
+    hash::Hash,
     marker::PhantomData,
     pin::Pin,
     task::{Context, Poll},
@@ -25,10 +26,11 @@ pub enum Settings {
     OrigProtoUpgrade,
 }
 
-pub struct MakeClient<C, B> {
+pub struct MakeClient<C, B, M = ()> {
     connect: C,
     h1_pool: h1::PoolSettings,
     h2_settings: h2::Settings,
+    pool_metrics: M,
     _marker: PhantomData<fn(B)>,
 }
 
@@ -46,6 +48,26 @@ pub fn layer<C, B>(
         connect,
         h1_pool,
         h2_settings,
+        pool_metrics: (),
+        _marker: PhantomData,
+    })
+}
+
+/// Like `layer`, but reports the state of each target's HTTP/1 connection pool
+/// to `pool_metrics`.
+pub fn layer_with_pool_metrics<C, B, L>(
+    h1_pool: h1::PoolSettings,
+    h2_settings: h2::Settings,
+    pool_metrics: h1::pool::Registry<L>,
+) -> impl layer::Layer<C, Service = MakeClient<C, B, h1::pool::Registry<L>>> + Clone
+where
+    L: Hash + Eq,
+{
+    layer::mk(move |connect: C| MakeClient {
+        connect,
+        h1_pool,
+        h2_settings,
+        pool_metrics: pool_metrics.clone(),
         _marker: PhantomData,
     })
 }
@@ -64,10 +86,11 @@ impl From<crate::Version> for Settings {
 type MakeFuture<C, T, B> =
     Pin<Box<dyn Future<Output = Result<Client<C, T, B>, Error>> + Send + 'static>>;
 
-impl<C, T, B> tower::Service<T> for MakeClient<C, B>
+impl<C, T, B, M> tower::Service<T> for MakeClient<C, B, M>
 where
     T: Clone + Send + Sync + 'static,
     T: Param<Settings>,
+    M: h1::pool::NewMetrics<T>,
     C: tower::make::MakeConnection<T> + Clone + Unpin + Send + Sync + 'static,
     C::Future: Unpin + Send + 'static,
     C::Error: Into<Error>,
@@ -88,6 +111,7 @@ where
         let connect = self.connect.clone();
         let h1_pool = self.h1_pool;
         let h2_settings = self.h2_settings;
+        let pool_metrics = self.pool_metrics.new_metrics(&target);
 
         Box::pin(async move {
             let settings = target.param();
@@ -100,12 +124,14 @@ where
                         .await?;
                     Client::H2(h2)
                 }
-                Settings::Http1 => Client::Http1(h1::Client::new(connect, target, h1_pool)),
+                Settings::Http1 => {
+                    Client::Http1(h1::Client::new(connect, target, h1_pool, pool_metrics))
+                }
                 Settings::OrigProtoUpgrade => {
                     let h2 = h2::Connect::new(connect.clone(), h2_settings)
                         .oneshot(target.clone())
                         .await?;
-                    let http1 = h1::Client::new(connect, target, h1_pool);
+                    let http1 = h1::Client::new(connect, target, h1_pool, pool_metrics);
                     Client::OrigProtoUpgrade(orig_proto::Upgrade::new(http1, h2))
                 }
             };
@@ -115,12 +141,13 @@ where
     }
 }
 
-impl<C: Clone, B> Clone for MakeClient<C, B> {
+impl<C: Clone, B, M: Clone> Clone for MakeClient<C, B, M> {
     fn clone(&self) -> Self {
         Self {
             connect: self.connect.clone(),
             h1_pool: self.h1_pool,
             h2_settings: self.h2_settings,
+            pool_metrics: self.pool_metrics.clone(),
             _marker: self._marker,
         }
     }
@@ -1,4 +1,4 @@
-use crate::{upgrade::Http11Upgrade, HasH2Reason};
+use crate::{h1::pool, upgrade::Http11Upgrade, HasH2Reason};
 use bytes::Bytes;
 use futures::TryFuture;
 use hyper::body::HttpBody;
@@ -19,6 +19,10 @@ pub struct UpgradeBody {
     /// to be inserted into the Http11Upgrade half.
     body: hyper::Body,
     pub(super) upgrade: Option<(Http11Upgrade, hyper::upgrade::OnUpgrade)>,
+
+    /// Set on HTTP/1 client responses so that the request is counted by the
+    /// connection pool until its response body completes.
+    in_flight: Option<pool::InFlight>,
 }
 
 /// Glue for a `tower::Service` to used as a `hyper::server::Service`.
@@ -33,22 +37,30 @@ pub struct HyperConnect<C, T> {
     connect: C,
     absolute_form: bool,
     target: T,
+    pool: pool::Pool,
 }
 
 #[pin_project]
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Connection<T> {
     #[pin]
     transport: T,
     absolute_form: bool,
+    _pool: pool::Connected,
 }
 
 /// Future returned by `HyperConnect`.
+///
+/// Waits for the pool to permit a new connection before connecting, failing if
+/// it doesn't within the connect timeout.
 #[pin_project]
 pub struct HyperConnectFuture<F> {
+    reserve: Option<pool::Reserve>,
+    permit: Option<tokio::sync::OwnedSemaphorePermit>,
     #[pin]
     inner: F,
     absolute_form: bool,
+    pool: pool::Pool,
 }
 // ===== impl UpgradeBody =====
 
@@ -64,9 +76,13 @@ impl HttpBody for UpgradeBody {
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
-        let body = self.project().body;
-        let poll = futures::ready!(Pin::new(body) // `hyper::Body` is Unpin
+        let this = self.project();
+        let poll = futures::ready!(Pin::new(this.body) // `hyper::Body` is Unpin
             .poll_data(cx));
+        if poll.is_none() {
+            // The connection is released once the body completes.
+            drop(this.in_flight.take());
+        }
         Poll::Ready(poll.map(|x| {
             x.map_err(|e| {
                 debug!("http body error: {}", e);
@@ -100,6 +116,7 @@ impl From<hyper::Body> for UpgradeBody {
         Self {
             body,
             upgrade: None,
+            in_flight: None,
         }
     }
 }
@@ -109,7 +126,16 @@ impl UpgradeBody {
         body: hyper::Body,
         upgrade: Option<(Http11Upgrade, hyper::upgrade::OnUpgrade)>,
     ) -> Self {
-        Self { body, upgrade }
+        Self {
+            body,
+            upgrade,
+            in_flight: None,
+        }
+    }
+
+    pub(crate) fn with_in_flight(mut self, in_flight: Option<pool::InFlight>) -> Self {
+        self.in_flight = in_flight;
+        self
     }
 }
 
@@ -152,11 +178,12 @@ where
 // ===== impl HyperConnect =====
 
 impl<C, T> HyperConnect<C, T> {
-    pub(super) fn new(connect: C, target: T, absolute_form: bool) -> Self {
+    pub(super) fn new(connect: C, target: T, absolute_form: bool, pool: pool::Pool) -> Self {
         HyperConnect {
             connect,
             absolute_form,
             target,
+            pool,
         }
     }
 }
@@ -180,8 +207,11 @@ where
 
     fn call(&mut self, _dst: hyper::Uri) -> Self::Future {
         HyperConnectFuture {
+            reserve: Some(self.pool.reserve()),
+            permit: None,
             inner: self.connect.make_connection(self.target.clone()),
             absolute_form: self.absolute_form,
+            pool: self.pool.clone(),
         }
     }
 }
@@ -195,10 +225,15 @@ where
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
+        if let Some(reserve) = this.reserve.as_mut() {
+            *this.permit = futures::ready!(reserve.as_mut().poll(cx))?;
+            *this.reserve = None;
+        }
         let transport = futures::ready!(this.inner.try_poll(cx)).map_err(Into::into)?;
         Poll::Ready(Ok(Connection {
             transport,
             absolute_form: *this.absolute_form,
+            _pool: this.pool.connected(this.permit.take()),
         }))
     }
 }
@@ -8,9 +8,11 @@ use http::{
     uri::{Authority, Parts, Scheme, Uri},
 };
 use linkerd_error::Error;
-use std::{future::Future, mem, pin::Pin, time::Duration};
+use std::{future::Future, mem, pin::Pin, sync::Arc, time::Duration};
 use tracing::{debug, trace};
 
+pub mod pool;
+
 #[derive(Copy, Clone, Debug)]
 pub struct WasAbsoluteForm(pub(crate) ());
 
@@ -18,6 +20,14 @@ pub struct WasAbsoluteForm(pub(crate) ());
 pub struct PoolSettings {
     pub max_idle: usize,
     pub idle_timeout: Duration,
+
+    /// Limits the number of connections to each endpoint. When the limit is
+    /// reached, requests wait for a connection to become idle or to close.
+    pub max_connections: Option<usize>,
+
+    /// Bounds how long a request waits for the connection limit before
+    /// failing, as though its connection had timed out.
+    pub connect_timeout: Duration,
 }
 
 /// Communicates with HTTP/1.x servers.
@@ -33,15 +43,26 @@ pub struct Client<C, T, B> {
     absolute_form: Option<hyper::Client<HyperConnect<C, T>, B>>,
     origin_form: Option<hyper::Client<HyperConnect<C, T>, B>>,
     pool: PoolSettings,
+
+    /// Shared by both of the absolute-form and origin-form pools, as well as
+    /// one-off clients, so that all connections to the target are limited and
+    /// reported together.
+    connections: pool::Pool,
 }
 
 impl<C, T, B> Client<C, T, B> {
-    pub fn new(connect: C, target: T, pool: PoolSettings) -> Self {
+    pub fn new(
+        connect: C,
+        target: T,
+        pool: PoolSettings,
+        metrics: Option<Arc<pool::Metrics>>,
+    ) -> Self {
         Self {
             connect,
             target,
             absolute_form: None,
             origin_form: None,
+            connections: pool::Pool::new(pool.max_connections, pool.connect_timeout, metrics),
             pool,
         }
     }
@@ -55,6 +76,7 @@ impl<C: Clone, T: Clone, B> Clone for Client<C, T, B> {
             absolute_form: self.absolute_form.clone(),
             origin_form: self.origin_form.clone(),
             pool: self.pool,
+            connections: self.connections.clone(),
         }
     }
 }
@@ -89,6 +111,7 @@ where
             .map(|v| v.is_empty())
             .unwrap_or(true);
 
+        let in_flight = self.connections.dispatch();
         let rsp_fut = if req.version() == http::Version::HTTP_10 || is_missing_host {
             // If there's no authority, we assume we're on some weird HTTP/1.0
             // ish, so we just build a one-off client for the connection.
@@ -101,6 +124,7 @@ where
                     self.connect.clone(),
                     self.target.clone(),
                     use_absolute_form,
+                    self.connections.clone(),
                 ))
                 .request(req)
         } else {
@@ -127,6 +151,7 @@ where
                             self.connect.clone(),
                             self.target.clone(),
                             use_absolute_form,
+                            self.connections.clone(),
                         )),
                 );
             }
@@ -152,7 +177,7 @@ where
                 strip_connection_headers(rsp.headers_mut());
             }
 
-            rsp.map(UpgradeBody::from)
+            rsp.map(|body| UpgradeBody::from(body).with_in_flight(in_flight))
         }))
     }
 }
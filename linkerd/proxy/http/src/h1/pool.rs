@@ -0,0 +1,361 @@
+//! Limits and instruments the connections in an HTTP/1 client's pool.
+//!
+//! Hyper's pool is opaque, so the pool is observed from outside of it: a
+//! connection is counted from when it is established until its transport is
+//! dropped, and a request is counted from when it is dispatched until its
+//! response body completes. Because an HTTP/1 connection serves one request at
+//! a time, requests in excess of the open connections are waiting for one.
+//!
+//! A `Registry` shares an endpoint's connection limit between all of its
+//! clients, so that the limit holds as clients are rebuilt.
+
+use indexmap::IndexMap;
+use linkerd_metrics::{metrics, Counter, FmtLabels, FmtMetrics, Gauge};
+use linkerd_stack::Param;
+use std::{
+    fmt,
+    future::Future,
+    hash::Hash,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time,
+};
+use tracing::trace;
+
+metrics! {
+    http_client_pool_connections: Gauge {
+        "The number of open HTTP/1 connections to an endpoint"
+    },
+    http_client_pool_in_use_connections: Gauge {
+        "The number of open HTTP/1 connections to an endpoint that are serving a request"
+    },
+    http_client_pool_idle_connections: Gauge {
+        "The number of open HTTP/1 connections to an endpoint that are idle"
+    },
+    http_client_pool_pending_requests: Gauge {
+        "The number of HTTP/1 requests to an endpoint that are waiting for a connection"
+    },
+    http_client_pool_misses_total: Counter {
+        "Total number of times an HTTP/1 request to an endpoint could not use an idle connection"
+    }
+}
+
+type Shared<L> = Arc<Mutex<IndexMap<L, Arc<Metrics>>>>;
+
+/// Reports the state of the connection pool for each `L`-labeled endpoint.
+///
+/// Endpoints are reported for as long as their clients or connections are held.
+#[derive(Debug)]
+pub struct Registry<L: Hash + Eq>(Shared<L>);
+
+/// Builds the metrics for a target's pool, if it is instrumented.
+pub trait NewMetrics<T> {
+    fn new_metrics(&self, target: &T) -> Option<Arc<Metrics>>;
+}
+
+/// The state of an endpoint's connection pool, shared by all of its clients.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    connections: Gauge,
+    requests: Gauge,
+    misses: Counter,
+
+    /// Set by the first client that limits the endpoint's connections.
+    limit: Mutex<Option<Arc<Semaphore>>>,
+}
+
+/// Indicates that a new connection could not be opened because the
+/// endpoint's connection limit was reached for the entire connect timeout.
+#[derive(Clone, Debug)]
+pub struct LimitTimeout(Duration);
+
+/// The connections to a single endpoint.
+#[derive(Clone, Debug)]
+pub(crate) struct Pool {
+    /// Unset when the number of connections is not limited.
+    limit: Option<Arc<Semaphore>>,
+    connect_timeout: Duration,
+    metrics: Option<Arc<Metrics>>,
+}
+
+pub(crate) type Reserve = Pin<
+    Box<dyn Future<Output = Result<Option<OwnedSemaphorePermit>, LimitTimeout>> + Send + 'static>,
+>;
+
+/// Held by a connection for as long as it is open.
+#[derive(Debug)]
+pub(crate) struct Connected {
+    _permit: Option<OwnedSemaphorePermit>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+/// Held by a request until its response completes.
+#[derive(Debug)]
+pub(crate) struct InFlight(Arc<Metrics>);
+
+// === impl Registry ===
+
+impl<L: Hash + Eq> Default for Registry<L> {
+    fn default() -> Self {
+        Registry(Shared::default())
+    }
+}
+
+impl<L: Hash + Eq> Clone for Registry<L> {
+    fn clone(&self) -> Self {
+        Registry(self.0.clone())
+    }
+}
+
+impl<T, L> NewMetrics<T> for Registry<L>
+where
+    T: Param<L>,
+    L: Hash + Eq,
+{
+    fn new_metrics(&self, target: &T) -> Option<Arc<Metrics>> {
+        let labels = target.param();
+        let mut metrics = self.0.lock().expect("pool metrics lock poisoned");
+        if !metrics.contains_key(&labels) {
+            // Drop endpoints whose clients have been dropped, in case the
+            // registry is not reported.
+            metrics.retain(|_, m| Arc::strong_count(m) > 1);
+        }
+        Some(metrics.entry(labels).or_default().clone())
+    }
+}
+
+impl<T> NewMetrics<T> for () {
+    fn new_metrics(&self, _: &T) -> Option<Arc<Metrics>> {
+        None
+    }
+}
+
+impl<L: FmtLabels + Hash + Eq> FmtMetrics for Registry<L> {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut metrics = self.0.lock().expect("pool metrics lock poisoned");
+
+        // Drop endpoints whose clients have been dropped.
+        metrics.retain(|_, m| Arc::strong_count(m) > 1);
+        if metrics.is_empty() {
+            return Ok(());
+        }
+
+        http_client_pool_connections.fmt_help(f)?;
+        http_client_pool_connections.fmt_scopes(f, metrics.iter(), |m| &m.connections)?;
+
+        http_client_pool_in_use_connections.fmt_help(f)?;
+        for (labels, m) in metrics.iter() {
+            let in_use = Gauge::from(m.in_use());
+            http_client_pool_in_use_connections.fmt_metric_labeled(f, &in_use, labels)?;
+        }
+
+        http_client_pool_idle_connections.fmt_help(f)?;
+        for (labels, m) in metrics.iter() {
+            let idle = Gauge::from(m.connections.value() - m.in_use());
+            http_client_pool_idle_connections.fmt_metric_labeled(f, &idle, labels)?;
+        }
+
+        http_client_pool_pending_requests.fmt_help(f)?;
+        for (labels, m) in metrics.iter() {
+            let pending = Gauge::from(m.requests.value().saturating_sub(m.in_use()));
+            http_client_pool_pending_requests.fmt_metric_labeled(f, &pending, labels)?;
+        }
+
+        http_client_pool_misses_total.fmt_help(f)?;
+        http_client_pool_misses_total.fmt_scopes(f, metrics.iter(), |m| &m.misses)?;
+
+        Ok(())
+    }
+}
+
+// === impl Metrics ===
+
+impl Metrics {
+    fn in_use(&self) -> u64 {
+        self.connections.value().min(self.requests.value())
+    }
+
+    fn limit(&self, max: usize) -> Arc<Semaphore> {
+        self.limit
+            .lock()
+            .expect("pool limit lock poisoned")
+            .get_or_insert_with(|| Arc::new(Semaphore::new(max)))
+            .clone()
+    }
+}
+
+// === impl LimitTimeout ===
+
+impl fmt::Display for LimitTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "connection limit reached; no connection available after {:?}",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for LimitTimeout {}
+
+// === impl Pool ===
+
+impl Pool {
+    /// Limits connections to `max_connections`. When the endpoint's pool is
+    /// shared (i.e. `metrics` is set), so is the limit.
+    pub(crate) fn new(
+        max_connections: Option<usize>,
+        connect_timeout: Duration,
+        metrics: Option<Arc<Metrics>>,
+    ) -> Self {
+        let limit = max_connections.map(|max| match metrics.as_ref() {
+            Some(metrics) => metrics.limit(max),
+            None => Arc::new(Semaphore::new(max)),
+        });
+        Self {
+            limit,
+            connect_timeout,
+            metrics,
+        }
+    }
+
+    /// Waits until a new connection may be opened without exceeding the limit,
+    /// failing if none may be opened within the connect timeout.
+    ///
+    /// This is only called when the pool has no idle connection for a request.
+    /// Meanwhile, the request may still be served by a connection that becomes
+    /// idle, in which case the new connection is added to the pool (or
+    /// abandoned, if the limit is not released in time).
+    pub(crate) fn reserve(&self) -> Reserve {
+        let pool = self.clone();
+        Box::pin(async move {
+            if let Some(metrics) = pool.metrics.as_ref() {
+                metrics.misses.incr();
+            }
+            match pool.limit {
+                None => Ok(None),
+                Some(limit) => {
+                    if limit.available_permits() == 0 {
+                        trace!("Waiting for a connection");
+                    }
+                    let timeout = pool.connect_timeout;
+                    let permit = time::timeout(timeout, limit.acquire_owned())
+                        .await
+                        .map_err(|_| LimitTimeout(timeout))?
+                        .expect("pool semaphore must not be closed");
+                    Ok(Some(permit))
+                }
+            }
+        })
+    }
+
+    /// Tracks a connection opened with a reservation.
+    pub(crate) fn connected(&self, permit: Option<OwnedSemaphorePermit>) -> Connected {
+        if let Some(metrics) = self.metrics.as_ref() {
+            metrics.connections.incr();
+        }
+        Connected {
+            _permit: permit,
+            metrics: self.metrics.clone(),
+        }
+    }
+
+    /// Tracks a request dispatched to the pool.
+    pub(crate) fn dispatch(&self) -> Option<InFlight> {
+        let metrics = self.metrics.clone()?;
+        metrics.requests.incr();
+        Some(InFlight(metrics))
+    }
+}
+
+// === impl Connected ===
+
+impl Drop for Connected {
+    fn drop(&mut self) {
+        if let Some(metrics) = self.metrics.as_ref() {
+            metrics.connections.decr();
+        }
+    }
+}
+
+// === impl InFlight ===
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.requests.decr();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[tokio::test]
+    async fn limits_connections() {
+        let metrics = Arc::new(Metrics::default());
+        let pool = Pool::new(Some(1), CONNECT_TIMEOUT, Some(metrics.clone()));
+
+        let conn = pool.connected(pool.reserve().await.unwrap());
+        let mut reserve = pool.reserve();
+        assert!(futures::poll!(reserve.as_mut()).is_pending());
+        assert_eq!(metrics.connections.value(), 1);
+        assert_eq!(metrics.misses.value(), 2.0);
+
+        // Closing the connection allows another to be opened.
+        drop(conn);
+        assert_eq!(metrics.connections.value(), 0);
+        let _conn = pool.connected(reserve.await.unwrap());
+        assert_eq!(metrics.connections.value(), 1);
+    }
+
+    #[tokio::test]
+    async fn limit_wait_times_out() {
+        time::pause();
+        let pool = Pool::new(Some(1), CONNECT_TIMEOUT, None);
+
+        let _conn = pool.connected(pool.reserve().await.unwrap());
+        let reserve = pool.reserve();
+        time::advance(CONNECT_TIMEOUT).await;
+        assert!(reserve.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn shares_limit_across_clients() {
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        struct Labels;
+
+        let registry = Registry::<Labels>::default();
+        let pool0 = Pool::new(Some(1), CONNECT_TIMEOUT, registry.new_metrics(&Labels));
+        let _conn = pool0.connected(pool0.reserve().await.unwrap());
+
+        // A rebuilt client for the same endpoint is bound by the same limit.
+        let pool1 = Pool::new(Some(1), CONNECT_TIMEOUT, registry.new_metrics(&Labels));
+        let mut reserve = pool1.reserve();
+        assert!(futures::poll!(reserve.as_mut()).is_pending());
+    }
+
+    #[test]
+    fn in_use_is_bounded_by_requests() {
+        let metrics = Arc::new(Metrics::default());
+        let pool = Pool::new(None, CONNECT_TIMEOUT, Some(metrics.clone()));
+
+        let _c0 = pool.connected(None);
+        let _c1 = pool.connected(None);
+        let r0 = pool.dispatch();
+        assert_eq!(metrics.in_use(), 1);
+
+        let _r1 = pool.dispatch();
+        let _r2 = pool.dispatch();
+        assert_eq!(metrics.in_use(), 2);
+        assert_eq!(metrics.requests.value(), 3);
+
+        drop(r0);
+        assert_eq!(metrics.in_use(), 2);
+    }
+}
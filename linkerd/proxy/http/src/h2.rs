@@ -20,6 +20,21 @@ use tracing::{debug, debug_span, trace_span};
 pub struct Settings {
     pub initial_stream_window_size: Option<u32>,
     pub initial_connection_window_size: Option<u32>,
+
+    /// Sizes flow control windows from an estimate of the connection's
+    /// bandwidth-delay product. When enabled, the initial window sizes are
+    /// ignored.
+    pub adaptive_window: bool,
+
+    /// Limits the number of streams that a client may open concurrently.
+    ///
+    /// Only applies to servers; clients honor the limit advertised by the
+    /// server.
+    pub max_concurrent_streams: Option<u32>,
+
+    /// The largest frame payload that the peer may send.
+    pub max_frame_size: Option<u32>,
+
     pub keepalive_timeout: Option<Duration>,
 }
 
@@ -82,7 +97,10 @@ where
         let Settings {
             initial_connection_window_size,
             initial_stream_window_size,
+            adaptive_window,
+            max_frame_size,
             keepalive_timeout,
+            ..
         } = self.h2_settings;
 
         let connect = self
@@ -98,6 +116,8 @@ where
                     .http2_only(true)
                     .http2_initial_stream_window_size(initial_stream_window_size)
                     .http2_initial_connection_window_size(initial_connection_window_size)
+                    .http2_adaptive_window(adaptive_window)
+                    .http2_max_frame_size(max_frame_size)
                     .executor(trace::Executor::new());
 
                 // Configure HTTP/2 PING frames
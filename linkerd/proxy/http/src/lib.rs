@@ -41,7 +41,7 @@ pub use http::{
     uri, Request, Response, StatusCode,
 };
 pub use hyper::body::HttpBody;
-pub use linkerd_http_box::{BoxBody, BoxRequest, BoxResponse};
+pub use linkerd_http_box::{BoxBody, BoxRequest, BoxResponse, EraseRequest};
 use std::str::FromStr;
 
 #[derive(Clone, Debug)]
@@ -81,11 +81,11 @@ where
     header_value_from_request(req, header, |s: &str| s.parse::<Authority>().ok())
 }
 
-pub fn identity_from_header<B, K>(req: &http::Request<B>, header: K) -> Option<identity::Name>
+pub fn identity_from_header<B, K>(req: &http::Request<B>, header: K) -> Option<identity::Id>
 where
     K: AsHeaderName,
 {
-    header_value_from_request(req, header, |s: &str| identity::Name::from_str(s).ok())
+    header_value_from_request(req, header, |s: &str| identity::Id::from_str(s).ok())
 }
 
 fn header_value_from_request<B, K, F, T>(
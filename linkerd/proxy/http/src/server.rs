@@ -49,7 +49,10 @@ impl<N> NewServeHttp<N> {
         let mut server = hyper::server::conn::Http::new().with_executor(trace::Executor::new());
         server
             .http2_initial_stream_window_size(h2.initial_stream_window_size)
-            .http2_initial_connection_window_size(h2.initial_connection_window_size);
+            .http2_initial_connection_window_size(h2.initial_connection_window_size)
+            .http2_adaptive_window(h2.adaptive_window)
+            .http2_max_concurrent_streams(h2.max_concurrent_streams)
+            .http2_max_frame_size(h2.max_frame_size);
 
         // Configure HTTP/2 PING frames
         if let Some(timeout) = h2.keepalive_timeout {
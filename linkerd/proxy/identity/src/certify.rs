@@ -1,3 +1,5 @@
+use crate::trust_anchors;
+use futures::prelude::*;
 use http_body::Body as HttpBody;
 use linkerd2_proxy_api::identity as api;
 use linkerd_error::Error;
@@ -16,12 +18,12 @@ use tonic::{
     body::{Body, BoxBody},
     client::GrpcService,
 };
-use tracing::{debug, error, trace};
+use tracing::{debug, error, trace, warn};
 
 /// Configures the Identity service and local identity.
 #[derive(Clone, Debug)]
 pub struct Config {
-    pub trust_anchors: id::TrustAnchors,
+    pub trust_anchors: trust_anchors::Config,
     pub key: id::Key,
     pub csr: id::Csr,
     pub token: id::TokenSource,
@@ -36,7 +38,7 @@ pub struct Config {
 #[pin_project]
 #[derive(Clone, Debug)]
 pub struct LocalCrtKey {
-    trust_anchors: id::TrustAnchors,
+    trust_anchors: trust_anchors::Receiver,
     id: id::LocalId,
     crt_key: watch::Receiver<Option<id::CrtKey>>,
     refreshes: Arc<Counter>,
@@ -55,6 +57,8 @@ pub type CrtKeySender = watch::Sender<Option<id::CrtKey>>;
 pub struct Daemon {
     crt_key_watch: CrtKeySender,
     refreshes: Arc<linkerd_metrics::Counter>,
+    trust_anchors: trust_anchors::Receiver,
+    reload: trust_anchors::Daemon,
     config: Config,
 }
 
@@ -94,90 +98,146 @@ impl Daemon {
         let Self {
             crt_key_watch,
             refreshes,
+            mut trust_anchors,
+            reload,
             config,
         } = self;
 
         debug!("Identity daemon running");
-        let mut curr_expiry = UNIX_EPOCH;
-        let mut client = api::identity_client::IdentityClient::new(client);
-
-        loop {
-            match config.token.load() {
-                Ok(token) => {
-                    let req = grpc::Request::new(api::CertifyRequest {
-                        token,
-                        identity: config.local_id.to_string(),
-                        certificate_signing_request: config.csr.to_vec(),
-                    });
-                    trace!("daemon certifying");
-                    let rsp = client.certify(req).await;
-                    match rsp {
-                        Err(e) => error!("Failed to certify identity: {}", e),
-                        Ok(rsp) => {
-                            let api::CertifyResponse {
-                                leaf_certificate,
-                                intermediate_certificates,
-                                valid_until,
-                            } = rsp.into_inner();
-                            match valid_until.and_then(|d| SystemTime::try_from(d).ok()) {
-                                None => error!(
-                                    "Identity service did not specify a certificate expiration."
-                                ),
-                                Some(expiry) => {
-                                    let key = config.key.clone();
-                                    let crt = id::Crt::new(
-                                        config.local_id.clone(),
-                                        leaf_certificate,
-                                        intermediate_certificates,
-                                        expiry,
-                                    );
-
-                                    match config.trust_anchors.certify(key, crt) {
-                                        Err(e) => {
-                                            error!("Received invalid certificate: {}", e);
-                                        }
-                                        Ok(crt_key) => {
-                                            debug!("daemon certified until {:?}", expiry);
-                                            if crt_key_watch.send(Some(crt_key)).is_err() {
-                                                // If we can't store a value, than all observations
-                                                // have been dropped and we can stop refreshing.
-                                                return;
+        let certify = async move {
+            let mut curr_expiry = UNIX_EPOCH;
+            let mut curr_crt = None;
+            let mut client = api::identity_client::IdentityClient::new(client);
+
+            loop {
+                match config.token.load() {
+                    Ok(token) => {
+                        let req = grpc::Request::new(api::CertifyRequest {
+                            token,
+                            identity: config.local_id.to_string(),
+                            certificate_signing_request: config.csr.to_vec(),
+                        });
+                        trace!("daemon certifying");
+                        let rsp = client.certify(req).await;
+                        match rsp {
+                            Err(e) => error!("Failed to certify identity: {}", e),
+                            Ok(rsp) => {
+                                let api::CertifyResponse {
+                                    leaf_certificate,
+                                    intermediate_certificates,
+                                    valid_until,
+                                } = rsp.into_inner();
+                                match valid_until.and_then(|d| SystemTime::try_from(d).ok()) {
+                                    None => error!(
+                                        "Identity service did not specify a certificate expiration."
+                                    ),
+                                    Some(expiry) => {
+                                        let key = config.key.clone();
+                                        let crt = id::Crt::new(
+                                            config.local_id.clone(),
+                                            leaf_certificate,
+                                            intermediate_certificates,
+                                            expiry,
+                                        );
+
+                                        let trust_anchors = trust_anchors.borrow().clone();
+                                        match trust_anchors.certify(key, crt.clone()) {
+                                            Err(e) => {
+                                                error!("Received invalid certificate: {}", e);
                                             }
+                                            Ok(crt_key) => {
+                                                debug!("daemon certified until {:?}", expiry);
+                                                if crt_key_watch.send(Some(crt_key)).is_err() {
+                                                    // If we can't store a value, than all observations
+                                                    // have been dropped and we can stop refreshing.
+                                                    return;
+                                                }
 
-                                            refreshes.incr();
-                                            curr_expiry = expiry;
+                                                refreshes.incr();
+                                                curr_expiry = expiry;
+                                                curr_crt = Some(crt);
+                                            }
                                         }
                                     }
                                 }
                             }
                         }
                     }
+                    Err(e) => error!("Failed to read authentication token: {}", e),
+                }
+
+                // Wait to refresh the certificate. If the trust anchors change
+                // in the meantime, the current certificate is certified again
+                // so that new connections use the new trust anchors.
+                let mut refresh = Box::pin(config.refresh(curr_expiry)).fuse();
+                loop {
+                    let changed = futures::select_biased! {
+                        () = refresh => break,
+                        changed = trust_anchors.changed().fuse() => changed,
+                    };
+                    if changed.is_err() {
+                        // The trust anchors are no longer being reloaded, so
+                        // the daemon is shutting down.
+                        return;
+                    }
+
+                    if let Some(crt) = curr_crt.clone() {
+                        let trust_anchors = trust_anchors.borrow().clone();
+                        match trust_anchors.certify(config.key.clone(), crt) {
+                            Err(e) => warn!(
+                                "Current certificate is not valid with the new trust anchors: {}",
+                                e
+                            ),
+                            Ok(crt_key) => {
+                                debug!("daemon certified with new trust anchors");
+                                if crt_key_watch.send(Some(crt_key)).is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                    }
                 }
-                Err(e) => error!("Failed to read authentication token: {}", e),
             }
-            config.refresh(curr_expiry).await;
-        }
+        };
+
+        // The trust anchors are reloaded for as long as the identity is.
+        futures::pin_mut!(certify);
+        futures::future::select(certify, Box::pin(reload.run())).await;
     }
 }
 
 // === impl LocalCrtKey ===
 
 impl LocalCrtKey {
-    pub fn new(config: &Config) -> (Self, Daemon) {
+    /// Fails if the trust anchors can't be loaded.
+    pub fn new(config: &Config) -> Result<(Self, Daemon), Error> {
+        let (trust_anchors, reload) = config.trust_anchors.load()?;
+        let (l, s, refreshes) = Self::uncertified(config.local_id.clone(), trust_anchors.clone());
+        let daemon = Daemon {
+            config: config.clone(),
+            refreshes,
+            trust_anchors,
+            reload,
+            crt_key_watch: s,
+        };
+        Ok((l, daemon))
+    }
+
+    /// Returns an identity without a certificate, along with the sender used
+    /// to publish certificates and the counter of certificate updates.
+    pub(crate) fn uncertified(
+        id: id::LocalId,
+        trust_anchors: trust_anchors::Receiver,
+    ) -> (Self, CrtKeySender, Arc<Counter>) {
         let (s, w) = watch::channel(None);
         let refreshes = Arc::new(Counter::new());
         let l = Self {
-            id: config.local_id.clone(),
-            trust_anchors: config.trust_anchors.clone(),
+            id,
+            trust_anchors,
             crt_key: w,
             refreshes: refreshes.clone(),
         };
-        let daemon = Daemon {
-            config: config.clone(),
-            refreshes,
-            crt_key_watch: s,
-        };
-        (l, daemon)
+        (l, s, refreshes)
     }
 
     pub async fn await_crt(mut self) -> Result<Self, LostDaemon> {
@@ -191,7 +251,11 @@ impl LocalCrtKey {
     }
 
     pub fn metrics(&self) -> crate::metrics::Report {
-        crate::metrics::Report::new(self.crt_key.clone(), self.refreshes.clone())
+        crate::metrics::Report::new(
+            self.crt_key.clone(),
+            self.trust_anchors.clone(),
+            self.refreshes.clone(),
+        )
     }
 
     pub fn id(&self) -> &id::LocalId {
@@ -207,7 +271,7 @@ impl LocalCrtKey {
             return c.client_config();
         }
 
-        self.trust_anchors.client_config()
+        self.trust_anchors.borrow().client_config()
     }
 
     pub fn server_config(&self) -> tls::server::Config {
@@ -0,0 +1,194 @@
+//! Loads the local identity from files that are provisioned outside of the
+//! proxy, e.g. by cert-manager or a SPIFFE agent, rather than from the
+//! Identity service.
+
+use crate::{trust_anchors, CrtKeySender, LocalCrtKey};
+use linkerd_error::Error;
+use linkerd_identity as id;
+use linkerd_metrics::Counter;
+use std::{fmt, path::PathBuf, sync::Arc, time::Duration};
+use tokio::time;
+use tracing::{debug, info, warn};
+
+/// Configures the files from which the local identity is loaded.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub local_id: id::LocalId,
+
+    pub trust_anchors: trust_anchors::Config,
+
+    /// A PEM-encoded certificate chain, listing the leaf certificate first.
+    pub crt: PathBuf,
+
+    /// A PEM-encoded ECDSA P-256 private key, either PKCS#8 (`PRIVATE KEY`) or
+    /// SEC1 (`EC PRIVATE KEY`).
+    pub key: PathBuf,
+}
+
+/// Polls the configured files, publishing a new certificate each time they or
+/// the trust anchors change and are valid.
+#[derive(Debug)]
+pub struct Daemon {
+    crt_key_watch: CrtKeySender,
+    refreshes: Arc<Counter>,
+    trust_anchors: trust_anchors::Receiver,
+    reload: trust_anchors::Daemon,
+    config: Config,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct InvalidFile(pub(crate) PathBuf);
+
+/// The contents of the certificate and key files.
+type Contents = (String, String);
+
+// === impl LocalCrtKey ===
+
+impl LocalCrtKey {
+    /// Loads the trust anchors, failing if they can't be read, and returns a
+    /// daemon that loads the certificate and key.
+    ///
+    /// The identity has no certificate until the daemon loads a valid one.
+    pub fn from_files(config: &Config) -> Result<(Self, Daemon), Error> {
+        let (trust_anchors, reload) = config.trust_anchors.load()?;
+        let (local, crt_key_watch, refreshes) =
+            Self::uncertified(config.local_id.clone(), trust_anchors.clone());
+        let daemon = Daemon {
+            crt_key_watch,
+            refreshes,
+            trust_anchors,
+            reload,
+            config: config.clone(),
+        };
+        Ok((local, daemon))
+    }
+}
+
+// === impl Daemon ===
+
+impl Daemon {
+    const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Runs until all observers of the identity have been dropped.
+    ///
+    /// Files are polled rather than watched, so that files that are replaced
+    /// (e.g. by swapping a symlink, as Kubernetes does when a Secret volume is
+    /// updated) are reloaded like files that are written in place. A version
+    /// that can't be read or isn't valid is logged and ignored, so the last
+    /// valid certificate continues to be used.
+    pub async fn run(self) {
+        let Self {
+            crt_key_watch,
+            refreshes,
+            trust_anchors,
+            reload,
+            config,
+        } = self;
+
+        debug!("Identity file daemon running");
+        let certify = async move {
+            let mut loaded = None;
+            loop {
+                let contents = config
+                    .read()
+                    .await
+                    .map(|contents| (trust_anchors.borrow().clone(), contents));
+                match contents {
+                    Err(error) => warn!(%error, "Failed to read identity files"),
+                    Ok(contents) if loaded.as_ref() == Some(&contents) => {}
+                    Ok(contents) => {
+                        match config.certify(&contents) {
+                            Err(error) => warn!(%error, "Ignoring invalid identity files"),
+                            Ok(crt_key) => {
+                                info!(expiry = ?crt_key.expiry(), "Loaded certificate");
+                                if crt_key_watch.send(Some(crt_key)).is_err() {
+                                    // If we can't store a value, than all observations
+                                    // have been dropped and we can stop polling.
+                                    return;
+                                }
+                                refreshes.incr();
+                            }
+                        }
+                        loaded = Some(contents);
+                    }
+                }
+
+                time::sleep(Self::POLL_INTERVAL).await;
+            }
+        };
+
+        // The trust anchors are reloaded for as long as the identity is.
+        futures::pin_mut!(certify);
+        futures::future::select(certify, Box::pin(reload.run())).await;
+    }
+}
+
+// === impl Config ===
+
+impl Config {
+    async fn read(&self) -> Result<Contents, Error> {
+        let crt = tokio::fs::read_to_string(&self.crt).await?;
+        let key = tokio::fs::read_to_string(&self.key).await?;
+        Ok((crt, key))
+    }
+
+    /// Validates the certificate and key against the trust anchors.
+    fn certify(
+        &self,
+        (trust_anchors, (crt, key)): &(id::TrustAnchors, Contents),
+    ) -> Result<id::CrtKey, Error> {
+        let crt = id::Crt::from_pem(self.local_id.clone(), crt)
+            .ok_or_else(|| InvalidFile(self.crt.clone()))?;
+        let key = id::Key::from_pem(key).ok_or_else(|| InvalidFile(self.key.clone()))?;
+        Ok(trust_anchors.certify(key, crt)?)
+    }
+}
+
+// === impl InvalidFile ===
+
+impl fmt::Display for InvalidFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no valid PEM data in {}", self.0.display())
+    }
+}
+
+impl std::error::Error for InvalidFile {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const TESTDATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../identity/src/testdata");
+
+    fn config(crt: &str) -> Config {
+        let testdata = Path::new(TESTDATA);
+        Config {
+            local_id: id::LocalId(
+                "foo.ns1.serviceaccount.identity.linkerd.cluster.local"
+                    .parse()
+                    .unwrap(),
+            ),
+            trust_anchors: trust_anchors::Config::Files(vec![testdata.join("ca1.pem")]),
+            crt: testdata.join(crt).join("crt.pem"),
+            key: testdata.join(crt).join("key.pem"),
+        }
+    }
+
+    #[tokio::test]
+    async fn loads_valid_certificates() {
+        let (local, daemon) = LocalCrtKey::from_files(&config("foo-ns1-ca1")).unwrap();
+        tokio::spawn(daemon.run());
+        time::timeout(Duration::from_secs(5), local.await_crt())
+            .await
+            .expect("must be certified")
+            .unwrap();
+
+        // A certificate issued by another CA is not loaded.
+        let (local, daemon) = LocalCrtKey::from_files(&config("foo-ns1-ca2")).unwrap();
+        tokio::spawn(daemon.run());
+        assert!(time::timeout(Duration::from_millis(100), local.await_crt())
+            .await
+            .is_err());
+    }
+}
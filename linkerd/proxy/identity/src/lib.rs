@@ -1,6 +1,8 @@
 #![deny(warnings, rust_2018_idioms)]
 
 pub mod certify;
+pub mod files;
 pub mod metrics;
+pub mod trust_anchors;
 
 pub use self::certify::{AwaitCrt, CrtKeySender, LocalCrtKey};
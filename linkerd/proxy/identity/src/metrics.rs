@@ -1,5 +1,6 @@
-use linkerd_identity::CrtKey;
-use linkerd_metrics::{metrics, Counter, FmtMetrics, Gauge};
+use crate::trust_anchors;
+use linkerd_identity::{CrtKey, Fingerprint};
+use linkerd_metrics::{metrics, Counter, FmtLabels, FmtMetrics, Gauge};
 use std::{fmt, sync::Arc, time::UNIX_EPOCH};
 use tokio::sync::watch;
 
@@ -15,17 +16,26 @@ metrics! {
 
     identity_cert_refresh_count: Counter {
         "The total number of times this proxy's mTLS identity certificate has been refreshed by the Identity service."
+    },
+
+    identity_trust_anchor_info: Gauge {
+        "Describes the root certificates that this proxy trusts to issue peers' certificates, labeled by their SHA-256 fingerprints."
     }
 }
 
+/// Labels a trust anchor by its fingerprint.
+struct TrustAnchorLabels<'a>(&'a Fingerprint);
+
 impl Report {
     pub(crate) fn new(
         crt_key_watch: watch::Receiver<Option<CrtKey>>,
+        trust_anchors: trust_anchors::Receiver,
         refreshes: Arc<Counter>,
     ) -> Self {
         Self {
             inner: Some(Inner {
                 crt_key_watch,
+                trust_anchors,
                 refreshes,
             }),
         }
@@ -39,6 +49,7 @@ impl Report {
 #[derive(Debug, Clone)]
 struct Inner {
     crt_key_watch: watch::Receiver<Option<CrtKey>>,
+    trust_anchors: trust_anchors::Receiver,
     refreshes: Arc<Counter>,
 }
 
@@ -65,6 +76,21 @@ impl FmtMetrics for Report {
         identity_cert_refresh_count.fmt_help(f)?;
         identity_cert_refresh_count.fmt_metric(f, &this.refreshes)?;
 
+        identity_trust_anchor_info.fmt_help(f)?;
+        for fingerprint in this.trust_anchors.borrow().fingerprints() {
+            identity_trust_anchor_info.fmt_metric_labeled(
+                f,
+                &Gauge::from(1),
+                &TrustAnchorLabels(fingerprint),
+            )?;
+        }
+
         Ok(())
     }
 }
+
+impl FmtLabels for TrustAnchorLabels<'_> {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fingerprint=\"{}\"", self.0)
+    }
+}
@@ -0,0 +1,159 @@
+//! Trust anchors that may be reloaded at runtime, so that a root CA can be
+//! rotated without restarting the proxy.
+
+use crate::files::InvalidFile;
+use linkerd_error::Error;
+use linkerd_identity as id;
+use std::{path::PathBuf, time::Duration};
+use tokio::{sync::watch, time};
+use tracing::{debug, info, warn};
+
+/// Configures the trust anchors used to validate peers' certificates.
+#[derive(Clone, Debug)]
+pub enum Config {
+    /// Trust anchors that never change.
+    Static(id::TrustAnchors),
+
+    /// PEM-encoded trust bundles that are reloaded as they change. The anchors
+    /// in all of the bundles are trusted, so a new root can be trusted
+    /// alongside the old one while certificates are reissued.
+    Files(Vec<PathBuf>),
+}
+
+/// Observes the current trust anchors.
+pub type Receiver = watch::Receiver<id::TrustAnchors>;
+
+/// Polls the configured trust bundles, publishing new trust anchors each time
+/// they change and are valid.
+#[derive(Debug)]
+pub struct Daemon {
+    paths: Vec<PathBuf>,
+    loaded: Vec<String>,
+    tx: watch::Sender<id::TrustAnchors>,
+}
+
+// === impl Config ===
+
+impl Config {
+    /// Loads the trust anchors, failing if they can't be read, and returns a
+    /// daemon that reloads them.
+    pub fn load(&self) -> Result<(Receiver, Daemon), Error> {
+        let (trust_anchors, paths, loaded) = match self {
+            Self::Static(trust_anchors) => (trust_anchors.clone(), vec![], vec![]),
+            Self::Files(paths) => {
+                let bundles = paths
+                    .iter()
+                    .map(std::fs::read_to_string)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let trust_anchors = parse(paths, &bundles)?;
+                (trust_anchors, paths.clone(), bundles)
+            }
+        };
+        debug!(fingerprints = ?trust_anchors.fingerprints(), "Loaded trust anchors");
+
+        let (tx, rx) = watch::channel(trust_anchors);
+        let daemon = Daemon { paths, loaded, tx };
+        Ok((rx, daemon))
+    }
+}
+
+// === impl Daemon ===
+
+impl Daemon {
+    const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Runs until all observers of the trust anchors have been dropped.
+    ///
+    /// As with the identity files, bundles are polled rather than watched. A
+    /// version that can't be read or isn't valid is logged and ignored, so the
+    /// last valid trust anchors continue to be used.
+    pub async fn run(self) {
+        let Self {
+            paths,
+            mut loaded,
+            tx,
+        } = self;
+
+        if paths.is_empty() {
+            // Static trust anchors never change, but observers must not see the
+            // sender dropped.
+            return futures::future::pending().await;
+        }
+
+        loop {
+            time::sleep(Self::POLL_INTERVAL).await;
+
+            match read(&paths).await {
+                Err(error) => warn!(%error, "Failed to read trust anchors"),
+                Ok(bundles) if bundles == loaded => {}
+                Ok(bundles) => {
+                    match parse(&paths, &bundles) {
+                        Err(error) => warn!(%error, "Ignoring invalid trust anchors"),
+                        Ok(trust_anchors) => {
+                            info!(
+                                fingerprints = ?trust_anchors.fingerprints(),
+                                "Reloaded trust anchors"
+                            );
+                            if tx.send(trust_anchors).is_err() {
+                                // All observations have been dropped, so we can
+                                // stop polling.
+                                return;
+                            }
+                        }
+                    }
+                    loaded = bundles;
+                }
+            }
+        }
+    }
+}
+
+async fn read(paths: &[PathBuf]) -> Result<Vec<String>, Error> {
+    let mut bundles = Vec::with_capacity(paths.len());
+    for path in paths {
+        bundles.push(tokio::fs::read_to_string(path).await?);
+    }
+    Ok(bundles)
+}
+
+/// Merges the bundles read from each path, each of which must be valid.
+fn parse(paths: &[PathBuf], bundles: &[String]) -> Result<id::TrustAnchors, Error> {
+    let mut trust_anchors = Vec::with_capacity(bundles.len());
+    for (path, pem) in paths.iter().zip(bundles) {
+        let bundle = id::TrustAnchors::from_pem(pem).ok_or_else(|| InvalidFile(path.clone()))?;
+        trust_anchors.push(bundle);
+    }
+    id::TrustAnchors::merge(trust_anchors).ok_or_else(|| "no trust anchors configured".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const TESTDATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../identity/src/testdata");
+
+    #[tokio::test]
+    async fn reloads_trust_bundles() {
+        let dir = std::env::temp_dir().join(format!("trust-anchors-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bundle = dir.join("bundle.pem");
+        let ca1 = Path::new(TESTDATA).join("ca1.pem");
+        let ca2 = Path::new(TESTDATA).join("ca2.pem");
+        std::fs::copy(&ca1, &bundle).unwrap();
+
+        time::pause();
+        let config = Config::Files(vec![ca1.clone(), bundle.clone()]);
+        let (mut trust_anchors, daemon) = config.load().unwrap();
+        assert_eq!(trust_anchors.borrow().fingerprints().len(), 1);
+        tokio::spawn(daemon.run());
+
+        // The second bundle now trusts a new root alongside the first.
+        std::fs::copy(&ca2, &bundle).unwrap();
+        trust_anchors.changed().await.unwrap();
+        assert_eq!(trust_anchors.borrow().fingerprints().len(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(Config::Files(vec![ca1, bundle]).load().is_err());
+    }
+}
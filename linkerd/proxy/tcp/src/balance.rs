@@ -1,4 +1,6 @@
 use linkerd_error::Error;
+use linkerd_proxy_balance::{self as balance, RoundRobin};
+pub use linkerd_proxy_balance::{MakeBalance, Strategy};
 use linkerd_stack::layer;
 use rand::thread_rng;
 use std::{hash::Hash, time::Duration};
@@ -6,7 +8,12 @@ pub use tower::{
     balance::p2c::Balance,
     load::{Load, PeakEwmaDiscover},
 };
-use tower::{discover::Discover, load::CompleteOnResponse};
+use tower::{
+    discover::Discover,
+    load::{CompleteOnResponse, PendingRequestsDiscover},
+    util::Either,
+};
+use tracing::debug;
 
 /// Produces a PeakEWMA balancer that uses connect latency (and pending
 /// connections) as its load metric.
@@ -26,3 +33,66 @@ where
         Balance::from_rng(loaded, &mut thread_rng()).expect("RNG must be valid")
     })
 }
+
+/// Builds a balancer for each of the supported balancing strategies.
+///
+/// Power-of-two-choices balancers treat a connection as pending until it is
+/// established. Connections carry no hash key, so ring-hash balancing isn't
+/// supported; the default peak-EWMA balancer is used instead.
+#[derive(Clone, Debug)]
+pub struct NewBalance {
+    decay: Duration,
+    default_rtt: Duration,
+}
+
+/// A balancer that distributes connections according to a `Strategy`.
+pub type StrategyBalance<D, T> = Either<
+    Either<
+        Balance<PeakEwmaDiscover<D, CompleteOnResponse>, T>,
+        Balance<PendingRequestsDiscover<D, CompleteOnResponse>, T>,
+    >,
+    balance::Balance<D, RoundRobin<<D as Discover>::Key>, T>,
+>;
+
+// === impl NewBalance ===
+
+impl NewBalance {
+    pub fn new(default_rtt: Duration, decay: Duration) -> Self {
+        Self { decay, default_rtt }
+    }
+}
+
+impl<D> balance::NewBalance<D> for NewBalance
+where
+    D: Discover,
+    D::Key: Hash,
+    D::Service: tower::Service<()>,
+    <D::Service as tower::Service<()>>::Error: Into<Error>,
+{
+    type Service = StrategyBalance<D, ()>;
+
+    fn new_balance(&self, strategy: &Strategy, discover: D) -> Self::Service {
+        let instrument = CompleteOnResponse::default();
+        match strategy {
+            Strategy::PeakEwma | Strategy::RingHash(_) => {
+                if let Strategy::RingHash(key) = strategy {
+                    debug!(?key, "Connections can't be hashed; using peak-EWMA");
+                }
+                let loaded =
+                    PeakEwmaDiscover::new(discover, self.default_rtt, self.decay, instrument);
+                let balance =
+                    Balance::from_rng(loaded, &mut thread_rng()).expect("RNG must be valid");
+                Either::A(Either::A(balance))
+            }
+            Strategy::LeastRequest => {
+                let loaded = PendingRequestsDiscover::new(discover, instrument);
+                let balance =
+                    Balance::from_rng(loaded, &mut thread_rng()).expect("RNG must be valid");
+                Either::A(Either::B(balance))
+            }
+            Strategy::RoundRobin => {
+                Either::B(balance::Balance::new(discover, RoundRobin::default()))
+            }
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::addrs::{Remote, ServerAddr};
 use futures::{ready, TryFuture};
 use linkerd_errno::Errno;
 use linkerd_io as io;
@@ -10,12 +11,13 @@ use std::collections::HashMap;
 use std::fmt;
 use std::future::Future;
 use std::hash::Hash;
+use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncRead, AsyncWrite};
-use tracing::debug;
+use tracing::{debug, info};
 
 metrics! {
     tcp_open_total: Counter { "Total count of opened connections" },
@@ -32,7 +34,11 @@ pub fn new<K: Eq + Hash + FmtLabels>(retain_idle: Duration) -> (Registry<K>, Rep
         metrics: inner.clone(),
         retain_idle,
     };
-    (Registry(inner), report)
+    let registry = Registry {
+        metrics: inner,
+        log_connections: false,
+    };
+    (registry, report)
 }
 
 /// Implements `FmtMetrics` to render prometheus-formatted metrics for all transports.
@@ -43,29 +49,39 @@ pub struct Report<K: Eq + Hash + FmtLabels> {
 }
 
 #[derive(Clone, Debug)]
-pub struct Registry<K: Eq + Hash + FmtLabels>(Arc<Mutex<Inner<K>>>);
+pub struct Registry<K: Eq + Hash + FmtLabels> {
+    metrics: Arc<Mutex<Inner<K>>>,
+    log_connections: bool,
+}
 
 #[derive(Debug)]
 pub struct ConnectLayer<K: Eq + Hash + FmtLabels> {
     registry: Arc<Mutex<Inner<K>>>,
+    log_connections: bool,
 }
 
 #[derive(Debug)]
 pub struct MakeAccept<K: Eq + Hash + FmtLabels, M> {
     inner: M,
     registry: Arc<Mutex<Inner<K>>>,
+    log_connections: bool,
 }
 
 #[derive(Clone, Debug)]
 pub struct Accept<A> {
     inner: A,
     metrics: Arc<Metrics>,
+
+    /// The formatted labels of the connection's class, set when connections
+    /// are logged.
+    log_labels: Option<Arc<str>>,
 }
 
 #[derive(Debug)]
 pub struct Connect<K: Eq + Hash + FmtLabels, M> {
     inner: M,
     registry: Arc<Mutex<Inner<K>>>,
+    log_connections: bool,
 }
 
 #[pin_project]
@@ -111,41 +127,69 @@ struct EosMetrics {
 pub struct Sensor {
     metrics: Option<Arc<Metrics>>,
     opened_at: Instant,
+
+    /// Set when the connection is logged as it closes.
+    log: Option<ConnectionLog>,
 }
 
 pub type SensorIo<T> = io::SensorIo<T, Sensor>;
 
 /// Lazily builds instances of `Sensor`.
 #[derive(Clone, Debug)]
-struct NewSensor(Arc<Metrics>);
+struct NewSensor(Arc<Metrics>, Option<ConnectionLog>);
+
+/// Describes a connection in the log line written when it closes.
+#[derive(Clone, Debug)]
+struct ConnectionLog {
+    labels: Arc<str>,
+    client_addr: Option<SocketAddr>,
+    server_addr: Option<SocketAddr>,
+    read_bytes: u64,
+    write_bytes: u64,
+}
+
+/// Formats a key's labels for the connection log.
+struct LogLabels<'k, K>(&'k K);
 
 type Inner<K> = Store<K, Metrics>;
 
 // ===== impl Registry =====
 
 impl<K: Eq + Hash + FmtLabels> Registry<K> {
+    /// Logs each connection as it closes, with its labels, client and server
+    /// addresses, byte counts, duration, and the error (if any) that closed it.
+    pub fn log_connections(mut self, enabled: bool) -> Self {
+        self.log_connections = enabled;
+        self
+    }
+
     pub fn layer_connect(&self) -> ConnectLayer<K> {
-        ConnectLayer::new(self.0.clone())
+        ConnectLayer::new(self.metrics.clone(), self.log_connections)
     }
 
     pub fn layer_accept<M>(&self) -> impl layer::Layer<M, Service = MakeAccept<K, M>> + Clone {
-        let registry = self.0.clone();
+        let registry = self.metrics.clone();
+        let log_connections = self.log_connections;
         layer::mk(move |inner| MakeAccept {
             inner,
             registry: registry.clone(),
+            log_connections,
         })
     }
 }
 
 impl<K: Eq + Hash + FmtLabels> ConnectLayer<K> {
-    fn new(registry: Arc<Mutex<Inner<K>>>) -> Self {
-        Self { registry }
+    fn new(registry: Arc<Mutex<Inner<K>>>, log_connections: bool) -> Self {
+        Self {
+            registry,
+            log_connections,
+        }
     }
 }
 
 impl<K: Eq + Hash + FmtLabels> Clone for ConnectLayer<K> {
     fn clone(&self) -> Self {
-        Self::new(self.registry.clone())
+        Self::new(self.registry.clone(), self.log_connections)
     }
 }
 
@@ -156,6 +200,7 @@ impl<K: Eq + Hash + FmtLabels, M> tower::layer::Layer<M> for ConnectLayer<K> {
         Connect {
             inner,
             registry: self.registry.clone(),
+            log_connections: self.log_connections,
         }
     }
 }
@@ -171,6 +216,7 @@ where
         Self {
             inner: self.inner.clone(),
             registry: self.registry.clone(),
+            log_connections: self.log_connections,
         }
     }
 }
@@ -185,6 +231,11 @@ where
 
     fn new_service(&mut self, target: T) -> Self::Service {
         let labels = Param::<K>::param(&target);
+        let log_labels = if self.log_connections {
+            Some(LogLabels(&labels).to_string().into())
+        } else {
+            None
+        };
         let metrics = self
             .registry
             .lock()
@@ -193,12 +244,17 @@ where
             .clone();
 
         let inner = self.inner.new_service(target);
-        Accept { metrics, inner }
+        Accept {
+            metrics,
+            inner,
+            log_labels,
+        }
     }
 }
 
 impl<I, A> tower::Service<I> for Accept<A>
 where
+    I: io::PeerAddr,
     A: tower::Service<SensorIo<I>, Response = ()>,
 {
     type Response = ();
@@ -210,7 +266,11 @@ where
     }
 
     fn call(&mut self, io: I) -> Self::Future {
-        let io = SensorIo::new(io, Sensor::open(self.metrics.clone()));
+        let log = self
+            .log_labels
+            .clone()
+            .map(|labels| ConnectionLog::accept(labels, &io));
+        let io = SensorIo::new(io, Sensor::open(self.metrics.clone(), log));
         self.inner.call(io)
     }
 }
@@ -226,15 +286,17 @@ where
         Connect {
             inner: self.inner.clone(),
             registry: self.registry.clone(),
+            log_connections: self.log_connections,
         }
     }
 }
 
 impl<K, T, M> tower::Service<T> for Connect<K, M>
 where
-    T: Param<K>,
+    T: Param<K> + Param<Remote<ServerAddr>>,
     K: Eq + Hash + FmtLabels,
     M: tower::make::MakeConnection<T>,
+    M::Connection: io::PeerAddr,
 {
     type Response = SensorIo<M::Connection>;
     type Error = M::Error;
@@ -246,7 +308,14 @@ where
     }
 
     fn call(&mut self, target: T) -> Self::Future {
-        let labels = target.param();
+        let labels: K = target.param();
+        let log = if self.log_connections {
+            let Remote(ServerAddr(addr)) = target.param();
+            let labels = LogLabels(&labels).to_string().into();
+            Some(ConnectionLog::connect(labels, addr))
+        } else {
+            None
+        };
         let metrics = self
             .registry
             .lock()
@@ -255,7 +324,7 @@ where
             .clone();
 
         Connecting {
-            new_sensor: Some(NewSensor(metrics)),
+            new_sensor: Some(NewSensor(metrics, log)),
             underlying: self.inner.make_connection(target),
         }
     }
@@ -266,7 +335,7 @@ where
 impl<F> Future for Connecting<F>
 where
     F: TryFuture,
-    F::Ok: AsyncRead + AsyncWrite,
+    F::Ok: AsyncRead + AsyncWrite + io::PeerAddr,
 {
     type Output = Result<SensorIo<F::Ok>, F::Error>;
 
@@ -279,7 +348,7 @@ where
             .new_sensor
             .take()
             .expect("future must not be polled after ready")
-            .new_sensor();
+            .new_sensor(&io);
         let t = SensorIo::new(io, sensor);
         Poll::Ready(Ok(t))
     }
@@ -342,7 +411,7 @@ impl<K: Eq + Hash + FmtLabels + 'static> FmtMetrics for Report<K> {
 // ===== impl Sensor =====
 
 impl Sensor {
-    fn open(metrics: Arc<Metrics>) -> Self {
+    fn open(metrics: Arc<Metrics>, log: Option<ConnectionLog>) -> Self {
         metrics.open_total.incr();
         metrics.open_connections.incr();
         if let Ok(mut by_eos) = metrics.by_eos.lock() {
@@ -351,12 +420,16 @@ impl Sensor {
         Self {
             metrics: Some(metrics),
             opened_at: Instant::now(),
+            log,
         }
     }
 }
 
 impl io::Sensor for Sensor {
     fn record_read(&mut self, sz: usize) {
+        if let Some(ref mut log) = self.log {
+            log.read_bytes += sz as u64;
+        }
         if let Some(ref m) = self.metrics {
             m.read_bytes_total.add(sz as u64);
             if let Ok(mut by_eos) = m.by_eos.lock() {
@@ -366,6 +439,9 @@ impl io::Sensor for Sensor {
     }
 
     fn record_write(&mut self, sz: usize) {
+        if let Some(ref mut log) = self.log {
+            log.write_bytes += sz as u64;
+        }
         if let Some(ref m) = self.metrics {
             m.write_bytes_total.add(sz as u64);
             if let Ok(mut by_eos) = m.by_eos.lock() {
@@ -388,6 +464,11 @@ impl io::Sensor for Sensor {
                 .or_insert_with(EosMetrics::default);
             class.close_total.incr();
             by_eos.last_update = Instant::now();
+            drop(by_eos);
+
+            if let Some(log) = self.log.take() {
+                log.closed(self.opened_at.elapsed(), eos);
+            }
         }
     }
 
@@ -420,8 +501,63 @@ impl Drop for Sensor {
 // ===== impl NewSensor =====
 
 impl NewSensor {
-    fn new_sensor(self) -> Sensor {
-        Sensor::open(self.0)
+    fn new_sensor(self, io: &impl io::PeerAddr) -> Sensor {
+        let log = self.1.map(|log| log.connected(io));
+        Sensor::open(self.0, log)
+    }
+}
+
+// ===== impl ConnectionLog =====
+
+impl ConnectionLog {
+    /// Describes a connection accepted by the proxy, which is the server.
+    fn accept(labels: Arc<str>, io: &impl io::PeerAddr) -> Self {
+        Self {
+            labels,
+            client_addr: io.peer_addr().ok(),
+            server_addr: io.local_addr().ok(),
+            read_bytes: 0,
+            write_bytes: 0,
+        }
+    }
+
+    /// Describes a connection initiated by the proxy, which is the client. The
+    /// client address is only known once the connection is established.
+    fn connect(labels: Arc<str>, server_addr: SocketAddr) -> Self {
+        Self {
+            labels,
+            client_addr: None,
+            server_addr: Some(server_addr),
+            read_bytes: 0,
+            write_bytes: 0,
+        }
+    }
+
+    fn connected(self, io: &impl io::PeerAddr) -> Self {
+        Self {
+            client_addr: io.local_addr().ok(),
+            ..self
+        }
+    }
+
+    fn closed(self, duration: Duration, eos: Option<Errno>) {
+        info!(
+            target: "linkerd::connections",
+            labels = %self.labels,
+            client.addr = %self.client_addr.map(|a| a.to_string()).unwrap_or_default(),
+            server.addr = %self.server_addr.map(|a| a.to_string()).unwrap_or_default(),
+            read_bytes = self.read_bytes,
+            write_bytes = self.write_bytes,
+            duration_ms = duration.as_millis() as u64,
+            errno = %eos.map(|e| e.to_string()).unwrap_or_default(),
+            "Connection closed"
+        );
+    }
+}
+
+impl<'k, K: FmtLabels> fmt::Display for LogLabels<'k, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_labels(f)
     }
 }
 
@@ -476,7 +612,7 @@ mod tests {
 
         let retain_idle_for = Duration::from_secs(1);
         let (r, report) = super::new(retain_idle_for);
-        let mut registry = r.0.lock().unwrap();
+        let mut registry = r.metrics.lock().unwrap();
 
         let before_update = Instant::now();
         let metrics = registry.entry(Target(123)).or_default().clone();
@@ -507,4 +643,59 @@ mod tests {
 
         drop((registry, report));
     }
+
+    mod connection_log {
+        use super::super::*;
+        use tokio::net::{TcpListener, TcpStream};
+
+        #[tokio::test]
+        async fn accept_logs_client_and_server() {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let client = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (server, _) = listener.accept().await.unwrap();
+
+            let log = ConnectionLog::accept("n=\"1\"".into(), &server);
+            assert_eq!(log.client_addr, Some(client.local_addr().unwrap()));
+            assert_eq!(log.server_addr, Some(listener.local_addr().unwrap()));
+        }
+
+        #[tokio::test]
+        async fn connect_logs_client_and_server() {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let server_addr = listener.local_addr().unwrap();
+            let client = TcpStream::connect(server_addr).await.unwrap();
+
+            let log = ConnectionLog::connect("n=\"1\"".into(), server_addr);
+            assert_eq!(log.client_addr, None);
+            let new_sensor = NewSensor(Arc::new(Metrics::default()), Some(log));
+            let log = new_sensor.new_sensor(&client).log.take().unwrap();
+            assert_eq!(log.client_addr, Some(client.local_addr().unwrap()));
+            assert_eq!(log.server_addr, Some(server_addr));
+        }
+
+        #[test]
+        fn counts_bytes_until_closed() {
+            use io::Sensor as _;
+
+            let metrics = Arc::new(Metrics::default());
+            let log = ConnectionLog::connect("n=\"1\"".into(), ([127, 0, 0, 1], 4143).into());
+            let mut sensor = Sensor::open(metrics.clone(), Some(log));
+            sensor.record_read(3);
+            sensor.record_write(5);
+            sensor.record_read(7);
+
+            let log = sensor.log.as_ref().unwrap();
+            assert_eq!(log.read_bytes, 10);
+            assert_eq!(log.write_bytes, 5);
+            assert_eq!(metrics.read_bytes_total.value(), 10.0);
+
+            sensor.record_close(None);
+            assert!(
+                sensor.log.is_none(),
+                "connection must be logged once closed"
+            );
+        }
+    }
 }
@@ -0,0 +1,429 @@
+//! Limits the rate of requests with token buckets.
+//!
+//! Each target may name a key, and all services built for targets with the
+//! same key share a bucket, so the limit applies to the key rather than to an
+//! individual service. Buckets are refilled continuously at the limit's rate
+//! and hold at most the limit's burst; a request is admitted if it can take a
+//! token from its bucket and fails with `RateLimited` otherwise.
+
+#![deny(warnings, rust_2018_idioms)]
+
+mod metrics;
+
+pub use self::metrics::Registry;
+use futures::{future, prelude::*};
+use linkerd_error::Error;
+use linkerd_metrics::Counter;
+use linkerd_stack::{layer, NewService, Proxy};
+use std::{
+    fmt,
+    hash::Hash,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+use tokio::time::Instant;
+use tracing::{debug, trace};
+
+/// The rate at which requests are admitted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Limit {
+    per_second: u32,
+    burst: u32,
+}
+
+/// Describes how requests to a target are rate limited.
+pub trait HasRateLimit {
+    type Key;
+
+    /// Returns the key of the bucket shared by requests to this target, or
+    /// `None` if requests to this target are not limited.
+    fn rate_limit_key(&self) -> Option<Self::Key>;
+
+    /// Returns a limit that overrides the layer's default limit.
+    fn rate_limit(&self) -> Option<Limit> {
+        None
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NewRateLimit<K: Hash + Eq, N> {
+    default: Option<Limit>,
+    registry: Registry<K>,
+    inner: N,
+}
+
+#[derive(Clone, Debug)]
+pub struct RateLimit<S> {
+    inner: S,
+
+    /// Unset when requests to the target are not limited.
+    bucket: Option<Arc<Bucket>>,
+}
+
+/// Indicates that a request was rejected because its rate limit was exceeded.
+#[derive(Debug)]
+pub struct RateLimited(Limit);
+
+/// A token bucket that is refilled continuously at its limit's rate and holds
+/// at most its limit's burst.
+#[derive(Debug)]
+pub struct TokenBucket {
+    limit: Limit,
+    tokens: f64,
+    refilled: Instant,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    state: Mutex<TokenBucket>,
+    allowed: Counter,
+    limited: Counter,
+}
+
+// === impl Limit ===
+
+impl Limit {
+    /// Admits `per_second` requests each second, with bursts of up to `burst`
+    /// requests.
+    ///
+    /// Returns `None` if either value is zero, since no request would ever be
+    /// admitted.
+    pub fn new(per_second: u32, burst: u32) -> Option<Self> {
+        if per_second == 0 || burst == 0 {
+            return None;
+        }
+        Some(Self { per_second, burst })
+    }
+
+    pub fn per_second(&self) -> u32 {
+        self.per_second
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+}
+
+// === impl NewRateLimit ===
+
+impl<K: Hash + Eq, N> NewRateLimit<K, N> {
+    /// Limits requests to targets that don't specify their own limit to
+    /// `default`. When there is no default, only targets that specify a limit
+    /// are limited.
+    pub fn layer(
+        default: Option<Limit>,
+        registry: Registry<K>,
+    ) -> impl layer::Layer<N, Service = Self> + Clone {
+        layer::mk(move |inner| Self {
+            default,
+            registry: registry.clone(),
+            inner,
+        })
+    }
+}
+
+impl<T, K, N> NewService<T> for NewRateLimit<K, N>
+where
+    T: HasRateLimit<Key = K>,
+    K: Hash + Eq,
+    N: NewService<T>,
+{
+    type Service = RateLimit<N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        let bucket = match (
+            target.rate_limit().or(self.default),
+            target.rate_limit_key(),
+        ) {
+            (Some(limit), Some(key)) => Some(self.registry.bucket(key, limit)),
+            _ => None,
+        };
+        RateLimit {
+            inner: self.inner.new_service(target),
+            bucket,
+        }
+    }
+}
+
+// === impl RateLimit ===
+
+impl<S> RateLimit<S> {
+    fn acquire(&self) -> Result<(), Error> {
+        if let Some(bucket) = self.bucket.as_ref() {
+            if let Err(limited) = bucket.acquire(Instant::now()) {
+                debug!(limit.per_second = %limited.0.per_second, "Rate limited");
+                return Err(limited.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<Req, S> tower::Service<Req> for RateLimit<S>
+where
+    S: tower::Service<Req>,
+    S::Error: Into<Error>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = future::Either<
+        future::ErrInto<S::Future, Error>,
+        future::Ready<Result<S::Response, Error>>,
+    >;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        if let Err(e) = self.acquire() {
+            return future::Either::Right(future::err(e));
+        }
+        future::Either::Left(self.inner.call(req).err_into::<Error>())
+    }
+}
+
+impl<P, S, Req> Proxy<Req, S> for RateLimit<P>
+where
+    P: Proxy<Req, S>,
+    S: tower::Service<P::Request>,
+{
+    type Request = P::Request;
+    type Response = P::Response;
+    type Error = Error;
+    type Future = future::Either<
+        future::ErrInto<P::Future, Error>,
+        future::Ready<Result<P::Response, Error>>,
+    >;
+
+    fn proxy(&self, svc: &mut S, req: Req) -> Self::Future {
+        if let Err(e) = self.acquire() {
+            return future::Either::Right(future::err(e));
+        }
+        future::Either::Left(self.inner.proxy(svc, req).err_into::<Error>())
+    }
+}
+
+// === impl Bucket ===
+
+impl Bucket {
+    fn new(limit: Limit) -> Self {
+        Self {
+            state: Mutex::new(TokenBucket::new(limit)),
+            allowed: Counter::default(),
+            limited: Counter::default(),
+        }
+    }
+
+    fn set_limit(&self, limit: Limit) {
+        self.state
+            .lock()
+            .expect("rate limit lock poisoned")
+            .set_limit(limit);
+    }
+
+    fn is_full(&self, now: Instant) -> bool {
+        self.state
+            .lock()
+            .expect("rate limit lock poisoned")
+            .is_full(now)
+    }
+
+    fn acquire(&self, now: Instant) -> Result<(), RateLimited> {
+        let mut state = self.state.lock().expect("rate limit lock poisoned");
+        if state.try_acquire(now) {
+            self.allowed.incr();
+            Ok(())
+        } else {
+            self.limited.incr();
+            Err(RateLimited(state.limit))
+        }
+    }
+}
+
+// === impl TokenBucket ===
+
+impl TokenBucket {
+    /// Returns a bucket that holds its full burst.
+    pub fn new(limit: Limit) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst.into(),
+            refilled: Instant::now(),
+        }
+    }
+
+    pub fn limit(&self) -> Limit {
+        self.limit
+    }
+
+    /// Updates the bucket's limit, keeping no more tokens than the new burst.
+    pub fn set_limit(&mut self, limit: Limit) {
+        if self.limit != limit {
+            trace!(?limit, "Updating limit");
+            self.limit = limit;
+            self.tokens = self.tokens.min(limit.burst.into());
+        }
+    }
+
+    /// Takes a token from the bucket, if it holds one at `now`.
+    pub fn try_acquire(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true if the bucket would hold its full burst at `now`.
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.limit.burst.into()
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * f64::from(self.limit.per_second)).min(self.limit.burst.into());
+        self.refilled = now;
+    }
+}
+
+// === impl RateLimited ===
+
+impl RateLimited {
+    pub fn limit(&self) -> Limit {
+        self.0
+    }
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rate limit of {} requests per second exceeded",
+            self.0.per_second
+        )
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_stack::layer::Layer;
+    use std::time::Duration;
+    use tower::{service_fn, Service, ServiceExt};
+
+    #[derive(Clone, Debug)]
+    struct Target(Option<&'static str>, Option<Limit>);
+
+    impl HasRateLimit for Target {
+        type Key = &'static str;
+
+        fn rate_limit_key(&self) -> Option<Self::Key> {
+            self.0
+        }
+
+        fn rate_limit(&self) -> Option<Limit> {
+            self.1
+        }
+    }
+
+    #[test]
+    fn refills_up_to_burst() {
+        let bucket = Bucket::new(Limit::new(10, 2).unwrap());
+        let start = Instant::now();
+        assert!(bucket.acquire(start).is_ok());
+        assert!(bucket.acquire(start).is_ok());
+        assert!(bucket.acquire(start).is_err());
+
+        // One token is added every 100ms.
+        let t = start + Duration::from_millis(100);
+        assert!(bucket.acquire(t).is_ok());
+        assert!(bucket.acquire(t).is_err());
+
+        // No more than the burst accrues.
+        let t = t + Duration::from_secs(10);
+        assert!(bucket.acquire(t).is_ok());
+        assert!(bucket.acquire(t).is_ok());
+        assert!(bucket.acquire(t).is_err());
+
+        assert_eq!(bucket.allowed.value(), 5.0);
+        assert_eq!(bucket.limited.value(), 3.0);
+    }
+
+    #[tokio::test]
+    async fn shares_buckets_by_key() {
+        tokio::time::pause();
+        let registry = Registry::default();
+        let mut new_limit = NewRateLimit::layer(Limit::new(1, 1), registry.clone())
+            .layer(|_: Target| service_fn(|()| future::ok::<_, Error>(())));
+
+        let mut a0 = new_limit.new_service(Target(Some("a"), None));
+        let mut a1 = new_limit.new_service(Target(Some("a"), None));
+        let mut b = new_limit.new_service(Target(Some("b"), None));
+        let mut unkeyed = new_limit.new_service(Target(None, None));
+
+        a0.ready().await.unwrap().call(()).await.unwrap();
+        let err = a1.ready().await.unwrap().call(()).await.unwrap_err();
+        assert!(err.is::<RateLimited>());
+
+        // Other keys are limited independently and targets without a key are
+        // not limited.
+        b.ready().await.unwrap().call(()).await.unwrap();
+        for _ in 0..3 {
+            unkeyed.ready().await.unwrap().call(()).await.unwrap();
+        }
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        a1.ready().await.unwrap().call(()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn target_overrides_default() {
+        tokio::time::pause();
+        let mut new_limit = NewRateLimit::layer(None, Registry::default())
+            .layer(|_: Target| service_fn(|()| future::ok::<_, Error>(())));
+
+        let mut default = new_limit.new_service(Target(Some("a"), None));
+        for _ in 0..3 {
+            default.ready().await.unwrap().call(()).await.unwrap();
+        }
+
+        let mut limited = new_limit.new_service(Target(Some("b"), Limit::new(1, 1)));
+        limited.ready().await.unwrap().call(()).await.unwrap();
+        assert!(limited.ready().await.unwrap().call(()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn evicts_idle_buckets() {
+        tokio::time::pause();
+        let registry = Registry::default();
+        let mut new_limit = NewRateLimit::layer(Limit::new(1, 1), registry.clone())
+            .layer(|_: Target| service_fn(|()| future::ok::<_, Error>(())));
+
+        let mut a = new_limit.new_service(Target(Some("a"), None));
+        a.ready().await.unwrap().call(()).await.unwrap();
+        drop(a);
+
+        // The bucket is retained while it's still limiting requests, even
+        // though no service uses it.
+        let _b = new_limit.new_service(Target(Some("b"), None));
+        assert_eq!(registry.0.lock().unwrap().len(), 2);
+        let mut a = new_limit.new_service(Target(Some("a"), None));
+        assert!(a.ready().await.unwrap().call(()).await.is_err());
+        drop(a);
+
+        // Once the bucket has refilled, it's evicted. Buckets that are in use
+        // are never evicted.
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let _c = new_limit.new_service(Target(Some("c"), None));
+        let buckets = registry.0.lock().unwrap();
+        assert_eq!(buckets.keys().copied().collect::<Vec<_>>(), vec!["b", "c"]);
+    }
+}
@@ -0,0 +1,76 @@
+use super::{Bucket, Limit};
+use indexmap::IndexMap;
+use linkerd_metrics::{metrics, Counter, FmtLabels, FmtMetrics};
+use std::{
+    fmt,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+use tokio::time::Instant;
+
+metrics! {
+    rate_limit_allowed_total: Counter {
+        "Total number of requests admitted by each rate limit"
+    },
+    rate_limit_limited_total: Counter {
+        "Total number of requests rejected by each rate limit"
+    }
+}
+
+type Shared<K> = Arc<Mutex<IndexMap<K, Arc<Bucket>>>>;
+
+/// Holds the bucket for each `K`-labeled rate limit.
+///
+/// Buckets are retained after the services that share them are dropped so that
+/// a limit is not reset when, e.g., a client reconnects. Once such a bucket has
+/// refilled completely, it is no different from a new bucket, so it is evicted
+/// (along with its metrics) when a new bucket is added.
+#[derive(Debug)]
+pub struct Registry<K: Hash + Eq>(pub(super) Shared<K>);
+
+// === impl Registry ===
+
+impl<K: Hash + Eq> Registry<K> {
+    pub(super) fn bucket(&self, key: K, limit: Limit) -> Arc<Bucket> {
+        let mut buckets = self.0.lock().expect("rate limit metrics lock poisoned");
+        if let Some(bucket) = buckets.get(&key) {
+            bucket.set_limit(limit);
+            return bucket.clone();
+        }
+
+        let now = Instant::now();
+        buckets.retain(|_, b| Arc::strong_count(b) > 1 || !b.is_full(now));
+        let bucket = Arc::new(Bucket::new(limit));
+        buckets.insert(key, bucket.clone());
+        bucket
+    }
+}
+
+impl<K: Hash + Eq> Default for Registry<K> {
+    fn default() -> Self {
+        Registry(Shared::default())
+    }
+}
+
+impl<K: Hash + Eq> Clone for Registry<K> {
+    fn clone(&self) -> Self {
+        Registry(self.0.clone())
+    }
+}
+
+impl<K: FmtLabels + Hash + Eq> FmtMetrics for Registry<K> {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let buckets = self.0.lock().expect("rate limit metrics lock poisoned");
+        if buckets.is_empty() {
+            return Ok(());
+        }
+
+        rate_limit_allowed_total.fmt_help(f)?;
+        rate_limit_allowed_total.fmt_scopes(f, buckets.iter(), |b| &b.allowed)?;
+
+        rate_limit_limited_total.fmt_help(f)?;
+        rate_limit_limited_total.fmt_scopes(f, buckets.iter(), |b| &b.limited)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,436 @@
+use crate::NewPolicy;
+use linkerd_error::Error;
+use linkerd_stack::{NewService, Proxy, ProxyService};
+use pin_project::pin_project;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time;
+use tower::util::{Oneshot, ServiceExt};
+use tracing::trace;
+
+/// A policy that determines whether, and when, a request is hedged.
+///
+/// A hedged request is a second copy of a request that is issued when the
+/// original request has not completed within some delay. Whichever request
+/// succeeds first is used and the other is canceled; the request only fails
+/// once both requests have failed.
+pub trait HedgePolicy<Req> {
+    /// Returns how long to wait for the original request to complete before
+    /// issuing a hedged request. The request is not hedged if `None` is
+    /// returned.
+    fn delay(&self, req: &Req) -> Option<Duration>;
+
+    /// Clones a request so that it may be hedged. The request is not hedged
+    /// if `None` is returned.
+    fn clone_request(&self, req: &Req) -> Option<Req>;
+
+    /// Called with the original request and its copy before the original
+    /// request is issued, so that the two requests may be associated with each
+    /// other (e.g. so that they are dispatched to different endpoints).
+    fn link(&self, _original: &mut Req, _hedge: &mut Req) {}
+
+    /// Called when a hedged request is issued.
+    fn on_hedge(&self) {}
+
+    /// Called when the hedged request succeeds before the original request.
+    fn on_hedge_won(&self) {}
+}
+
+/// A layer that applies per-target hedging polices.
+///
+/// Composes `NewService`s that produce a `Proxy`.
+#[derive(Clone, Debug)]
+pub struct NewHedgeLayer<P> {
+    new_policy: P,
+}
+
+#[derive(Clone, Debug)]
+pub struct NewHedge<P, N> {
+    new_policy: P,
+    inner: N,
+}
+
+#[derive(Clone, Debug)]
+pub struct Hedge<P, S> {
+    policy: Option<P>,
+    inner: S,
+}
+
+#[pin_project]
+pub struct ResponseFuture<F, H, P, S, Req>
+where
+    P: Proxy<Req, S>,
+    S: tower::Service<P::Request>,
+    S::Error: Into<Error>,
+{
+    #[pin]
+    original: F,
+    /// Set when the original request fails after the hedged request has been
+    /// issued, in which case the hedged request may still succeed.
+    original_error: Option<Error>,
+    hedge: Option<State<H, P, S, Req>>,
+}
+
+enum State<H, P, S, Req>
+where
+    P: Proxy<Req, S>,
+    S: tower::Service<P::Request>,
+    S::Error: Into<Error>,
+{
+    /// Waiting for the delay to elapse before issuing the hedged request.
+    Waiting {
+        sleep: Pin<Box<time::Sleep>>,
+        policy: H,
+        request: Req,
+        inner: P,
+        svc: S,
+    },
+
+    /// The hedged request has been issued.
+    Hedged {
+        policy: H,
+        future: Pin<Box<Oneshot<ProxyService<P, S>, Req>>>,
+    },
+}
+
+// === impl NewHedgeLayer ===
+
+impl<P> NewHedgeLayer<P> {
+    pub fn new(new_policy: P) -> Self {
+        Self { new_policy }
+    }
+}
+
+impl<P: Clone, N> tower::layer::Layer<N> for NewHedgeLayer<P> {
+    type Service = NewHedge<P, N>;
+
+    fn layer(&self, inner: N) -> Self::Service {
+        Self::Service {
+            inner,
+            new_policy: self.new_policy.clone(),
+        }
+    }
+}
+
+// === impl NewHedge ===
+
+impl<T, N, P> NewService<T> for NewHedge<P, N>
+where
+    N: NewService<T>,
+    P: NewPolicy<T>,
+{
+    type Service = Hedge<P::Policy, N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        // Determine if there is a hedging policy for the given target.
+        let policy = self.new_policy.new_policy(&target);
+
+        let inner = self.inner.new_service(target);
+        Hedge { policy, inner }
+    }
+}
+
+// === impl Hedge ===
+
+impl<H, P, Req, S> Proxy<Req, S> for Hedge<H, P>
+where
+    H: HedgePolicy<Req> + Clone,
+    P: Proxy<Req, S> + Clone,
+    P::Error: Into<Error>,
+    S: tower::Service<P::Request> + Clone,
+    S::Error: Into<Error>,
+{
+    type Request = P::Request;
+    type Response = P::Response;
+    type Error = Error;
+    type Future = ResponseFuture<P::Future, H, P, S, Req>;
+
+    fn proxy(&self, svc: &mut S, mut req: Req) -> Self::Future {
+        let hedge = self.policy.as_ref().and_then(|policy| {
+            let delay = policy.delay(&req)?;
+            let mut request = policy.clone_request(&req)?;
+            policy.link(&mut req, &mut request);
+            trace!(?delay, "Request may be hedged");
+            Some(State::Waiting {
+                sleep: Box::pin(time::sleep(delay)),
+                policy: policy.clone(),
+                request,
+                inner: self.inner.clone(),
+                svc: svc.clone(),
+            })
+        });
+
+        ResponseFuture {
+            original: self.inner.proxy(svc, req),
+            original_error: None,
+            hedge,
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<F, H, P, S, Req> Future for ResponseFuture<F, H, P, S, Req>
+where
+    F: Future<Output = Result<P::Response, P::Error>>,
+    H: HedgePolicy<Req>,
+    P: Proxy<Req, S>,
+    P::Error: Into<Error>,
+    S: tower::Service<P::Request>,
+    S::Error: Into<Error>,
+{
+    type Output = Result<P::Response, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if this.original_error.is_none() {
+            if let Poll::Ready(res) = this.original.poll(cx) {
+                match res.map_err(Into::into) {
+                    // If the original request succeeds first, the hedged
+                    // request (if any) is dropped.
+                    Ok(rsp) => return Poll::Ready(Ok(rsp)),
+                    Err(error) => match this.hedge {
+                        // The hedged request may still succeed.
+                        Some(State::Hedged { .. }) => {
+                            trace!(%error, "Original request failed; awaiting hedge");
+                            *this.original_error = Some(error);
+                        }
+                        // The request has not been hedged, so it fails as it
+                        // would have without hedging.
+                        _ => return Poll::Ready(Err(error)),
+                    },
+                }
+            }
+        }
+
+        loop {
+            match this.hedge.as_mut() {
+                None => return Poll::Pending,
+                Some(State::Waiting { sleep, .. }) => {
+                    futures::ready!(sleep.as_mut().poll(cx));
+                    if let Some(State::Waiting {
+                        policy,
+                        request,
+                        inner,
+                        svc,
+                        ..
+                    }) = this.hedge.take()
+                    {
+                        trace!("Issuing hedged request");
+                        policy.on_hedge();
+                        let future = Box::pin(ProxyService::new(inner, svc).oneshot(request));
+                        *this.hedge = Some(State::Hedged { policy, future });
+                    }
+                }
+                Some(State::Hedged { policy, future }) => {
+                    return match futures::ready!(future.as_mut().poll(cx)) {
+                        Ok(rsp) => {
+                            trace!("Hedged request succeeded first");
+                            policy.on_hedge_won();
+                            Poll::Ready(Ok(rsp))
+                        }
+                        Err(error) => match this.original_error.take() {
+                            // Both requests have failed, so the original
+                            // request's error is returned as it would have
+                            // been without hedging.
+                            Some(original) => {
+                                trace!(%error, "Hedged request failed");
+                                Poll::Ready(Err(original))
+                            }
+                            // The original request is still pending and was
+                            // polled above, so it will wake this task.
+                            None => {
+                                trace!(%error, "Hedged request failed; awaiting original");
+                                *this.hedge = None;
+                                Poll::Pending
+                            }
+                        },
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[derive(Clone, Default)]
+    struct TestPolicy {
+        hedged: Arc<AtomicUsize>,
+        won: Arc<AtomicUsize>,
+    }
+
+    impl HedgePolicy<usize> for TestPolicy {
+        fn delay(&self, _: &usize) -> Option<Duration> {
+            Some(Duration::from_millis(100))
+        }
+
+        fn clone_request(&self, req: &usize) -> Option<usize> {
+            Some(*req)
+        }
+
+        fn on_hedge(&self) {
+            self.hedged.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_hedge_won(&self) {
+            self.won.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Returns a service that responds with the index of each call after the
+    /// corresponding delay.
+    fn delayed(
+        delays: &'static [u64],
+    ) -> impl tower::Service<
+        usize,
+        Response = usize,
+        Error = Error,
+        Future = impl Future<Output = Result<usize, Error>>,
+    > + Clone {
+        failing(delays, &[])
+    }
+
+    /// Like `delayed`, except that the calls at the given indices fail.
+    fn failing(
+        delays: &'static [u64],
+        failures: &'static [usize],
+    ) -> impl tower::Service<
+        usize,
+        Response = usize,
+        Error = Error,
+        Future = impl Future<Output = Result<usize, Error>>,
+    > + Clone {
+        let calls = Arc::new(AtomicUsize::new(0));
+        tower::service_fn(move |_: usize| {
+            let n = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                time::sleep(Duration::from_millis(delays[n])).await;
+                if failures.contains(&n) {
+                    return Err(format!("call {} failed", n).into());
+                }
+                Ok::<_, Error>(n)
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn hedge_wins() {
+        time::pause();
+        let policy = TestPolicy::default();
+        let hedge = Hedge {
+            policy: Some(policy.clone()),
+            inner: (),
+        };
+
+        let mut svc = delayed(&[1_000, 10]);
+        let rsp = hedge.proxy(&mut svc, 0).await.expect("must succeed");
+        assert_eq!(rsp, 1, "the hedged request must complete first");
+        assert_eq!(policy.hedged.load(Ordering::SeqCst), 1);
+        assert_eq!(policy.won.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn original_wins() {
+        time::pause();
+        let policy = TestPolicy::default();
+        let hedge = Hedge {
+            policy: Some(policy.clone()),
+            inner: (),
+        };
+
+        let mut svc = delayed(&[200, 1_000]);
+        let rsp = hedge.proxy(&mut svc, 0).await.expect("must succeed");
+        assert_eq!(rsp, 0, "the original request must complete first");
+        assert_eq!(policy.hedged.load(Ordering::SeqCst), 1);
+        assert_eq!(policy.won.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn not_hedged_before_delay() {
+        time::pause();
+        let policy = TestPolicy::default();
+        let hedge = Hedge {
+            policy: Some(policy.clone()),
+            inner: (),
+        };
+
+        let mut svc = delayed(&[10]);
+        let rsp = hedge.proxy(&mut svc, 0).await.expect("must succeed");
+        assert_eq!(rsp, 0);
+        assert_eq!(policy.hedged.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn original_wins_after_hedge_fails() {
+        time::pause();
+        let policy = TestPolicy::default();
+        let hedge = Hedge {
+            policy: Some(policy.clone()),
+            inner: (),
+        };
+
+        let mut svc = failing(&[1_000, 10], &[1]);
+        let rsp = hedge.proxy(&mut svc, 0).await.expect("must succeed");
+        assert_eq!(rsp, 0, "the original request must be used");
+        assert_eq!(policy.hedged.load(Ordering::SeqCst), 1);
+        assert_eq!(policy.won.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn hedge_wins_after_original_fails() {
+        time::pause();
+        let policy = TestPolicy::default();
+        let hedge = Hedge {
+            policy: Some(policy.clone()),
+            inner: (),
+        };
+
+        let mut svc = failing(&[200, 1_000], &[0]);
+        let rsp = hedge.proxy(&mut svc, 0).await.expect("must succeed");
+        assert_eq!(rsp, 1, "the hedged request must be used");
+        assert_eq!(policy.hedged.load(Ordering::SeqCst), 1);
+        assert_eq!(policy.won.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn fails_when_both_fail() {
+        time::pause();
+        let policy = TestPolicy::default();
+        let hedge = Hedge {
+            policy: Some(policy.clone()),
+            inner: (),
+        };
+
+        let mut svc = failing(&[200, 10], &[0, 1]);
+        let error = hedge.proxy(&mut svc, 0).await.expect_err("must fail");
+        assert_eq!(error.to_string(), "call 0 failed");
+        assert_eq!(policy.won.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn fails_before_hedge() {
+        time::pause();
+        let policy = TestPolicy::default();
+        let hedge = Hedge {
+            policy: Some(policy.clone()),
+            inner: (),
+        };
+
+        let mut svc = failing(&[10], &[0]);
+        let error = hedge.proxy(&mut svc, 0).await.expect_err("must fail");
+        assert_eq!(error.to_string(), "call 0 failed");
+        assert_eq!(policy.hedged.load(Ordering::SeqCst), 0);
+    }
+}
@@ -1,7 +1,10 @@
 #![deny(warnings, rust_2018_idioms)]
 
+mod hedge;
+
+pub use self::hedge::{Hedge, HedgePolicy, NewHedge, NewHedgeLayer};
 use linkerd_error::Error;
-use linkerd_stack::{NewService, Proxy, ProxyService};
+use linkerd_stack::{Either, NewService, Proxy, ProxyService};
 use pin_project::pin_project;
 use std::future::Future;
 use std::pin::Pin;
@@ -17,6 +20,18 @@ pub trait NewPolicy<T> {
     fn new_policy(&self, target: &T) -> Option<Self::Policy>;
 }
 
+/// A retry policy that may transform requests so that they can be retried.
+pub trait PrepareRequest<Req, Rsp, E>: Policy<Self::RetryRequest, Rsp, E> {
+    /// The type of request that can be retried.
+    type RetryRequest;
+
+    /// Prepares a request so that it may be retried.
+    ///
+    /// If the request cannot be retried, it is returned unchanged (as
+    /// `Either::B`) and is dispatched without a retry policy.
+    fn prepare_request(&self, req: Req) -> Either<Self::RetryRequest, Req>;
+}
+
 /// A layer that applies per-target retry polcies.
 ///
 /// Composes `NewService`s that produce a `Proxy`.
@@ -38,15 +53,9 @@ pub struct Retry<P, S> {
 }
 
 #[pin_project(project = ResponseFutureProj)]
-pub enum ResponseFuture<R, P, S, Req>
-where
-    R: tower::retry::Policy<Req, P::Response, Error> + Clone,
-    P: Proxy<Req, S> + Clone,
-    S: tower::Service<P::Request> + Clone,
-    S::Error: Into<Error>,
-{
-    Disabled(#[pin] P::Future),
-    Retry(#[pin] Oneshot<tower::retry::Retry<R, ProxyService<P, S>>, Req>),
+pub enum ResponseFuture<F, R> {
+    Disabled(#[pin] F),
+    Retry(#[pin] R),
 }
 
 // === impl NewRetryLayer ===
@@ -88,39 +97,53 @@ where
 
 // === impl Retry ===
 
-impl<R, P, Req, S> Proxy<Req, S> for Retry<R, P>
+impl<R, P, Req, S, PReq, Rsp> Proxy<Req, S> for Retry<R, P>
 where
-    R: tower::retry::Policy<Req, P::Response, Error> + Clone,
-    P: Proxy<Req, S> + Clone,
-    S: tower::Service<P::Request> + Clone,
+    R: PrepareRequest<Req, Rsp, Error> + Clone,
+    P: Proxy<Req, S, Request = PReq, Response = Rsp>
+        + Proxy<R::RetryRequest, S, Request = PReq, Response = Rsp>
+        + Clone,
+    S: tower::Service<PReq> + Clone,
     S::Error: Into<Error>,
 {
-    type Request = P::Request;
-    type Response = P::Response;
+    type Request = PReq;
+    type Response = Rsp;
     type Error = Error;
-    type Future = ResponseFuture<R, P, S, Req>;
+    type Future = ResponseFuture<
+        <P as Proxy<Req, S>>::Future,
+        Oneshot<tower::retry::Retry<R, ProxyService<P, S>>, R::RetryRequest>,
+    >;
 
     fn proxy(&self, svc: &mut S, req: Req) -> Self::Future {
         trace!(retryable = %self.policy.is_some());
 
-        if let Some(policy) = self.policy.as_ref() {
-            let inner = self.inner.clone().wrap_service(svc.clone());
-            let retry = tower::retry::Retry::new(policy.clone(), inner);
-            return ResponseFuture::Retry(retry.oneshot(req));
-        }
-
-        ResponseFuture::Disabled(self.inner.proxy(svc, req))
+        let req = match self.policy.as_ref() {
+            None => req,
+            Some(policy) => match policy.prepare_request(req) {
+                Either::A(req) => {
+                    let inner = ProxyService::new(self.inner.clone(), svc.clone());
+                    let retry = tower::retry::Retry::new(policy.clone(), inner);
+                    return ResponseFuture::Retry(retry.oneshot(req));
+                }
+                Either::B(req) => {
+                    trace!("Request cannot be retried");
+                    req
+                }
+            },
+        };
+
+        ResponseFuture::Disabled(Proxy::<Req, S>::proxy(&self.inner, svc, req))
     }
 }
 
-impl<R, P, S, Req> Future for ResponseFuture<R, P, S, Req>
+impl<F, R, Rsp, E1, E2> Future for ResponseFuture<F, R>
 where
-    R: tower::retry::Policy<Req, P::Response, Error> + Clone,
-    P: Proxy<Req, S> + Clone,
-    S: tower::Service<P::Request> + Clone,
-    S::Error: Into<Error>,
+    F: Future<Output = Result<Rsp, E1>>,
+    R: Future<Output = Result<Rsp, E2>>,
+    E1: Into<Error>,
+    E2: Into<Error>,
 {
-    type Output = Result<P::Response, Error>;
+    type Output = Result<Rsp, Error>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         match self.project() {
@@ -1,4 +1,4 @@
-use crate::{http, LogicalAddr, Profile, Receiver, Target};
+use crate::{http, overrides, LogicalAddr, Profile, Receiver, Target};
 use api::destination_client::DestinationClient;
 use futures::{future, prelude::*, ready, select_biased};
 use http_body::Body as HttpBody;
@@ -9,7 +9,6 @@ use linkerd_error::{Error, Recover};
 use linkerd_proxy_api_resolve::pb as resolve;
 use linkerd_stack::Param;
 use pin_project::pin_project;
-use regex::Regex;
 use std::{
     convert::TryInto,
     future::Future,
@@ -34,6 +33,7 @@ pub struct Client<S, R> {
     service: DestinationClient<S>,
     recover: R,
     context_token: String,
+    overrides: Option<overrides::Receiver>,
 }
 
 #[pin_project]
@@ -44,6 +44,7 @@ where
 {
     #[pin]
     inner: Option<Inner<S, R>>,
+    overrides: Option<overrides::Receiver>,
 }
 
 #[pin_project]
@@ -105,6 +106,16 @@ where
             service: DestinationClient::new(service),
             recover,
             context_token,
+            overrides: None,
+        }
+    }
+
+    /// Applies the overrides to each discovered profile, updating profiles as
+    /// the overrides change.
+    pub fn with_overrides(self, overrides: overrides::Receiver) -> Self {
+        Self {
+            overrides: Some(overrides),
+            ..self
         }
     }
 }
@@ -144,7 +155,10 @@ where
             recover: self.recover.clone(),
             state: State::Disconnected { backoff: None },
         };
-        ProfileFuture { inner: Some(inner) }
+        ProfileFuture {
+            inner: Some(inner),
+            overrides: self.overrides.clone(),
+        }
     }
 }
 
@@ -179,16 +193,22 @@ where
         };
 
         trace!("daemonizing");
-        let (tx, rx) = watch::channel(profile);
         let inner = this.inner.take().expect("polled after ready");
+        let mut overrides = this.overrides.take();
+        let name = inner.request.path.clone();
+        let (tx, rx) = watch::channel(apply_overrides(overrides.as_ref(), &name, profile.clone()));
         let daemon = async move {
             tokio::pin!(inner);
+            let mut discovered = profile;
             loop {
                 select_biased! {
                     _ = tx.closed().fuse() => {
                         trace!("profile observation dropped");
                         return;
                     },
+                    _ = overrides_changed(&mut overrides).fuse() => {
+                        trace!("overrides changed");
+                    },
                     profile = future::poll_fn(|cx|
                         inner.as_mut().poll_profile(cx)
                         ).fuse() => {
@@ -197,16 +217,17 @@ where
                                 error!(%error, "profile client died");
                                 return;
                             }
-                            Ok(profile) => {
-                                trace!(?profile, "publishing");
-                                if tx.send(profile).is_err() {
-                                    trace!("failed to publish profile");
-                                    return;
-                                }
-                            }
+                            Ok(profile) => discovered = profile,
                         }
                     }
                 }
+
+                let profile = apply_overrides(overrides.as_ref(), &name, discovered.clone());
+                trace!(?profile, "publishing");
+                if tx.send(profile).is_err() {
+                    trace!("failed to publish profile");
+                    return;
+                }
             }
         };
         tokio::spawn(daemon.in_current_span());
@@ -215,6 +236,28 @@ where
     }
 }
 
+fn apply_overrides(
+    overrides: Option<&overrides::Receiver>,
+    name: &str,
+    profile: Profile,
+) -> Profile {
+    match overrides {
+        Some(overrides) => overrides.borrow().apply(name, profile),
+        None => profile,
+    }
+}
+
+/// Completes when the overrides change. If there are no overrides (or they can
+/// no longer change), this never completes.
+async fn overrides_changed(overrides: &mut Option<overrides::Receiver>) {
+    if let Some(rx) = overrides.as_mut() {
+        if rx.changed().await.is_ok() {
+            return;
+        }
+    }
+    future::pending::<()>().await
+}
+
 // === impl Inner ===
 
 impl<S, R> Inner<S, R>
@@ -255,8 +298,11 @@ where
                     name,
                     http_routes,
                     targets,
+                    target_overrides: Vec::new(),
+                    split_key: None,
                     opaque_protocol: proto.opaque_protocol,
                     endpoint,
+                    balancer: None,
                 }
             })
         });
@@ -388,16 +434,7 @@ fn convert_req_match(orig: api::RequestMatch) -> Option<http::RequestMatch> {
             http::RequestMatch::Not(Box::new(m))
         }
         api::request_match::Match::Path(api::PathMatch { regex }) => {
-            let regex = regex.trim();
-            let re = match (regex.starts_with('^'), regex.ends_with('$')) {
-                (true, true) => Regex::new(regex).ok()?,
-                (hd_anchor, tl_anchor) => {
-                    let hd = if hd_anchor { "" } else { "^" };
-                    let tl = if tl_anchor { "" } else { "$" };
-                    let re = format!("{}{}{}", hd, regex, tl);
-                    Regex::new(&re).ok()?
-                }
-            };
+            let re = http::anchored_regex(&regex).ok()?;
             http::RequestMatch::Path(Box::new(re))
         }
         api::request_match::Match::Method(mm) => {
@@ -505,8 +542,128 @@ fn convert_retry_budget(orig: api::RetryBudget) -> Option<Arc<Budget>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use linkerd2_proxy_api::http_types::{http_method, HttpMethod};
     use quickcheck::*;
 
+    fn path(regex: &str) -> api::RequestMatch {
+        api::RequestMatch {
+            r#match: Some(api::request_match::Match::Path(api::PathMatch {
+                regex: regex.to_string(),
+            })),
+        }
+    }
+
+    fn method(method: http_method::Registered) -> api::RequestMatch {
+        api::RequestMatch {
+            r#match: Some(api::request_match::Match::Method(HttpMethod {
+                r#type: Some(http_method::Type::Registered(method as i32)),
+            })),
+        }
+    }
+
+    fn req(method: ::http::Method, uri: &str) -> ::http::Request<()> {
+        ::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(())
+            .unwrap()
+    }
+
+    #[test]
+    fn converts_request_matches() {
+        let all = api::RequestMatch {
+            r#match: Some(api::request_match::Match::All(api::request_match::Seq {
+                matches: vec![path("/books/[^/]+"), method(http_method::Registered::Get)],
+            })),
+        };
+        let m = convert_req_match(all).expect("must convert");
+        assert!(m.is_match(&req(::http::Method::GET, "/books/1")));
+        assert!(!m.is_match(&req(::http::Method::POST, "/books/1")));
+        assert!(
+            !m.is_match(&req(::http::Method::GET, "/books/1/pages")),
+            "path regexes must be anchored"
+        );
+
+        let any = api::RequestMatch {
+            r#match: Some(api::request_match::Match::Any(api::request_match::Seq {
+                matches: vec![path("/a"), path("/b")],
+            })),
+        };
+        let m = convert_req_match(any).expect("must convert");
+        assert!(m.is_match(&req(::http::Method::GET, "/a")));
+        assert!(m.is_match(&req(::http::Method::GET, "/b")));
+        assert!(!m.is_match(&req(::http::Method::GET, "/c")));
+
+        let not = api::RequestMatch {
+            r#match: Some(api::request_match::Match::Not(Box::new(method(
+                http_method::Registered::Get,
+            )))),
+        };
+        let m = convert_req_match(not).expect("must convert");
+        assert!(!m.is_match(&req(::http::Method::GET, "/")));
+        assert!(m.is_match(&req(::http::Method::DELETE, "/")));
+    }
+
+    fn route(is_retryable: bool, timeout: Option<prost_types::Duration>) -> api::Route {
+        api::Route {
+            condition: Some(path("/")),
+            is_retryable,
+            timeout,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn converts_route_retries() {
+        let budget = Arc::new(Budget::new(Duration::from_secs(10), 10, 0.2));
+
+        let (_, rt) = convert_route(route(true, None), Some(&budget)).expect("must convert");
+        let retries = rt.retries().expect("retryable routes must have retries");
+        assert!(Arc::ptr_eq(retries.budget(), &budget));
+        // Discovered routes use the proxy's default attempt limit and backoff.
+        assert_eq!(retries.max_attempts(), None);
+        assert!(retries.backoff().is_none());
+
+        let (_, rt) = convert_route(route(false, None), Some(&budget)).expect("must convert");
+        assert!(rt.retries().is_none(), "only retryable routes are retried");
+
+        let (_, rt) = convert_route(route(true, None), None).expect("must convert");
+        assert!(rt.retries().is_none(), "retries require a budget");
+    }
+
+    #[test]
+    fn converts_route_timeouts() {
+        let timeout = prost_types::Duration {
+            seconds: 1,
+            nanos: 500_000_000,
+        };
+        let (_, rt) = convert_route(route(false, Some(timeout)), None).expect("must convert");
+        assert_eq!(rt.timeout(), Some(Duration::from_millis(1_500)));
+
+        let negative = prost_types::Duration {
+            seconds: -1,
+            nanos: 0,
+        };
+        let (_, rt) = convert_route(route(false, Some(negative)), None).expect("must convert");
+        assert_eq!(rt.timeout(), None, "negative timeouts are ignored");
+
+        assert!(
+            convert_route(api::Route::default(), None).is_none(),
+            "routes require a condition"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_request_matches() {
+        assert!(convert_req_match(path("(")).is_none(), "invalid regex");
+        assert!(convert_req_match(api::RequestMatch { r#match: None }).is_none());
+
+        let not = api::RequestMatch {
+            r#match: Some(api::request_match::Match::Not(Box::new(path("(")))),
+        };
+        assert!(convert_req_match(not).is_none());
+    }
+
     quickcheck! {
         fn retry_budget_from_proto(
             min_retries_per_second: u32,
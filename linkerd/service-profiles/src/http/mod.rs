@@ -1,5 +1,7 @@
 use crate::Receiver;
 use indexmap::IndexMap;
+use linkerd_exp_backoff::ExponentialBackoff;
+use linkerd_rate_limit::Limit;
 use regex::Regex;
 use std::{
     fmt,
@@ -18,7 +20,10 @@ pub struct Route {
     labels: Labels,
     response_classes: ResponseClasses,
     retries: Option<Retries>,
+    hedge: Option<Hedge>,
     timeout: Option<Duration>,
+    rate_limit: Option<Limit>,
+    sample_rate: Option<SampleRate>,
 }
 
 #[derive(Clone, Debug)]
@@ -28,6 +33,34 @@ pub enum RequestMatch {
     Not(Box<RequestMatch>),
     Path(Box<Regex>),
     Method(http::Method),
+    Header(HeaderMatch),
+    Query(QueryMatch),
+}
+
+/// Matches requests (or responses) that carry a header with a matching value.
+///
+/// When a header has multiple values, the message matches if any of them do.
+#[derive(Clone, Debug)]
+pub struct HeaderMatch {
+    name: http::header::HeaderName,
+    value: ValueMatch,
+}
+
+/// Matches requests with a query parameter that has a matching value.
+///
+/// Query parameters are compared as they appear in the URI, without
+/// percent-decoding.
+#[derive(Clone, Debug)]
+pub struct QueryMatch {
+    name: String,
+    value: ValueMatch,
+}
+
+#[derive(Clone, Debug)]
+pub enum ValueMatch {
+    Exact(String),
+    Prefix(String),
+    Regex(Box<Regex>),
 }
 
 #[derive(Clone, Debug)]
@@ -48,11 +81,35 @@ pub enum ResponseMatch {
         min: http::StatusCode,
         max: http::StatusCode,
     },
+    Header(HeaderMatch),
+    /// Matches gRPC responses with a `grpc-status` in the given range.
+    ///
+    /// The status is read from the response trailers or, for trailers-only
+    /// responses, from the response headers.
+    GrpcStatus {
+        min: u32,
+        max: u32,
+    },
 }
 
 #[derive(Clone, Debug)]
 pub struct Retries {
     budget: Arc<Budget>,
+    max_attempts: Option<usize>,
+    backoff: Option<ExponentialBackoff>,
+}
+
+/// Configures when requests on a route are hedged.
+#[derive(Copy, Clone, Debug)]
+pub struct Hedge {
+    latency_percentile: f64,
+}
+
+/// Configures the probability that requests on a route that arrive without a
+/// trace context start a new trace.
+#[derive(Copy, Clone, Debug)]
+pub struct SampleRate {
+    probability: f64,
 }
 
 #[derive(Clone, Default)]
@@ -75,7 +132,10 @@ impl Route {
             labels,
             response_classes: ResponseClasses(response_classes.into()),
             retries: None,
+            hedge: None,
             timeout: None,
+            rate_limit: None,
+            sample_rate: None,
         }
     }
 
@@ -91,26 +151,64 @@ impl Route {
         self.retries.as_ref()
     }
 
+    pub fn retries_mut(&mut self) -> Option<&mut Retries> {
+        self.retries.as_mut()
+    }
+
+    /// Returns the route's hedging policy. When unset, the outbound proxy may
+    /// hedge the route if it is retryable.
+    pub fn hedge(&self) -> Option<Hedge> {
+        self.hedge
+    }
+
     pub fn timeout(&self) -> Option<Duration> {
         self.timeout
     }
 
+    /// Returns a limit that overrides the proxy's route rate limit.
+    pub fn rate_limit(&self) -> Option<Limit> {
+        self.rate_limit
+    }
+
+    /// Returns a sample rate that overrides the proxy's default probability.
+    pub fn sample_rate(&self) -> Option<SampleRate> {
+        self.sample_rate
+    }
+
     pub fn set_retries(&mut self, budget: Arc<Budget>) {
-        self.retries = Some(Retries { budget });
+        self.retries = Some(Retries {
+            budget,
+            max_attempts: None,
+            backoff: None,
+        });
+    }
+
+    pub fn set_hedge(&mut self, hedge: Hedge) {
+        self.hedge = Some(hedge);
     }
 
     pub fn set_timeout(&mut self, timeout: Duration) {
         self.timeout = Some(timeout);
     }
+
+    pub fn set_rate_limit(&mut self, limit: Limit) {
+        self.rate_limit = Some(limit);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.sample_rate = Some(sample_rate);
+    }
 }
 
 // === impl RequestMatch ===
 
 impl RequestMatch {
-    fn is_match<B>(&self, req: &http::Request<B>) -> bool {
+    pub(crate) fn is_match<B>(&self, req: &http::Request<B>) -> bool {
         match self {
             RequestMatch::Method(ref method) => req.method() == *method,
             RequestMatch::Path(ref re) => re.is_match(req.uri().path()),
+            RequestMatch::Header(ref m) => m.is_match(req.headers()),
+            RequestMatch::Query(ref m) => m.is_match(req.uri().query()),
             RequestMatch::Not(ref m) => !m.is_match(req),
             RequestMatch::All(ref ms) => ms.iter().all(|m| m.is_match(req)),
             RequestMatch::Any(ref ms) => ms.iter().any(|m| m.is_match(req)),
@@ -118,6 +216,81 @@ impl RequestMatch {
     }
 }
 
+// === impl HeaderMatch ===
+
+impl HeaderMatch {
+    pub fn new(name: http::header::HeaderName, value: ValueMatch) -> Self {
+        Self { name, value }
+    }
+
+    fn is_match(&self, headers: &http::HeaderMap) -> bool {
+        headers
+            .get_all(&self.name)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .any(|v| self.value.is_match(v))
+    }
+}
+
+// === impl QueryMatch ===
+
+impl QueryMatch {
+    pub fn new(name: impl Into<String>, value: ValueMatch) -> Self {
+        Self {
+            name: name.into(),
+            value,
+        }
+    }
+
+    fn is_match(&self, query: Option<&str>) -> bool {
+        let query = match query {
+            Some(q) => q,
+            None => return false,
+        };
+        query
+            .split('&')
+            .map(|pair| {
+                let mut kv = pair.splitn(2, '=');
+                let k = kv.next().unwrap_or("");
+                let v = kv.next().unwrap_or("");
+                (k, v)
+            })
+            .any(|(k, v)| k == self.name && self.value.is_match(v))
+    }
+}
+
+// === impl ValueMatch ===
+
+impl ValueMatch {
+    /// Builds a regex matcher that must match the entire value.
+    ///
+    /// The expression is anchored at both ends unless it is already anchored.
+    pub fn regex(regex: &str) -> Result<Self, regex::Error> {
+        anchored_regex(regex).map(|re| ValueMatch::Regex(Box::new(re)))
+    }
+
+    fn is_match(&self, value: &str) -> bool {
+        match self {
+            ValueMatch::Exact(ref v) => value == v,
+            ValueMatch::Prefix(ref p) => value.starts_with(p.as_str()),
+            ValueMatch::Regex(ref re) => re.is_match(value),
+        }
+    }
+}
+
+/// Compiles a regular expression so that it must match an entire input.
+pub fn anchored_regex(regex: &str) -> Result<Regex, regex::Error> {
+    let regex = regex.trim();
+    match (regex.starts_with('^'), regex.ends_with('$')) {
+        (true, true) => Regex::new(regex),
+        (hd_anchor, tl_anchor) => {
+            let hd = if hd_anchor { "" } else { "^" };
+            let tl = if tl_anchor { "" } else { "$" };
+            Regex::new(&format!("{}{}{}", hd, regex, tl))
+        }
+    }
+}
+
 // === impl ResponseClass ===
 
 impl ResponseClass {
@@ -129,8 +302,25 @@ impl ResponseClass {
         self.is_failure
     }
 
-    pub fn is_match<B>(&self, req: &http::Response<B>) -> bool {
-        self.match_.is_match(req)
+    /// Matches a response from its headers alone.
+    pub fn is_match<B>(&self, rsp: &http::Response<B>) -> bool {
+        self.match_.is_match(rsp.status(), rsp.headers(), None)
+    }
+
+    /// Matches a response once its trailers, if any, have been received.
+    pub fn is_match_eos(
+        &self,
+        status: http::StatusCode,
+        headers: &http::HeaderMap,
+        trailers: Option<&http::HeaderMap>,
+    ) -> bool {
+        self.match_.is_match(status, headers, trailers)
+    }
+
+    /// Indicates whether the class matches on the response's gRPC status,
+    /// which may not be known until trailers are received.
+    pub fn is_grpc(&self) -> bool {
+        self.match_.is_grpc()
     }
 }
 
@@ -167,29 +357,88 @@ impl fmt::Debug for ResponseClasses {
 // === impl ResponseMatch ===
 
 impl ResponseMatch {
-    fn is_match<B>(&self, req: &http::Response<B>) -> bool {
+    fn is_match(
+        &self,
+        status: http::StatusCode,
+        headers: &http::HeaderMap,
+        trailers: Option<&http::HeaderMap>,
+    ) -> bool {
+        match self {
+            ResponseMatch::Status { ref min, ref max } => *min <= status && status <= *max,
+            ResponseMatch::Header(ref m) => m.is_match(headers),
+            ResponseMatch::GrpcStatus { min, max } => trailers
+                .and_then(grpc_status)
+                .or_else(|| grpc_status(headers))
+                .map(|code| *min <= code && code <= *max)
+                .unwrap_or(false),
+            ResponseMatch::Not(ref m) => !m.is_match(status, headers, trailers),
+            ResponseMatch::All(ref ms) => ms.iter().all(|m| m.is_match(status, headers, trailers)),
+            ResponseMatch::Any(ref ms) => ms.iter().any(|m| m.is_match(status, headers, trailers)),
+        }
+    }
+
+    fn is_grpc(&self) -> bool {
         match self {
-            ResponseMatch::Status { ref min, ref max } => {
-                *min <= req.status() && req.status() <= *max
+            ResponseMatch::GrpcStatus { .. } => true,
+            ResponseMatch::Status { .. } | ResponseMatch::Header(_) => false,
+            ResponseMatch::Not(ref m) => m.is_grpc(),
+            ResponseMatch::All(ref ms) | ResponseMatch::Any(ref ms) => {
+                ms.iter().any(|m| m.is_grpc())
             }
-            ResponseMatch::Not(ref m) => !m.is_match(req),
-            ResponseMatch::All(ref ms) => ms.iter().all(|m| m.is_match(req)),
-            ResponseMatch::Any(ref ms) => ms.iter().any(|m| m.is_match(req)),
         }
     }
 }
 
+/// Reads the `grpc-status` from response headers or trailers.
+pub fn grpc_status(headers: &http::HeaderMap) -> Option<u32> {
+    headers
+        .get("grpc-status")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse().ok())
+}
+
 // === impl Retries ===
 
 impl Retries {
     pub fn budget(&self) -> &Arc<Budget> {
         &self.budget
     }
+
+    /// The maximum number of times a request may be attempted on this route,
+    /// including the original request.
+    ///
+    /// When unset, the proxy's default applies.
+    pub fn max_attempts(&self) -> Option<usize> {
+        self.max_attempts
+    }
+
+    /// The backoff to apply between attempts on this route.
+    ///
+    /// When unset, the proxy's default applies.
+    pub fn backoff(&self) -> Option<ExponentialBackoff> {
+        self.backoff
+    }
+
+    pub fn set_max_attempts(&mut self, max_attempts: usize) {
+        self.max_attempts = Some(max_attempts);
+    }
+
+    pub fn set_backoff(&mut self, backoff: ExponentialBackoff) {
+        self.backoff = Some(backoff);
+    }
+
+    /// Identifies the backoff by its raw bits, so that retry policies can be
+    /// compared and hashed like the rest of the route.
+    fn backoff_bits(&self) -> Option<(Duration, Duration, u64)> {
+        self.backoff.map(|b| (b.min, b.max, b.jitter.to_bits()))
+    }
 }
 
 impl PartialEq for Retries {
     fn eq(&self, other: &Self) -> bool {
         Arc::ptr_eq(&self.budget, &other.budget)
+            && self.max_attempts == other.max_attempts
+            && self.backoff_bits() == other.backoff_bits()
     }
 }
 
@@ -198,6 +447,77 @@ impl Eq for Retries {}
 impl Hash for Retries {
     fn hash<H: Hasher>(&self, state: &mut H) {
         state.write_usize(Arc::as_ref(&self.budget) as *const _ as usize);
+        self.max_attempts.hash(state);
+        self.backoff_bits().hash(state);
+    }
+}
+
+// === impl Hedge ===
+
+impl Hedge {
+    /// Hedges requests that have not completed once the given percentile (on
+    /// `(0, 100)`) of the route's response latency has elapsed.
+    ///
+    /// Returns `None` if the percentile is out of range.
+    pub fn new(latency_percentile: f64) -> Option<Self> {
+        if latency_percentile > 0.0 && latency_percentile < 100.0 {
+            Some(Self { latency_percentile })
+        } else {
+            None
+        }
+    }
+
+    pub fn latency_percentile(&self) -> f64 {
+        self.latency_percentile
+    }
+}
+
+impl PartialEq for Hedge {
+    fn eq(&self, other: &Self) -> bool {
+        self.latency_percentile.to_bits() == other.latency_percentile.to_bits()
+    }
+}
+
+// The percentile's raw bits are compared, so equality is total.
+impl Eq for Hedge {}
+
+impl Hash for Hedge {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.latency_percentile.to_bits().hash(state);
+    }
+}
+
+// === impl SampleRate ===
+
+impl SampleRate {
+    /// Samples requests with the given probability (on `[0, 1]`).
+    ///
+    /// Returns `None` if the probability is out of range.
+    pub fn new(probability: f64) -> Option<Self> {
+        if (0.0..=1.0).contains(&probability) {
+            Some(Self { probability })
+        } else {
+            None
+        }
+    }
+
+    pub fn probability(&self) -> f64 {
+        self.probability
+    }
+}
+
+impl PartialEq for SampleRate {
+    fn eq(&self, other: &Self) -> bool {
+        self.probability.to_bits() == other.probability.to_bits()
+    }
+}
+
+// The probability's raw bits are compared, so equality is total.
+impl Eq for SampleRate {}
+
+impl Hash for SampleRate {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.probability.to_bits().hash(state);
     }
 }
 
@@ -222,3 +542,118 @@ impl fmt::Debug for Labels {
         self.0.fmt(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn req(uri: &str, headers: &[(&'static str, &'static str)]) -> http::Request<()> {
+        let mut req = http::Request::builder().uri(uri);
+        for (k, v) in headers {
+            req = req.header(*k, *v);
+        }
+        req.body(()).unwrap()
+    }
+
+    #[test]
+    fn header_value_matches() {
+        let exact = RequestMatch::Header(HeaderMatch::new(
+            http::header::CONTENT_TYPE,
+            ValueMatch::Exact("application/json".into()),
+        ));
+        let prefix = RequestMatch::Header(HeaderMatch::new(
+            http::header::CONTENT_TYPE,
+            ValueMatch::Prefix("application/grpc".into()),
+        ));
+        let regex = RequestMatch::Header(HeaderMatch::new(
+            http::header::HeaderName::from_static("x-tenant-id"),
+            ValueMatch::regex("tenant-[0-9]+").unwrap(),
+        ));
+
+        let json = req("/", &[("content-type", "application/json")]);
+        assert!(exact.is_match(&json));
+        assert!(!prefix.is_match(&json));
+        assert!(!regex.is_match(&json));
+
+        let grpc = req(
+            "/",
+            &[
+                ("content-type", "application/grpc+proto"),
+                ("x-tenant-id", "other"),
+                ("x-tenant-id", "tenant-42"),
+            ],
+        );
+        assert!(!exact.is_match(&grpc));
+        assert!(prefix.is_match(&grpc));
+        assert!(regex.is_match(&grpc));
+
+        let partial = req("/", &[("x-tenant-id", "tenant-42x")]);
+        assert!(!regex.is_match(&partial));
+    }
+
+    #[test]
+    fn query_param_matches() {
+        let m = RequestMatch::Query(QueryMatch::new("version", ValueMatch::Prefix("v2".into())));
+        assert!(m.is_match(&req("/foo?version=v2.1", &[])));
+        assert!(m.is_match(&req("/foo?a=b&version=v2&c", &[])));
+        assert!(!m.is_match(&req("/foo?version=v1", &[])));
+        assert!(!m.is_match(&req("/foo?versions=v2", &[])));
+        assert!(!m.is_match(&req("/foo", &[])));
+
+        let empty = RequestMatch::Query(QueryMatch::new("debug", ValueMatch::Exact("".into())));
+        assert!(empty.is_match(&req("/foo?debug", &[])));
+    }
+
+    #[test]
+    fn combined_matches() {
+        let m = RequestMatch::All(vec![
+            RequestMatch::Method(http::Method::GET),
+            RequestMatch::Header(HeaderMatch::new(
+                http::header::HeaderName::from_static("x-canary"),
+                ValueMatch::Exact("true".into()),
+            )),
+        ]);
+        assert!(m.is_match(&req("/", &[("x-canary", "true")])));
+        assert!(!m.is_match(&req("/", &[("x-canary", "false")])));
+    }
+
+    #[test]
+    fn grpc_status_matches() {
+        let unavailable = ResponseClass::new(true, ResponseMatch::GrpcStatus { min: 14, max: 14 });
+        assert!(unavailable.is_grpc());
+
+        let ok = http::Response::builder().body(()).unwrap();
+        assert!(!unavailable.is_match(&ok));
+
+        let mut trailers = http::HeaderMap::new();
+        trailers.insert("grpc-status", 14.into());
+        assert!(unavailable.is_match_eos(ok.status(), ok.headers(), Some(&trailers)));
+        trailers.insert("grpc-status", 5.into());
+        assert!(!unavailable.is_match_eos(ok.status(), ok.headers(), Some(&trailers)));
+
+        let trailers_only = http::Response::builder()
+            .header("grpc-status", "14")
+            .body(())
+            .unwrap();
+        assert!(unavailable.is_match(&trailers_only));
+    }
+
+    #[test]
+    fn response_header_matches() {
+        let class = ResponseClass::new(
+            true,
+            ResponseMatch::Header(HeaderMatch::new(
+                http::header::HeaderName::from_static("x-error"),
+                ValueMatch::Prefix("retry".into()),
+            )),
+        );
+        assert!(!class.is_grpc());
+
+        let rsp = http::Response::builder()
+            .header("x-error", "retry-later")
+            .body(())
+            .unwrap();
+        assert!(class.is_match(&rsp));
+        assert!(!class.is_match(&http::Response::new(())));
+    }
+}
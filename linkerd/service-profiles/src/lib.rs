@@ -5,6 +5,7 @@ use linkerd_addr::Addr;
 pub use linkerd_dns_name::Name;
 use linkerd_error::Error;
 use linkerd_proxy_api_resolve::Metadata;
+use linkerd_proxy_balance::Strategy;
 use std::{
     future::Future,
     net::SocketAddr,
@@ -17,6 +18,7 @@ mod client;
 mod default;
 pub mod discover;
 pub mod http;
+pub mod overrides;
 pub mod split;
 
 pub use self::client::Client;
@@ -28,8 +30,16 @@ pub struct Profile {
     pub name: Option<Name>,
     pub http_routes: Vec<(self::http::RequestMatch, self::http::Route)>,
     pub targets: Vec<Target>,
+    /// Forces requests that match an override to its target, regardless of
+    /// the targets' weights.
+    pub target_overrides: Vec<TargetOverride>,
+    /// When set, requests are split over targets by hashing this key, so that
+    /// all requests with the same key are split to the same target.
+    pub split_key: Option<SplitKey>,
     pub opaque_protocol: bool,
     pub endpoint: Option<(SocketAddr, Metadata)>,
+    /// Overrides the proxy's default load balancing strategy.
+    pub balancer: Option<Strategy>,
 }
 
 /// A profile lookup target.
@@ -42,6 +52,23 @@ pub struct Target {
     pub weight: u32,
 }
 
+/// Splits requests that match `request_match` to the `addr` target.
+#[derive(Clone, Debug)]
+pub struct TargetOverride {
+    pub request_match: self::http::RequestMatch,
+    pub addr: Addr,
+}
+
+/// A request property that is hashed to choose a request's target.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SplitKey {
+    /// The value of a request header.
+    Header(::http::header::HeaderName),
+
+    /// The value of a named cookie.
+    Cookie(String),
+}
+
 #[derive(Clone, Debug)]
 pub struct GetProfileService<P>(P);
 
@@ -0,0 +1,795 @@
+//! Profile configuration that the destination API can't describe.
+//!
+//! The destination API (as of linkerd2-proxy-api v0.1.18) describes only a
+//! subset of the proxy's profile features. Everything else is configured by a
+//! JSON file of per-profile overrides, which is applied to each discovered
+//! profile and reloaded as it changes.
+
+use crate::{http, Profile, SplitKey, TargetOverride};
+use linkerd_addr::Addr;
+use linkerd_error::Error;
+use linkerd_exp_backoff::ExponentialBackoff;
+use linkerd_file_watch::json::{self, Object, Value};
+pub use linkerd_file_watch::Task;
+use linkerd_proxy_balance::{InvalidStrategy, Strategy};
+use linkerd_rate_limit::Limit;
+use std::{collections::HashMap, convert::TryFrom, fmt, path::PathBuf, sync::Arc, time::Duration};
+use tokio::sync::watch;
+use tower::retry::budget::Budget;
+
+/// Reads overrides from a JSON file that is reloaded as it changes. The file
+/// maps each profile's lookup name to its overrides, e.g.:
+///
+/// ```json
+/// {
+///   "books.default.svc.cluster.local:8080": {
+///     "balancer": "ring-hash:header:x-session-id",
+///     "split": {
+///       "key": {"cookie": "session"},
+///       "overrides": [
+///         {"condition": {"header": {"name": "x-canary", "exact": "true"}},
+///          "target": "books-canary.default.svc.cluster.local:8080"}
+///       ]
+///     },
+///     "routes": [{
+///       "name": "GET /books/{id}",
+///       "condition": {"all": [
+///         {"method": "GET"},
+///         {"path": "/books/[^/]+"},
+///         {"header": {"name": "x-canary", "exact": "true"}}
+///       ]},
+///       "response_classes": [
+///         {"condition": {"grpc_status": {"min": 14, "max": 14}}, "is_failure": true}
+///       ],
+///       "timeout_ms": 1000,
+///       "retries": {"max_attempts": 3}
+///     }]
+///   }
+/// }
+/// ```
+///
+/// A profile's `balancer` overrides the proxy's default load balancing
+/// strategy for its endpoints. It is one of `peak-ewma`, `least-request`,
+/// `round-robin`, `ring-hash:client-addr`, or `ring-hash:header:<name>`.
+///
+/// A profile's `split` configures how HTTP requests are split over its
+/// targets: requests with the same `header` or `cookie` `key` are split to the
+/// same target, and requests that match an override's request `condition` are
+/// always split to its `target`.
+///
+/// Routes are matched before the profile's discovered routes. Request
+/// conditions may match on `method`, `path` (a regex), `header` or `query` (by
+/// `name` and an `exact`, `prefix` or `regex` value) and combine conditions
+/// with `all`, `any` and `not`. Response conditions may match on `status` or
+/// `grpc_status` ranges or on a `header`, combined in the same way. Routes
+/// may also set a `timeout_ms`, `retries`, a `hedge` (e.g.
+/// `{"latency_percentile": 95}`), which hedges requests that take longer than
+/// the given percentile of the route's latency, and a `rate_limit` (e.g.
+/// `{"per_second": 100, "burst": 200}`, where the burst defaults to the rate),
+/// which overrides the proxy's inbound route rate limit, and a `sample_rate`
+/// (on `[0, 1]`), which overrides the probability that the route's requests
+/// start a new trace.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub path: PathBuf,
+}
+
+/// Overrides for each profile, by lookup name.
+#[derive(Clone, Debug, Default)]
+pub struct Overrides(HashMap<String, ProfileOverrides>);
+
+#[derive(Clone, Debug, Default)]
+struct ProfileOverrides {
+    http_routes: Vec<(http::RequestMatch, http::Route)>,
+    balancer: Option<Strategy>,
+    split_key: Option<SplitKey>,
+    target_overrides: Vec<TargetOverride>,
+}
+
+pub type Receiver = watch::Receiver<Arc<Overrides>>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidOverrides(String);
+
+// === impl Config ===
+
+impl Config {
+    const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Loads the overrides, failing if they are invalid, and returns a task
+    /// that reloads them as the file changes.
+    pub fn build(self) -> Result<(Receiver, Task), Error> {
+        linkerd_file_watch::watch(self.path, Self::POLL_INTERVAL, |bytes| {
+            Ok(Arc::new(Overrides::from_json(bytes)?))
+        })
+    }
+}
+
+// === impl Overrides ===
+
+impl Overrides {
+    pub fn from_json(bytes: &[u8]) -> Result<Self, InvalidOverrides> {
+        let value = serde_json::from_slice::<Value>(bytes)
+            .map_err(|e| InvalidOverrides(format!("invalid JSON: {}", e)))?;
+        let profiles = into_object(value, "overrides")?
+            .into_iter()
+            .map(|(name, profile)| {
+                let profile = ProfileOverrides::from_json(profile, &name)?;
+                Ok((name, profile))
+            })
+            .collect::<Result<_, InvalidOverrides>>()?;
+        Ok(Self(profiles))
+    }
+
+    /// Applies the overrides for the profile named `name`, if there are any.
+    pub(crate) fn apply(&self, name: &str, mut profile: Profile) -> Profile {
+        if let Some(overrides) = self.0.get(name) {
+            if !overrides.http_routes.is_empty() {
+                let discovered = std::mem::take(&mut profile.http_routes);
+                profile.http_routes = overrides
+                    .http_routes
+                    .iter()
+                    .cloned()
+                    .chain(discovered)
+                    .collect();
+            }
+            if overrides.balancer.is_some() {
+                profile.balancer = overrides.balancer.clone();
+            }
+            if overrides.split_key.is_some() {
+                profile.split_key = overrides.split_key.clone();
+            }
+            if !overrides.target_overrides.is_empty() {
+                let discovered = std::mem::take(&mut profile.target_overrides);
+                profile.target_overrides = overrides
+                    .target_overrides
+                    .iter()
+                    .cloned()
+                    .chain(discovered)
+                    .collect();
+            }
+        }
+        profile
+    }
+}
+
+// === impl ProfileOverrides ===
+
+impl ProfileOverrides {
+    fn from_json(value: Value, name: &str) -> Result<Self, InvalidOverrides> {
+        let mut profile = into_object(value, name)?;
+        let http_routes = match profile.remove("routes") {
+            None => Vec::new(),
+            Some(Value::Array(routes)) => routes
+                .into_iter()
+                .map(|route| route_from_json(route, name))
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(invalid(name, "routes must be an array")),
+        };
+        let balancer = match profile.remove("balancer") {
+            None => None,
+            Some(Value::String(balancer)) => Some(
+                balancer
+                    .parse()
+                    .map_err(|e: InvalidStrategy| invalid(name, e))?,
+            ),
+            Some(_) => return Err(invalid(name, "balancer must be a string")),
+        };
+        let (split_key, target_overrides) = match profile.remove("split") {
+            None => (None, Vec::new()),
+            Some(split) => split_from_json(split, name)?,
+        };
+        deny_unknown(&profile, name)?;
+        Ok(Self {
+            http_routes,
+            balancer,
+            split_key,
+            target_overrides,
+        })
+    }
+}
+
+fn split_from_json(
+    value: Value,
+    profile: &str,
+) -> Result<(Option<SplitKey>, Vec<TargetOverride>), InvalidOverrides> {
+    let what = format!("{}: split", profile);
+    let mut split = into_object(value, &what)?;
+
+    let key = match split.remove("key") {
+        None => None,
+        Some(key) => {
+            let (kind, name) = single_field(key, &what, "split keys")?;
+            let name = name
+                .as_str()
+                .ok_or_else(|| invalid(&what, "split keys must be strings"))?;
+            let key = match kind.as_str() {
+                "header" => ::http::header::HeaderName::from_bytes(name.as_bytes())
+                    .map(SplitKey::Header)
+                    .map_err(|_| invalid(&what, format!("invalid header name '{}'", name)))?,
+                "cookie" if !name.is_empty() => SplitKey::Cookie(name.to_string()),
+                kind => return Err(invalid(&what, format!("invalid {} split key", kind))),
+            };
+            Some(key)
+        }
+    };
+
+    let overrides = match split.remove("overrides") {
+        None => Vec::new(),
+        Some(Value::Array(overrides)) => overrides
+            .into_iter()
+            .map(|o| {
+                let mut o = into_object(o, &what)?;
+                let condition = o
+                    .remove("condition")
+                    .ok_or_else(|| invalid(&what, "overrides must have a condition"))?;
+                let addr = o
+                    .remove("target")
+                    .as_ref()
+                    .and_then(Value::as_str)
+                    .and_then(|addr| addr.parse::<Addr>().ok())
+                    .ok_or_else(|| invalid(&what, "overrides must have a target address"))?;
+                deny_unknown(&o, &what)?;
+                Ok(TargetOverride {
+                    request_match: request_match(condition, &what)?,
+                    addr,
+                })
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(invalid(&what, "overrides must be an array")),
+    };
+
+    deny_unknown(&split, &what)?;
+    Ok((key, overrides))
+}
+
+fn route_from_json(
+    value: Value,
+    profile: &str,
+) -> Result<(http::RequestMatch, http::Route), InvalidOverrides> {
+    let mut route = into_object(value, profile)?;
+
+    let name = match route.remove("name") {
+        Some(Value::String(name)) => name,
+        _ => return Err(invalid(profile, "routes must have a name")),
+    };
+    let what = format!("{}: route '{}'", profile, name);
+
+    let condition = route
+        .remove("condition")
+        .ok_or_else(|| invalid(&what, "missing condition"))?;
+    let condition = request_match(condition, &what)?;
+
+    let response_classes = match route.remove("response_classes") {
+        None => Vec::new(),
+        Some(Value::Array(classes)) => classes
+            .into_iter()
+            .map(|class| response_class(class, &what))
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(invalid(&what, "response_classes must be an array")),
+    };
+
+    let mut http_route = http::Route::new(
+        std::iter::once(("route".to_string(), name)),
+        response_classes,
+    );
+    if let Some(timeout) = route.remove("timeout_ms") {
+        http_route.set_timeout(millis(timeout, &what, "timeout_ms")?);
+    }
+    if let Some(retries) = route.remove("retries") {
+        set_retries(&mut http_route, retries, &what)?;
+    }
+    if let Some(hedge) = route.remove("hedge") {
+        let hedge = hedge
+            .as_object()
+            .filter(|hedge| hedge.len() == 1)
+            .and_then(|hedge| hedge.get("latency_percentile"))
+            .and_then(Value::as_f64)
+            .and_then(http::Hedge::new)
+            .ok_or_else(|| invalid(&what, "hedge must have a latency_percentile on (0, 100)"))?;
+        http_route.set_hedge(hedge);
+    }
+    if let Some(limit) = route.remove("rate_limit") {
+        let mut limit = into_object(limit, &what)?;
+        let mut rate = |name: &str| {
+            limit
+                .remove(name)
+                .map(|v| {
+                    v.as_u64()
+                        .and_then(|v| u32::try_from(v).ok())
+                        .ok_or_else(|| invalid(&what, format!("invalid rate limit {}", name)))
+                })
+                .transpose()
+        };
+        let per_second = rate("per_second")?;
+        let burst = rate("burst")?;
+        deny_unknown(&limit, &what)?;
+        let limit = per_second
+            .and_then(|per_second| Limit::new(per_second, burst.unwrap_or(per_second)))
+            .ok_or_else(|| {
+                invalid(
+                    &what,
+                    "rate limits must have a positive per_second and burst",
+                )
+            })?;
+        http_route.set_rate_limit(limit);
+    }
+    if let Some(rate) = route.remove("sample_rate") {
+        let rate = rate
+            .as_f64()
+            .and_then(http::SampleRate::new)
+            .ok_or_else(|| invalid(&what, "sample_rate must be on [0, 1]"))?;
+        http_route.set_sample_rate(rate);
+    }
+    deny_unknown(&route, &what)?;
+
+    Ok((condition, http_route))
+}
+
+/// Reads a route's retry policy, e.g.:
+///
+/// ```json
+/// {
+///   "max_attempts": 3,
+///   "backoff": {"min_ms": 25, "max_ms": 250, "jitter": 0.5},
+///   "budget": {"ttl_ms": 10000, "min_retries_per_second": 10, "retry_ratio": 0.2}
+/// }
+/// ```
+///
+/// All fields are optional. The attempt limit and backoff default to the
+/// proxy's, and each route has its own budget, which defaults to that of a
+/// ServiceProfile without a `retryBudget`.
+fn set_retries(route: &mut http::Route, value: Value, what: &str) -> Result<(), InvalidOverrides> {
+    let mut retries = into_object(value, what)?;
+
+    let budget = match retries.remove("budget") {
+        None => Budget::new(Duration::from_secs(10), 10, 0.2),
+        Some(budget) => {
+            let mut budget = into_object(budget, what)?;
+            let ttl = match budget.remove("ttl_ms") {
+                None => Duration::from_secs(10),
+                Some(ttl) => millis(ttl, what, "ttl_ms")?,
+            };
+            if ttl < Duration::from_secs(1) || ttl > Duration::from_secs(60) {
+                return Err(invalid(
+                    what,
+                    "retry budget ttl_ms must be on [1000, 60000]",
+                ));
+            }
+            let min_per_second = match budget.remove("min_retries_per_second") {
+                None => 10,
+                Some(min) => min
+                    .as_u64()
+                    .and_then(|min| u32::try_from(min).ok())
+                    .filter(|min| *min <= i32::MAX as u32)
+                    .ok_or_else(|| invalid(what, "invalid min_retries_per_second"))?,
+            };
+            let ratio = match budget.remove("retry_ratio") {
+                None => 0.2,
+                Some(ratio) => ratio
+                    .as_f64()
+                    .filter(|r| (0.0..=1000.0).contains(r))
+                    .ok_or_else(|| invalid(what, "retry_ratio must be on [0, 1000]"))?,
+            };
+            deny_unknown(&budget, what)?;
+            Budget::new(ttl, min_per_second, ratio as f32)
+        }
+    };
+    route.set_retries(Arc::new(budget));
+    let policy = route.retries_mut().expect("retries must be set");
+
+    if let Some(max) = retries.remove("max_attempts") {
+        let max = max
+            .as_u64()
+            .and_then(|max| usize::try_from(max).ok())
+            .filter(|max| *max > 0)
+            .ok_or_else(|| invalid(what, "max_attempts must be a positive number"))?;
+        policy.set_max_attempts(max);
+    }
+
+    if let Some(backoff) = retries.remove("backoff") {
+        let mut backoff = into_object(backoff, what)?;
+        let mut duration = |name: &str| match backoff.remove(name) {
+            Some(ms) => millis(ms, what, name),
+            None => Err(invalid(what, format!("backoff must have a {}", name))),
+        };
+        let (min, max) = (duration("min_ms")?, duration("max_ms")?);
+        let jitter = match backoff.remove("jitter") {
+            None => 0.0,
+            Some(jitter) => jitter
+                .as_f64()
+                .ok_or_else(|| invalid(what, "jitter must be a number"))?,
+        };
+        deny_unknown(&backoff, what)?;
+        let backoff = ExponentialBackoff::new(min, max, jitter)
+            .map_err(|e| invalid(what, format!("invalid backoff: {}", e)))?;
+        policy.set_backoff(backoff);
+    }
+
+    deny_unknown(&retries, what)
+}
+
+fn request_match(value: Value, what: &str) -> Result<http::RequestMatch, InvalidOverrides> {
+    let (kind, value) = single_field(value, what, "request conditions")?;
+    let m = match kind.as_str() {
+        "all" => http::RequestMatch::All(request_matches(value, what)?),
+        "any" => http::RequestMatch::Any(request_matches(value, what)?),
+        "not" => http::RequestMatch::Not(Box::new(request_match(value, what)?)),
+        "method" => {
+            let method = value
+                .as_str()
+                .and_then(|m| ::http::Method::from_bytes(m.as_bytes()).ok())
+                .ok_or_else(|| invalid(what, "invalid method"))?;
+            http::RequestMatch::Method(method)
+        }
+        "path" => {
+            let re = value
+                .as_str()
+                .and_then(|re| http::anchored_regex(re).ok())
+                .ok_or_else(|| invalid(what, "invalid path regex"))?;
+            http::RequestMatch::Path(Box::new(re))
+        }
+        "header" => http::RequestMatch::Header(header_match(value, what)?),
+        "query" => {
+            let (name, value) = named_value(value, what)?;
+            http::RequestMatch::Query(http::QueryMatch::new(name, value))
+        }
+        kind => {
+            return Err(invalid(
+                what,
+                format!("unknown request condition '{}'", kind),
+            ))
+        }
+    };
+    Ok(m)
+}
+
+fn request_matches(value: Value, what: &str) -> Result<Vec<http::RequestMatch>, InvalidOverrides> {
+    match value {
+        Value::Array(ms) => ms.into_iter().map(|m| request_match(m, what)).collect(),
+        _ => Err(invalid(what, "all and any conditions must be arrays")),
+    }
+}
+
+fn response_class(value: Value, what: &str) -> Result<http::ResponseClass, InvalidOverrides> {
+    let mut class = into_object(value, what)?;
+    let condition = class
+        .remove("condition")
+        .ok_or_else(|| invalid(what, "response classes must have a condition"))?;
+    let is_failure = match class.remove("is_failure") {
+        None => false,
+        Some(Value::Bool(is_failure)) => is_failure,
+        Some(_) => return Err(invalid(what, "is_failure must be a boolean")),
+    };
+    deny_unknown(&class, what)?;
+    Ok(http::ResponseClass::new(
+        is_failure,
+        response_match(condition, what)?,
+    ))
+}
+
+fn response_match(value: Value, what: &str) -> Result<http::ResponseMatch, InvalidOverrides> {
+    let (kind, value) = single_field(value, what, "response conditions")?;
+    let m = match kind.as_str() {
+        "all" => http::ResponseMatch::All(response_matches(value, what)?),
+        "any" => http::ResponseMatch::Any(response_matches(value, what)?),
+        "not" => http::ResponseMatch::Not(Box::new(response_match(value, what)?)),
+        "status" => {
+            let (min, max) = range(value, what)?;
+            let status = |code: u64| {
+                u16::try_from(code)
+                    .ok()
+                    .and_then(|code| ::http::StatusCode::from_u16(code).ok())
+                    .ok_or_else(|| invalid(what, "invalid status"))
+            };
+            http::ResponseMatch::Status {
+                min: status(min)?,
+                max: status(max)?,
+            }
+        }
+        "grpc_status" => {
+            let (min, max) = range(value, what)?;
+            let code =
+                |code: u64| u32::try_from(code).map_err(|_| invalid(what, "invalid grpc_status"));
+            http::ResponseMatch::GrpcStatus {
+                min: code(min)?,
+                max: code(max)?,
+            }
+        }
+        "header" => http::ResponseMatch::Header(header_match(value, what)?),
+        kind => {
+            return Err(invalid(
+                what,
+                format!("unknown response condition '{}'", kind),
+            ))
+        }
+    };
+    Ok(m)
+}
+
+fn response_matches(
+    value: Value,
+    what: &str,
+) -> Result<Vec<http::ResponseMatch>, InvalidOverrides> {
+    match value {
+        Value::Array(ms) if !ms.is_empty() => {
+            ms.into_iter().map(|m| response_match(m, what)).collect()
+        }
+        _ => Err(invalid(
+            what,
+            "all and any conditions must be non-empty arrays",
+        )),
+    }
+}
+
+fn header_match(value: Value, what: &str) -> Result<http::HeaderMatch, InvalidOverrides> {
+    let (name, value) = named_value(value, what)?;
+    let name = ::http::header::HeaderName::from_bytes(name.as_bytes())
+        .map_err(|_| invalid(what, format!("invalid header name '{}'", name)))?;
+    Ok(http::HeaderMatch::new(name, value))
+}
+
+/// Reads an object with a `name` and one of an `exact`, `prefix` or `regex`
+/// value.
+fn named_value(value: Value, what: &str) -> Result<(String, http::ValueMatch), InvalidOverrides> {
+    let mut obj = into_object(value, what)?;
+    let name = match obj.remove("name") {
+        Some(Value::String(name)) => name,
+        _ => {
+            return Err(invalid(
+                what,
+                "header and query conditions must have a name",
+            ))
+        }
+    };
+    let (kind, value) = single_field(Value::Object(obj), what, "values")?;
+    let value = value
+        .as_str()
+        .ok_or_else(|| invalid(what, "values must be strings"))?;
+    let value = match kind.as_str() {
+        "exact" => http::ValueMatch::Exact(value.to_string()),
+        "prefix" => http::ValueMatch::Prefix(value.to_string()),
+        "regex" => http::ValueMatch::regex(value)
+            .map_err(|_| invalid(what, format!("invalid regex '{}'", value)))?,
+        kind => return Err(invalid(what, format!("unknown value match '{}'", kind))),
+    };
+    Ok((name, value))
+}
+
+/// Reads a number of milliseconds.
+fn millis(value: Value, what: &str, name: &str) -> Result<Duration, InvalidOverrides> {
+    value
+        .as_u64()
+        .map(Duration::from_millis)
+        .ok_or_else(|| invalid(what, format!("{} must be a number", name)))
+}
+
+/// Reads an object with `min` and `max` numbers.
+fn range(value: Value, what: &str) -> Result<(u64, u64), InvalidOverrides> {
+    let mut obj = into_object(value, what)?;
+    let mut bound = |name: &str| {
+        obj.remove(name)
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| invalid(what, "ranges must have a numeric min and max"))
+    };
+    let (min, max) = (bound("min")?, bound("max")?);
+    deny_unknown(&obj, what)?;
+    if min > max {
+        return Err(invalid(what, "range min must not exceed its max"));
+    }
+    Ok((min, max))
+}
+
+/// Reads an object that must have exactly one field.
+fn single_field(value: Value, what: &str, kind: &str) -> Result<(String, Value), InvalidOverrides> {
+    let obj = into_object(value, what)?;
+    if obj.len() != 1 {
+        return Err(invalid(
+            what,
+            format!("{} must have exactly one field", kind),
+        ));
+    }
+    Ok(obj.into_iter().next().expect("object must have one field"))
+}
+
+// === impl InvalidOverrides ===
+
+impl fmt::Display for InvalidOverrides {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid profile overrides: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidOverrides {}
+
+fn invalid(what: &str, msg: impl fmt::Display) -> InvalidOverrides {
+    InvalidOverrides(format!("{}: {}", what, msg))
+}
+
+fn into_object(value: Value, what: &str) -> Result<Object, InvalidOverrides> {
+    json::into_object(value).ok_or_else(|| invalid(what, "must be an object"))
+}
+
+fn deny_unknown(obj: &Object, what: &str) -> Result<(), InvalidOverrides> {
+    match obj.keys().next() {
+        Some(field) => Err(invalid(what, format!("unknown field '{}'", field))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAME: &str = "books.default.svc.cluster.local:8080";
+
+    fn req(method: ::http::Method, uri: &str, headers: &[(&str, &str)]) -> ::http::Request<()> {
+        let mut req = ::http::Request::builder().method(method).uri(uri);
+        for (k, v) in headers {
+            req = req.header(*k, *v);
+        }
+        req.body(()).unwrap()
+    }
+
+    fn overrides(profile: &str) -> Result<Overrides, InvalidOverrides> {
+        Overrides::from_json(format!(r#"{{"{}": {}}}"#, NAME, profile).as_bytes())
+    }
+
+    #[test]
+    fn prepends_routes() {
+        let overrides = overrides(
+            r#"{
+                "balancer": "round-robin",
+                "split": {
+                    "key": {"cookie": "user"},
+                    "overrides": [{
+                        "condition": {"header": {"name": "x-canary", "exact": "true"}},
+                        "target": "canary.default.svc.cluster.local:8080"
+                    }]
+                },
+                "routes": [{
+                    "name": "canary",
+                    "condition": {"all": [
+                        {"method": "GET"},
+                        {"path": "/books/[^/]+"},
+                        {"header": {"name": "x-canary", "exact": "true"}},
+                        {"not": {"query": {"name": "version", "prefix": "v1"}}}
+                    ]},
+                    "response_classes": [{
+                        "condition": {"any": [
+                            {"grpc_status": {"min": 14, "max": 14}},
+                            {"status": {"min": 500, "max": 599}}
+                        ]},
+                        "is_failure": true
+                    }],
+                    "timeout_ms": 1500,
+                    "retries": {
+                        "max_attempts": 3,
+                        "backoff": {"min_ms": 10, "max_ms": 100, "jitter": 0.5}
+                    },
+                    "hedge": {"latency_percentile": 95},
+                    "rate_limit": {"per_second": 100},
+                    "sample_rate": 0.25
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let discovered = (
+            http::RequestMatch::Path(Box::new(http::anchored_regex("/.*").unwrap())),
+            http::Route::default(),
+        );
+        let profile = Profile {
+            http_routes: vec![discovered],
+            ..Profile::default()
+        };
+
+        let untouched = overrides.apply("other.default.svc.cluster.local:80", profile.clone());
+        assert_eq!(untouched.http_routes.len(), 1);
+        assert_eq!(untouched.balancer, None);
+        assert_eq!(untouched.split_key, None);
+        assert!(untouched.target_overrides.is_empty());
+
+        let profile = overrides.apply(NAME, profile);
+        assert_eq!(profile.http_routes.len(), 2);
+        assert_eq!(profile.balancer, Some(Strategy::RoundRobin));
+        assert_eq!(profile.split_key, Some(SplitKey::Cookie("user".into())));
+        assert_eq!(profile.target_overrides.len(), 1);
+        let canary_target = &profile.target_overrides[0];
+        assert_eq!(
+            canary_target.addr,
+            "canary.default.svc.cluster.local:8080"
+                .parse::<Addr>()
+                .unwrap()
+        );
+        assert!(canary_target.request_match.is_match(&req(
+            ::http::Method::GET,
+            "/",
+            &[("x-canary", "true")]
+        )));
+        let (ref m, ref route) = profile.http_routes[0];
+        assert_eq!(route.labels()["route"], "canary");
+        assert_eq!(route.timeout(), Some(Duration::from_millis(1500)));
+        assert!(route.response_classes()[0].is_grpc());
+        assert_eq!(route.hedge(), http::Hedge::new(95.0));
+        assert_eq!(route.rate_limit(), Limit::new(100, 100));
+        assert_eq!(route.sample_rate(), http::SampleRate::new(0.25));
+        let retries = route.retries().expect("route must be retryable");
+        assert_eq!(retries.max_attempts(), Some(3));
+        assert_eq!(
+            retries.backoff().map(|b| (b.min, b.max)),
+            Some((Duration::from_millis(10), Duration::from_millis(100)))
+        );
+
+        let canary = [("x-canary", "true")];
+        assert!(m.is_match(&req(::http::Method::GET, "/books/1?version=v2", &canary)));
+        assert!(!m.is_match(&req(::http::Method::GET, "/books/1?version=v1", &canary)));
+        assert!(!m.is_match(&req(::http::Method::GET, "/books/1", &[])));
+        assert!(!m.is_match(&req(::http::Method::POST, "/books/1", &canary)));
+
+        // Routes are built once per version of the file, so that reapplying
+        // the same overrides doesn't replace the route's services.
+        let again = overrides.apply(NAME, Profile::default());
+        assert_eq!(again.http_routes[0].1, profile.http_routes[0].1);
+    }
+
+    #[test]
+    fn rejects_invalid_overrides() {
+        assert!(Overrides::from_json(b"{}").is_ok());
+        assert!(Overrides::from_json(b"[]").is_err());
+
+        for invalid in &[
+            r#"[]"#,
+            r#"{"routes": {}}"#,
+            r#"{"routes": [{"condition": {"path": "/"}}]}"#,
+            r#"{"routes": [{"name": "a"}]}"#,
+            r#"{"routes": [{"name": "a", "condition": {"path": "("}}]}"#,
+            r#"{"routes": [{"name": "a", "condition": {"path": "/", "method": "GET"}}]}"#,
+            r#"{"routes": [{"name": "a", "condition": {"host": "a"}}]}"#,
+            r#"{"routes": [{"name": "a", "condition": {"header": {"exact": "a"}}}]}"#,
+            r#"{"routes": [{"name": "a", "condition": {"query": {"name": "a"}}}]}"#,
+            r#"{"routes": [{"name": "a", "condition": {"path": "/"}, "timeout_ms": "1s"}]}"#,
+            r#"{"routes": [{
+                "name": "a",
+                "condition": {"path": "/"},
+                "response_classes": [{"condition": {"status": {"min": 599, "max": 500}}}]
+            }]}"#,
+            r#"{"routes": [{"name": "a", "condition": {"path": "/"}, "retries": true}]}"#,
+            r#"{"routes": [{
+                "name": "a",
+                "condition": {"path": "/"},
+                "retries": {"max_attempts": 0}
+            }]}"#,
+            r#"{"routes": [{
+                "name": "a",
+                "condition": {"path": "/"},
+                "hedge": {"latency_percentile": 100}
+            }]}"#,
+            r#"{"routes": [{
+                "name": "a",
+                "condition": {"path": "/"},
+                "retries": {"backoff": {"min_ms": 100, "max_ms": 10}}
+            }]}"#,
+            r#"{"routes": [{
+                "name": "a",
+                "condition": {"path": "/"},
+                "retries": {"budget": {"ttl_ms": 0}}
+            }]}"#,
+            r#"{"routes": [{
+                "name": "a",
+                "condition": {"path": "/"},
+                "rate_limit": {"per_second": 10, "burst": 0}
+            }]}"#,
+            r#"{"routes": [{"name": "a", "condition": {"path": "/"}, "rate_limit": {"burst": 1}}]}"#,
+            r#"{"routes": [{"name": "a", "condition": {"path": "/"}, "sample_rate": 2}]}"#,
+            r#"{"targets": []}"#,
+            r#"{"balancer": "random"}"#,
+            r#"{"split": {"key": {"query": "user"}}}"#,
+            r#"{"split": {"key": {"header": "x user"}}}"#,
+            r#"{"split": {"overrides": [{"condition": {"path": "/"}}]}}"#,
+            r#"{"split": {"overrides": [{"condition": {"path": "/"}, "target": "a:b"}]}}"#,
+        ] {
+            assert!(overrides(invalid).is_err(), "{} must be invalid", invalid);
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{LogicalAddr, Profile, Receiver, Target};
+use crate::{LogicalAddr, Profile, Receiver, SplitKey, Target, TargetOverride};
+use fnv::FnvHasher;
 use futures::{prelude::*, ready};
 use indexmap::IndexSet;
 use linkerd_addr::Addr;
@@ -8,6 +9,7 @@ use linkerd_stack::{layer, NewService, Param};
 use rand::distributions::{Distribution, WeightedIndex};
 use rand::{rngs::SmallRng, thread_rng, SeedableRng};
 use std::{
+    hash::Hasher,
     marker::PhantomData,
     pin::Pin,
     task::{Context, Poll},
@@ -15,55 +17,100 @@ use std::{
 use tower::ready_cache::ReadyCache;
 use tracing::{debug, trace};
 
+/// Splits requests randomly over a profile's targets.
 pub fn layer<N, S, Req>() -> impl layer::Layer<N, Service = NewSplit<N, S, Req>> + Clone {
-    // This RNG doesn't need to be cryptographically secure. Small and fast is
-    // preferable.
+    layer_by(Random(()))
+}
+
+/// Splits HTTP requests over a profile's targets, honoring the profile's
+/// split key and target overrides.
+pub fn http_layer<N, S, B>(
+) -> impl layer::Layer<N, Service = NewSplit<N, S, http::Request<B>, Http>> + Clone {
+    layer_by(Http(()))
+}
+
+fn layer_by<N, S, Req, P: SplitBy<Req> + Copy>(
+    split_by: P,
+) -> impl layer::Layer<N, Service = NewSplit<N, S, Req, P>> + Clone {
     layer::mk(move |inner| NewSplit {
         inner,
+        split_by,
         _service: PhantomData,
     })
 }
 
+/// Determines how a request's properties influence the target it is split
+/// to.
+pub trait SplitBy<Req> {
+    /// Returns the profile's target overrides that this splitter honors.
+    ///
+    /// Override targets that are not also weighted targets are only built
+    /// when an override is honored, so splitters that can't match requests
+    /// don't build them at all.
+    fn target_overrides(&self, profile: &Profile) -> Vec<TargetOverride>;
+
+    /// Returns the address of the first override that matches the request.
+    fn override_addr<'o>(&self, overrides: &'o [TargetOverride], req: &Req) -> Option<&'o Addr>;
+
+    /// Returns the hash of the request's split key, if the request has one.
+    fn hash_key(&self, key: &SplitKey, req: &Req) -> Option<u64>;
+}
+
+/// Splits requests randomly, ignoring their properties.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Random(());
+
+/// Splits HTTP requests by their headers and cookies.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Http(());
+
 #[derive(Debug)]
-pub struct NewSplit<N, S, Req> {
+pub struct NewSplit<N, S, Req, P = Random> {
     inner: N,
+    split_by: P,
     _service: PhantomData<fn(Req) -> S>,
 }
 
-pub enum Split<T, N, S, Req> {
+pub enum Split<T, N, S, Req, P = Random> {
     Default(S),
-    Split(Box<Inner<T, N, S, Req>>),
+    Split(Box<Inner<T, N, S, Req, P>>),
 }
 
-pub struct Inner<T, N, S, Req> {
+pub struct Inner<T, N, S, Req, P> {
     rng: SmallRng,
     rx: Pin<Box<dyn Stream<Item = Profile> + Send + Sync>>,
     target: T,
     new_service: N,
+    split_by: P,
     distribution: WeightedIndex<u32>,
+    weights: Vec<u32>,
+    split_key: Option<SplitKey>,
+    overrides: Vec<TargetOverride>,
     addrs: IndexSet<Addr>,
     services: ReadyCache<Addr, S, Req>,
 }
 
 // === impl NewSplit ===
 
-impl<N: Clone, S, Req> Clone for NewSplit<N, S, Req> {
+impl<N: Clone, S, Req, P: Copy> Clone for NewSplit<N, S, Req, P> {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
+            split_by: self.split_by,
             _service: self._service,
         }
     }
 }
 
-impl<T, N, S, Req> NewService<T> for NewSplit<N, S, Req>
+impl<T, N, S, Req, P> NewService<T> for NewSplit<N, S, Req, P>
 where
+    P: SplitBy<Req> + Copy,
     T: Clone + Param<LogicalAddr> + Param<Option<Receiver>>,
     N: NewService<(Option<ConcreteAddr>, T), Service = S> + Clone,
     S: tower::Service<Req>,
     S::Error: Into<Error>,
 {
-    type Service = Split<T, N, S, Req>;
+    type Service = Split<T, N, S, Req, P>;
 
     fn new_service(&mut self, target: T) -> Self::Service {
         // If there is a profile, it is used to configure one or more inner
@@ -78,11 +125,12 @@ where
                 Split::Default(self.inner.new_service((None, target)))
             }
             Some(rx) => {
-                let mut targets = rx.borrow().targets.clone();
-                if targets.is_empty() {
-                    let LogicalAddr(addr) = target.param();
-                    targets.push(Target { addr, weight: 1 })
-                }
+                let (targets, split_key, overrides) = {
+                    let profile = rx.borrow();
+                    let overrides = self.split_by.target_overrides(&profile);
+                    let targets = split_targets(&profile, &overrides, &target);
+                    (targets, profile.split_key.clone(), overrides)
+                };
                 trace!(?targets, "Building split service");
 
                 let mut addrs = IndexSet::with_capacity(targets.len());
@@ -104,7 +152,13 @@ where
                     new_service,
                     services,
                     addrs,
-                    distribution: WeightedIndex::new(weights).unwrap(),
+                    distribution: WeightedIndex::new(&weights).unwrap(),
+                    weights,
+                    split_key,
+                    overrides,
+                    split_by: self.split_by,
+                    // This RNG doesn't need to be cryptographically secure. Small
+                    // and fast is preferable.
                     rng: SmallRng::from_rng(&mut thread_rng()).expect("RNG must initialize"),
                 }))
             }
@@ -114,8 +168,9 @@ where
 
 // === impl Split ===
 
-impl<T, N, S, Req> tower::Service<Req> for Split<T, N, S, Req>
+impl<T, N, S, Req, P> tower::Service<Req> for Split<T, N, S, Req, P>
 where
+    P: SplitBy<Req>,
     Req: Send + 'static,
     T: Clone + Param<LogicalAddr>,
     N: NewService<(Option<ConcreteAddr>, T), Service = S> + Clone,
@@ -139,12 +194,12 @@ where
 
                 // Every time the profile updates, rebuild the distribution, reusing
                 // services that existed in the prior state.
-                if let Some(Profile { mut targets, .. }) = update {
-                    if targets.is_empty() {
-                        let LogicalAddr(addr) = inner.target.param();
-                        targets.push(Target { addr, weight: 1 })
-                    }
+                if let Some(profile) = update {
+                    let overrides = inner.split_by.target_overrides(&profile);
+                    let targets = split_targets(&profile, &overrides, &inner.target);
                     debug!(?targets, "Updating");
+                    inner.split_key = profile.split_key;
+                    inner.overrides = overrides;
 
                     // Replace the old set of addresses with an empty set. The
                     // prior set is used to determine whether a new service
@@ -171,7 +226,8 @@ where
                         weights.push(weight);
                     }
 
-                    inner.distribution = WeightedIndex::new(weights).unwrap();
+                    inner.distribution = WeightedIndex::new(&weights).unwrap();
+                    inner.weights = weights;
 
                     // Remove all prior services that did not exist in the new
                     // set of targets.
@@ -191,11 +247,7 @@ where
         match self {
             Self::Default(ref mut svc) => Box::pin(svc.call(req).err_into::<Error>()),
             Self::Split(ref mut inner) => {
-                let idx = if inner.addrs.len() == 1 {
-                    0
-                } else {
-                    inner.distribution.sample(&mut inner.rng)
-                };
+                let idx = inner.select(&req);
                 let addr = inner.addrs.get_index(idx).expect("invalid index");
                 trace!(?addr, "Dispatching");
                 Box::pin(inner.services.call_ready(addr, req).err_into::<Error>())
@@ -203,3 +255,267 @@ where
         }
     }
 }
+
+// === impl Inner ===
+
+impl<T, N, S, Req, P: SplitBy<Req>> Inner<T, N, S, Req, P> {
+    /// Returns the index of the target that should handle the request.
+    fn select(&mut self, req: &Req) -> usize {
+        if let Some(addr) = self.split_by.override_addr(&self.overrides, req) {
+            if let Some((idx, _)) = self.addrs.get_full(addr) {
+                trace!(%addr, "Overriding split");
+                return idx;
+            }
+        }
+
+        if self.addrs.len() == 1 {
+            return 0;
+        }
+
+        // Requests with a split key are always split to the same target (as
+        // long as the targets don't change); all other requests are split
+        // randomly.
+        let hash = match self.split_key.as_ref() {
+            Some(key) => self.split_by.hash_key(key, req),
+            None => None,
+        };
+        match hash {
+            Some(hash) => weighted_index(&self.weights, hash),
+            None => self.distribution.sample(&mut self.rng),
+        }
+    }
+}
+
+/// Returns the profile's targets, or the logical address if the profile has
+/// no targets, followed by the targets of any honored overrides that are not
+/// already targets.
+///
+/// Override targets are given no weight so that only matching requests are
+/// split to them.
+fn split_targets<T: Param<LogicalAddr>>(
+    profile: &Profile,
+    overrides: &[TargetOverride],
+    target: &T,
+) -> Vec<Target> {
+    let mut targets = profile.targets.clone();
+    if targets.is_empty() {
+        let LogicalAddr(addr) = target.param();
+        targets.push(Target { addr, weight: 1 })
+    }
+    for TargetOverride { addr, .. } in overrides.iter() {
+        if !targets.iter().any(|t| t.addr == *addr) {
+            targets.push(Target {
+                addr: addr.clone(),
+                weight: 0,
+            });
+        }
+    }
+    targets
+}
+
+/// Maps a hash onto the cumulative distribution of `weights`.
+fn weighted_index(weights: &[u32], hash: u64) -> usize {
+    let total = weights.iter().map(|w| u64::from(*w)).sum::<u64>();
+    let mut point = hash % total;
+    for (idx, weight) in weights.iter().enumerate() {
+        let weight = u64::from(*weight);
+        if point < weight {
+            return idx;
+        }
+        point -= weight;
+    }
+    unreachable!("point must fall within the total weight")
+}
+
+// === impl Random ===
+
+impl<Req> SplitBy<Req> for Random {
+    fn target_overrides(&self, _: &Profile) -> Vec<TargetOverride> {
+        Vec::new()
+    }
+
+    fn override_addr<'o>(&self, _: &'o [TargetOverride], _: &Req) -> Option<&'o Addr> {
+        None
+    }
+
+    fn hash_key(&self, _: &SplitKey, _: &Req) -> Option<u64> {
+        None
+    }
+}
+
+// === impl Http ===
+
+impl<B> SplitBy<http::Request<B>> for Http {
+    fn target_overrides(&self, profile: &Profile) -> Vec<TargetOverride> {
+        profile.target_overrides.clone()
+    }
+
+    fn override_addr<'o>(
+        &self,
+        overrides: &'o [TargetOverride],
+        req: &http::Request<B>,
+    ) -> Option<&'o Addr> {
+        overrides
+            .iter()
+            .find(|o| o.request_match.is_match(req))
+            .map(|o| &o.addr)
+    }
+
+    /// Keys are hashed with FNV-1a, which, unlike the standard library's
+    /// hasher, is specified, so all proxies split a given key to the same
+    /// target.
+    fn hash_key(&self, key: &SplitKey, req: &http::Request<B>) -> Option<u64> {
+        let mut hasher = FnvHasher::default();
+        match key {
+            SplitKey::Header(name) => hasher.write(req.headers().get(name)?.as_bytes()),
+            SplitKey::Cookie(name) => hasher.write(cookie(req.headers(), name)?.as_bytes()),
+        }
+        Some(hasher.finish())
+    }
+}
+
+/// Returns the value of the first cookie with the given name.
+fn cookie<'h>(headers: &'h http::HeaderMap, name: &str) -> Option<&'h str> {
+    headers
+        .get_all(http::header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|c| {
+            let c = c.trim();
+            let eq = c.find('=')?;
+            if &c[..eq] == name {
+                Some(&c[eq + 1..])
+            } else {
+                None
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{HeaderMatch, RequestMatch, ValueMatch};
+    use std::str::FromStr;
+
+    fn req(headers: &[(&'static str, &'static str)]) -> http::Request<()> {
+        let mut req = http::Request::builder();
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        req.body(()).unwrap()
+    }
+
+    #[test]
+    fn weighted_index_follows_weights() {
+        let weights = [1, 0, 3];
+        let mut counts = [0; 3];
+        for hash in 0..400 {
+            counts[weighted_index(&weights, hash)] += 1;
+        }
+        assert_eq!(counts, [100, 0, 300]);
+    }
+
+    #[test]
+    fn hashes_header() {
+        let key = SplitKey::Header(http::header::HeaderName::from_static("x-user"));
+        let a = Http(()).hash_key(&key, &req(&[("x-user", "a")]));
+        assert!(a.is_some());
+        assert_eq!(a, Http(()).hash_key(&key, &req(&[("x-user", "a")])));
+        assert_ne!(a, Http(()).hash_key(&key, &req(&[("x-user", "b")])));
+        assert_eq!(Http(()).hash_key(&key, &req(&[])), None);
+    }
+
+    #[test]
+    fn hashes_are_stable() {
+        // The FNV-1a hash of "a" must not change between builds.
+        let key = SplitKey::Header(http::header::HeaderName::from_static("x-user"));
+        let hash = Http(()).hash_key(&key, &req(&[("x-user", "a")]));
+        assert_eq!(hash, Some(0xaf63_dc4c_8601_ec8c));
+
+        let key = SplitKey::Cookie("user".into());
+        let hash = Http(()).hash_key(&key, &req(&[("cookie", "user=a")]));
+        assert_eq!(hash, Some(0xaf63_dc4c_8601_ec8c));
+    }
+
+    #[test]
+    fn hashes_cookie() {
+        let key = SplitKey::Cookie("user".into());
+        let a = Http(()).hash_key(&key, &req(&[("cookie", "user=a")]));
+        assert!(a.is_some());
+        assert_eq!(
+            a,
+            Http(()).hash_key(&key, &req(&[("cookie", "theme=dark; user=a")]))
+        );
+        assert_eq!(
+            a,
+            Http(()).hash_key(
+                &key,
+                &req(&[("cookie", "theme=dark"), ("cookie", "user=a")])
+            )
+        );
+        assert_ne!(a, Http(()).hash_key(&key, &req(&[("cookie", "user=b")])));
+        assert_eq!(
+            Http(()).hash_key(&key, &req(&[("cookie", "username=a")])),
+            None
+        );
+    }
+
+    #[test]
+    fn builds_honored_override_targets() {
+        struct Logical(Addr);
+        impl Param<LogicalAddr> for Logical {
+            fn param(&self) -> LogicalAddr {
+                LogicalAddr(self.0.clone())
+            }
+        }
+
+        let logical = Logical(Addr::from_str("books.ns.svc.cluster.local:80").unwrap());
+        let canary = Addr::from_str("canary.ns.svc.cluster.local:80").unwrap();
+        let profile = Profile {
+            target_overrides: vec![TargetOverride {
+                request_match: RequestMatch::Method(http::Method::GET),
+                addr: canary.clone(),
+            }],
+            ..Profile::default()
+        };
+
+        let overrides = SplitBy::<()>::target_overrides(&Random(()), &profile);
+        let targets = split_targets(&profile, &overrides, &logical);
+        assert_eq!(
+            targets.iter().map(|t| &t.addr).collect::<Vec<_>>(),
+            vec![&logical.0]
+        );
+
+        let overrides = SplitBy::<http::Request<()>>::target_overrides(&Http(()), &profile);
+        let targets = split_targets(&profile, &overrides, &logical);
+        assert_eq!(
+            targets
+                .iter()
+                .map(|t| (&t.addr, t.weight))
+                .collect::<Vec<_>>(),
+            vec![(&logical.0, 1), (&canary, 0)]
+        );
+    }
+
+    #[test]
+    fn overrides_by_header() {
+        let canary = Addr::from_str("canary.ns.svc.cluster.local:80").unwrap();
+        let overrides = vec![TargetOverride {
+            request_match: RequestMatch::Header(HeaderMatch::new(
+                http::header::HeaderName::from_static("x-canary"),
+                ValueMatch::Exact("1".into()),
+            )),
+            addr: canary.clone(),
+        }];
+        assert_eq!(
+            Http(()).override_addr(&overrides, &req(&[("x-canary", "1")])),
+            Some(&canary)
+        );
+        assert_eq!(
+            Http(()).override_addr(&overrides, &req(&[("x-canary", "0")])),
+            None
+        );
+        assert_eq!(Http(()).override_addr(&overrides, &req(&[])), None);
+    }
+}
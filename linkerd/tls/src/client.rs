@@ -19,8 +19,13 @@ pub use tokio_rustls::client::TlsStream;
 use tracing::{debug, trace};
 
 /// A newtype for target server identities.
+///
+/// Servers identified by a DNS-like name are authenticated by the name in
+/// their certificate's DNS subject alternative names. Servers identified by a
+/// SPIFFE ID are authenticated by the ID in their certificate's URI subject
+/// alternative names; SNI isn't sent to these servers.
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
-pub struct ServerId(pub id::Name);
+pub struct ServerId(pub id::Id);
 
 /// A stack paramter that configures a `Client` to establish a TLS connection.
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
@@ -120,20 +125,28 @@ where
             Some(local) => {
                 // Build a rustls ClientConfig for this connection.
                 //
-                // If ALPN protocols are configured by the endpoint, we have to clone the
-                // entire configuration and set the protocols. If there are no
-                // ALPN options, clone the Arc'd base configuration without
-                // extra allocation.
+                // If ALPN protocols are configured by the endpoint or the
+                // server is identified by a SPIFFE ID, we have to clone the
+                // entire configuration and set the protocols or the verifier.
+                // Otherwise, clone the Arc'd base configuration without extra
+                // allocation.
                 //
                 // TODO it would be better to avoid cloning the whole TLS config
                 // per-connection.
-                match alpn {
-                    None => tokio_rustls::TlsConnector::from(local.param()),
-                    Some(AlpnProtocols(protocols)) => {
+                match (&server_id.0, alpn) {
+                    (id::Id::Dns(_), None) => tokio_rustls::TlsConnector::from(local.param()),
+                    (id::Id::Dns(_), Some(AlpnProtocols(protocols))) => {
                         let mut config: rustls::ClientConfig = local.param().as_ref().clone();
                         config.alpn_protocols = protocols;
                         tokio_rustls::TlsConnector::from(Arc::new(config))
                     }
+                    (id::Id::Spiffe(spiffe_id), alpn) => {
+                        let mut config = spiffe_id.client_config(&local.param());
+                        if let Some(AlpnProtocols(protocols)) = alpn {
+                            config.alpn_protocols = protocols;
+                        }
+                        tokio_rustls::TlsConnector::from(Arc::new(config))
+                    }
                 }
             }
             None => {
@@ -146,7 +159,7 @@ where
         let connect = self.inner.call(target);
         Either::Right(Box::pin(async move {
             let io = connect.await?;
-            let io = handshake.connect((&server_id.0).into(), io).await?;
+            let io = handshake.connect(server_id.dns_name(), io).await?;
             if let Some(alpn) = io.get_ref().1.get_alpn_protocol() {
                 debug!(alpn = ?std::str::from_utf8(alpn));
             }
@@ -157,20 +170,46 @@ where
 
 // === impl ServerId ===
 
+impl ServerId {
+    /// The name passed to the TLS connector.
+    ///
+    /// A SPIFFE ID isn't a DNS name, so a placeholder is used for servers
+    /// identified by one. The placeholder is neither sent nor verified.
+    fn dns_name(&self) -> webpki::DNSNameRef<'_> {
+        match self.0 {
+            id::Id::Dns(ref name) => name.into(),
+            id::Id::Spiffe(_) => webpki::DNSNameRef::try_from_ascii_str("spiffe.invalid")
+                .expect("placeholder name must be valid"),
+        }
+    }
+}
+
+impl From<id::Id> for ServerId {
+    fn from(id: id::Id) -> Self {
+        Self(id)
+    }
+}
+
 impl From<id::Name> for ServerId {
     fn from(n: id::Name) -> Self {
-        Self(n)
+        Self(n.into())
+    }
+}
+
+impl From<id::SpiffeId> for ServerId {
+    fn from(id: id::SpiffeId) -> Self {
+        Self(id.into())
     }
 }
 
-impl Into<id::Name> for ServerId {
-    fn into(self) -> id::Name {
+impl Into<id::Id> for ServerId {
+    fn into(self) -> id::Id {
         self.0
     }
 }
 
-impl AsRef<id::Name> for ServerId {
-    fn as_ref(&self) -> &id::Name {
+impl AsRef<id::Id> for ServerId {
+    fn as_ref(&self) -> &id::Id {
         &self.0
     }
 }
@@ -178,7 +217,7 @@ impl AsRef<id::Name> for ServerId {
 impl FromStr for ServerId {
     type Err = id::InvalidName;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        id::Name::from_str(s).map(ServerId)
+        id::Id::from_str(s).map(ServerId)
     }
 }
 
@@ -32,7 +32,7 @@ pub fn parse_sni(input: &[u8]) -> Result<Option<ServerId>, Incomplete> {
         Ok(Some(sni)) => {
             let sni = id::Name::try_from(sni.as_slice_less_safe())
                 .ok()
-                .map(ServerId);
+                .map(ServerId::from);
             trace!(?sni, "parse_sni: parsed correctly up to SNI");
             Ok(sni)
         }
@@ -211,7 +211,10 @@ mod tests {
 
         // The same result will be returned for all longer prefixes.
         for i in i..input.len() {
-            assert_eq!(Ok(Some(ServerId(identity.clone()))), parse_sni(&input[..i]))
+            assert_eq!(
+                Ok(Some(ServerId::from(identity.clone()))),
+                parse_sni(&input[..i])
+            )
         }
     }
 }
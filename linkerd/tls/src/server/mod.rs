@@ -31,8 +31,12 @@ pub fn empty_config() -> Config {
 }
 
 /// A newtype for remote client idenities.
+///
+/// A client is identified by the first DNS subject alternative name in its
+/// certificate or, if it has none, by the first SPIFFE ID in its URI subject
+/// alternative names.
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
-pub struct ClientId(pub id::Name);
+pub struct ClientId(pub id::Id);
 
 /// Indicates a serverside connection's TLS status.
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
@@ -207,7 +211,7 @@ where
     let sz = io.peek(&mut buf).await?;
     debug!(sz, "Peeked bytes from TCP stream");
     match client_hello::parse_sni(&buf) {
-        Ok(Some(ServerId(sni))) if sni == local_id => {
+        Ok(Some(ServerId(id::Id::Dns(sni)))) if sni == local_id => {
             trace!(%sni, "Identified matching SNI via peek");
             // Terminate the TLS stream.
             let (tls, io) = handshake(tls_config, PrefixedIo::from(io)).await?;
@@ -236,7 +240,7 @@ where
     while io.read_buf(&mut buf).await? != 0 {
         debug!(buf.len = %buf.len(), "Read bytes from TCP stream");
         match client_hello::parse_sni(buf.as_ref()) {
-            Ok(Some(ServerId(sni))) if sni == local_id => {
+            Ok(Some(ServerId(id::Id::Dns(sni)))) if sni == local_id => {
                 trace!(%sni, "Identified matching SNI via buffered read");
                 // Terminate the TLS stream.
                 let (tls, io) =
@@ -307,14 +311,15 @@ fn client_identity<S>(tls: &TlsStream<S>) -> Option<ClientId> {
     let end_cert = webpki::EndEntityCert::from(c).ok()?;
     let dns_names = end_cert.dns_names().ok()?;
 
-    match dns_names.first()? {
-        GeneralDNSNameRef::DNSName(n) => {
-            Some(ClientId(id::Name::from(dns::Name::from(n.to_owned()))))
-        }
-        GeneralDNSNameRef::Wildcard(_) => {
+    match dns_names.first() {
+        Some(GeneralDNSNameRef::DNSName(n)) => Some(ClientId::from(id::Name::from(
+            dns::Name::from(n.to_owned()),
+        ))),
+        Some(GeneralDNSNameRef::Wildcard(_)) => {
             // Wildcards can perhaps be handled in a future path...
             None
         }
+        None => id::SpiffeId::from_crt(c).map(ClientId::from),
     }
 }
 
@@ -328,20 +333,32 @@ impl std::error::Error for DetectTimeout {}
 
 // === impl ClientId ===
 
+impl From<id::Id> for ClientId {
+    fn from(id: id::Id) -> Self {
+        Self(id)
+    }
+}
+
 impl From<id::Name> for ClientId {
     fn from(n: id::Name) -> Self {
-        Self(n)
+        Self(n.into())
+    }
+}
+
+impl From<id::SpiffeId> for ClientId {
+    fn from(id: id::SpiffeId) -> Self {
+        Self(id.into())
     }
 }
 
-impl Into<id::Name> for ClientId {
-    fn into(self) -> id::Name {
+impl Into<id::Id> for ClientId {
+    fn into(self) -> id::Id {
         self.0
     }
 }
 
-impl AsRef<id::Name> for ClientId {
-    fn as_ref(&self) -> &id::Name {
+impl AsRef<id::Id> for ClientId {
+    fn as_ref(&self) -> &id::Id {
         &self.0
     }
 }
@@ -355,7 +372,7 @@ impl fmt::Display for ClientId {
 impl FromStr for ClientId {
     type Err = id::InvalidName;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        id::Name::from_str(s).map(Self)
+        id::Id::from_str(s).map(Self)
     }
 }
 
@@ -51,7 +51,7 @@ async fn plaintext() {
 async fn proxy_to_proxy_tls_works() {
     let server_tls = id::test_util::FOO_NS1.validate().unwrap();
     let client_tls = id::test_util::BAR_NS1.validate().unwrap();
-    let server_id = tls::ServerId(server_tls.name().clone());
+    let server_id = tls::ServerId::from(server_tls.name().clone());
     let (client_result, server_result) = run_test(
         Conditional::Some((client_tls.clone(), server_id.clone())),
         |conn| write_then_read(conn, PING),
@@ -70,7 +70,7 @@ async fn proxy_to_proxy_tls_works() {
     assert_eq!(
         server_result.tls,
         Some(Conditional::Some(tls::ServerTls::Established {
-            client_id: Some(tls::ClientId(client_tls.name().clone())),
+            client_id: Some(tls::ClientId::from(client_tls.name().clone())),
             negotiated_protocol: None,
         }))
     );
@@ -89,7 +89,7 @@ async fn proxy_to_proxy_tls_pass_through_when_identity_does_not_match() {
     let sni = id::test_util::BAR_NS1.crt().name().clone();
 
     let (client_result, server_result) = run_test(
-        Conditional::Some((client_tls, tls::ServerId(sni.clone()))),
+        Conditional::Some((client_tls, tls::ServerId::from(sni.clone()))),
         |conn| write_then_read(conn, PING),
         Some(server_tls),
         |(_, conn)| read_then_write(conn, START_OF_TLS.len(), PONG),
@@ -103,7 +103,7 @@ async fn proxy_to_proxy_tls_pass_through_when_identity_does_not_match() {
     assert_eq!(
         server_result.tls,
         Some(Conditional::Some(tls::ServerTls::Passthru {
-            sni: tls::ServerId(sni)
+            sni: tls::ServerId::from(sni)
         }))
     );
     assert_eq!(&server_result.result.unwrap()[..], START_OF_TLS);
@@ -0,0 +1,267 @@
+//! Attributes recorded on spans as a request is processed.
+//!
+//! When a span is recorded, a `SpanAttributes` handle is set as a request
+//! extension. Layers that learn more about the request as it moves through
+//! the proxy (e.g. its route or the endpoint it's dispatched to) record
+//! attributes on the handle, and these are added to the span when it
+//! completes. Other consumers (e.g. access logs) may set a handle and read
+//! the attributes recorded on it. The outermost span shares such a handle, so
+//! these consumers see its attributes whether they're above or below it.
+
+use http::header::{HeaderMap, HeaderName};
+use linkerd_stack::{layer, NewService, Proxy};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+/// Headers that are recorded as span attributes.
+#[derive(Clone, Debug, Default)]
+pub struct CaptureHeaders {
+    pub request: Vec<HeaderName>,
+    pub response: Vec<HeaderName>,
+}
+
+/// A request extension that records attributes on the request's span.
+#[derive(Clone, Debug, Default)]
+pub struct SpanAttributes {
+    attributes: Arc<Mutex<HashMap<String, String>>>,
+
+    /// Set when the handle belongs to a span, so that nested spans fork it.
+    is_span: bool,
+}
+
+/// Describes the span attributes of requests to a target.
+pub trait HasSpanAttributes {
+    fn span_attributes(&self) -> Vec<(&'static str, String)>;
+}
+
+/// Records each target's attributes on the spans of requests to the target.
+#[derive(Clone, Debug)]
+pub struct NewRecordAttributes<N> {
+    inner: N,
+}
+
+#[derive(Clone, Debug)]
+pub struct RecordAttributes<S> {
+    inner: S,
+    attributes: Arc<Vec<(&'static str, String)>>,
+}
+
+// === impl CaptureHeaders ===
+
+impl CaptureHeaders {
+    pub(crate) fn request(&self, headers: &HeaderMap, labels: &mut HashMap<String, String>) {
+        Self::capture("request", &self.request, headers, labels)
+    }
+
+    pub(crate) fn response(&self, headers: &HeaderMap, labels: &mut HashMap<String, String>) {
+        Self::capture("response", &self.response, headers, labels)
+    }
+
+    fn capture(
+        direction: &str,
+        names: &[HeaderName],
+        headers: &HeaderMap,
+        labels: &mut HashMap<String, String>,
+    ) {
+        for name in names {
+            let values = headers
+                .get_all(name)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .collect::<Vec<_>>();
+            if !values.is_empty() {
+                let key = format!("http.{}.header.{}", direction, name);
+                labels.insert(key, values.join(","));
+            }
+        }
+    }
+}
+
+// === impl SpanAttributes ===
+
+impl SpanAttributes {
+    /// Records an attribute on the span of the request, if it has one.
+    pub fn record<B>(req: &http::Request<B>, key: impl Into<String>, value: impl Into<String>) {
+        if let Some(attributes) = req.extensions().get::<Self>() {
+            attributes.insert(key, value);
+        }
+    }
+
+    pub fn insert(&self, key: impl Into<String>, value: impl Into<String>) {
+        self.attributes
+            .lock()
+            .expect("span attributes lock poisoned")
+            .insert(key.into(), value.into());
+    }
+
+    /// Returns a new handle with a copy of these attributes.
+    ///
+    /// Attributes recorded on the new handle aren't recorded on this one, so
+    /// nested spans (e.g. each attempt of a retried request) may record their
+    /// own attributes.
+    pub fn fork(&self) -> Self {
+        Self {
+            attributes: Arc::new(Mutex::new(self.to_map())),
+            is_span: true,
+        }
+    }
+
+    /// Returns the handle for a new span on a request with these attributes.
+    ///
+    /// A handle that doesn't belong to a span (e.g. one set by an access log)
+    /// is shared by the span rather than forked.
+    pub(crate) fn nest(&self) -> Self {
+        if self.is_span {
+            return self.fork();
+        }
+        Self {
+            attributes: self.attributes.clone(),
+            is_span: true,
+        }
+    }
+
+    pub(crate) fn new_span() -> Self {
+        Self {
+            is_span: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.attributes
+            .lock()
+            .expect("span attributes lock poisoned")
+            .get(key)
+            .cloned()
+    }
+
+    pub(crate) fn to_map(&self) -> HashMap<String, String> {
+        self.attributes
+            .lock()
+            .expect("span attributes lock poisoned")
+            .clone()
+    }
+}
+
+// === impl NewRecordAttributes ===
+
+impl<N> NewRecordAttributes<N> {
+    pub fn layer() -> impl layer::Layer<N, Service = Self> + Clone {
+        layer::mk(|inner| Self { inner })
+    }
+}
+
+impl<T: HasSpanAttributes, N: NewService<T>> NewService<T> for NewRecordAttributes<N> {
+    type Service = RecordAttributes<N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        RecordAttributes {
+            attributes: Arc::new(target.span_attributes()),
+            inner: self.inner.new_service(target),
+        }
+    }
+}
+
+// === impl RecordAttributes ===
+
+impl<S> RecordAttributes<S> {
+    fn record<B>(&self, req: &http::Request<B>) {
+        if let Some(span) = req.extensions().get::<SpanAttributes>() {
+            for (key, value) in self.attributes.iter() {
+                span.insert(*key, value.clone());
+            }
+        }
+    }
+}
+
+impl<B, S> tower::Service<http::Request<B>> for RecordAttributes<S>
+where
+    S: tower::Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        self.record(&req);
+        self.inner.call(req)
+    }
+}
+
+impl<B, P, S> Proxy<http::Request<B>, S> for RecordAttributes<P>
+where
+    P: Proxy<http::Request<B>, S>,
+    S: tower::Service<P::Request>,
+{
+    type Request = P::Request;
+    type Response = P::Response;
+    type Error = P::Error;
+    type Future = P::Future;
+
+    fn proxy(&self, svc: &mut S, req: http::Request<B>) -> Self::Future {
+        self.record(&req);
+        self.inner.proxy(svc, req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_headers() {
+        let capture = CaptureHeaders {
+            request: vec![HeaderName::from_static("x-request-id")],
+            response: vec![HeaderName::from_static("x-cache")],
+        };
+        let mut headers = HeaderMap::new();
+        headers.append("x-request-id", "abc".parse().unwrap());
+        headers.append("x-cache", "hit".parse().unwrap());
+        headers.append("x-cache", "stale".parse().unwrap());
+
+        let mut labels = HashMap::new();
+        capture.request(&headers, &mut labels);
+        capture.response(&headers, &mut labels);
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels["http.request.header.x-request-id"], "abc");
+        assert_eq!(labels["http.response.header.x-cache"], "hit,stale");
+    }
+
+    #[test]
+    fn forks_attributes() {
+        let span = SpanAttributes::default();
+        span.insert("http.route", "GET /books");
+
+        let attempt = span.fork();
+        attempt.insert("retry.attempt", "2");
+        assert_eq!(attempt.to_map().len(), 2);
+        assert_eq!(attempt.get("retry.attempt").as_deref(), Some("2"));
+        assert_eq!(span.to_map().len(), 1);
+        assert_eq!(span.get("retry.attempt"), None);
+    }
+
+    #[test]
+    fn nests_spans() {
+        // A handle set outside of any span (e.g. by an access log) is shared
+        // with the outermost span...
+        let log = SpanAttributes::default();
+        let server = log.nest();
+        server.insert("http.route", "GET /books");
+        assert_eq!(log.get("http.route").as_deref(), Some("GET /books"));
+
+        // ...but nested spans record their own attributes.
+        let client = server.nest();
+        client.insert("retry.attempt", "2");
+        assert_eq!(client.to_map().len(), 2);
+        assert_eq!(server.get("retry.attempt"), None);
+        assert_eq!(log.get("retry.attempt"), None);
+    }
+}
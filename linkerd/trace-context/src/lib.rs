@@ -1,9 +1,11 @@
 #![deny(warnings, rust_2018_idioms)]
 
+pub mod attributes;
 mod propagation;
+pub mod sample;
 mod service;
 
-pub use self::service::TraceContext;
+pub use self::service::{ResponseBody, TraceContext};
 use bytes::Bytes;
 use linkerd_channel as mpsc;
 use linkerd_error::Error;
@@ -14,8 +16,9 @@ use std::fmt;
 use std::time::SystemTime;
 
 const SPAN_ID_LEN: usize = 8;
+const TRACE_ID_LEN: usize = 16;
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct Id(Vec<u8>);
 
 #[derive(Debug, Default)]
@@ -32,7 +35,15 @@ pub struct Span {
     pub span_name: String,
     pub start: SystemTime,
     pub end: SystemTime,
-    pub labels: HashMap<&'static str, String>,
+    pub labels: HashMap<String, String>,
+    pub events: Vec<Event>,
+}
+
+/// Marks a point in time during a span.
+#[derive(Debug)]
+pub struct Event {
+    pub name: &'static str,
+    pub time: SystemTime,
 }
 
 pub trait SpanSink {
@@ -73,6 +84,16 @@ impl Id {
         rng.fill(bytes.as_mut_slice());
         Self(bytes)
     }
+
+    fn new_trace_id<R: Rng>(rng: &mut R) -> Self {
+        let mut bytes = vec![0; TRACE_ID_LEN];
+        rng.fill(bytes.as_mut_slice());
+        Self(bytes)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 impl Into<Vec<u8>> for Id {
@@ -11,6 +11,12 @@ const HTTP_TRACE_ID_HEADER: &str = "x-b3-traceid";
 const HTTP_SPAN_ID_HEADER: &str = "x-b3-spanid";
 const HTTP_SAMPLED_HEADER: &str = "x-b3-sampled";
 
+const B3_SINGLE_HEADER: &str = "b3";
+
+const W3C_TRACEPARENT_HEADER: &str = "traceparent";
+const W3C_VERSION: &str = "00";
+const W3C_INVALID_VERSION: &str = "ff";
+
 const GRPC_TRACE_HEADER: &str = "grpc-trace-bin";
 const GRPC_TRACE_FIELD_TRACE_ID: u8 = 0;
 const GRPC_TRACE_FIELD_SPAN_ID: u8 = 1;
@@ -18,8 +24,15 @@ const GRPC_TRACE_FIELD_TRACE_OPTIONS: u8 = 2;
 
 #[derive(Debug)]
 pub enum Propagation {
+    /// B3 multi-header propagation (`x-b3-*`).
     Http,
+    /// B3 single-header propagation (`b3`).
+    B3Single,
+    /// Binary propagation (`grpc-trace-bin`).
     Grpc,
+    /// W3C Trace Context propagation (`traceparent`). The `tracestate` header
+    /// is opaque to the proxy and is forwarded unmodified.
+    W3c,
 }
 
 #[derive(Debug)]
@@ -51,8 +64,22 @@ impl TraceContext {
     }
 }
 
+/// Starts a new trace, propagated with W3C Trace Context.
+pub fn new_trace_context(trace_id: Id, sampled: bool) -> TraceContext {
+    TraceContext {
+        propagation: Propagation::W3c,
+        trace_id,
+        // The proxy's span is the root of the trace.
+        parent_id: Id::default(),
+        flags: Flags(sampled as u8),
+    }
+}
+
 pub fn unpack_trace_context<B>(request: &http::Request<B>) -> Option<TraceContext> {
-    unpack_grpc_trace_context(request).or_else(|| unpack_http_trace_context(request))
+    unpack_grpc_trace_context(request)
+        .or_else(|| unpack_w3c_trace_context(request))
+        .or_else(|| unpack_b3_single_trace_context(request))
+        .or_else(|| unpack_http_trace_context(request))
 }
 
 // Generates a new span id, writes it to the request in the appropriate
@@ -61,6 +88,8 @@ pub fn increment_span_id<B>(request: &mut http::Request<B>, context: &TraceConte
     match context.propagation {
         Propagation::Grpc => increment_grpc_span_id(request, context),
         Propagation::Http => increment_http_span_id(request),
+        Propagation::B3Single => increment_b3_single_span_id(request),
+        Propagation::W3c => increment_w3c_span_id(request, context),
     }
 }
 
@@ -209,6 +238,160 @@ fn increment_http_span_id<B>(request: &mut http::Request<B>) -> Id {
     span_id
 }
 
+fn unpack_w3c_trace_context<B>(request: &http::Request<B>) -> Option<TraceContext> {
+    let header_str = get_header_str(request, W3C_TRACEPARENT_HEADER)?;
+    let context = parse_traceparent(header_str);
+    if context.is_none() {
+        warn!(
+            "invalid {} header: {:?}",
+            W3C_TRACEPARENT_HEADER, header_str
+        );
+    }
+    context
+}
+
+/// Parses a `traceparent` header of the form
+/// `{version}-{trace-id}-{parent-id}-{trace-flags}`.
+fn parse_traceparent(header_str: &str) -> Option<TraceContext> {
+    let mut parts = header_str.trim().splitn(5, '-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let parent_id = parts.next()?;
+    let flags = parts.next()?;
+
+    // Later versions may append fields, but this version may not.
+    if version.len() != 2 || version == W3C_INVALID_VERSION {
+        return None;
+    }
+    if version == W3C_VERSION && parts.next().is_some() {
+        return None;
+    }
+
+    let trace_id = parse_w3c_id(trace_id, 16)?;
+    let parent_id = parse_w3c_id(parent_id, 8)?;
+    let flags = match hex::decode(flags) {
+        Ok(ref flags) if flags.len() == 1 => Flags(flags[0]),
+        _ => return None,
+    };
+    Some(TraceContext {
+        propagation: Propagation::W3c,
+        trace_id,
+        parent_id,
+        flags,
+    })
+}
+
+/// W3C ids have a fixed length and may not be all zeroes.
+fn parse_w3c_id(s: &str, len: usize) -> Option<Id> {
+    let id = hex::decode(s).ok()?;
+    if id.len() != len || id.iter().all(|b| *b == 0) {
+        return None;
+    }
+    Some(Id(id))
+}
+
+fn increment_w3c_span_id<B>(request: &mut http::Request<B>, context: &TraceContext) -> Id {
+    let span_id = Id::new_span_id(&mut thread_rng());
+
+    trace!(message = "incremented span id", %span_id);
+
+    set_traceparent(request, &context.trace_id, &span_id, &context.flags);
+    span_id
+}
+
+/// Propagates a trace started by the proxy, with the proxy's span at its root.
+///
+/// Only sampled traces are propagated. Otherwise, the request is forwarded
+/// without a `traceparent` header, as it was received.
+pub fn set_root_traceparent<B>(
+    request: &mut http::Request<B>,
+    trace_id: &Id,
+    span_id: &Id,
+    sampled: bool,
+) {
+    if sampled {
+        set_traceparent(request, trace_id, span_id, &Flags(1));
+    } else {
+        request.headers_mut().remove(W3C_TRACEPARENT_HEADER);
+    }
+}
+
+fn set_traceparent<B>(request: &mut http::Request<B>, trace_id: &Id, span_id: &Id, flags: &Flags) {
+    let traceparent = format!("{}-{}-{}-{}", W3C_VERSION, trace_id, span_id, flags);
+    if let Result::Ok(hv) = HeaderValue::from_str(&traceparent) {
+        request.headers_mut().insert(W3C_TRACEPARENT_HEADER, hv);
+    } else {
+        warn!(
+            "invalid {} header: {:?}",
+            W3C_TRACEPARENT_HEADER, traceparent
+        );
+    }
+}
+
+fn unpack_b3_single_trace_context<B>(request: &http::Request<B>) -> Option<TraceContext> {
+    let header_str = get_header_str(request, B3_SINGLE_HEADER)?;
+    let context = parse_b3_single(header_str);
+    // A header that only carries a sampling decision has no context to join.
+    if context.is_none() && header_str.contains('-') {
+        warn!("invalid {} header: {:?}", B3_SINGLE_HEADER, header_str);
+    }
+    context
+}
+
+/// Parses a `b3` header of the form
+/// `{trace-id}-{span-id}[-{sampling-state}[-{parent-span-id}]]`.
+fn parse_b3_single(header_str: &str) -> Option<TraceContext> {
+    let mut parts = header_str.trim().split('-');
+    let trace_id = parts.next()?;
+    let span_id = parts.next()?;
+    let flags = match parts.next() {
+        // Debug implies that the request is sampled.
+        Some("1") | Some("d") => Flags(1),
+        Some("0") | None => Flags(0),
+        Some(_) => return None,
+    };
+
+    if trace_id.len() != 16 && trace_id.len() != 32 {
+        return None;
+    }
+    if span_id.len() != 16 {
+        return None;
+    }
+    Some(TraceContext {
+        propagation: Propagation::B3Single,
+        trace_id: parse_id(trace_id, 16).ok()?,
+        parent_id: parse_id(span_id, 8).ok()?,
+        flags,
+    })
+}
+
+fn increment_b3_single_span_id<B>(request: &mut http::Request<B>) -> Id {
+    let span_id = Id::new_span_id(&mut thread_rng());
+
+    trace!(message = "incremented span id", %span_id);
+
+    // The trace ID and sampling state are forwarded as they were received
+    // and the received span becomes the parent of the proxy's span.
+    let b3 = get_header_str(request, B3_SINGLE_HEADER).map(|header_str| {
+        let mut parts = header_str.trim().split('-');
+        let trace_id = parts.next().unwrap_or_default();
+        let parent_id = parts.next().unwrap_or_default();
+        match parts.next() {
+            Some(sampled) => format!("{}-{}-{}-{}", trace_id, span_id, sampled, parent_id),
+            None => format!("{}-{}", trace_id, span_id),
+        }
+    });
+
+    if let Some(b3) = b3 {
+        if let Result::Ok(hv) = HeaderValue::from_str(&b3) {
+            request.headers_mut().insert(B3_SINGLE_HEADER, hv);
+        } else {
+            warn!("invalid {} header: {:?}", B3_SINGLE_HEADER, b3);
+        }
+    }
+    span_id
+}
+
 fn get_header_str<'a, B>(request: &'a http::Request<B>, header: &str) -> Option<&'a str> {
     let hv = request.headers().get(header)?;
     hv.to_str()
@@ -218,21 +401,25 @@ fn get_header_str<'a, B>(request: &'a http::Request<B>, header: &str) -> Option<
 
 fn parse_header_id<B>(request: &http::Request<B>, header: &str, pad_to: usize) -> Option<Id> {
     let header_value = get_header_str(request, header)?;
-    hex::decode(header_value)
-        .map(|mut data| {
-            if data.len() < pad_to {
-                let padding = pad_to - data.len();
-                let mut padded = vec![0u8; padding];
-                padded.append(&mut data);
-                Id(padded)
-            } else {
-                Id(data)
-            }
-        })
+    parse_id(header_value, pad_to)
         .map_err(|e| warn!("Header {} does not contain a hex value: {}", header, e))
         .ok()
 }
 
+/// Decodes a hex-encoded id, left-padding it with zeroes to `pad_to` bytes.
+fn parse_id(s: &str, pad_to: usize) -> Result<Id, hex::FromHexError> {
+    hex::decode(s).map(|mut data| {
+        if data.len() < pad_to {
+            let padding = pad_to - data.len();
+            let mut padded = vec![0u8; padding];
+            padded.append(&mut data);
+            Id(padded)
+        } else {
+            Id(data)
+        }
+    })
+}
+
 /// Attempt to split_to the given index.  If there are not enough bytes then
 /// Err is returned and the given Bytes is not modified.
 fn try_split_to(buf: &mut Bytes, n: usize) -> Result<Bytes, InsufficientBytes> {
@@ -242,3 +429,133 @@ fn try_split_to(buf: &mut Bytes, n: usize) -> Result<Bytes, InsufficientBytes> {
         Err(InsufficientBytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const SPAN_ID: &str = "00f067aa0ba902b7";
+
+    fn request(headers: &[(&'static str, &str)]) -> http::Request<()> {
+        let mut req = http::Request::new(());
+        for (name, value) in headers {
+            req.headers_mut()
+                .insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        req
+    }
+
+    fn header<'a>(req: &'a http::Request<()>, name: &str) -> &'a str {
+        req.headers().get(name).unwrap().to_str().unwrap()
+    }
+
+    #[test]
+    fn w3c_traceparent() {
+        let traceparent = format!("00-{}-{}-01", TRACE_ID, SPAN_ID);
+        let mut req = request(&[
+            ("traceparent", &traceparent),
+            ("tracestate", "vendor=value"),
+            ("x-b3-traceid", "0000000000000001"),
+            ("x-b3-spanid", "0000000000000002"),
+        ]);
+
+        let context = unpack_trace_context(&req).expect("must parse traceparent");
+        assert!(matches!(context.propagation, Propagation::W3c));
+        assert_eq!(context.trace_id.to_string(), TRACE_ID);
+        assert_eq!(context.parent_id.to_string(), SPAN_ID);
+        assert!(context.is_sampled());
+
+        let span_id = increment_span_id(&mut req, &context);
+        assert_eq!(
+            header(&req, "traceparent"),
+            format!("00-{}-{}-01", TRACE_ID, span_id)
+        );
+        assert_eq!(header(&req, "tracestate"), "vendor=value");
+    }
+
+    #[test]
+    fn new_trace() {
+        let context = new_trace_context(Id::new_trace_id(&mut thread_rng()), false);
+        assert!(context.parent_id.is_empty());
+
+        // Unsampled traces aren't propagated.
+        let span_id = Id::new_span_id(&mut thread_rng());
+        let mut req = request(&[]);
+        set_root_traceparent(&mut req, &context.trace_id, &span_id, false);
+        assert!(req.headers().get("traceparent").is_none());
+
+        set_root_traceparent(&mut req, &context.trace_id, &span_id, true);
+        assert_eq!(
+            header(&req, "traceparent"),
+            format!("00-{}-{}-01", context.trace_id, span_id)
+        );
+
+        set_root_traceparent(&mut req, &context.trace_id, &span_id, false);
+        assert!(req.headers().get("traceparent").is_none());
+    }
+
+    #[test]
+    fn w3c_invalid_traceparent() {
+        let zeroes = "0".repeat(32);
+        for traceparent in &[
+            format!("ff-{}-{}-01", TRACE_ID, SPAN_ID),
+            format!("00-{}-{}-01", zeroes, SPAN_ID),
+            format!("00-{}-{}-01", TRACE_ID, &zeroes[..16]),
+            format!("00-{}-{}-01", &TRACE_ID[..16], SPAN_ID),
+            format!("00-{}-{}-01-extra", TRACE_ID, SPAN_ID),
+            format!("00-{}-{}", TRACE_ID, SPAN_ID),
+        ] {
+            assert!(parse_traceparent(traceparent).is_none(), "{}", traceparent);
+        }
+
+        // Later versions may add fields.
+        let traceparent = format!("01-{}-{}-00-extra", TRACE_ID, SPAN_ID);
+        let context = parse_traceparent(&traceparent).expect("must parse");
+        assert!(!context.is_sampled());
+    }
+
+    #[test]
+    fn b3_single() {
+        let b3 = format!("{}-{}-1-{}", TRACE_ID, SPAN_ID, "0000000000000001");
+        let mut req = request(&[("b3", &b3)]);
+
+        let context = unpack_trace_context(&req).expect("must parse b3");
+        assert!(matches!(context.propagation, Propagation::B3Single));
+        assert_eq!(context.trace_id.to_string(), TRACE_ID);
+        assert_eq!(context.parent_id.to_string(), SPAN_ID);
+        assert!(context.is_sampled());
+
+        let span_id = increment_span_id(&mut req, &context);
+        assert_eq!(
+            header(&req, "b3"),
+            format!("{}-{}-1-{}", TRACE_ID, span_id, SPAN_ID)
+        );
+    }
+
+    #[test]
+    fn b3_single_short_trace_id() {
+        let b3 = format!("{}-{}", &TRACE_ID[16..], SPAN_ID);
+        let mut req = request(&[("b3", &b3)]);
+
+        let context = unpack_trace_context(&req).expect("must parse b3");
+        assert_eq!(
+            context.trace_id.to_string(),
+            format!("{}{}", "0".repeat(16), &TRACE_ID[16..])
+        );
+        assert!(!context.is_sampled());
+
+        // The trace ID is forwarded as it was received.
+        let span_id = increment_span_id(&mut req, &context);
+        assert_eq!(
+            header(&req, "b3"),
+            format!("{}-{}", &TRACE_ID[16..], span_id)
+        );
+    }
+
+    #[test]
+    fn b3_single_sampling_only() {
+        assert!(unpack_trace_context(&request(&[("b3", "0")])).is_none());
+        assert!(parse_b3_single(&format!("{}-{}-x", TRACE_ID, SPAN_ID)).is_none());
+    }
+}
@@ -0,0 +1,356 @@
+//! Head sampling for requests that arrive without a trace context.
+//!
+//! When a server receives a request that isn't part of a trace, the proxy may
+//! start one. The decision is made when the request is received, with the
+//! sampler's default probability. Once the request's route is known, the
+//! route may override that probability, and the decision is revised before the
+//! request is forwarded. The number of traces started each second may also be
+//! limited so that bursts of traffic don't flood the collector.
+//!
+//! Each request is rolled once, and the same roll is compared against each
+//! probability. Requests whose roll exceeds the probability of the sampler and
+//! of every route are never sampled, so no trace is started for them at all.
+//!
+//! A trace started by the proxy is only propagated (with a `traceparent`
+//! header) while it is sampled, so unsampled requests are forwarded untouched.
+
+use crate::{
+    propagation::{self, TraceContext},
+    Id,
+};
+use linkerd_rate_limit::{Limit, TokenBucket};
+use linkerd_stack::{layer, NewService, Proxy};
+use rand::{thread_rng, Rng};
+use std::{
+    num::NonZeroU32,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+};
+use tokio::time::Instant;
+use tracing::trace;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Config {
+    /// The probability that a request starts a new trace.
+    pub probability: f64,
+
+    /// Limits the number of traces started each second.
+    pub max_per_second: Option<NonZeroU32>,
+}
+
+/// Decides whether requests that arrive without a trace context start a new
+/// trace.
+#[derive(Clone, Debug)]
+pub struct Sampler(Arc<Inner>);
+
+/// Describes the probability that requests to a target start a new trace.
+pub trait HasSampleRate {
+    /// Returns a probability that overrides the sampler's default, if any.
+    fn sample_rate(&self) -> Option<f64>;
+}
+
+/// Revises the sampling decisions of requests to each target with the
+/// target's sample rate.
+#[derive(Clone, Debug)]
+pub struct NewSampleRoute<N> {
+    inner: N,
+
+    /// Unset when the proxy doesn't start traces.
+    sampler: Option<Sampler>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SampleRoute<S> {
+    inner: S,
+
+    /// Unset when the target uses the sampler's default.
+    probability: Option<f64>,
+}
+
+/// Set as a request extension when the proxy starts a trace for the request,
+/// so that the decision may be revised.
+#[derive(Clone, Debug)]
+pub(crate) struct HeadSampled(Arc<Decision>);
+
+#[derive(Debug)]
+struct Decision {
+    sampler: Sampler,
+    sampled: AtomicBool,
+
+    /// A uniformly random number on `[0, 1)` that is sampled by any greater
+    /// probability.
+    roll: f64,
+    trace_id: Id,
+
+    /// The id of the proxy's span, at the root of the trace.
+    span_id: Id,
+}
+
+#[derive(Debug)]
+struct Inner {
+    probability: f64,
+
+    /// The bits of the greatest probability of any route that has been built.
+    /// Probabilities are never negative, so their bits are ordered as they
+    /// are.
+    max_route_probability: AtomicU64,
+
+    /// Unset when the number of traces is not limited.
+    limit: Option<Mutex<TokenBucket>>,
+}
+
+// === impl Sampler ===
+
+impl Sampler {
+    pub fn new(config: Config) -> Self {
+        let limit = config.max_per_second.map(|max| {
+            let limit = Limit::new(max.get(), max.get()).expect("limit must not be zero");
+            Mutex::new(TokenBucket::new(limit))
+        });
+        Sampler(Arc::new(Inner {
+            probability: config.probability,
+            max_route_probability: AtomicU64::new(0.0f64.to_bits()),
+            limit,
+        }))
+    }
+
+    /// Notes that a route may sample requests with `probability`.
+    fn add_route(&self, probability: f64) {
+        self.0
+            .max_route_probability
+            .fetch_max(probability.max(0.0).to_bits(), Ordering::AcqRel);
+    }
+
+    /// Returns the greatest probability with which any request may be
+    /// sampled.
+    fn max_probability(&self) -> f64 {
+        let route = f64::from_bits(self.0.max_route_probability.load(Ordering::Acquire));
+        self.0.probability.max(route)
+    }
+
+    fn acquire(&self) -> bool {
+        let acquired = match self.0.limit.as_ref() {
+            None => true,
+            Some(limit) => limit
+                .lock()
+                .expect("sampler lock poisoned")
+                .try_acquire(Instant::now()),
+        };
+        if !acquired {
+            trace!("Trace limit reached");
+        }
+        acquired
+    }
+}
+
+// === impl HeadSampled ===
+
+impl HeadSampled {
+    /// Decides whether a new trace is sampled with the sampler's default
+    /// probability.
+    ///
+    /// Returns `None` if no route could sample the request either, so that
+    /// no trace need be started.
+    pub(crate) fn new(sampler: &Sampler) -> Option<Self> {
+        let roll = thread_rng().gen::<f64>();
+        let sampled = roll < sampler.0.probability && sampler.acquire();
+        if !sampled && roll >= sampler.max_probability() {
+            return None;
+        }
+        Some(HeadSampled(Arc::new(Decision {
+            sampler: sampler.clone(),
+            sampled: AtomicBool::new(sampled),
+            roll,
+            trace_id: Id::new_trace_id(&mut thread_rng()),
+            span_id: Id::new_span_id(&mut thread_rng()),
+        })))
+    }
+
+    pub(crate) fn is_sampled(&self) -> bool {
+        self.0.sampled.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn context(&self) -> TraceContext {
+        propagation::new_trace_context(self.0.trace_id.clone(), self.is_sampled())
+    }
+
+    /// Propagates the trace on the request if it is sampled, returning the id
+    /// of the proxy's span.
+    pub(crate) fn propagate<B>(&self, req: &mut http::Request<B>) -> Id {
+        let Decision {
+            trace_id, span_id, ..
+        } = &*self.0;
+        propagation::set_root_traceparent(req, trace_id, span_id, self.is_sampled());
+        span_id.clone()
+    }
+
+    /// Revises the decision with the given probability.
+    ///
+    /// A trace that was already sampled doesn't count against the limit
+    /// again.
+    fn resample(&self, probability: f64) -> bool {
+        let sampled = self.0.roll < probability && (self.is_sampled() || self.0.sampler.acquire());
+        self.0.sampled.store(sampled, Ordering::Release);
+        sampled
+    }
+}
+
+// === impl NewSampleRoute ===
+
+impl<N> NewSampleRoute<N> {
+    pub fn layer(sampler: Option<Sampler>) -> impl layer::Layer<N, Service = Self> + Clone {
+        layer::mk(move |inner| Self {
+            inner,
+            sampler: sampler.clone(),
+        })
+    }
+}
+
+impl<T: HasSampleRate, N: NewService<T>> NewService<T> for NewSampleRoute<N> {
+    type Service = SampleRoute<N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        let probability = target.sample_rate();
+        if let (Some(sampler), Some(probability)) = (self.sampler.as_ref(), probability) {
+            sampler.add_route(probability);
+        }
+        SampleRoute {
+            probability,
+            inner: self.inner.new_service(target),
+        }
+    }
+}
+
+// === impl SampleRoute ===
+
+impl<S> SampleRoute<S> {
+    fn resample<B>(&self, req: &mut http::Request<B>) {
+        if let Some(probability) = self.probability {
+            if let Some(head) = req.extensions().get::<HeadSampled>().cloned() {
+                let sampled = head.resample(probability);
+                trace!(%probability, sampled, "Revised sampling decision");
+                head.propagate(req);
+            }
+        }
+    }
+}
+
+impl<B, S> tower::Service<http::Request<B>> for SampleRoute<S>
+where
+    S: tower::Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        self.resample(&mut req);
+        self.inner.call(req)
+    }
+}
+
+impl<B, P, S> Proxy<http::Request<B>, S> for SampleRoute<P>
+where
+    P: Proxy<http::Request<B>, S>,
+    S: tower::Service<P::Request>,
+{
+    type Request = P::Request;
+    type Response = P::Response;
+    type Error = P::Error;
+    type Future = P::Future;
+
+    fn proxy(&self, svc: &mut S, mut req: http::Request<B>) -> Self::Future {
+        self.resample(&mut req);
+        self.inner.proxy(svc, req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sampler(probability: f64, max_per_second: Option<u32>) -> Sampler {
+        Sampler::new(Config {
+            probability,
+            max_per_second: max_per_second.and_then(NonZeroU32::new),
+        })
+    }
+
+    fn is_sampled(head: Option<HeadSampled>) -> bool {
+        head.map(|h| h.is_sampled()).unwrap_or(false)
+    }
+
+    #[test]
+    fn samples_with_probability() {
+        assert!((0..100).all(|_| is_sampled(HeadSampled::new(&sampler(1.0, None)))));
+        assert!((0..100).all(|_| !is_sampled(HeadSampled::new(&sampler(0.0, None)))));
+    }
+
+    #[test]
+    fn skips_traces_no_route_samples() {
+        let sampler = sampler(0.0, None);
+        assert!(HeadSampled::new(&sampler).is_none());
+
+        sampler.add_route(0.0);
+        assert!(HeadSampled::new(&sampler).is_none());
+
+        sampler.add_route(1.0);
+        sampler.add_route(0.5);
+        let head = HeadSampled::new(&sampler).expect("a route may sample the trace");
+        assert!(!head.is_sampled());
+    }
+
+    #[test]
+    fn limits_traces_per_second() {
+        let sampler = sampler(1.0, Some(2));
+        let sampled = (0..10)
+            .filter(|_| is_sampled(HeadSampled::new(&sampler)))
+            .count();
+        assert_eq!(sampled, 2);
+    }
+
+    #[test]
+    fn routes_revise_decisions() {
+        let sampler = sampler(0.0, Some(1));
+        sampler.add_route(1.0);
+        let head = HeadSampled::new(&sampler).expect("a route may sample the trace");
+        assert!(!head.is_sampled());
+
+        assert!(head.resample(1.0));
+        assert!(head.is_sampled());
+
+        // A sampled trace keeps its permit when it is revised...
+        assert!(head.resample(1.0));
+        // ...but other traces are still limited.
+        assert!(!HeadSampled::new(&sampler).unwrap().resample(1.0));
+
+        assert!(!head.resample(0.0));
+        assert!(!head.is_sampled());
+    }
+
+    #[test]
+    fn propagates_sampled_traces() {
+        let sampler = sampler(0.0, None);
+        sampler.add_route(1.0);
+        let head = HeadSampled::new(&sampler).expect("a route may sample the trace");
+        let mut req = http::Request::new(());
+        head.propagate(&mut req);
+        assert!(req.headers().get("traceparent").is_none());
+
+        head.resample(1.0);
+        let span_id = head.propagate(&mut req);
+        let traceparent = req.headers().get("traceparent").expect("must be sampled");
+        assert!(traceparent
+            .to_str()
+            .unwrap()
+            .ends_with(&format!("-{}-01", span_id)));
+    }
+}
@@ -1,10 +1,22 @@
-use crate::{propagation, Span, SpanSink};
-use futures::{future::Either, prelude::*};
+use crate::{
+    attributes::{CaptureHeaders, SpanAttributes},
+    propagation,
+    sample::{HeadSampled, Sampler},
+    Event, Span, SpanSink,
+};
+use futures::{
+    future::{Either, MapOk},
+    prelude::*,
+    ready,
+};
+use http_body::Body as HttpBody;
 use linkerd_stack::layer;
+use pin_project::{pin_project, pinned_drop};
 use std::{
     collections::HashMap,
     future::Future,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
     time::SystemTime,
 };
@@ -19,47 +31,73 @@ use tracing::{debug, info, trace};
 /// the request. If the sampled bit of the header was set, we emit metadata
 /// about the span to the given SpanSink when the span is complete, i.e. when
 /// we receive the response.
+///
+/// When a sampler is configured, requests without a trace context may start a
+/// new trace, with the proxy's span at its root.
+///
+/// Spans end when the response body completes. Events mark when the response
+/// headers were received and when the body completed.
 #[derive(Clone, Debug)]
 pub struct TraceContext<K, S> {
     inner: S,
     sink: K,
+    sampler: Option<Sampler>,
+    headers: Arc<CaptureHeaders>,
 }
 
+/// A response body that emits the response's span when it completes.
+#[pin_project(PinnedDrop)]
+pub struct ResponseBody<B> {
+    #[pin]
+    inner: B,
+    span: Option<PendingSpan>,
+}
+
+/// A span that is emitted once its response completes.
+struct PendingSpan {
+    span: Span,
+    attributes: SpanAttributes,
+    sink: Box<dyn SpanSink + Send>,
+}
+
+const RESPONSE_HEADERS: &str = "response headers received";
+const RESPONSE_END: &str = "response body completed";
+
 // === impl TraceContext ===
 
 impl<K: Clone, S> TraceContext<K, S> {
-    pub fn layer(sink: K) -> impl layer::Layer<S, Service = TraceContext<K, S>> + Clone {
+    pub fn layer(
+        sink: K,
+        sampler: Option<Sampler>,
+        headers: CaptureHeaders,
+    ) -> impl layer::Layer<S, Service = TraceContext<K, S>> + Clone {
+        let headers = Arc::new(headers);
         layer::mk(move |inner| TraceContext {
             inner,
             sink: sink.clone(),
+            sampler: sampler.clone(),
+            headers: headers.clone(),
         })
     }
 
-    fn request_labels<B>(req: &http::Request<B>) -> HashMap<&'static str, String> {
-        let mut labels = HashMap::with_capacity(5);
-        labels.insert("http.method", format!("{}", req.method()));
+    fn request_labels<B>(&self, req: &http::Request<B>) -> HashMap<String, String> {
+        let mut labels = HashMap::with_capacity(5 + self.headers.request.len());
+        labels.insert("http.method".to_string(), format!("{}", req.method()));
         let path = req
             .uri()
             .path_and_query()
             .map(|pq| pq.as_str().to_owned())
             .unwrap_or_default();
-        labels.insert("http.path", path);
+        labels.insert("http.path".to_string(), path);
         if let Some(authority) = req.uri().authority() {
-            labels.insert("http.authority", authority.as_str().to_string());
+            labels.insert("http.authority".to_string(), authority.as_str().to_string());
         }
         if let Some(host) = req.headers().get("host") {
             if let Ok(host) = host.to_str() {
-                labels.insert("http.host", host.to_string());
+                labels.insert("http.host".to_string(), host.to_string());
             }
         }
-        labels
-    }
-
-    fn add_response_labels<B>(
-        mut labels: HashMap<&'static str, String>,
-        rsp: &http::Response<B>,
-    ) -> HashMap<&'static str, String> {
-        labels.insert("http.status_code", rsp.status().as_str().to_string());
+        self.headers.request(req.headers(), &mut labels);
         labels
     }
 }
@@ -70,12 +108,13 @@ where
     S: tower::Service<http::Request<ReqB>, Response = http::Response<RspB>>,
     S::Error: Send,
     S::Future: Send + 'static,
+    RspB: HttpBody,
 {
-    type Response = S::Response;
+    type Response = http::Response<ResponseBody<RspB>>;
     type Error = S::Error;
     type Future = Either<
-        S::Future,
-        Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send + 'static>>,
+        MapOk<S::Future, fn(http::Response<RspB>) -> Self::Response>,
+        Pin<Box<dyn Future<Output = Result<Self::Response, S::Error>> + Send + 'static>>,
     >;
 
     #[inline]
@@ -85,44 +124,191 @@ where
 
     fn call(&mut self, mut req: http::Request<ReqB>) -> Self::Future {
         if self.sink.is_enabled() {
-            if let Some(context) = propagation::unpack_trace_context(&req) {
-                // Update the trace ID if the request set one and the proxy is configured to emit
-                // spans.
-                let span_id = propagation::increment_span_id(&mut req, &context);
+            let (context, head) = match propagation::unpack_trace_context(&req) {
+                Some(context) => (Some(context), None),
+                // Start a new trace, unless no route could sample it. The
+                // sampling decision may be revised once the request's route
+                // is known.
+                None => match self.sampler.as_ref().and_then(HeadSampled::new) {
+                    Some(head) => {
+                        req.extensions_mut().insert(head.clone());
+                        (Some(head.context()), Some(head))
+                    }
+                    None => (None, None),
+                },
+            };
+
+            if let Some(context) = context {
+                let span_id = match head.as_ref() {
+                    // A trace started by the proxy is only propagated if it's sampled.
+                    Some(head) => head.propagate(&mut req),
+                    // Update the trace ID if the request set one and the proxy is configured to
+                    // emit spans.
+                    None => propagation::increment_span_id(&mut req, &context),
+                };
                 debug!(?span_id, sampled = context.is_sampled());
 
-                if context.is_sampled() {
-                    // If the request has been marked for sampling, record its metadata.
+                if context.is_sampled() || head.is_some() {
+                    // If the request has been marked for sampling (or may yet be,
+                    // once its route is known), record its metadata.
                     let start = SystemTime::now();
-                    let req_labels = Self::request_labels(&req);
-                    let mut sink = self.sink.clone();
+                    let mut labels = self.request_labels(&req);
                     let span_name = req
                         .uri()
                         .path_and_query()
                         .map(|pq| pq.as_str().to_owned())
                         .unwrap_or_default();
+
+                    // Inner layers record attributes on this span. If the
+                    // request is already part of a span, this span starts
+                    // with its attributes. Otherwise, it shares any handle
+                    // that's already set (e.g. by an access log).
+                    let attributes = req
+                        .extensions()
+                        .get::<SpanAttributes>()
+                        .map(SpanAttributes::nest)
+                        .unwrap_or_else(SpanAttributes::new_span);
+                    req.extensions_mut().insert(attributes.clone());
+
+                    let sink = self.sink.clone();
+                    let headers = self.headers.clone();
                     return Either::Right(Box::pin(self.inner.call(req).map_ok(move |rsp| {
-                        // Emit the completed span with the response metadata.
-                        let span = Span {
-                            span_id,
-                            trace_id: context.trace_id,
-                            parent_id: context.parent_id,
-                            span_name,
-                            start,
-                            end: SystemTime::now(),
-                            labels: Self::add_response_labels(req_labels, &rsp),
+                        if let Some(head) = head {
+                            if !head.is_sampled() {
+                                return untraced(rsp);
+                            }
+                        }
+
+                        labels.insert(
+                            "http.status_code".to_string(),
+                            rsp.status().as_str().to_string(),
+                        );
+                        headers.response(rsp.headers(), &mut labels);
+
+                        let now = SystemTime::now();
+                        let span = PendingSpan {
+                            span: Span {
+                                span_id,
+                                trace_id: context.trace_id,
+                                parent_id: context.parent_id,
+                                span_name,
+                                start,
+                                end: now,
+                                labels,
+                                events: vec![Event {
+                                    name: RESPONSE_HEADERS,
+                                    time: now,
+                                }],
+                            },
+                            attributes,
+                            sink: Box::new(sink),
                         };
-                        trace!(?span);
-                        if let Err(error) = sink.try_send(span) {
-                            info!(%error, "Span dropped");
+
+                        // If the response has no body, the span is complete.
+                        if rsp.body().is_end_stream() {
+                            span.end(true);
+                            return untraced(rsp);
                         }
-                        rsp
+
+                        rsp.map(|inner| ResponseBody {
+                            inner,
+                            span: Some(span),
+                        })
                     })));
                 }
             }
         }
 
         // If there's no tracing to be done, just pass on the request to the inner service.
-        Either::Left(self.inner.call(req))
+        Either::Left(self.inner.call(req).map_ok(untraced as fn(_) -> _))
+    }
+}
+
+fn untraced<B>(rsp: http::Response<B>) -> http::Response<ResponseBody<B>> {
+    rsp.map(|inner| ResponseBody { inner, span: None })
+}
+
+// === impl ResponseBody ===
+
+impl<B: HttpBody> HttpBody for ResponseBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, B::Error>>> {
+        let mut this = self.project();
+        let frame = ready!(this.inner.as_mut().poll_data(cx));
+        match frame {
+            Some(Err(_)) => end(this.span, false),
+            // The body may end without having reported the end of the stream,
+            // and its trailers may never be polled.
+            None => end(this.span, true),
+            _ if this.inner.is_end_stream() => end(this.span, true),
+            _ => {}
+        }
+        Poll::Ready(frame)
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, B::Error>> {
+        let this = self.project();
+        let trailers = ready!(this.inner.poll_trailers(cx));
+        end(this.span, trailers.is_ok());
+        Poll::Ready(trailers)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[pinned_drop]
+impl<B> PinnedDrop for ResponseBody<B> {
+    fn drop(self: Pin<&mut Self>) {
+        // If the body is dropped before it completes, the span ends when it's
+        // dropped.
+        end(self.project().span, false);
+    }
+}
+
+fn end(span: &mut Option<PendingSpan>, completed: bool) {
+    if let Some(span) = span.take() {
+        span.end(completed);
+    }
+}
+
+// === impl PendingSpan ===
+
+impl PendingSpan {
+    fn end(self, completed: bool) {
+        let Self {
+            mut span,
+            attributes,
+            mut sink,
+        } = self;
+
+        span.end = SystemTime::now();
+        if completed {
+            span.events.push(Event {
+                name: RESPONSE_END,
+                time: span.end,
+            });
+        }
+        span.labels.extend(attributes.to_map());
+
+        // Emit the completed span with the response metadata.
+        trace!(?span);
+        if let Err(error) = sink.try_send(span) {
+            info!(%error, "Span dropped");
+        }
     }
 }
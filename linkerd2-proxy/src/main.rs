@@ -50,12 +50,14 @@ fn main() {
             None => warn!("Identity is DISABLED"),
             Some(identity) => {
                 info!("Local identity is {}", identity.name());
-                let addr = app.identity_addr().expect("must have identity addr");
-                match addr.identity.value() {
-                    None => info!("Identity verified via {}", addr.addr),
-                    Some(tls) => {
-                        info!("Identity verified via {} ({})", addr.addr, tls.server_id);
-                    }
+                match app.identity_addr() {
+                    None => info!("Identity loaded from files"),
+                    Some(addr) => match addr.identity.value() {
+                        None => info!("Identity verified via {}", addr.addr),
+                        Some(tls) => {
+                            info!("Identity verified via {} ({})", addr.addr, tls.server_id);
+                        }
+                    },
                 }
             }
         }